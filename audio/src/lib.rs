@@ -22,8 +22,24 @@
 
 #![allow(dead_code)] // Temporary
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Format that the OS output device actually provides which can differ from what was asked for
+#[derive(Clone, Copy, Debug)]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub period_frames: u32, // Frames (samples per channel) of every output_callback
+}
+
 pub trait OutputCallback {
     fn output_callback(&mut self, samples: &mut [f32]) -> bool;
+
+    /// Called once with the negotiated format before the callback loop starts
+    /// (and before checking the expected channels) so the application can adapt to it,
+    /// such as sizing a resampler instead of assuming 48kHz
+    fn output_format(&mut self, _format: OutputFormat) {}
 }
 
 pub trait InputTrait {
@@ -65,6 +81,84 @@ pub enum Error {
     InputCallback,
 }
 
+/// Scheduling priority requested for an audio thread
+#[derive(Clone, Copy, Debug)]
+pub enum ThreadPriority {
+    /// Leave the thread at the default OS priority
+    Default,
+    /// Raise the thread above normal priority
+    ///
+    /// Windows: Registers the thread with the "Pro Audio" multimedia class (MMCSS)
+    /// which does not require any special privileges
+    ///
+    /// Linux: Uses SCHED_RR at the minimum real-time priority which requires the
+    /// CAP_SYS_NICE capability or a nonzero RLIMIT_RTPRIO (usually set in /etc/security/limits.conf)
+    Elevated,
+    /// Run the thread at the highest available real-time priority
+    ///
+    /// Windows: Same as Elevated with the critical MMCSS priority and a time critical thread priority
+    ///
+    /// Linux: Uses SCHED_FIFO at the maximum allowed real-time priority with the same
+    /// permission requirements as Elevated, a misbehaving callback can starve the rest of the system
+    Realtime,
+}
+
+/// Options applied to the output and input audio threads before their callback loops start
+///
+/// Options that are not permitted or not supported on the platform are logged and otherwise ignored
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadOptions {
+    pub priority: ThreadPriority,
+    /// Pin the thread to the logical core with this index (not supported on macOS)
+    pub core_affinity: Option<usize>,
+}
+
+impl Default for ThreadOptions {
+    fn default() -> Self {
+        Self {
+            priority: ThreadPriority::Default,
+            core_affinity: None,
+        }
+    }
+}
+
+/// Pauses and resumes a running output or input callback loop from any thread
+///
+/// Pausing stops the audio device without tearing down the stream so the format that was
+/// negotiated when the stream was created stays the same after resuming, the callback
+/// is not called while paused. This saves power compared to a gain based mute which keeps the device running.
+///
+/// Resuming is noticed within PAUSE_POLL_DURATION (15 ms) and the first callback follows once the
+/// restarted device needs its next period, the device restart itself usually adds a few milliseconds.
+/// A paused callback loop can only exit after it has been resumed.
+#[derive(Clone, Default)]
+pub struct PauseControl {
+    paused: Arc<AtomicBool>,
+}
+
+impl PauseControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The device gets stopped before the next callback
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// The device gets restarted with the same format
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+// How often a paused callback loop checks whether it was resumed
+const PAUSE_POLL_DURATION: std::time::Duration = std::time::Duration::from_millis(15);
+
 /// Takes control of thread and calls the callback function with a fillable sample buffer every
 /// desired_period with the expected_channel count
 ///
@@ -74,13 +168,52 @@ pub fn run_output(
     desired_period: u32,
     expected_channels: u32,
     callback: impl OutputCallback + 'static,
+) -> Result<bool, Error> {
+    run_output_with_options(
+        desired_period,
+        expected_channels,
+        callback,
+        ThreadOptions::default(),
+    )
+}
+
+/// Same as run_output but applies the thread options to the calling thread first
+pub fn run_output_with_options(
+    desired_period: u32,
+    expected_channels: u32,
+    callback: impl OutputCallback + 'static,
+    thread_options: ThreadOptions,
+) -> Result<bool, Error> {
+    run_output_with_pause_control(
+        desired_period,
+        expected_channels,
+        callback,
+        thread_options,
+        PauseControl::new(),
+    )
+}
+
+/// Same as run_output_with_options but the output can be paused and resumed with the pause control
+pub fn run_output_with_pause_control(
+    desired_period: u32,
+    expected_channels: u32,
+    callback: impl OutputCallback + 'static,
+    thread_options: ThreadOptions,
+    pause_control: PauseControl,
 ) -> Result<bool, Error> {
     let owner = match AudioOwner::new() {
         Some(d) => d,
         None => return Err(Error::OwnerCreation),
     };
 
-    output_thread(&owner, desired_period, expected_channels, callback)
+    output_thread(
+        &owner,
+        desired_period,
+        expected_channels,
+        callback,
+        &thread_options,
+        &pause_control,
+    )
 }
 
 pub fn run_input_output(
@@ -89,6 +222,54 @@ pub fn run_input_output(
     input_expected_channels: u32,
     output_callback: impl OutputCallback + Send + 'static,
     input_trait: impl InputTrait + Send,
+) -> Result<(), Error> {
+    run_input_output_with_options(
+        desired_period,
+        output_expected_channels,
+        input_expected_channels,
+        output_callback,
+        input_trait,
+        ThreadOptions::default(),
+        ThreadOptions::default(),
+    )
+}
+
+/// Same as run_input_output but applies the thread options to the spawned output and input threads
+pub fn run_input_output_with_options(
+    desired_period: u32,
+    output_expected_channels: u32,
+    input_expected_channels: u32,
+    output_callback: impl OutputCallback + Send + 'static,
+    input_trait: impl InputTrait + Send,
+    output_thread_options: ThreadOptions,
+    input_thread_options: ThreadOptions,
+) -> Result<(), Error> {
+    run_input_output_with_pause_controls(
+        desired_period,
+        output_expected_channels,
+        input_expected_channels,
+        output_callback,
+        input_trait,
+        output_thread_options,
+        input_thread_options,
+        PauseControl::new(),
+        PauseControl::new(),
+    )
+}
+
+/// Same as run_input_output_with_options but the output and input can each be paused and resumed
+/// with their own pause control
+#[allow(clippy::too_many_arguments)]
+pub fn run_input_output_with_pause_controls(
+    desired_period: u32,
+    output_expected_channels: u32,
+    input_expected_channels: u32,
+    output_callback: impl OutputCallback + Send + 'static,
+    input_trait: impl InputTrait + Send,
+    output_thread_options: ThreadOptions,
+    input_thread_options: ThreadOptions,
+    output_pause_control: PauseControl,
+    input_pause_control: PauseControl,
 ) -> Result<(), Error> {
     let owner = match AudioOwner::new() {
         Some(d) => d,
@@ -102,9 +283,20 @@ pub fn run_input_output(
                 desired_period,
                 output_expected_channels,
                 output_callback,
+                &output_thread_options,
+                &output_pause_control,
+            )
+        });
+        scope.spawn(|| {
+            input_thread(
+                &owner,
+                desired_period,
+                input_expected_channels,
+                input_trait,
+                &input_thread_options,
+                &input_pause_control,
             )
         });
-        scope.spawn(|| input_thread(&owner, desired_period, input_expected_channels, input_trait));
     });
 
     Ok(())
@@ -114,18 +306,24 @@ fn output_thread(
     owner: &AudioOwner,
     desired_period: u32,
     expected_channels: u32,
-    callback: impl OutputCallback + 'static,
+    mut callback: impl OutputCallback + 'static,
+    thread_options: &ThreadOptions,
+    pause_control: &PauseControl,
 ) -> Result<bool, Error> {
+    apply_thread_options(thread_options);
+
     let output = match AudioOutput::new(owner, desired_period) {
         Some(d) => d,
         None => return Err(Error::OutputCreation),
     };
 
+    callback.output_format(output.get_format());
+
     if output.get_channels() != expected_channels {
         return Err(Error::ChannelMismatch);
     }
 
-    Ok(output.run_callback_loop(callback))
+    Ok(output.run_callback_loop(callback, pause_control))
 }
 
 fn input_thread(
@@ -133,20 +331,31 @@ fn input_thread(
     desired_period: u32,
     expected_channels: u32,
     mut input_trait: impl InputTrait,
+    thread_options: &ThreadOptions,
+    pause_control: &PauseControl,
 ) {
+    apply_thread_options(thread_options);
+
     loop {
         if input_trait.wait_to_start() {
             match AudioInput::new(owner, desired_period, expected_channels) {
                 Some(input) => {
                     if input.get_channels() == expected_channels {
-                        match input.run_callback_loop(&mut input_trait) {
+                        match input.run_callback_loop(&mut input_trait, pause_control) {
                             true => {}
                             false => {
                                 input_trait.error(Error::InputCallback, true);
                             }
                         }
                     } else if (input.get_channels() == 2) && (expected_channels == 1) {
-                        match input.run_callback_loop2(&mut input_trait) {
+                        match input.run_callback_loop2(&mut input_trait, pause_control) {
+                            true => {}
+                            false => {
+                                input_trait.error(Error::InputCallback, true);
+                            }
+                        }
+                    } else if (input.get_channels() == 1) && (expected_channels == 2) {
+                        match input.run_callback_loop_upmix(&mut input_trait, pause_control) {
                             true => {}
                             false => {
                                 input_trait.error(Error::InputCallback, true);
@@ -165,3 +374,21 @@ fn input_thread(
         }
     }
 }
+
+// Elevated priority commonly fails without extra permissions so the audio still runs without it
+fn apply_thread_options(thread_options: &ThreadOptions) {
+    if let Err(e) = os::set_thread_priority(thread_options.priority) {
+        println!(
+            "Audio Thread Priority {:?} Not Applied: {:?}",
+            thread_options.priority, e
+        );
+    }
+    if let Some(core_index) = thread_options.core_affinity {
+        if let Err(e) = os::set_thread_affinity(core_index) {
+            println!(
+                "Audio Thread Core Affinity {} Not Applied: {:?}",
+                core_index, e
+            );
+        }
+    }
+}
@@ -174,6 +174,7 @@ extern "C" {
     // Start and stop the PCM device
     fn snd_pcm_start(pcm_handle: *mut OpaqueStructure) -> c_int;
     fn snd_pcm_drop(pcm_handle: *mut OpaqueStructure) -> c_int;
+    fn snd_pcm_prepare(pcm_handle: *mut OpaqueStructure) -> c_int;
 
     /// Alsa PCM wait til ready
     fn snd_pcm_wait(pcm_handle: *mut OpaqueStructure, timeout: c_int) -> c_int;
@@ -247,6 +248,18 @@ extern "C" {
         buffer: *mut c_ulong,
     ) -> c_int;
 
+    fn snd_pcm_hw_params_get_rate(
+        hw_params_handle: *mut OpaqueStructure,
+        rate: *mut c_uint,
+        direction: *mut c_int,
+    ) -> c_int;
+
+    fn snd_pcm_hw_params_get_period_size(
+        hw_params_handle: *mut OpaqueStructure,
+        period: *mut c_ulong,
+        direction: *mut c_int,
+    ) -> c_int;
+
     // Sw Parameter Functions
     fn snd_pcm_sw_params_malloc(handle_ptr: *mut *mut OpaqueStructure) -> c_int;
     fn snd_pcm_sw_params_free(pcm_handle: *mut OpaqueStructure) -> c_int;
@@ -344,6 +357,15 @@ impl Pcm {
         Ok(())
     }
 
+    // A stopped PCM keeps its hardware parameters and needs to be prepared before starting again
+    pub(super) fn prepare(&self) -> Result<(), Error> {
+        let errnum = unsafe { snd_pcm_prepare(self.handle) };
+        if errnum != 0 {
+            return Err(Error::from_errnum(errnum));
+        }
+        Ok(())
+    }
+
     pub(super) fn wait_until_ready(&self, timeout: i32) -> Result<bool, Error> {
         // In Future check if this is even allowed based on the pcm state
         let status = unsafe { snd_pcm_wait(self.handle, timeout as c_int) };
@@ -513,6 +535,22 @@ impl<'a> PcmHwParams<'a> {
         }
         Ok(())
     }
+
+    // Returns the (sample rate, period frames) which are only final in the current hw params
+    pub(super) fn get_rate_period(&self) -> Result<(u32, c_ulong), Error> {
+        let mut rate = 0;
+        let errnum = unsafe { snd_pcm_hw_params_get_rate(self.handle, &mut rate, ptr::null_mut()) };
+        if errnum != 0 {
+            return Err(Error::from_errnum(errnum));
+        }
+        let mut period = 0;
+        let errnum =
+            unsafe { snd_pcm_hw_params_get_period_size(self.handle, &mut period, ptr::null_mut()) };
+        if errnum != 0 {
+            return Err(Error::from_errnum(errnum));
+        }
+        Ok((rate, period))
+    }
 }
 
 impl<'a> Drop for PcmHwParams<'a> {
@@ -47,6 +47,64 @@ pub(super) enum Error {
     Generic,
 }
 
+#[derive(Debug)]
+pub(super) enum ThreadError {
+    Scheduler(i32),
+    AffinityIndex,
+    Affinity(i32),
+}
+
+// POSIX thread scheduling from the C library that std already links
+#[repr(C)]
+struct SchedParam {
+    sched_priority: i32,
+}
+
+const SCHED_FIFO: i32 = 1;
+const SCHED_RR: i32 = 2;
+const CPU_SET_SIZE: usize = 128;
+
+extern "C" {
+    fn pthread_self() -> usize;
+    fn pthread_setschedparam(thread: usize, policy: i32, param: *const SchedParam) -> i32;
+    fn sched_get_priority_min(policy: i32) -> i32;
+    fn sched_get_priority_max(policy: i32) -> i32;
+    fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u8) -> i32;
+}
+
+// Fails with EPERM without the CAP_SYS_NICE capability or a large enough RLIMIT_RTPRIO
+pub(super) fn set_thread_priority(priority: crate::ThreadPriority) -> Result<(), ThreadError> {
+    let (policy, sched_priority) = match priority {
+        crate::ThreadPriority::Default => return Ok(()),
+        crate::ThreadPriority::Elevated => (SCHED_RR, unsafe { sched_get_priority_min(SCHED_RR) }),
+        crate::ThreadPriority::Realtime => {
+            (SCHED_FIFO, unsafe { sched_get_priority_max(SCHED_FIFO) })
+        }
+    };
+
+    let param = SchedParam { sched_priority };
+    match unsafe { pthread_setschedparam(pthread_self(), policy, &param) } {
+        0 => Ok(()),
+        errno => Err(ThreadError::Scheduler(errno)),
+    }
+}
+
+pub(super) fn set_thread_affinity(core_index: usize) -> Result<(), ThreadError> {
+    if core_index >= (CPU_SET_SIZE * 8) {
+        return Err(ThreadError::AffinityIndex);
+    }
+    let mut cpu_set = [0u8; CPU_SET_SIZE];
+    cpu_set[core_index >> 3] = 1 << (core_index & 7);
+
+    // A pid of 0 applies the mask to the calling thread
+    match unsafe { sched_setaffinity(0, CPU_SET_SIZE, cpu_set.as_ptr()) } {
+        0 => Ok(()),
+        _ => Err(ThreadError::Affinity(
+            std::io::Error::last_os_error().raw_os_error().unwrap_or(0),
+        )),
+    }
+}
+
 pub(super) struct AudioOwner {
     info: u64,
 }
@@ -62,6 +120,7 @@ pub(super) struct AudioOutput<'a> {
     device: Pcm,
     frame_period: u32,
     channels: u32,
+    sample_rate: u32,
     //channel_mask: u32,
     //volume_control: Audio::ISimpleAudioVolume,
 }
@@ -128,6 +187,21 @@ impl<'a> AudioOutput<'a> {
 
         handle_alsa_state(pcm_device.get_state());
 
+        // The nearest period can differ from the desired period
+        let (sample_rate, frame_period) = match alsa::PcmHwParams::current_from_pcm(&pcm_device) {
+            Ok(p) => match p.get_rate_period() {
+                Ok((rate, period)) => (rate, period as u32),
+                Err(e) => {
+                    handle_alsa_error(e);
+                    return None;
+                }
+            },
+            Err(e) => {
+                handle_alsa_error(e);
+                return None;
+            }
+        };
+
         // let hw_params = match als::PcmHwParams::current_from_pcm(&pcm_device) {
         //     Ok(p) => p,
         //     Err(e) => {
@@ -151,8 +225,9 @@ impl<'a> AudioOutput<'a> {
         Some(AudioOutput {
             owner: audio_owner,
             device: pcm_device,
-            frame_period: desired_period,
+            frame_period,
             channels: 2,
+            sample_rate,
         })
     }
 
@@ -160,6 +235,14 @@ impl<'a> AudioOutput<'a> {
         self.channels
     }
 
+    pub(super) fn get_format(&self) -> crate::OutputFormat {
+        crate::OutputFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            period_frames: self.frame_period,
+        }
+    }
+
     // Returns true if started
     fn start(&self) -> bool {
         // Need to do an initial read to clear stuff based on documentation
@@ -170,14 +253,38 @@ impl<'a> AudioOutput<'a> {
         self.device.stop().is_ok()
     }
 
-    pub(super) fn run_callback_loop(&self, mut callback: impl crate::OutputCallback) -> bool {
+    fn resume(&self) -> bool {
+        self.device.prepare().is_ok() && self.start()
+    }
+
+    pub(super) fn run_callback_loop(
+        &self,
+        mut callback: impl crate::OutputCallback,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
         let buffer_len = (self.frame_period * self.channels) as usize;
         let mut data_vec = vec![0.0 as f32; buffer_len];
 
         if !self.start() {
             return false;
         }
+        let mut is_running = true;
         loop {
+            if pause_control.is_paused() {
+                if is_running {
+                    if !self.stop() {
+                        return false;
+                    }
+                    is_running = false;
+                }
+                std::thread::sleep(crate::PAUSE_POLL_DURATION);
+                continue;
+            } else if !is_running {
+                if !self.resume() {
+                    return false;
+                }
+                is_running = true;
+            }
             match self.device.wait_until_ready(15) {
                 Ok(true) => {
                     // Process Frames
@@ -313,14 +420,38 @@ impl<'a> AudioInput<'a> {
         self.device.stop().is_ok()
     }
 
-    pub(super) fn run_callback_loop(&self, mut callback: impl crate::InputCallback) -> bool {
+    fn resume(&self) -> bool {
+        self.device.prepare().is_ok() && self.start()
+    }
+
+    pub(super) fn run_callback_loop(
+        &self,
+        mut callback: impl crate::InputCallback,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
         let buffer_len = (self.frame_period * self.channels) as usize;
         let mut data_vec = vec![0.0 as f32; buffer_len];
 
         if !self.start() {
             return false;
         }
+        let mut is_running = true;
         loop {
+            if pause_control.is_paused() {
+                if is_running {
+                    if !self.stop() {
+                        return false;
+                    }
+                    is_running = false;
+                }
+                std::thread::sleep(crate::PAUSE_POLL_DURATION);
+                continue;
+            } else if !is_running {
+                if !self.resume() {
+                    return false;
+                }
+                is_running = true;
+            }
             match self.device.wait_until_ready(15) {
                 Ok(true) => {
                     // Process Frames
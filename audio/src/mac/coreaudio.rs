@@ -790,6 +790,39 @@ impl Device {
         Ok(stream_data.channels_per_frame)
     }
 
+    pub(super) fn get_sample_rate(&self) -> Result<u32, Error> {
+        let stream_data = self.get_stream_description()?;
+        Ok(stream_data.sample_rate as u32)
+    }
+
+    pub(super) fn get_period(&self) -> Result<u32, Error> {
+        let scope = match self.is_capture {
+            false => PropertyScope::Output,
+            true => PropertyScope::Input,
+        };
+
+        let property_address = PropertyAddress::new(PropertySelector::BufferFrameSize, &scope);
+
+        let mut data_size = size_of::<u32>() as u32;
+        let data: u32 = 0;
+
+        let errnum = unsafe {
+            AudioObjectGetPropertyData(
+                self.id,
+                &property_address,
+                0,
+                ptr::null(),
+                &mut data_size,
+                ptr::addr_of!(data) as *mut i8,
+            )
+        };
+        if errnum != 0 {
+            return Err(Error::from_i32(errnum));
+        }
+
+        Ok(data)
+    }
+
     pub(super) fn print_stream_format(&self) -> Result<(), Error> {
         let stream_data = self.get_stream_description()?;
         stream_data.print();
@@ -826,9 +859,37 @@ impl Device {
         Ok(())
     }
 
+    // Waits for the callback to stop while stopping and restarting the audio unit for any pauses
+    fn wait_for_callback_stop(
+        &self,
+        sync_rx: &std::sync::mpsc::Receiver<CallbackStop>,
+        pause_control: &crate::PauseControl,
+    ) -> Result<CallbackStop, Error> {
+        let mut is_running = true;
+        loop {
+            match sync_rx.recv_timeout(crate::PAUSE_POLL_DURATION) {
+                Ok(cs) => return Ok(cs),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(_) => return Err(Error::Test),
+            }
+            if pause_control.is_paused() == is_running {
+                let errnum = if is_running {
+                    unsafe { AudioOutputUnitStop(self.audio_unit) }
+                } else {
+                    unsafe { AudioOutputUnitStart(self.audio_unit) }
+                };
+                if errnum != 0 {
+                    return Err(Error::from_i32(errnum));
+                }
+                is_running = !is_running;
+            }
+        }
+    }
+
     pub(super) fn run_output_callback_loop(
         &self,
         mut closure: &mut OutputClosure,
+        pause_control: &crate::PauseControl,
     ) -> Result<(), Error> {
         // Double check the canonical default
         let stream_data = self.get_stream_description()?;
@@ -909,10 +970,7 @@ impl Device {
             return Err(Error::from_i32(errnum));
         }
 
-        let callback_stop = match sync_rx.recv() {
-            Ok(cs) => cs,
-            Err(_) => return Err(Error::Test),
-        };
+        let callback_stop = self.wait_for_callback_stop(&sync_rx, pause_control)?;
 
         let errnum = unsafe { AudioOutputUnitStop(self.audio_unit) };
         if errnum != 0 {
@@ -953,6 +1011,7 @@ impl Device {
         &self,
         channels: u32,
         mut closure: &mut dyn crate::InputTrait,
+        pause_control: &crate::PauseControl,
     ) -> Result<(), Error> {
         // let mut data_size = size_of::<AudioStreamBasicDescription>() as u32;
         // let stream_data_result = AudioStreamBasicDescription::new_blank();
@@ -1072,10 +1131,7 @@ impl Device {
             return Err(Error::from_i32(errnum));
         }
 
-        let callback_stop = match sync_rx.recv() {
-            Ok(cs) => cs,
-            Err(_) => return Err(Error::Test),
-        };
+        let callback_stop = self.wait_for_callback_stop(&sync_rx, pause_control)?;
 
         let errnum = unsafe { AudioOutputUnitStop(self.audio_unit) };
         if errnum != 0 {
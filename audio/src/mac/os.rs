@@ -36,6 +36,21 @@ pub(super) enum Error {
     Generic,
 }
 
+#[derive(Debug)]
+pub(super) enum ThreadError {
+    AffinityUnsupported,
+}
+
+// The callbacks run on the CoreAudio I/O thread which is already a real-time thread
+pub(super) fn set_thread_priority(_priority: crate::ThreadPriority) -> Result<(), ThreadError> {
+    Ok(())
+}
+
+// macOS does not allow pinning threads to specific cores
+pub(super) fn set_thread_affinity(_core_index: usize) -> Result<(), ThreadError> {
+    Err(ThreadError::AffinityUnsupported)
+}
+
 pub(super) struct AudioOwner {
     info: u64,
 }
@@ -52,6 +67,7 @@ pub(super) struct AudioOutput<'a> {
     frame_period: u32,
     //buffer_size: i64,
     channels: u32,
+    sample_rate: u32,
     //channel_mask: u32,
     //volume_control: Audio::ISimpleAudioVolume,
 }
@@ -75,6 +91,22 @@ impl<'a> AudioOutput<'a> {
             }
         };
 
+        let sample_rate = match device.get_sample_rate() {
+            Ok(r) => r,
+            Err(e) => {
+                handle_coreaudio_error(e);
+                return None;
+            }
+        };
+
+        let frame_period = match device.get_period() {
+            Ok(p) => p,
+            Err(e) => {
+                handle_coreaudio_error(e);
+                return None;
+            }
+        };
+
         // if let Err(e) = device.print_stream_format() {
         //     handle_coreaudio_error(e);
         //     return None;
@@ -97,8 +129,9 @@ impl<'a> AudioOutput<'a> {
         Some(AudioOutput {
             owner: audio_owner,
             device,
-            frame_period: desired_period,
+            frame_period,
             channels,
+            sample_rate,
         })
     }
 
@@ -106,12 +139,23 @@ impl<'a> AudioOutput<'a> {
         self.channels
     }
 
+    pub(super) fn get_format(&self) -> crate::OutputFormat {
+        crate::OutputFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            period_frames: self.frame_period,
+        }
+    }
+
     pub(super) fn run_callback_loop(
         &self,
         mut callback: impl crate::OutputCallback + 'static,
+        pause_control: &crate::PauseControl,
     ) -> bool {
         let mut closure = move |samples: &mut [f32]| callback.output_callback(samples);
-        self.device.run_output_callback_loop(&mut closure).is_ok()
+        self.device
+            .run_output_callback_loop(&mut closure, pause_control)
+            .is_ok()
     }
 }
 
@@ -152,13 +196,30 @@ impl<'a> AudioInput<'a> {
         self.channels
     }
 
-    pub(super) fn run_callback_loop(&self, callback: &mut impl crate::InputTrait) -> bool {
+    pub(super) fn run_callback_loop(
+        &self,
+        callback: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
         self.device
-            .run_input_callback_loop(self.channels, callback)
+            .run_input_callback_loop(self.channels, callback, pause_control)
             .is_ok()
     }
 
-    pub(super) fn run_callback_loop2(&self, callback: &mut impl crate::InputTrait) -> bool {
+    pub(super) fn run_callback_loop2(
+        &self,
+        callback: &mut impl crate::InputTrait,
+        _pause_control: &crate::PauseControl,
+    ) -> bool {
+        // Need to implement logic here later
+        false
+    }
+
+    pub(super) fn run_callback_loop_upmix(
+        &self,
+        callback: &mut impl crate::InputTrait,
+        _pause_control: &crate::PauseControl,
+    ) -> bool {
         // Need to implement logic here later
         false
     }
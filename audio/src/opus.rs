@@ -29,19 +29,34 @@ enum Channels {
     Stereo = 2,
 }
 
+/// What the encoder tunes the encoding for
+#[derive(Clone, Copy, Debug)]
 #[repr(C)]
-enum EncodingApplication {
-    Voip = 2048,
-    Audio = 2049,
-    LowDelay = 2051,
+pub enum EncodingApplication {
+    Voip = 2048,     // Speech such as mic input
+    Audio = 2049,    // Music and other audio that should stay close to the input
+    LowDelay = 2051, // Lowest delay modes only
 }
 
+// Encoder control requests
+const OPUS_SET_BITRATE_REQUEST: c_int = 4002;
+const OPUS_AUTO: c_int = -1000;
+
 #[link(name = "opus", kind = "static")]
 extern "C" {
     fn opus_decoder_get_size(channels: Channels) -> c_int;
 
     fn opus_decoder_init(decoder: *mut u8, sample_rate: c_int, channels: Channels) -> c_int;
 
+    fn opus_decode(
+        decoder: *mut u8,
+        data: *const c_uchar,
+        data_len: c_int,
+        samples: *mut i16,
+        samples_len: c_int,
+        decode_fec: c_int,
+    ) -> c_int;
+
     fn opus_decode_float(
         decoder: *mut u8,
         data: *const c_uchar,
@@ -60,6 +75,14 @@ extern "C" {
         mode: EncodingApplication,
     ) -> c_int;
 
+    fn opus_encode(
+        encoder: *mut u8,
+        samples: *const i16,
+        samples_len: c_int,
+        data: *mut c_uchar,
+        data_len: c_int,
+    ) -> c_int;
+
     fn opus_encode_float(
         encoder: *mut u8,
         samples: *const f32,
@@ -67,6 +90,8 @@ extern "C" {
         data: *mut c_uchar,
         data_len: c_int,
     ) -> c_int;
+
+    fn opus_encoder_ctl(encoder: *mut u8, request: c_int, ...) -> c_int;
 }
 
 #[derive(Debug)]
@@ -106,22 +131,37 @@ pub struct Decoder {
 }
 
 impl Decoder {
-    pub fn new(is_stereo: bool) -> Result<Self, Error> {
-        let channels = match is_stereo {
-            true => Channels::Stereo,
-            false => Channels::Mono,
+    /// Creates a decoder that outputs at the sample rate (8000, 12000, 16000, 24000 or 48000 Hz)
+    /// no matter what rate the packets were encoded at, with 1 (mono) or 2 (stereo) channels
+    /// where stereo output is interleaved
+    ///
+    /// The rest of the audio stack runs at 48kHz
+    pub fn new(sample_rate: u32, channels: usize) -> Result<Self, Error> {
+        let (channels, is_stereo) = match channels {
+            1 => (Channels::Mono, false),
+            2 => (Channels::Stereo, true),
+            _ => return Err(Error::BadArg),
+        };
+        let rate = match c_int::try_from(sample_rate) {
+            Ok(v) => v,
+            Err(_) => return Err(Error::BadArg),
         };
         let decoder_size = unsafe { opus_decoder_get_size(channels) };
         let mut decoder = vec![0; decoder_size as usize];
 
-        let status = unsafe { opus_decoder_init(decoder.as_mut_ptr(), 48000, channels) };
+        let status = unsafe { opus_decoder_init(decoder.as_mut_ptr(), rate, channels) };
         if status != Error::Ok as i32 {
             return Err(Error::from_i32(status));
         }
         Ok(Decoder { decoder, is_stereo })
     }
 
-    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32]) -> Result<usize, Error> {
+    // Returns the packet pointer and length (a null packet for packet loss) and the output samples per channel
+    fn get_lengths(
+        &self,
+        input: &[u8],
+        output_len: usize,
+    ) -> Result<(*const u8, c_int, c_int), Error> {
         // Packet loss when input.len() is zero
         let ptr = match input.len() {
             0 => std::ptr::null(),
@@ -132,7 +172,7 @@ impl Decoder {
             Err(_) => return Err(Error::SliceTooLong),
         };
 
-        let samples_len = match c_int::try_from(output.len()) {
+        let samples_len = match c_int::try_from(output_len) {
             Ok(v) => {
                 if self.is_stereo {
                     v >> 1
@@ -142,6 +182,34 @@ impl Decoder {
             }
             Err(_) => return Err(Error::SliceTooLong),
         };
+        Ok((ptr, data_len, samples_len))
+    }
+
+    /// Decodes an opus packet (an empty packet conceals a lost one) into 16-bit samples
+    /// and returns the number of samples per channel written to the start of output
+    pub fn decode(&mut self, input: &[u8], output: &mut [i16]) -> Result<usize, Error> {
+        let (ptr, data_len, samples_len) = self.get_lengths(input, output.len())?;
+
+        let status = unsafe {
+            opus_decode(
+                self.decoder.as_mut_ptr(),
+                ptr,
+                data_len,
+                output.as_mut_ptr(),
+                samples_len,
+                0,
+            )
+        };
+
+        if status < 0 {
+            return Err(Error::from_i32(status));
+        }
+        Ok(status as usize)
+    }
+
+    /// Same as decode but for float samples (nominally -1.0 to 1.0)
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32]) -> Result<usize, Error> {
+        let (ptr, data_len, samples_len) = self.get_lengths(input, output.len())?;
 
         let status = unsafe {
             opus_decode_float(
@@ -164,42 +232,102 @@ impl Decoder {
 pub struct Encoder {
     encoder: Vec<u8>,
     is_stereo: bool,
+    sample_rate: u32,
 }
 
 impl Encoder {
-    pub fn new(is_stereo: bool, is_voip: bool) -> Result<Self, Error> {
-        let channels = match is_stereo {
-            true => Channels::Stereo,
-            false => Channels::Mono,
+    /// Creates an encoder for input at the sample rate (8000, 12000, 16000, 24000 or 48000 Hz)
+    /// with 1 (mono) or 2 (stereo) channels where stereo input is interleaved
+    ///
+    /// The rest of the audio stack runs at 48kHz
+    pub fn new(
+        sample_rate: u32,
+        channels: usize,
+        application: EncodingApplication,
+    ) -> Result<Self, Error> {
+        let (channels, is_stereo) = match channels {
+            1 => (Channels::Mono, false),
+            2 => (Channels::Stereo, true),
+            _ => return Err(Error::BadArg),
+        };
+        let rate = match c_int::try_from(sample_rate) {
+            Ok(v) => v,
+            Err(_) => return Err(Error::BadArg),
         };
         let encoder_size = unsafe { opus_encoder_get_size(channels) };
         let mut encoder = vec![0; encoder_size as usize];
 
-        let mode = match is_voip {
-            true => EncodingApplication::Voip,
-            false => EncodingApplication::Audio,
-        };
-
-        let status = unsafe { opus_encoder_init(encoder.as_mut_ptr(), 48000, channels, mode) };
+        let status =
+            unsafe { opus_encoder_init(encoder.as_mut_ptr(), rate, channels, application) };
         if status != Error::Ok as i32 {
             return Err(Error::from_i32(status));
         }
-        Ok(Encoder { encoder, is_stereo })
+        Ok(Encoder {
+            encoder,
+            is_stereo,
+            sample_rate,
+        })
     }
 
-    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, Error> {
-        if (input.len() != 480) && (input.len() != 960) {
+    // Returns the samples per channel of a 10ms or 20ms input frame
+    fn get_frame_size(&self, input_len: usize) -> Result<c_int, Error> {
+        // Stereo input has two interleaved values per sample
+        let frame_size = match self.is_stereo {
+            true => {
+                if (input_len & 0x1) != 0 {
+                    return Err(Error::InputSize);
+                }
+                input_len >> 1
+            }
+            false => input_len,
+        };
+        let frame_size_10ms = (self.sample_rate / 100) as usize;
+        if (frame_size != frame_size_10ms) && (frame_size != (frame_size_10ms * 2)) {
             return Err(Error::InputSize);
         }
-        let samples = input.as_ptr();
+        Ok(frame_size as c_int)
+    }
+
+    /// Encodes a 10ms or 20ms frame (480 or 960 samples per channel at 48kHz) of 16-bit samples
+    /// into an opus packet and returns the packet length written to the start of output
+    pub fn encode(&mut self, input: &[i16], output: &mut [u8]) -> Result<usize, Error> {
+        let frame_size = self.get_frame_size(input.len())?;
+        let data_len = match c_int::try_from(output.len()) {
+            Ok(v) => v,
+            Err(_) => return Err(Error::SliceTooLong),
+        };
+
+        let status = unsafe {
+            opus_encode(
+                self.encoder.as_mut_ptr(),
+                input.as_ptr(),
+                frame_size,
+                output.as_mut_ptr(),
+                data_len,
+            )
+        };
+
+        if status < 0 {
+            return Err(Error::from_i32(status));
+        }
+        Ok(status as usize)
+    }
+
+    /// Same as encode but for float samples (nominally -1.0 to 1.0)
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, Error> {
+        let frame_size = self.get_frame_size(input.len())?;
+        let data_len = match c_int::try_from(output.len()) {
+            Ok(v) => v,
+            Err(_) => return Err(Error::SliceTooLong),
+        };
 
         let status = unsafe {
             opus_encode_float(
                 self.encoder.as_mut_ptr(),
-                samples,
-                input.len() as c_int,
+                input.as_ptr(),
+                frame_size,
                 output.as_mut_ptr(),
-                output.len() as c_int,
+                data_len,
             )
         };
 
@@ -208,6 +336,84 @@ impl Encoder {
         }
         Ok(status as usize)
     }
+
+    /// Sets the target bitrate in bits per second (500 to 512000), None lets the encoder pick
+    /// based on the channels and application
+    pub fn set_bitrate(&mut self, bits_per_second: Option<u32>) -> Result<(), Error> {
+        let bitrate = match bits_per_second {
+            Some(b) => match c_int::try_from(b) {
+                Ok(v) => v,
+                Err(_) => return Err(Error::BadArg),
+            },
+            None => OPUS_AUTO,
+        };
+        let status = unsafe {
+            opus_encoder_ctl(self.encoder.as_mut_ptr(), OPUS_SET_BITRATE_REQUEST, bitrate)
+        };
+        if status != Error::Ok as i32 {
+            return Err(Error::from_i32(status));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of samples per channel that the packet decodes into at the sample rate
+/// (8000, 12000, 16000, 24000 or 48000 Hz) by parsing the TOC byte without decoding
+///
+/// Returns None for an empty or malformed packet and for other sample rates
+pub fn packet_sample_count(packet: &[u8], sample_rate: u32) -> Option<usize> {
+    if !matches!(sample_rate, 8000 | 12000 | 16000 | 24000 | 48000) {
+        return None;
+    }
+    let toc = *packet.first()?;
+    // Frame size in 48kHz samples from the configuration number (RFC 6716 section 3.1)
+    let config = (toc >> 3) as usize;
+    let frame_samples = match config {
+        0..=11 => [480, 960, 1920, 2880][config & 0x3], // SILK 10, 20, 40 or 60 ms
+        12..=15 => [480, 960][config & 0x1],            // Hybrid 10 or 20 ms
+        _ => [120, 240, 480, 960][config & 0x3],        // CELT 2.5, 5, 10 or 20 ms
+    };
+    let frame_count = match toc & 0x3 {
+        0 => 1,
+        1 => {
+            // Two frames of the same length
+            if ((packet.len() - 1) & 0x1) != 0 {
+                return None;
+            }
+            2
+        }
+        2 => {
+            // Two frames where the length of the first frame (one or two bytes) follows the TOC byte
+            let length_byte = *packet.get(1)? as usize;
+            let (first_frame_len, header_len) = match length_byte {
+                0..=251 => (length_byte, 2),
+                _ => (length_byte + ((*packet.get(2)? as usize) * 4), 3),
+            };
+            if (header_len + first_frame_len) > packet.len() {
+                return None;
+            }
+            2
+        }
+        _ => (*packet.get(1)? & 0x3F) as usize, // Code 3 has the frame count in the next byte
+    };
+    let samples = frame_samples * frame_count;
+    // A packet holds at least one frame and at most 120 ms of audio
+    if frame_count == 0 || samples > 5760 {
+        return None;
+    }
+    Some(samples * (sample_rate as usize) / 48000)
+}
+
+/// Returns the number of channels (1 or 2) that the packet was encoded with from its TOC byte
+///
+/// Returns None for an empty packet
+pub fn packet_channels(packet: &[u8]) -> Option<usize> {
+    let toc = *packet.first()?;
+    if (toc & 0x4) > 0 {
+        Some(2)
+    } else {
+        Some(1)
+    }
 }
 
 enum OggPageHeaderAnalysisResult {
@@ -532,38 +738,48 @@ impl OpusData {
         }
     }
 
+    /// Decodes every packet into interleaved 48kHz stereo samples (like for an output callback)
+    /// where mono audio gets copied to both channels
     pub fn get_stereo(&self) -> Option<Vec<f32>> {
-        if self.is_stereo {
-            let mut decoder = match Decoder::new(true) {
-                Ok(decoder) => decoder,
-                Err(_) => {
-                    return None;
-                }
-            };
+        let channels = match self.is_stereo {
+            true => 2,
+            false => 1,
+        };
+        let mut decoder = match Decoder::new(48000, channels) {
+            Ok(decoder) => decoder,
+            Err(_) => {
+                return None;
+            }
+        };
 
-            let mut stereo = Vec::new();
-            let mut stereo_data = vec![0.0; 1920];
-            let mut packet_data_postion = 0;
-            for l in &self.packet_len {
-                let next_packet_data_position = packet_data_postion + (*l as usize);
-                match decoder.decode_float(
-                    &self.packet_data[packet_data_postion..next_packet_data_position],
-                    &mut stereo_data,
-                ) {
-                    Ok(frames_decoded) => {
-                        stereo.extend_from_slice(&stereo_data[..frames_decoded * 2]);
-                        packet_data_postion = next_packet_data_position;
-                    }
-                    Err(_) => {
-                        return None;
+        let mut stereo = Vec::new();
+        // Large enough for the longest (120ms) stereo packet
+        let mut decoded_data = vec![0.0; 11520];
+        let mut packet_data_postion = 0;
+        for l in &self.packet_len {
+            let next_packet_data_position = packet_data_postion + (*l as usize);
+            match decoder.decode_float(
+                &self.packet_data[packet_data_postion..next_packet_data_position],
+                &mut decoded_data,
+            ) {
+                Ok(frames_decoded) => {
+                    if self.is_stereo {
+                        stereo.extend_from_slice(&decoded_data[..frames_decoded * 2]);
+                    } else {
+                        for m in &decoded_data[..frames_decoded] {
+                            stereo.push(*m);
+                            stereo.push(*m);
+                        }
                     }
+                    packet_data_postion = next_packet_data_position;
+                }
+                Err(_) => {
+                    return None;
                 }
             }
-
-            Some(stereo)
-        } else {
-            None
         }
+
+        Some(stereo)
     }
 
     pub fn to_data(&self) -> (u8, usize, usize, Vec<u8>) {
@@ -601,3 +817,61 @@ impl OpusData {
         data.extend_from_slice(&self.packet_data);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_sample_count_standard_frame_sizes() {
+        // Configuration number and the samples of a single frame at 48kHz
+        let configs = [
+            (0, 480),  // SILK 10 ms
+            (1, 960),  // SILK 20 ms
+            (2, 1920), // SILK 40 ms
+            (3, 2880), // SILK 60 ms
+            (12, 480), // Hybrid 10 ms
+            (13, 960), // Hybrid 20 ms
+            (28, 120), // CELT 2.5 ms
+            (29, 240), // CELT 5 ms
+            (30, 480), // CELT 10 ms
+            (31, 960), // CELT 20 ms
+        ];
+        for (config, samples) in configs {
+            let toc = config << 3;
+            assert_eq!(packet_sample_count(&[toc, 0, 0], 48000), Some(samples));
+            assert_eq!(packet_sample_count(&[toc, 0, 0], 24000), Some(samples / 2));
+            // Code 1 with two frames of the same length
+            assert_eq!(
+                packet_sample_count(&[toc | 1, 0, 0], 48000),
+                Some(samples * 2)
+            );
+            // Code 2 with a first frame length of 1
+            assert_eq!(
+                packet_sample_count(&[toc | 2, 1, 0, 0], 48000),
+                Some(samples * 2)
+            );
+        }
+        // Code 3 with 3 frames of 20 ms
+        assert_eq!(
+            packet_sample_count(&[(31 << 3) | 3, 3, 0], 48000),
+            Some(2880)
+        );
+    }
+
+    #[test]
+    fn packet_sample_count_malformed_packets() {
+        let toc = 31 << 3;
+        assert_eq!(packet_sample_count(&[], 48000), None);
+        assert_eq!(packet_sample_count(&[toc], 44100), None);
+        // Code 1 frames that can not be the same length
+        assert_eq!(packet_sample_count(&[toc | 1, 0], 48000), None);
+        // Code 2 without its length byte or with a first frame longer than the packet
+        assert_eq!(packet_sample_count(&[toc | 2], 48000), None);
+        assert_eq!(packet_sample_count(&[toc | 2, 4, 0, 0], 48000), None);
+        assert_eq!(packet_sample_count(&[toc | 2, 252], 48000), None);
+        // Code 3 without frames or with more than 120 ms
+        assert_eq!(packet_sample_count(&[toc | 3, 0], 48000), None);
+        assert_eq!(packet_sample_count(&[toc | 3, 7], 48000), None);
+    }
+}
@@ -22,6 +22,61 @@
 
 mod wasapi;
 
+use windows::Win32::System::Threading;
+
+#[derive(Debug)]
+pub(super) enum ThreadError {
+    Mmcss(windows::core::Error),
+    Priority(windows::core::Error),
+    AffinityIndex,
+    Affinity(windows::core::Error),
+}
+
+// Registers the calling thread with the Multimedia Class Scheduler Service (MMCSS)
+// The registration lasts until the thread exits
+pub(super) fn set_thread_priority(priority: crate::ThreadPriority) -> Result<(), ThreadError> {
+    let (avrt_priority, thread_priority) = match priority {
+        crate::ThreadPriority::Default => return Ok(()),
+        crate::ThreadPriority::Elevated => (
+            Threading::AVRT_PRIORITY_HIGH,
+            Threading::THREAD_PRIORITY_HIGHEST,
+        ),
+        crate::ThreadPriority::Realtime => (
+            Threading::AVRT_PRIORITY_CRITICAL,
+            Threading::THREAD_PRIORITY_TIME_CRITICAL,
+        ),
+    };
+
+    let mut task_index = 0;
+    let avrt_handle = match unsafe {
+        Threading::AvSetMmThreadCharacteristicsW(windows::core::w!("Pro Audio"), &mut task_index)
+    } {
+        Ok(h) => h,
+        Err(e) => return Err(ThreadError::Mmcss(e)),
+    };
+
+    if let Err(e) = unsafe { Threading::AvSetMmThreadPriority(avrt_handle, avrt_priority) } {
+        return Err(ThreadError::Mmcss(e));
+    }
+
+    match unsafe { Threading::SetThreadPriority(Threading::GetCurrentThread(), thread_priority) } {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ThreadError::Priority(e)),
+    }
+}
+
+pub(super) fn set_thread_affinity(core_index: usize) -> Result<(), ThreadError> {
+    if core_index >= usize::BITS as usize {
+        return Err(ThreadError::AffinityIndex);
+    }
+    match unsafe {
+        Threading::SetThreadAffinityMask(Threading::GetCurrentThread(), 1 << core_index)
+    } {
+        0 => Err(ThreadError::Affinity(windows::core::Error::from_win32())),
+        _ => Ok(()),
+    }
+}
+
 pub(super) struct AudioOwner {
     owner: wasapi::ComOwner,
 }
@@ -58,8 +113,16 @@ impl<'a> AudioOutput<'a> {
         self.device.get_channels()
     }
 
-    pub(super) fn run_callback_loop(&self, callback: impl crate::OutputCallback) -> bool {
-        self.device.run_output_event_loop(callback)
+    pub(super) fn get_format(&self) -> crate::OutputFormat {
+        self.device.get_format()
+    }
+
+    pub(super) fn run_callback_loop(
+        &self,
+        callback: impl crate::OutputCallback,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
+        self.device.run_output_event_loop(callback, pause_control)
     }
 }
 
@@ -91,11 +154,29 @@ impl<'a> AudioInput<'a> {
         self.device.get_channels()
     }
 
-    pub(super) fn run_callback_loop(&self, input_trait: &mut impl crate::InputTrait) -> bool {
-        self.device.run_input_event_loop(input_trait)
+    pub(super) fn run_callback_loop(
+        &self,
+        input_trait: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
+        self.device.run_input_event_loop(input_trait, pause_control)
+    }
+
+    pub(super) fn run_callback_loop2(
+        &self,
+        input_trait: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
+        self.device
+            .run_input_event_loop2(input_trait, pause_control)
     }
 
-    pub(super) fn run_callback_loop2(&self, input_trait: &mut impl crate::InputTrait) -> bool {
-        self.device.run_input_event_loop2(input_trait)
+    pub(super) fn run_callback_loop_upmix(
+        &self,
+        input_trait: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
+        self.device
+            .run_input_event_loop_upmix(input_trait, pause_control)
     }
 }
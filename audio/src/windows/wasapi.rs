@@ -81,6 +81,7 @@ struct Device {
     event: HANDLE,
     buffer_size: u32,
     frame_period: u32,
+    sample_rate: u32,
 }
 
 impl Device {
@@ -261,6 +262,8 @@ impl Device {
         // Get Channels and Channel Mask BEFORE deallocating the final_format
         let channels = final_format.Format.nChannels as u32;
         let channel_mask = final_format.dwChannelMask;
+        // The closest matching format can have a different sample rate than the 48000 requested
+        let sample_rate = final_format.Format.nSamplesPerSec;
         unsafe { CoTaskMemFree(Some(final_format_ptr as *const c_void)) };
 
         let event = match unsafe {
@@ -297,6 +300,7 @@ impl Device {
             event,
             buffer_size,
             frame_period: period,
+            sample_rate,
         })
     }
 }
@@ -333,6 +337,14 @@ impl OutputDevice {
         self.device.channels
     }
 
+    pub(super) fn get_format(&self) -> crate::OutputFormat {
+        crate::OutputFormat {
+            sample_rate: self.device.sample_rate,
+            channels: self.device.channels,
+            period_frames: self.device.frame_period,
+        }
+    }
+
     fn start(&self) -> bool {
         // Need to do an initial read to clear stuff based on documentation
 
@@ -364,6 +376,11 @@ impl OutputDevice {
         unsafe { self.device.manager.Stop() }.is_ok()
     }
 
+    // Stopped stream buffered data is discarded so that the stream resumes without stale audio
+    fn pause(&self) -> bool {
+        self.stop() && unsafe { self.device.manager.Reset() }.is_ok()
+    }
+
     fn wait_for_next_output(
         &self,
         millisecond_timeout: u32,
@@ -401,11 +418,31 @@ impl OutputDevice {
         unsafe { self.writer.ReleaseBuffer(self.device.frame_period, 0) }.is_ok()
     }
 
-    pub(super) fn run_output_event_loop(&self, mut callback: impl crate::OutputCallback) -> bool {
+    pub(super) fn run_output_event_loop(
+        &self,
+        mut callback: impl crate::OutputCallback,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
         if !self.start() {
             return false;
         }
+        let mut is_running = true;
         loop {
+            if pause_control.is_paused() {
+                if is_running {
+                    if !self.pause() {
+                        return false;
+                    }
+                    is_running = false;
+                }
+                std::thread::sleep(crate::PAUSE_POLL_DURATION);
+                continue;
+            } else if !is_running {
+                if !self.start() {
+                    return false;
+                }
+                is_running = true;
+            }
             match self.wait_for_next_output(15) {
                 Ok(Some(buffer)) => {
                     let callback_quit = callback.output_callback(buffer);
@@ -459,6 +496,11 @@ impl InputDevice {
         unsafe { self.device.manager.Stop() }.is_ok()
     }
 
+    // Stopped stream buffered data is discarded so that the stream resumes without stale audio
+    fn pause(&self) -> bool {
+        self.stop() && unsafe { self.device.manager.Reset() }.is_ok()
+    }
+
     fn wait_for_next_input(
         &self,
         millisecond_timeout: u32,
@@ -503,11 +545,31 @@ impl InputDevice {
         unsafe { self.reader.ReleaseBuffer(self.device.frame_period) }.is_ok()
     }
 
-    pub(super) fn run_input_event_loop(&self, input_trait: &mut impl crate::InputTrait) -> bool {
+    pub(super) fn run_input_event_loop(
+        &self,
+        input_trait: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
         if !self.start() {
             return false;
         }
+        let mut is_running = true;
         loop {
+            if pause_control.is_paused() {
+                if is_running {
+                    if !self.pause() {
+                        return false;
+                    }
+                    is_running = false;
+                }
+                std::thread::sleep(crate::PAUSE_POLL_DURATION);
+                continue;
+            } else if !is_running {
+                if !self.start() {
+                    return false;
+                }
+                is_running = true;
+            }
             match self.wait_for_next_input(15) {
                 Ok(Some(buffer)) => {
                     let callback_quit = input_trait.callback(buffer);
@@ -530,12 +592,32 @@ impl InputDevice {
         self.stop()
     }
 
-    pub(super) fn run_input_event_loop2(&self, input_trait: &mut impl crate::InputTrait) -> bool {
+    pub(super) fn run_input_event_loop2(
+        &self,
+        input_trait: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
         if !self.start() {
             return false;
         }
         let mut buffer_convert = vec![0.0; 480];
+        let mut is_running = true;
         loop {
+            if pause_control.is_paused() {
+                if is_running {
+                    if !self.pause() {
+                        return false;
+                    }
+                    is_running = false;
+                }
+                std::thread::sleep(crate::PAUSE_POLL_DURATION);
+                continue;
+            } else if !is_running {
+                if !self.start() {
+                    return false;
+                }
+                is_running = true;
+            }
             match self.wait_for_next_input(15) {
                 Ok(Some(buffer)) => {
                     for ind in 0..480 {
@@ -560,4 +642,57 @@ impl InputDevice {
 
         self.stop()
     }
+
+    pub(super) fn run_input_event_loop_upmix(
+        &self,
+        input_trait: &mut impl crate::InputTrait,
+        pause_control: &crate::PauseControl,
+    ) -> bool {
+        if !self.start() {
+            return false;
+        }
+        let mut buffer_convert = vec![0.0; 960];
+        let mut is_running = true;
+        loop {
+            if pause_control.is_paused() {
+                if is_running {
+                    if !self.pause() {
+                        return false;
+                    }
+                    is_running = false;
+                }
+                std::thread::sleep(crate::PAUSE_POLL_DURATION);
+                continue;
+            } else if !is_running {
+                if !self.start() {
+                    return false;
+                }
+                is_running = true;
+            }
+            match self.wait_for_next_input(15) {
+                Ok(Some(buffer)) => {
+                    // Mono input gets duplicated into interleaved stereo frames
+                    for ind in 0..480 {
+                        buffer_convert[ind << 1] = buffer[ind];
+                        buffer_convert[(ind << 1) + 1] = buffer[ind];
+                    }
+                    let callback_quit = input_trait.callback(&buffer_convert);
+                    if !self.release_input() {
+                        return false;
+                    }
+                    if callback_quit {
+                        break;
+                    }
+                }
+                Ok(None) => {
+                    // Timeout here
+                }
+                Err(e) => {
+                    println!("Input Wait Error: {:?}", e);
+                }
+            }
+        }
+
+        self.stop()
+    }
 }
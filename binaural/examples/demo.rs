@@ -88,8 +88,8 @@ fn main() {
     // Convert raw audio_here in future
     if raw_audio.is_ideal_sample_rate() {
         if let Some(mono_audio) = raw_audio.get_mono() {
-            let source = Source::new(1.0, 1.0, 1.0, mono_audio);
-            let stereo_data = listener.process_source(&source, &effects);
+            let mut source = Source::new(1.0, 1.0, 1.0, mono_audio);
+            let stereo_data = listener.process_source(&mut source, &effects);
 
             let output = Output::new(stereo_data);
 
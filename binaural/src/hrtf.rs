@@ -193,4 +193,65 @@ impl Hrtf {
             map,
         })
     }
+
+    pub(super) fn get_distance_of_measurement(&self) -> f32 {
+        self.distance_of_measurement
+    }
+
+    // Returns the (left, right) impulse response data and delays measured closest to the direction
+    // Interpolating between the closest impulse responses in future
+    pub(super) fn get_nearest_impulse_response(
+        &self,
+        azimuth: f32,
+        elevation: f32,
+    ) -> Option<[(&[f32], usize); 2]> {
+        let direction = get_unit_direction(azimuth, elevation);
+        let mut nearest: Option<(f32, &ImpulseResponse)> = None;
+        for (orientation, ir) in &self.map {
+            let d = get_unit_direction(orientation.azimuth as f32, orientation.elevation as f32);
+            // A larger dot product is a smaller angle between the directions
+            let dot = (direction.0 * d.0) + (direction.1 * d.1) + (direction.2 * d.2);
+            match nearest {
+                Some((nearest_dot, _)) if nearest_dot >= dot => {}
+                _ => nearest = Some((dot, ir)),
+            }
+        }
+        nearest.map(|(_, ir)| {
+            [
+                (ir.left_data.as_slice(), ir.left_delay as usize),
+                (ir.right_data.as_slice(), ir.right_delay as usize),
+            ]
+        })
+    }
+}
+
+// Azimuth is counterclockwise from the front and elevation is up from the horizontal plane (in degrees)
+fn get_unit_direction(azimuth: f32, elevation: f32) -> (f32, f32, f32) {
+    let (az_sin, az_cos) = azimuth.to_radians().sin_cos();
+    let (el_sin, el_cos) = elevation.to_radians().sin_cos();
+    (el_cos * az_cos, el_cos * az_sin, el_sin)
+}
+
+// Convolves a block of input samples with an impulse response that starts after delay samples
+// The tail holds the samples past the end of the previous blocks that get added to this block
+// and it is updated with the samples past the end of this block
+// FFT based (partitioned) convolution in future
+pub(super) fn convolve_block(
+    input: &[f32],
+    impulse_response: &[f32],
+    delay: usize,
+    tail: &mut Vec<f32>,
+) -> Vec<f32> {
+    let convolution_len = input.len() + impulse_response.len().max(1) - 1 + delay;
+    let mut output = vec![0.0; convolution_len.max(tail.len()).max(input.len())];
+    for (o, t) in output.iter_mut().zip(tail.iter()) {
+        *o += *t;
+    }
+    for (n, x) in input.iter().enumerate() {
+        for (o, h) in output[(n + delay)..].iter_mut().zip(impulse_response) {
+            *o += x * h;
+        }
+    }
+    *tail = output.split_off(input.len());
+    output
 }
@@ -28,7 +28,7 @@ struct Key {
     elevation: i32, // Azimuth angle of interaural coordinates, in degrees
 }
 
-struct BiquadFilters {
+pub(super) struct BiquadFilters {
     first: [f32; 5],
     second: [f32; 5],
 }
@@ -95,4 +95,44 @@ impl Ild {
 
         Some(Ild { sampling_rate, map })
     }
+
+    // Returns the filters with the closest distance and then the closest interaural azimuth
+    pub(super) fn get_nearest_filters(
+        &self,
+        distance_mm: i32,
+        interaural_azimuth: i32,
+    ) -> Option<&BiquadFilters> {
+        let mut nearest: Option<((i32, i32), &BiquadFilters)> = None;
+        for (key, filters) in &self.map {
+            let diff = (
+                (key.azimuth - distance_mm).abs(),
+                (key.elevation - interaural_azimuth).abs(),
+            );
+            match nearest {
+                Some((nearest_diff, _)) if nearest_diff <= diff => {}
+                _ => nearest = Some((diff, filters)),
+            }
+        }
+        nearest.map(|(_, filters)| filters)
+    }
+}
+
+impl BiquadFilters {
+    // Runs the samples through both biquad filters in cascade where the state holds the
+    // transposed direct form II delays of each filter so that consecutive blocks continue smoothly
+    // Coefficients are in the b0, b1, b2, a1, a2 order
+    pub(super) fn process(&self, state: &mut [[f32; 2]; 2], samples: &mut [f32]) {
+        for (coefs, z) in [&self.first, &self.second]
+            .into_iter()
+            .zip(state.iter_mut())
+        {
+            for s in samples.iter_mut() {
+                let x = *s;
+                let y = (coefs[0] * x) + z[0];
+                z[0] = (coefs[1] * x) - (coefs[3] * y) + z[1];
+                z[1] = (coefs[2] * x) - (coefs[4] * y);
+                *s = y;
+            }
+        }
+    }
 }
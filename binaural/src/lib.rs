@@ -30,56 +30,285 @@
 pub mod hrtf;
 pub mod ild;
 pub mod source;
+pub mod voice;
 
 use hrtf::Hrtf;
 use ild::Ild;
 use source::Source;
 
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Every listener gets its own id so that sources can keep per listener state
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(0);
+
 pub struct ListenerEffects {
     pub far_distance: bool,
     pub distance_attenuation: bool,
 }
 
 pub struct Listener {
+    id: u64,
     hrtf: Hrtf,
     ild: Ild,
     head_radius: f32,
     position: nalgebra::Point3<f32>,
-    orientation: nalgebra::Quaternion<f32>,
+    orientation: nalgebra::UnitQuaternion<f32>,
+    reference_distance: f32,
+    rolloff: f32,
+    min_gain: f32,
 }
 
 impl Listener {
     pub fn new(hrtf: Hrtf, ild: Ild, head_radius_option: Option<f32>) -> Self {
         let head_radius = head_radius_option.unwrap_or(0.0875);
         Listener {
+            id: NEXT_LISTENER_ID.fetch_add(1, Ordering::Relaxed),
             hrtf,
             ild,
             head_radius,
             position: nalgebra::Point3::new(0.0, 0.0, 0.0),
-            //orientation: nalgebra::Quaternion::new(0.0, 0.0, 0.0, 0.0),
-            orientation: nalgebra::Quaternion::default(),
+            orientation: nalgebra::UnitQuaternion::identity(),
+            reference_distance: 1.0,
+            rolloff: 1.0,
+            min_gain: 0.0,
         }
     }
 
+    /// Sets the inverse distance model used by ListenerEffects::distance_attenuation where sources
+    /// up to the reference distance (in meters) play at full gain and further sources get the gain
+    /// reference / (reference + rolloff * (distance - reference)) clamped to at least min_gain
+    ///
+    /// The default of a 1 meter reference and a rolloff of 1 lowers the level by about 6 dB
+    /// every time the distance doubles
+    pub fn set_distance_attenuation(
+        &mut self,
+        reference_distance: f32,
+        rolloff: f32,
+        min_gain: f32,
+    ) {
+        self.reference_distance = reference_distance.max(f32::EPSILON);
+        self.rolloff = rolloff.max(0.0);
+        self.min_gain = min_gain.clamp(0.0, 1.0);
+    }
+
+    /// Returns the inverse distance gain for a source at the distance (in meters)
+    pub fn get_distance_gain(&self, distance: f32) -> f32 {
+        let distance = distance.max(self.reference_distance);
+        let gain = self.reference_distance
+            / (self.reference_distance + (self.rolloff * (distance - self.reference_distance)));
+        gain.max(self.min_gain)
+    }
+
+    /// Turns the listener counterclockwise (seen from above) around the up axis by degrees
     pub fn rotate(&mut self, degrees: f32) {
-        self.orientation.i = degrees;
+        let yaw = nalgebra::UnitQuaternion::from_axis_angle(
+            &nalgebra::Vector3::z_axis(),
+            degrees.to_radians(),
+        );
+        self.orientation = yaw * self.orientation;
+    }
+
+    /// Sets the absolute head orientation (like from head tracking) in degrees where yaw turns
+    /// counterclockwise around the up axis, pitch tilts the nose down and roll tilts towards the right ear
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        self.orientation = nalgebra::UnitQuaternion::from_euler_angles(
+            roll.to_radians(),
+            pitch.to_radians(),
+            yaw.to_radians(),
+        );
+    }
+
+    pub fn set_position(&mut self, x_pos: f32, y_pos: f32, z_pos: f32) {
+        self.position = nalgebra::Point3::new(x_pos, y_pos, z_pos);
     }
 
-    pub fn process_source(&self, source: &Source, effects: &ListenerEffects) -> Vec<f32> {
-        if source.get_distance_from_position(&self.position) <= self.head_radius {
-            return source.get_stereo();
+    /// Returns the interleaved stereo samples of the source mono audio as heard by the listener
+    /// with the same number of frames as the mono audio
+    ///
+    /// Positions use the 3dti convention of x to the front, y to the left and z up and the source
+    /// position gets transformed into the rotated head frame first. The source keeps the convolution tail
+    /// so consecutive blocks (see Source::set_mono_audio) continue without clicks.
+    /// With distance attenuation a scaled copy of the mono block gets convolved, so the mono audio
+    /// stays untouched and the same block can be processed again (like for another listener)
+    pub fn process_source(&self, source: &mut Source, effects: &ListenerEffects) -> Vec<f32> {
+        let attenuated_mono = match effects.distance_attenuation {
+            true => {
+                let gain =
+                    self.get_distance_gain(source.get_distance_from_position(&self.position));
+                Some(source.get_attenuated_mono_audio(self.id, gain))
+            }
+            false => None,
+        };
+
+        // Source position in head relative coordinates
+        let head_relative = source.get_head_relative_position(&self.position, &self.orientation);
+        let distance = head_relative.norm();
+
+        let (source_mono, [left_tail, right_tail]) = source.get_mono_and_tails();
+        let mono = match &attenuated_mono {
+            Some(m) => m.as_slice(),
+            None => source_mono,
+        };
+        if distance <= self.head_radius {
+            return source::get_stereo_from_mono(mono);
         }
 
-        let stereo_data = Vec::new();
+        let (x, y, z) = (head_relative.x, head_relative.y, head_relative.z);
+        let azimuth = y.atan2(x).to_degrees();
+        let elevation = z.atan2(((x * x) + (y * y)).sqrt()).to_degrees();
 
-        if effects.far_distance {
-            // Do something here in future
+        let (mut left, mut right) = match self.hrtf.get_nearest_impulse_response(azimuth, elevation)
+        {
+            Some([(left_ir, left_delay), (right_ir, right_delay)]) => (
+                hrtf::convolve_block(mono, left_ir, left_delay, left_tail),
+                hrtf::convolve_block(mono, right_ir, right_delay, right_tail),
+            ),
+            None => return source::get_stereo_from_mono(mono),
+        };
+
+        // The impulse responses were measured far away so near sources get the near field compensation
+        if distance < self.hrtf.get_distance_of_measurement() {
+            let distance_mm = (distance * 1000.0) as i32;
+            // Interaural azimuth is positive towards the left ear and mirrored for the right ear
+            let interaural_azimuth = (y / distance).clamp(-1.0, 1.0).asin().to_degrees() as i32;
+            let [left_state, right_state] = &mut source.ild_states;
+            if let Some(filters) = self
+                .ild
+                .get_nearest_filters(distance_mm, interaural_azimuth)
+            {
+                filters.process(left_state, &mut left);
+            }
+            if let Some(filters) = self
+                .ild
+                .get_nearest_filters(distance_mm, -interaural_azimuth)
+            {
+                filters.process(right_state, &mut right);
+            }
         }
 
-        if effects.distance_attenuation {
+        let mut stereo_data = Vec::with_capacity(left.len() * 2);
+        for (l, r) in left.iter().zip(right.iter()) {
+            stereo_data.push(*l);
+            stereo_data.push(*r);
+        }
+
+        if effects.far_distance {
             // Do something here in future
         }
 
         stereo_data
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Creates 3dti HRTF data with single sample impulse responses of (azimuth, left gain, right gain)
+    fn get_test_hrtf(entries: &[(i32, f32, f32)]) -> Hrtf {
+        let mut d = vec![1];
+        d.extend_from_slice(&48000_u32.to_le_bytes());
+        d.extend_from_slice(&1_u32.to_le_bytes());
+        d.extend_from_slice(&1.0_f32.to_le_bytes());
+        d.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (azimuth, left_gain, right_gain) in entries {
+            d.extend_from_slice(&azimuth.to_le_bytes());
+            d.extend_from_slice(&0_i32.to_le_bytes());
+            d.extend_from_slice(&0_u64.to_le_bytes());
+            d.extend_from_slice(&0_u64.to_le_bytes());
+            d.extend_from_slice(&1_u64.to_le_bytes());
+            d.extend_from_slice(&left_gain.to_le_bytes());
+            d.extend_from_slice(&1_u64.to_le_bytes());
+            d.extend_from_slice(&right_gain.to_le_bytes());
+        }
+        Hrtf::new_from_3dti_data(&d).unwrap()
+    }
+
+    // Returns the (left, right) energy of interleaved stereo samples
+    fn get_channel_energy(stereo: &[f32]) -> (f32, f32) {
+        stereo.chunks_exact(2).fold((0.0, 0.0), |(l, r), s| {
+            (l + (s[0] * s[0]), r + (s[1] * s[1]))
+        })
+    }
+
+    // Creates 3dti ILD data without any near field filters
+    fn get_test_ild() -> Ild {
+        let mut d = vec![1];
+        d.extend_from_slice(&48000_u32.to_le_bytes());
+        d.extend_from_slice(&0_u64.to_le_bytes());
+        Ild::new_from_3dti_data(&d).unwrap()
+    }
+
+    #[test]
+    fn rotate_moves_source_between_ears() {
+        let hrtf = get_test_hrtf(&[
+            (0, 0.5, 0.5),
+            (90, 1.0, 0.25),
+            (180, 0.5, 0.5),
+            (270, 0.25, 1.0),
+        ]);
+        let mut listener = Listener::new(hrtf, get_test_ild(), None);
+        let effects = ListenerEffects {
+            far_distance: false,
+            distance_attenuation: false,
+        };
+        // Further away than the measurement distance so the near field filters are not used
+        let mut source = Source::new(0.0, -2.0, 0.0, vec![1.0; 64]);
+
+        let (left, right) = get_channel_energy(&listener.process_source(&mut source, &effects));
+        assert!(right > left * 4.0, "right: {right}, left: {left}");
+
+        // Facing the left the source on the right is behind the listener
+        listener.rotate(90.0);
+        let (left, right) = get_channel_energy(&listener.process_source(&mut source, &effects));
+        assert!((right - left).abs() < 0.001, "right: {right}, left: {left}");
+
+        // Facing the back the source ends up on the left
+        listener.rotate(90.0);
+        let (left, right) = get_channel_energy(&listener.process_source(&mut source, &effects));
+        assert!(left > right * 4.0, "right: {right}, left: {left}");
+    }
+
+    #[test]
+    fn doubling_distance_lowers_level_by_about_6_db() {
+        let listener = Listener::new(get_test_hrtf(&[(0, 1.0, 1.0)]), get_test_ild(), None);
+        let effects = ListenerEffects {
+            far_distance: false,
+            distance_attenuation: true,
+        };
+        let mut near = Source::new(2.0, 0.0, 0.0, vec![1.0; 64]);
+        let mut far = Source::new(4.0, 0.0, 0.0, vec![1.0; 64]);
+
+        let (near_energy, _) = get_channel_energy(&listener.process_source(&mut near, &effects));
+        let (far_energy, _) = get_channel_energy(&listener.process_source(&mut far, &effects));
+        let level_db = 10.0 * (far_energy / near_energy).log10();
+        assert!((level_db + 6.02).abs() < 0.1, "level: {level_db} dB");
+    }
+
+    #[test]
+    fn distance_attenuation_ramps_per_listener() {
+        let effects = ListenerEffects {
+            far_distance: false,
+            distance_attenuation: true,
+        };
+        let mut near_listener =
+            Listener::new(get_test_hrtf(&[(0, 1.0, 1.0)]), get_test_ild(), None);
+        let far_listener = Listener::new(get_test_hrtf(&[(0, 1.0, 1.0)]), get_test_ild(), None);
+        near_listener.set_position(2.0, 0.0, 0.0);
+        let mut source = Source::new(4.0, 0.0, 0.0, vec![1.0; 64]);
+
+        // The same block processed for another listener is not attenuated twice
+        let near = near_listener.process_source(&mut source, &effects);
+        let far = far_listener.process_source(&mut source, &effects);
+        assert!((near[126] - 0.5).abs() < 0.001, "near: {}", near[126]);
+        assert!((far[126] - 0.25).abs() < 0.001, "far: {}", far[126]);
+
+        // Each listener ramps from its own previous gain
+        near_listener.set_position(0.0, 0.0, 0.0);
+        let ramped = near_listener.process_source(&mut source, &effects);
+        assert!(ramped[0] > 0.49, "start: {}", ramped[0]);
+        assert!((ramped[126] - 0.25).abs() < 0.001, "end: {}", ramped[126]);
+        let steady = far_listener.process_source(&mut source, &effects);
+        assert!((steady[0] - 0.25).abs() < 0.001, "start: {}", steady[0]);
+    }
+}
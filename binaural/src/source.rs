@@ -23,6 +23,9 @@
 pub struct Source {
     position: nalgebra::Point3<f32>,
     mono_audio: Vec<f32>,
+    pub(super) convolution_tails: [Vec<f32>; 2], // Left and right samples that carry over into the next block
+    pub(super) ild_states: [[[f32; 2]; 2]; 2],   // Left and right biquad filter states
+    attenuation_gains: Vec<(u64, f32)>,          // Previous block distance gain per listener id
 }
 
 impl Source {
@@ -30,19 +33,89 @@ impl Source {
         Source {
             position: nalgebra::Point3::new(x_pos, y_pos, z_pos),
             mono_audio,
+            convolution_tails: [Vec::new(), Vec::new()],
+            ild_states: [[[0.0; 2]; 2]; 2],
+            attenuation_gains: Vec::new(),
         }
     }
 
+    /// Replaces the mono audio with the next block of samples so that the convolution tail
+    /// of the previous block carries over into the next Listener::process_source call
+    pub fn set_mono_audio(&mut self, mono_audio: Vec<f32>) {
+        self.mono_audio = mono_audio;
+    }
+
+    /// Gives access to the current mono audio block so the next block can be written over it
+    /// without allocating, the convolution tail carries over the same way as with set_mono_audio
+    pub fn get_mono_audio_mut(&mut self) -> &mut [f32] {
+        &mut self.mono_audio
+    }
+
+    pub fn set_position(&mut self, x_pos: f32, y_pos: f32, z_pos: f32) {
+        self.position = nalgebra::Point3::new(x_pos, y_pos, z_pos);
+    }
+
     pub(super) fn get_distance_from_position(&self, p2: &nalgebra::Point3<f32>) -> f32 {
         nalgebra::distance(&self.position, p2)
     }
 
+    // Rotates the offset from the listener by the inverse of the listener orientation
+    pub(super) fn get_head_relative_position(
+        &self,
+        listener_position: &nalgebra::Point3<f32>,
+        listener_orientation: &nalgebra::UnitQuaternion<f32>,
+    ) -> nalgebra::Vector3<f32> {
+        listener_orientation.inverse_transform_vector(&(self.position - listener_position))
+    }
+
+    // Returns a copy of the mono block scaled with a linear ramp from the previous block gain of the listener
+    // to the target gain to avoid zipper noise when the source moves quickly (the first block starts at the target gain)
+    pub(super) fn get_attenuated_mono_audio(
+        &mut self,
+        listener_id: u64,
+        target_gain: f32,
+    ) -> Vec<f32> {
+        let start_gain = match self
+            .attenuation_gains
+            .iter_mut()
+            .find(|(id, _)| *id == listener_id)
+        {
+            Some((_, gain)) => {
+                let start_gain = *gain;
+                *gain = target_gain;
+                start_gain
+            }
+            None => {
+                self.attenuation_gains.push((listener_id, target_gain));
+                target_gain
+            }
+        };
+        let step = match self.mono_audio.len() {
+            0 => 0.0,
+            len => (target_gain - start_gain) / (len as f32),
+        };
+        self.mono_audio
+            .iter()
+            .enumerate()
+            .map(|(ind, m)| *m * (start_gain + (step * ((ind + 1) as f32))))
+            .collect()
+    }
+
+    // Split borrow so the tails can be updated while convolving the mono audio
+    pub(super) fn get_mono_and_tails(&mut self) -> (&[f32], &mut [Vec<f32>; 2]) {
+        (&self.mono_audio, &mut self.convolution_tails)
+    }
+
     pub fn get_stereo(&self) -> Vec<f32> {
-        let mut stereo = Vec::with_capacity(self.mono_audio.len() * 2);
-        for m in &self.mono_audio {
-            stereo.push(*m);
-            stereo.push(*m);
-        }
-        stereo
+        get_stereo_from_mono(&self.mono_audio)
+    }
+}
+
+pub(super) fn get_stereo_from_mono(mono: &[f32]) -> Vec<f32> {
+    let mut stereo = Vec::with_capacity(mono.len() * 2);
+    for m in mono {
+        stereo.push(*m);
+        stereo.push(*m);
     }
+    stereo
 }
@@ -0,0 +1,335 @@
+//Media Enhanced Swiftlet Binaural Rust Library for Audio Conversions using HRTF Data
+//MIT License
+//Copyright (c) 2024 Jared Loewenthal
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+// Spatial voice pipeline from the network voice packets of many sources to the stereo output:
+// packet -> jitter buffer -> decoder -> spatializer (per source) -> mixer
+//
+// Threading model: the pipeline is meant to be owned by the audio output thread and is not shared.
+// The network thread forwards each (source id, sequence id, packet) through a wait-free ring buffer
+// (like the rtrb channels of the application) and the output callback pushes every waiting packet
+// with push_packet before calling fill_output. That way the jitter buffers are drained at the
+// audio clock rate and nothing in the pipeline needs a lock.
+
+use std::collections::VecDeque;
+
+use crate::source::Source;
+use crate::{Listener, ListenerEffects};
+
+// Packets further ahead than this (in sequence ids) reset the jitter buffer of the source
+const MAX_JITTER_PACKETS: usize = 64;
+
+/// Decodes the voice packets of a single source into mono samples
+pub trait VoiceDecoder {
+    /// Decodes a packet into the mono output and returns the number of samples written
+    ///
+    /// An empty packet means the packet was lost and the decoder should conceal it,
+    /// returns None when the packet could not be decoded
+    fn decode(&mut self, packet: &[u8], mono_output: &mut [f32]) -> Option<usize>;
+}
+
+/// The opus decoder has to be created in mono (Decoder::new(48000, 1)) for the pipeline
+#[cfg(feature = "opus")]
+impl VoiceDecoder for swiftlet_audio::opus::Decoder {
+    fn decode(&mut self, packet: &[u8], mono_output: &mut [f32]) -> Option<usize> {
+        self.decode_float(packet, mono_output).ok()
+    }
+}
+
+/// Turns the mono block of a source into interleaved stereo samples as heard by a listener
+pub trait Spatializer {
+    /// Returns the interleaved stereo samples with the same number of frames as the source mono audio
+    fn spatialize(&self, source: &mut Source) -> Vec<f32>;
+}
+
+impl Spatializer for Listener {
+    fn spatialize(&self, source: &mut Source) -> Vec<f32> {
+        // The listener effects do not do anything yet
+        let effects = ListenerEffects {
+            far_distance: false,
+            distance_attenuation: false,
+        };
+        self.process_source(source, &effects)
+    }
+}
+
+/// Result of taking the next packet out of a jitter buffer
+#[derive(Debug, PartialEq, Eq)]
+pub enum JitterOutput {
+    /// The next packet in sequence order
+    Packet(Vec<u8>),
+    /// The next packet did not arrive in time and should be concealed
+    Lost,
+    /// Not enough packets are buffered yet so nothing should be played
+    Buffering,
+}
+
+/// Reorders the packets of a source by their sequence id (like the real-time stream id)
+/// and holds back a number of packets to absorb the network jitter
+///
+/// Playback starts once the target depth of packets is buffered and buffering starts
+/// again after running out of packets. Packets that arrive after their turn are dropped
+pub struct JitterBuffer {
+    packets: VecDeque<Option<Vec<u8>>>, // Starting at the next sequence id
+    next_id: Option<u64>,
+    target_depth: usize,
+    is_playing: bool,
+}
+
+impl JitterBuffer {
+    pub fn new(target_depth: usize) -> Self {
+        JitterBuffer {
+            packets: VecDeque::with_capacity(MAX_JITTER_PACKETS),
+            next_id: None,
+            target_depth: target_depth.clamp(1, MAX_JITTER_PACKETS),
+            is_playing: false,
+        }
+    }
+
+    /// Adds a packet with its sequence id and returns false if it arrived too late to be played
+    pub fn push(&mut self, sequence_id: u64, packet: Vec<u8>) -> bool {
+        let next_id = *self.next_id.get_or_insert(sequence_id);
+        if sequence_id < next_id {
+            if (next_id - sequence_id) < (MAX_JITTER_PACKETS as u64) {
+                return false;
+            }
+            // Far behind means that the source started counting again (such as after reconnecting)
+            self.packets.clear();
+            self.next_id = Some(sequence_id);
+            self.is_playing = false;
+        }
+        let mut index = (sequence_id - self.next_id.unwrap_or(sequence_id)) as usize;
+        if index >= MAX_JITTER_PACKETS {
+            // The source jumped far ahead (such as after a long pause) so the old packets get dropped
+            self.packets.clear();
+            self.next_id = Some(sequence_id);
+            self.is_playing = false;
+            index = 0;
+        }
+        while self.packets.len() <= index {
+            self.packets.push_back(None);
+        }
+        self.packets[index] = Some(packet);
+        true
+    }
+
+    /// Takes out the next packet in sequence order once playback started
+    pub fn pop(&mut self) -> JitterOutput {
+        if !self.is_playing {
+            if self.packets.len() < self.target_depth {
+                return JitterOutput::Buffering;
+            }
+            self.is_playing = true;
+        }
+        match self.packets.pop_front() {
+            Some(packet_option) => {
+                if let Some(next_id) = &mut self.next_id {
+                    *next_id += 1;
+                }
+                match packet_option {
+                    Some(packet) => JitterOutput::Packet(packet),
+                    None => JitterOutput::Lost,
+                }
+            }
+            None => {
+                self.is_playing = false;
+                JitterOutput::Buffering
+            }
+        }
+    }
+
+    /// Returns the number of packet slots (including missing packets) that are waiting to be played
+    pub fn get_buffered_len(&self) -> usize {
+        self.packets.len()
+    }
+}
+
+struct VoiceSource<D: VoiceDecoder> {
+    id: u64,
+    decoder: D,
+    jitter_buffer: JitterBuffer,
+    source: Source,
+}
+
+/// Mixes the spatialized voices of many sources into the stereo output of an audio output callback
+///
+/// Every packet needs to decode to frame_len mono samples (480 for 10ms at 48000 Hz) and the
+/// sources get identified by an id (such as the connection id when every connection has one voice).
+/// The decoder of every source and the spatializer can be swapped out by implementing VoiceDecoder
+/// and Spatializer, see the top of this module for the threading model
+pub struct SpatialVoicePipeline<D: VoiceDecoder, S: Spatializer> {
+    spatializer: S,
+    sources: Vec<VoiceSource<D>>,
+    frame_len: usize,
+    jitter_depth: usize,
+    mix_frame: Vec<f32>,     // Reused interleaved stereo mix of a single frame
+    mix_data: VecDeque<f32>, // Interleaved stereo samples that did not fit into the last output yet
+}
+
+impl<D: VoiceDecoder, S: Spatializer> SpatialVoicePipeline<D, S> {
+    pub fn new(spatializer: S, frame_len: usize, jitter_depth: usize) -> Self {
+        let frame_len = frame_len.max(1);
+        SpatialVoicePipeline {
+            spatializer,
+            sources: Vec::new(),
+            frame_len,
+            jitter_depth,
+            mix_frame: vec![0.0; frame_len * 2],
+            mix_data: VecDeque::with_capacity(frame_len * 4),
+        }
+    }
+
+    /// Returns the spatializer so the listener can be moved and turned in between outputs
+    pub fn get_spatializer_mut(&mut self) -> &mut S {
+        &mut self.spatializer
+    }
+
+    /// Adds a source with its own decoder at a position (replacing any source with the same id)
+    pub fn add_source(&mut self, id: u64, decoder: D, x_pos: f32, y_pos: f32, z_pos: f32) {
+        self.remove_source(id);
+        self.sources.push(VoiceSource {
+            id,
+            decoder,
+            jitter_buffer: JitterBuffer::new(self.jitter_depth),
+            source: Source::new(x_pos, y_pos, z_pos, vec![0.0; self.frame_len]),
+        });
+    }
+
+    /// Returns false if there is no source with the id
+    pub fn remove_source(&mut self, id: u64) -> bool {
+        match self.sources.iter().position(|s| s.id == id) {
+            Some(index) => {
+                self.sources.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns false if there is no source with the id
+    pub fn set_source_position(&mut self, id: u64, x_pos: f32, y_pos: f32, z_pos: f32) -> bool {
+        match self.sources.iter_mut().find(|s| s.id == id) {
+            Some(voice_source) => {
+                voice_source.source.set_position(x_pos, y_pos, z_pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a received voice packet of a source with its sequence id (like the real-time stream id)
+    ///
+    /// Returns false if there is no source with the id or the packet arrived too late
+    pub fn push_packet(&mut self, id: u64, sequence_id: u64, packet: Vec<u8>) -> bool {
+        match self.sources.iter_mut().find(|s| s.id == id) {
+            Some(voice_source) => voice_source.jitter_buffer.push(sequence_id, packet),
+            None => false,
+        }
+    }
+
+    /// Fills the interleaved stereo output with the mix of all of the sources
+    ///
+    /// The output can be any length, frames that are mixed but do not fit
+    /// are kept for the next call. Sources that are still buffering are silent
+    pub fn fill_output(&mut self, stereo_output: &mut [f32]) {
+        let output_len = stereo_output.len();
+        while self.mix_data.len() < output_len {
+            self.mix_next_frame();
+        }
+        for (output, mixed) in stereo_output
+            .iter_mut()
+            .zip(self.mix_data.drain(..output_len))
+        {
+            *output = mixed;
+        }
+    }
+
+    fn mix_next_frame(&mut self) {
+        self.mix_frame.fill(0.0);
+        for voice_source in &mut self.sources {
+            let packet = match voice_source.jitter_buffer.pop() {
+                JitterOutput::Packet(packet) => packet,
+                JitterOutput::Lost => Vec::new(),
+                JitterOutput::Buffering => continue,
+            };
+            // Decoded straight into the mono audio of the source
+            let mono_audio = voice_source.source.get_mono_audio_mut();
+            mono_audio.fill(0.0);
+            if voice_source.decoder.decode(&packet, mono_audio).is_none() {
+                // An undecodable packet is played as silence so the source stays in sync
+                mono_audio.fill(0.0);
+            }
+            let stereo = self.spatializer.spatialize(&mut voice_source.source);
+            for (m, s) in self.mix_frame.iter_mut().zip(stereo.iter()) {
+                *m += *s;
+            }
+        }
+        for m in &self.mix_frame {
+            self.mix_data.push_back(m.clamp(-1.0, 1.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes increasing values so that the order of the output samples can be checked
+    struct CountingDecoder {
+        next_value: f32,
+    }
+
+    impl VoiceDecoder for CountingDecoder {
+        fn decode(&mut self, _packet: &[u8], mono_output: &mut [f32]) -> Option<usize> {
+            for m in mono_output.iter_mut() {
+                self.next_value += 0.125;
+                *m = self.next_value;
+            }
+            Some(mono_output.len())
+        }
+    }
+
+    struct CenterSpatializer;
+
+    impl Spatializer for CenterSpatializer {
+        fn spatialize(&self, source: &mut Source) -> Vec<f32> {
+            source.get_stereo()
+        }
+    }
+
+    #[test]
+    fn fill_output_keeps_frames_that_do_not_fit() {
+        let mut pipeline = SpatialVoicePipeline::new(CenterSpatializer, 2, 1);
+        pipeline.add_source(1, CountingDecoder { next_value: 0.0 }, 0.0, 0.0, 0.0);
+        for sequence_id in 0..2 {
+            assert!(pipeline.push_packet(1, sequence_id, vec![0]));
+        }
+
+        let mut first_output = [0.0; 3];
+        pipeline.fill_output(&mut first_output);
+        assert_eq!(first_output, [0.125, 0.125, 0.25]);
+
+        // The last sample of the first frame comes before the second frame
+        let mut second_output = [0.0; 5];
+        pipeline.fill_output(&mut second_output);
+        assert_eq!(second_output, [0.25, 0.375, 0.375, 0.5, 0.5]);
+    }
+}
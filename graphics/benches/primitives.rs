@@ -0,0 +1,90 @@
+//Media Enhanced Swiftlet Cross-Compile Friendly Graphics Primitive Building Benchmark
+//MIT License
+//Copyright (c) 2024 Jared Loewenthal
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use swiftlet_graphics::font::{Glyphs, TextBuffer};
+use swiftlet_graphics::vulkan::{
+    PrimitiveColor, PrimitivePosition, PrimitiveRectangleModifier, Primitives2dBuffer,
+};
+use swiftlet_graphics::DrawJustification;
+
+const FONT_PATH: &str = "../font/roboto/Roboto-Regular.ttf"; // Location of the Font (benchmarks run from the graphics directory)
+
+const NUM_RECTANGLES: usize = 1000;
+const NUM_PARAGRAPH_LINES: usize = 20;
+const PARAGRAPH_LINE: &str = "The quick brown fox jumped over the lazy dog! 0123456789";
+
+fn build_frame(c: &mut Criterion) {
+    let mut glyphs = Glyphs::new_from_font_file(FONT_PATH, 0, 2, "en").unwrap();
+    glyphs.add_glyph_outline_data(0, ' ', '~').unwrap();
+    let face_shaper = glyphs.get_font_face_shaper(0).unwrap();
+
+    let dpi = 96.0;
+    let pt_size = 12;
+    let line_height = {
+        let metrics = face_shaper.get_ascender_descender_gap(pt_size, dpi);
+        metrics.0 + metrics.1 + metrics.2
+    };
+    let mut text_buffer = TextBuffer::default();
+    text_buffer.add_text(PARAGRAPH_LINE);
+    // Shaping is done once so only the primitive building gets measured
+    let glyph_bri = face_shaper.create_glyph_buffer_render_info(pt_size, dpi, text_buffer);
+
+    let color = PrimitiveColor::new_from_linear_rgb_and_alpha([0.2, 0.4, 0.6], 1.0);
+    let mut buffer = Primitives2dBuffer::new(1 << 14);
+
+    let mut group = c.benchmark_group("primitives");
+    group.throughput(Throughput::Elements(
+        (NUM_RECTANGLES + (NUM_PARAGRAPH_LINES * PARAGRAPH_LINE.len())) as u64,
+    ));
+    group.bench_function("frame", |b| {
+        b.iter(|| {
+            let mut primitives = buffer.get_primitives(1920, 1080);
+            for ind in 0..NUM_RECTANGLES {
+                let x = ((ind % 40) as f32) * 48.0;
+                let y = ((ind / 40) as f32) * 40.0;
+                primitives.add_rectangle(
+                    (x, y),
+                    (40.0, 32.0),
+                    &color,
+                    PrimitiveRectangleModifier::RoundedCorners(4.0),
+                );
+            }
+            let mut baseline = PrimitivePosition { x: 16.0, y: 16.0 };
+            for _line in 0..NUM_PARAGRAPH_LINES {
+                baseline.y += line_height;
+                glyph_bri.draw_glyphs(
+                    &mut primitives,
+                    &baseline,
+                    &color,
+                    2,
+                    DrawJustification::Left,
+                );
+            }
+            black_box(primitives.get_num_triangles())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, build_frame);
+criterion_main!(benches);
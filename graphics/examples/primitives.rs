@@ -56,6 +56,9 @@ fn main() -> std::io::Result<()> {
         1 << 25,
         glyphs,
         swiftlet_graphics::Vulkan2dWindowMode::ValidationDebug,
+        swiftlet_graphics::LatencyMode::LowLatency,
+        None, // Frames in flight (from the latency mode)
+        None, // Device index
     ) {
         Ok(r) => r,
         Err(e) => {
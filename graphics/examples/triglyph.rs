@@ -38,6 +38,7 @@ fn main() -> std::io::Result<()> {
         720,
         104 * 8,
         triangle_example.glyphs.get_glyph_outline_data(),
+        swiftlet_graphics::LatencyMode::LowLatency,
         true,
     ) {
         Ok((w, s)) => (w, s),
@@ -20,6 +20,27 @@
 //OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 //SOFTWARE.
 
+// Color pipeline used by every color bearing primitive:
+// 1. sRGB input colors get linearized on the CPU (LinearRGB or get_linear_rgb_float_from_srgb_byte)
+// 2. PrimitiveColor premultiplies the linear rgb by the alpha on the CPU
+// 3. The shaders interpolate and blend the premultiplied linear values
+// 4. The render target has an sRGB format so the GPU does the sRGB encode when writing
+// Gradients, tints, outlines and shadows should all mix colors in linear space before step 2
+// and never mix sRGB values directly
+
+/// Color space that a color value is stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+/// Color space that the shaders expect every primitive color to be in
+pub const SHADER_COLOR_SPACE: ColorSpace = ColorSpace::Linear;
+
+/// Color space that the render target formats must encode into so that the GPU does the sRGB encode
+pub const RENDER_TARGET_COLOR_SPACE: ColorSpace = ColorSpace::Srgb;
+
 pub fn get_linear_rgb_float_from_srgb_byte(byte_value: u8) -> f32 {
     let base = (byte_value as f32) / 255.0;
     if base > 0.04045 {
@@ -30,6 +51,202 @@ pub fn get_linear_rgb_float_from_srgb_byte(byte_value: u8) -> f32 {
     }
 }
 
+/// Inverse of get_linear_rgb_float_from_srgb_byte that rounds to the nearest sRGB byte
+pub fn get_srgb_byte_from_linear_rgb_float(linear_value: f32) -> u8 {
+    let linear_value = linear_value.clamp(0.0, 1.0);
+    let base = if linear_value > 0.0031308 {
+        1.055 * linear_value.powf(1.0 / 2.4) - 0.055
+    } else {
+        linear_value * 12.92
+    };
+    (base * 255.0).round() as u8
+}
+
+/// Parses a "#RRGGBB" or "#RRGGBBAA" hex color into its bytes where a missing alpha byte is 255
+///
+/// Returns None when the text is not in one of those forms
+pub fn get_srgb_bytes_from_hex(hex: &str) -> Option<[u8; 4]> {
+    let digits = hex.strip_prefix('#')?;
+    if (digits.len() != 6 && digits.len() != 8) || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [255; 4];
+    for (ind, byte) in bytes.iter_mut().take(digits.len() / 2).enumerate() {
+        *byte = match u8::from_str_radix(&digits[ind * 2..ind * 2 + 2], 16) {
+            Ok(b) => b,
+            Err(_) => return None,
+        };
+    }
+    Some(bytes)
+}
+
+/// Mixes two linear rgb colors where t = 0.0 returns a and t = 1.0 returns b
+///
+/// A 50% mix of black and white is a linear value of 0.5 (sRGB byte 188)
+/// and not the sRGB midpoint of byte 128 (linear value of about 0.216)
+pub fn mix_linear_rgb(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    let t = t.clamp(0.0, 1.0);
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+/// Converts a premultiplied sRGB encoded RGBA pixel (red in the lowest byte) as rendered by step 4
+/// of the color pipeline into straight alpha (like PNG files expect)
+///
+/// The premultiply happened in linear space so the rgb gets linearized before the divide by the alpha,
+/// fully transparent pixels become transparent black
+pub fn get_straight_alpha_from_premultiplied_srgb_pixel(pixel: u32) -> u32 {
+    let alpha_byte = (pixel >> 24) as u8;
+    match alpha_byte {
+        0 => 0,
+        255 => pixel,
+        _ => {
+            let alpha = (alpha_byte as f32) / 255.0;
+            let mut straight = (alpha_byte as u32) << 24;
+            for shift in [0, 8, 16] {
+                let linear = get_linear_rgb_float_from_srgb_byte((pixel >> shift) as u8) / alpha;
+                straight |= (get_srgb_byte_from_linear_rgb_float(linear) as u32) << shift;
+            }
+            straight
+        }
+    }
+}
+
+// Video frames are YUV (Y'CbCr) so they need a conversion before step 2 of the color pipeline above.
+// The manual path is used for this (instead of VK_KHR_sampler_ycbcr_conversion) so that it works on
+// every device: the affine matrix below gets applied to the sampled normalized plane values.
+// TwoDimensionRender::set_video_frame uploads the planes as is and the fragment shader samples them
+
+/// Color matrix that a YUV video frame was encoded with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// Standard definition video
+    Bt601,
+    /// High definition video (the usual matrix for decoded call video)
+    #[default]
+    Bt709,
+}
+
+/// Value range that a YUV video frame was encoded with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YuvRange {
+    /// 16-235 luma and 16-240 chroma for 8 bit values
+    #[default]
+    Limited,
+    /// 0-255 luma and chroma for 8 bit values
+    Full,
+}
+
+/// Plane layout of an 8 bit 4:2:0 YUV video frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvFormat {
+    /// Y plane followed by a half resolution plane of interleaved U and V
+    Nv12,
+    /// Y plane followed by half resolution U and V planes
+    I420,
+}
+
+impl YuvFormat {
+    /// Returns the number of bytes of a tightly packed frame (the chroma size rounds up for odd sizes)
+    pub fn get_frame_len(&self, width: usize, height: usize) -> usize {
+        let chroma_len = width.div_ceil(2) * height.div_ceil(2);
+        (width * height) + (chroma_len * 2)
+    }
+
+    /// Returns the byte offsets of the first U and V values in a tightly packed frame
+    /// and the byte step between neighbouring chroma values (2 for the interleaved NV12 plane)
+    pub fn get_chroma_layout(&self, width: usize, height: usize) -> (usize, usize, usize) {
+        let chroma_start = width * height;
+        match self {
+            YuvFormat::Nv12 => (chroma_start, chroma_start + 1, 2),
+            YuvFormat::I420 => {
+                let chroma_len = width.div_ceil(2) * height.div_ceil(2);
+                (chroma_start, chroma_start + chroma_len, 1)
+            }
+        }
+    }
+
+    /// Returns the Y, U and V bytes of the pixel in a tightly packed frame
+    /// or None if the frame is too small for the pixel
+    pub fn get_yuv(
+        &self,
+        frame: &[u8],
+        width: usize,
+        height: usize,
+        x: usize,
+        y: usize,
+    ) -> Option<(u8, u8, u8)> {
+        if x >= width || y >= height || frame.len() < self.get_frame_len(width, height) {
+            return None;
+        }
+        let luma = frame[(y * width) + x];
+        let chroma_width = width.div_ceil(2);
+        let chroma_index = ((y / 2) * chroma_width) + (x / 2);
+        let (u_offset, v_offset, step) = self.get_chroma_layout(width, height);
+        let u = frame[u_offset + (chroma_index * step)];
+        let v = frame[v_offset + (chroma_index * step)];
+        Some((luma, u, v))
+    }
+}
+
+/// Returns the affine matrix that converts normalized (0.0 to 1.0) Y, U, V values into
+/// non-linear R'G'B' values where each row is [y, u, v, offset] for red, green and blue
+pub fn get_yuv_to_rgb_matrix(matrix: YuvMatrix, range: YuvRange) -> [[f32; 4]; 3] {
+    let (kr, kb) = match matrix {
+        YuvMatrix::Bt601 => (0.299, 0.114),
+        YuvMatrix::Bt709 => (0.2126, 0.0722),
+    };
+    let kg = 1.0 - kr - kb;
+    // Scales and offsets that map the stored values to luma 0.0 to 1.0 and chroma -0.5 to 0.5
+    let (y_scale, y_offset, c_scale) = match range {
+        YuvRange::Limited => (255.0 / 219.0, 16.0 / 255.0, 255.0 / 224.0),
+        YuvRange::Full => (1.0, 0.0, 1.0),
+    };
+    let c_offset = 128.0 / 255.0;
+
+    let rv = 2.0 * (1.0 - kr);
+    let bu = 2.0 * (1.0 - kb);
+    let gu = -bu * kb / kg;
+    let gv = -rv * kr / kg;
+    let rows = [[0.0, rv], [gu, gv], [bu, 0.0]];
+    rows.map(|[u, v]| {
+        let u = u * c_scale;
+        let v = v * c_scale;
+        [y_scale, u, v, -(y_scale * y_offset) - ((u + v) * c_offset)]
+    })
+}
+
+/// Converts an 8 bit YUV triple into linear rgb
+///
+/// The video transfer function is approximated by the sRGB curve (BT.709 shares the sRGB primaries)
+/// which is also how most desktop video players display it
+pub fn get_linear_rgb_from_yuv(
+    y: u8,
+    u: u8,
+    v: u8,
+    matrix: YuvMatrix,
+    range: YuvRange,
+) -> [f32; 3] {
+    let yuv = [(y as f32) / 255.0, (u as f32) / 255.0, (v as f32) / 255.0];
+    get_yuv_to_rgb_matrix(matrix, range).map(|row| {
+        let c = (row[0] * yuv[0]) + (row[1] * yuv[1]) + (row[2] * yuv[2]) + row[3];
+        let c = c.clamp(0.0, 1.0);
+        if c > 0.04045 {
+            ((c + 0.055) / 1.055).powf(2.4)
+        } else {
+            c / 12.92
+        }
+    })
+}
+
+/// Converts a limited range BT.709 YUV byte triple (like a decoded call video frame) into linear rgb
+pub fn get_linear_rgb_from_bt709_yuv(y: u8, u: u8, v: u8) -> [f32; 3] {
+    get_linear_rgb_from_yuv(y, u, v, YuvMatrix::Bt709, YuvRange::Limited)
+}
+
 pub struct LinearRGB {
     srgb_lut: [f32; 256],
 }
@@ -56,3 +273,51 @@ impl LinearRGB {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_linear_rgb_black_white_half() {
+        let mixed = mix_linear_rgb([0.0, 0.0, 0.0], [1.0, 1.0, 1.0], 0.5);
+        assert_eq!(mixed, [0.5, 0.5, 0.5]);
+        // The linear midpoint is brighter than the sRGB byte midpoint of 128
+        assert_eq!(get_srgb_byte_from_linear_rgb_float(mixed[0]), 188);
+    }
+
+    #[test]
+    fn yuv_chroma_layout_of_odd_size_frames() {
+        // A 3x3 frame has 9 luma bytes and 2x2 chroma values (rounded up)
+        let frame: Vec<u8> = (0..17).collect();
+        assert_eq!(YuvFormat::Nv12.get_chroma_layout(3, 3), (9, 10, 2));
+        assert_eq!(YuvFormat::I420.get_chroma_layout(3, 3), (9, 13, 1));
+        assert_eq!(
+            YuvFormat::Nv12.get_yuv(&frame, 3, 3, 2, 2),
+            Some((8, 15, 16))
+        );
+        assert_eq!(
+            YuvFormat::I420.get_yuv(&frame, 3, 3, 2, 2),
+            Some((8, 12, 16))
+        );
+        assert_eq!(YuvFormat::I420.get_yuv(&frame[..16], 3, 3, 0, 0), None);
+    }
+
+    #[test]
+    fn straight_alpha_from_premultiplied_half_transparent_red() {
+        // Linear red of 1.0 premultiplied by an alpha of 0.5 is the sRGB byte 188
+        let premultiplied = 0x80_00_00_BC;
+        assert_eq!(
+            get_straight_alpha_from_premultiplied_srgb_pixel(premultiplied),
+            0x80_00_00_FF
+        );
+        assert_eq!(
+            get_straight_alpha_from_premultiplied_srgb_pixel(0x00_12_34_56),
+            0
+        );
+        assert_eq!(
+            get_straight_alpha_from_premultiplied_srgb_pixel(0xFF_12_34_56),
+            0xFF_12_34_56
+        );
+    }
+}
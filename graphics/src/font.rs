@@ -21,6 +21,9 @@
 //SOFTWARE.
 
 use rustybuzz::Direction;
+use std::collections::HashMap;
+
+use crate::DrawJustification;
 
 #[derive(Debug)]
 pub enum Error {
@@ -36,6 +39,7 @@ pub enum Error {
     GlyphNotFoundInFonts(char),
     GlyphOutlineError(char),
     NoGlyphIdInOutlines(u32),
+    NoFonts,
 }
 
 pub struct FontIcons {
@@ -155,6 +159,7 @@ struct FontInfo {
     index: u32,
     dpi_scale: f32,
     outline_offset: usize,
+    outline_lookup: HashMap<u32, u32>, // Glyph id to outline index within the outline range of the font
 }
 
 pub struct Glyphs {
@@ -166,6 +171,7 @@ pub struct Glyphs {
     unicode_buffer_opt: Option<rustybuzz::UnicodeBuffer>,
     shape_features: Vec<rustybuzz::Feature>,
     line_render_info: Vec<GlyphLineRenderInfo>,
+    outlines_changed: bool,
 }
 
 impl Glyphs {
@@ -188,19 +194,40 @@ impl Glyphs {
             unicode_buffer_opt: Some(unicode_buffer),
             shape_features: Vec::new(),
             line_render_info: Vec::new(),
+            outlines_changed: false,
         })
     }
 
     pub fn new_from_font_file(
         font_path: &str,
         font_index: u32,
-        mut rays_per_outline_po2: u8,
+        rays_per_outline_po2: u8,
         _language: &str,
     ) -> Result<Self, Error> {
         let font_data = match std::fs::read(font_path) {
             Ok(d) => d,
             Err(e) => return Err(Error::FileRead(e)),
         };
+        Self::new_from_font_data(font_data, font_index, rays_per_outline_po2)
+    }
+
+    /// Loads the first face of a TTF/OTF font from bytes and creates the outline data for every
+    /// requested character in one call, ready to be used by Vulkan2dWindow::new
+    pub fn from_font_bytes(
+        bytes: &[u8],
+        chars: impl IntoIterator<Item = char>,
+        rays_per_outline_po2: u8,
+    ) -> Result<Self, Error> {
+        let mut glyphs = Self::new_from_font_data(bytes.to_vec(), 0, rays_per_outline_po2)?;
+        glyphs.add_code_point_outline_data(0, chars.into_iter(), false)?;
+        Ok(glyphs)
+    }
+
+    fn new_from_font_data(
+        font_data: Vec<u8>,
+        font_index: u32,
+        mut rays_per_outline_po2: u8,
+    ) -> Result<Self, Error> {
         let face = match rustybuzz::Face::from_slice(&font_data, font_index) {
             Some(f) => f,
             None => return Err(Error::CannotCreateFontFace),
@@ -211,6 +238,7 @@ impl Glyphs {
             index: font_index,
             dpi_scale: 1.0 / (72.0 * (face.units_per_em() as f32)),
             outline_offset: 0,
+            outline_lookup: HashMap::new(),
         }];
         let mut unicode_buffer = rustybuzz::UnicodeBuffer::new();
         //unicode_buffer.set_language(rustybuzz::Language(String::from(language)));
@@ -229,6 +257,7 @@ impl Glyphs {
             unicode_buffer_opt: Some(unicode_buffer),
             shape_features: Vec::new(),
             line_render_info: Vec::new(),
+            outlines_changed: false,
         })
     }
 
@@ -251,6 +280,7 @@ impl Glyphs {
             index: font_index,
             dpi_scale: 1.0 / (72.0 * (units_per_em as f32)),
             outline_offset: self.outline_data.len(),
+            outline_lookup: HashMap::new(),
         });
 
         Ok(())
@@ -273,6 +303,7 @@ impl Glyphs {
             index: font_index,
             dpi_scale: 1.0 / (72.0 * (units_per_em as f32)),
             outline_offset: self.outline_data.len(),
+            outline_lookup: HashMap::new(),
         });
 
         Ok(())
@@ -295,6 +326,16 @@ impl Glyphs {
         }
     }
 
+    /// Returns the outline bounding box of the glyph at the outline data index (icons come first)
+    /// in the font unit texture space that draw_icon passes to the shaders through set_render_info,
+    /// or None when the index is out of range or the glyph has no outline
+    pub fn glyph_bounds(&self, index: usize) -> Option<crate::vulkan::PrimitiveRect> {
+        match self.outline_data.get(index) {
+            Some(outline) => outline.get_bounds(),
+            None => None,
+        }
+    }
+
     fn does_font_exist(&self, font: usize) -> Result<(), Error> {
         if font < self.font_infos.len() {
             Ok(())
@@ -329,6 +370,7 @@ impl Glyphs {
             index: font_index,
             dpi_scale: font_info.dpi_scale,
             outline_offset: self.outline_data.len(),
+            outline_lookup: HashMap::new(),
         });
 
         Ok(())
@@ -353,17 +395,42 @@ impl Glyphs {
         code_point_start: char,
         code_point_end: char,
     ) -> Result<(), Error> {
+        self.add_code_point_outline_data(font, code_point_start..=code_point_end, false)?;
+        Ok(())
+    }
+
+    /// Lazy glyph loading for arbitrary text (such as chat messages) where preloading every
+    /// possible glyph is wasteful: report the characters about to be drawn from the tick
+    /// callback and any missing outlines get loaded from the font, characters that the font
+    /// does not have are skipped. The window uploads the new outlines to the GPU before the
+    /// next draw. Returns the number of newly loaded outlines
+    pub fn load_missing_chars(
+        &mut self,
+        font: usize,
+        chars: impl IntoIterator<Item = char>,
+    ) -> Result<usize, Error> {
+        self.add_code_point_outline_data(font, chars.into_iter(), true)
+    }
+
+    // Returns true once after outlines were added so the GPU glyph data can be updated
+    pub(super) fn take_outlines_changed(&mut self) -> bool {
+        let outlines_changed = self.outlines_changed;
+        self.outlines_changed = false;
+        outlines_changed
+    }
+
+    // Code points that share a glyph with an existing outline are skipped
+    fn add_code_point_outline_data(
+        &mut self,
+        font: usize,
+        code_points: impl Iterator<Item = char>,
+        skip_missing: bool,
+    ) -> Result<usize, Error> {
         let font_face = self.get_font_face(font)?;
-        let outline_index_start = if font == 0 {
-            self.num_icons
-        } else {
-            self.font_infos[font - 1].outline_offset
-        };
-        let outline_index_end = self.font_infos[font].outline_offset;
+        let (outline_index_start, outline_index_end) = self.get_font_outline_range(font);
 
-        let num_code_points = (code_point_end as usize) - (code_point_start as usize);
-        let mut new_outline_data: Vec<GlyphOutlineData> = Vec::with_capacity(num_code_points);
-        for cp in code_point_start..=code_point_end {
+        let mut new_outline_data: Vec<GlyphOutlineData> = Vec::new();
+        for cp in code_points {
             if let Some(glyph_id) = font_face.glyph_index(cp) {
                 let glyph_id_value = glyph_id.0 as u32;
                 match self.outline_data[outline_index_start..outline_index_end]
@@ -404,10 +471,13 @@ impl Glyphs {
                 // Compare bounding box in future
                 god.sort_segments_and_create_additional_segments(self.rays_per_outline_po2);
                 new_outline_data.push(god);
-            } else {
+            } else if !skip_missing {
                 return Err(Error::NoGlyphIndex(cp));
             }
         }
+        if new_outline_data.is_empty() {
+            return Ok(0);
+        }
 
         let new_outline_count = new_outline_data.len();
         self.outline_data.reserve(new_outline_count);
@@ -419,11 +489,28 @@ impl Glyphs {
         self.outline_data[outline_index_start..outline_index_end]
             .sort_unstable_by(|a, b| a.glyph_id.cmp(&b.glyph_id));
 
+        // Sorting moves the outline indicies so the whole lookup of the font is rebuilt
+        let outline_lookup = &mut self.font_infos[font].outline_lookup;
+        outline_lookup.clear();
+        for (ind, od) in self.outline_data[outline_index_start..outline_index_end]
+            .iter()
+            .enumerate()
+        {
+            outline_lookup.insert(od.glyph_id, ind as u32);
+        }
+
         for fi in &mut self.font_infos[font..] {
             fi.outline_offset += new_outline_count;
         }
+        self.outlines_changed = true;
 
-        Ok(())
+        Ok(new_outline_count)
+    }
+
+    /// Returns true when none of the outlines have segments, such as when the font did not load
+    /// any glyphs, which the renderers cannot create their glyph buffers from
+    pub fn is_empty(&self) -> bool {
+        self.outline_data.iter().all(|od| od.segments.is_empty())
     }
 
     pub fn get_glyph_outline_data(&self) -> (&[GlyphOutlineData], u8) {
@@ -464,12 +551,7 @@ impl Glyphs {
     ) -> Result<&[GlyphLineRenderInfo], Error> {
         if let Some(unicode_buffer) = self.unicode_buffer_opt.take() {
             let font_face = self.get_font_face(font)?;
-            let outline_index_start = if font == 0 {
-                0
-            } else {
-                self.font_infos[font - 1].outline_offset
-            };
-            let outline_index_end = self.font_infos[font].outline_offset;
+            let (outline_index_start, _outline_index_end) = self.get_font_outline_range(font);
             let dpi_scale = self.font_infos[font].dpi_scale;
             let scale = (pt_size as f32) * dpi_scale * dpi; // 92.36;
             let dp = 1.0 / scale;
@@ -479,12 +561,11 @@ impl Glyphs {
             let glyph_positions = glyph_buffer.glyph_positions();
             for (gp_ind, gp) in glyph_positions.iter().enumerate() {
                 let glyph_id = glyph_infos[gp_ind].glyph_id;
-                // Could cache certain high probability glyphs in future
-                let outline_index = match self.outline_data[outline_index_start..outline_index_end]
-                    .binary_search_by(|od| od.glyph_id.cmp(&glyph_id))
-                {
-                    Ok(found_ind) => found_ind,
-                    Err(_insert_ind) => return Err(Error::NoGlyphIdInOutlines(glyph_id)),
+                // The lookup is within the font so the start of the font outlines is added
+                // to get the index of the whole glyph data (icons and earlier fonts come first)
+                let outline_index = match self.font_infos[font].outline_lookup.get(&glyph_id) {
+                    Some(ind) => outline_index_start + (*ind as usize),
+                    None => return Err(Error::NoGlyphIdInOutlines(glyph_id)),
                 };
                 let lri = if self.outline_data[outline_index].get_num_segments() > 0 {
                     let u_min = self.outline_data[outline_index].x_min - dp;
@@ -526,12 +607,7 @@ impl Glyphs {
 
     pub fn get_font_face_shaper(&self, font: usize) -> Result<GlyphFaceShaper, Error> {
         let font_face = self.get_font_face(font)?;
-        let outline_index_start = if font == 0 {
-            0
-        } else {
-            self.font_infos[font - 1].outline_offset
-        };
-        let outline_index_end = self.font_infos[font].outline_offset;
+        let (outline_index_start, outline_index_end) = self.get_font_outline_range(font);
         let dpi_scale = self.font_infos[font].dpi_scale;
         let plan = rustybuzz::ShapePlan::new(
             &font_face,
@@ -547,8 +623,65 @@ impl Glyphs {
             dpi_scale,
             outline_index_offset: outline_index_start,
             outline_indicies: &self.outline_data[outline_index_start..outline_index_end],
+            outline_lookup: &self.font_infos[font].outline_lookup,
         })
     }
+
+    /// Returns a shaper that combines multiple fonts (such as a Latin UI font with a CJK fallback font)
+    /// where each character is drawn with the first font in the list that has a glyph for it
+    ///
+    /// The outlines of every font are part of the same glyph data so text that mixes scripts
+    /// can be drawn with a single draw_glyphs call, the outline indicies of all of the fonts together
+    /// still have to fit in the 30 bit glyph index field of the primitive data
+    pub fn get_font_fallback_shaper(
+        &self,
+        fonts: &[usize],
+    ) -> Result<GlyphFallbackShaper<'_>, Error> {
+        if fonts.is_empty() {
+            return Err(Error::NoFonts);
+        }
+        let mut face_shapers = Vec::with_capacity(fonts.len());
+        for font in fonts {
+            face_shapers.push(self.get_font_face_shaper(*font)?);
+        }
+        Ok(GlyphFallbackShaper { face_shapers })
+    }
+
+    pub fn get_num_fonts(&self) -> usize {
+        self.font_infos.len()
+    }
+
+    /// Returns the index of the first outline of a font in the whole glyph data
+    ///
+    /// Every font (face) gets a contiguous range of outlines after the icons and the fonts before it
+    /// so an outline index within a font plus this offset is the primitive glyph index
+    pub fn get_font_outline_offset(&self, font: usize) -> Result<usize, Error> {
+        self.does_font_exist(font)?;
+        let (outline_index_start, _outline_index_end) = self.get_font_outline_range(font);
+        Ok(outline_index_start)
+    }
+
+    /// Returns the outline index of a glyph id within the outlines of a font, or None when its outline
+    /// is not loaded
+    ///
+    /// The lookup is kept across frames (and updated when outlines are added) so it does not need
+    /// to search the outlines, the index plus the start of the font outlines is the primitive glyph index
+    pub fn get_outline_index(&self, font: usize, glyph_id: u32) -> Option<usize> {
+        match self.font_infos.get(font) {
+            Some(fi) => fi.outline_lookup.get(&glyph_id).map(|ind| *ind as usize),
+            None => None,
+        }
+    }
+
+    // Returns the outline data index range of a font (the icons come before the first font)
+    fn get_font_outline_range(&self, font: usize) -> (usize, usize) {
+        let outline_index_start = if font == 0 {
+            self.num_icons
+        } else {
+            self.font_infos[font - 1].outline_offset
+        };
+        (outline_index_start, self.font_infos[font].outline_offset)
+    }
 }
 
 pub type GlyphOutlinePoint = (f32, f32);
@@ -664,6 +797,21 @@ impl GlyphOutlineData {
         &self.segments
     }
 
+    /// Returns the outline bounding box in the same font unit texture space that set_render_info
+    /// uses (y up), x and y are the minimum corner, or None when the glyph has no outline segments
+    pub fn get_bounds(&self) -> Option<crate::vulkan::PrimitiveRect> {
+        if !self.segments.is_empty() {
+            Some(crate::vulkan::PrimitiveRect {
+                x: self.x_min,
+                y: self.y_min,
+                w: self.x_max - self.x_min,
+                h: self.y_max - self.y_min,
+            })
+        } else {
+            None
+        }
+    }
+
     pub fn set_render_info(
         &self,
         tex_min: &mut GlyphOutlinePoint,
@@ -793,9 +941,14 @@ pub struct GlyphFaceShaper<'a> {
     pub(super) dpi_scale: f32,
     pub(super) outline_index_offset: usize,
     pub(super) outline_indicies: &'a [GlyphOutlineData],
+    pub(super) outline_lookup: &'a HashMap<u32, u32>,
 }
 
 impl<'a> GlyphFaceShaper<'a> {
+    pub(super) fn get_outline_index(&self, glyph_id: u32) -> Option<usize> {
+        self.outline_lookup.get(&glyph_id).map(|ind| *ind as usize)
+    }
+
     pub fn get_ascender_descender_gap(&self, pt_size: u32, dpi: f32) -> (f32, f32, f32) {
         let scale = (pt_size as f32) * self.dpi_scale * dpi;
         let a = self.font_face.ascender();
@@ -809,31 +962,327 @@ impl<'a> GlyphFaceShaper<'a> {
         (ascender, descender, line_gap)
     }
 
+    /// Returns the unshaped horizontal advance in pixels of a single code point,
+    /// or 0.0 when the face does not have a glyph for it
+    ///
+    /// Shaped text can be narrower or wider (kerning and ligatures), use measure on the shaped text for lines
+    pub fn advance(&self, code_point: char, pt_size: u32, dpi: f32) -> f32 {
+        let scale = (pt_size as f32) * self.dpi_scale * dpi;
+        match self.font_face.glyph_index(code_point) {
+            Some(glyph_id) => match self.font_face.glyph_hor_advance(glyph_id) {
+                Some(advance) => (advance as f32) * scale,
+                None => 0.0,
+            },
+            None => 0.0,
+        }
+    }
+
     pub fn create_glyph_buffer_render_info(
         &self,
         pt_size: u32,
         dpi: f32,
-        mut text_buffer: TextBuffer,
+        text_buffer: TextBuffer,
     ) -> GlyphBufferRenderInfo {
-        text_buffer
-            .unicode_buffer
-            .set_script(rustybuzz::script::UNKNOWN);
-        let glyph_buffer =
-            rustybuzz::shape_with_plan(&self.font_face, &self.plan, text_buffer.unicode_buffer);
-        let scale = (pt_size as f32) * self.dpi_scale * dpi;
+        let (ascender, descender, _line_gap) = self.get_ascender_descender_gap(pt_size, dpi);
         GlyphBufferRenderInfo {
+            runs: vec![self.shape_run(pt_size, dpi, text_buffer.unicode_buffer)],
+            ascender,
+            descender,
+            chars: text_buffer.chars,
+            tab_clusters: text_buffer.tab_clusters,
+            tab_width: 0.0,
+            positioning: GlyphPositioning::Subpixel,
+        }
+    }
+
+    /// Wraps the text into lines no wider than max_width pixels and shapes every line on its own,
+    /// the line_spacing multiplies the ascender plus descender to get the distance between baselines
+    pub fn create_paragraph_layout(
+        &self,
+        pt_size: u32,
+        dpi: f32,
+        text: &str,
+        max_width: f32,
+        line_spacing: f32,
+    ) -> ParagraphLayout<'_> {
+        ParagraphLayout::new(text, max_width, line_spacing, |text_buffer| {
+            self.create_glyph_buffer_render_info(pt_size, dpi, text_buffer)
+        })
+    }
+
+    fn shape_run(
+        &self,
+        pt_size: u32,
+        dpi: f32,
+        mut unicode_buffer: rustybuzz::UnicodeBuffer,
+    ) -> GlyphRun<'a> {
+        unicode_buffer.set_script(rustybuzz::script::UNKNOWN);
+        // Shaping merges the character clusters of each grapheme together
+        unicode_buffer.set_cluster_level(rustybuzz::BufferClusterLevel::MonotoneGraphemes);
+        let glyph_buffer = rustybuzz::shape_with_plan(&self.font_face, &self.plan, unicode_buffer);
+        let scale = (pt_size as f32) * self.dpi_scale * dpi;
+        GlyphRun {
             glyph_buffer,
             scale,
             dp: 1.0 / scale,
             outline_index_offset: self.outline_index_offset as u32,
             outline_indicies: self.outline_indicies,
+            outline_lookup: self.outline_lookup,
+        }
+    }
+}
+
+/// Shapes text with multiple font faces that are chosen per character, see Glyphs::get_font_fallback_shaper
+///
+/// The text gets split into runs of characters that use the same face and each run is shaped on its own
+/// so ligatures and kerning only apply within a run
+pub struct GlyphFallbackShaper<'a> {
+    pub(super) face_shapers: Vec<GlyphFaceShaper<'a>>,
+}
+
+impl<'a> GlyphFallbackShaper<'a> {
+    /// Returns the largest ascender, descender and line gap of all of the faces
+    /// so that lines with mixed scripts get a consistent height
+    pub fn get_ascender_descender_gap(&self, pt_size: u32, dpi: f32) -> (f32, f32, f32) {
+        let mut max_gaps = (0.0_f32, 0.0_f32, 0.0_f32);
+        for fs in &self.face_shapers {
+            let gaps = fs.get_ascender_descender_gap(pt_size, dpi);
+            max_gaps.0 = max_gaps.0.max(gaps.0);
+            max_gaps.1 = max_gaps.1.max(gaps.1);
+            max_gaps.2 = max_gaps.2.max(gaps.2);
         }
+        max_gaps
+    }
+
+    pub fn create_glyph_buffer_render_info(
+        &self,
+        pt_size: u32,
+        dpi: f32,
+        mut text_buffer: TextBuffer,
+    ) -> GlyphBufferRenderInfo<'a> {
+        let mut runs = Vec::new();
+        let mut run_face = 0;
+        let mut run_start = 0;
+        for (char_index, c) in text_buffer.chars.iter().enumerate() {
+            let face = self.get_char_face(*c, run_face);
+            if face != run_face {
+                if char_index > run_start {
+                    let mut unicode_buffer = rustybuzz::UnicodeBuffer::new();
+                    add_run_chars(
+                        &mut unicode_buffer,
+                        &text_buffer.chars[run_start..char_index],
+                        run_start,
+                    );
+                    runs.push(self.face_shapers[run_face].shape_run(pt_size, dpi, unicode_buffer));
+                }
+                run_face = face;
+                run_start = char_index;
+            }
+        }
+        // The original buffer is reused for the last run so there is always at least one run
+        text_buffer.unicode_buffer.clear();
+        add_run_chars(
+            &mut text_buffer.unicode_buffer,
+            &text_buffer.chars[run_start..],
+            run_start,
+        );
+        runs.push(self.face_shapers[run_face].shape_run(pt_size, dpi, text_buffer.unicode_buffer));
+
+        let (ascender, descender, _line_gap) = self.get_ascender_descender_gap(pt_size, dpi);
+        GlyphBufferRenderInfo {
+            runs,
+            ascender,
+            descender,
+            chars: text_buffer.chars,
+            tab_clusters: text_buffer.tab_clusters,
+            tab_width: 0.0,
+            positioning: GlyphPositioning::Subpixel,
+        }
+    }
+
+    /// Same as GlyphFaceShaper::create_paragraph_layout with the faces chosen per character
+    pub fn create_paragraph_layout(
+        &self,
+        pt_size: u32,
+        dpi: f32,
+        text: &str,
+        max_width: f32,
+        line_spacing: f32,
+    ) -> ParagraphLayout<'a> {
+        ParagraphLayout::new(text, max_width, line_spacing, |text_buffer| {
+            self.create_glyph_buffer_render_info(pt_size, dpi, text_buffer)
+        })
+    }
+
+    // Whitespace and characters that continue a grapheme cluster stay with the current face
+    // so that runs do not split in between words or marks and their base characters
+    pub(super) fn get_char_face(&self, c: char, current_face: usize) -> usize {
+        let current_has_glyph = self.face_shapers[current_face]
+            .font_face
+            .glyph_index(c)
+            .is_some();
+        if current_has_glyph && (c.is_whitespace() || is_cluster_continuation(c)) {
+            return current_face;
+        }
+        for (face, fs) in self.face_shapers.iter().enumerate() {
+            if fs.font_face.glyph_index(c).is_some() {
+                return face;
+            }
+        }
+        current_face
+    }
+}
+
+// The clusters stay the character indicies of the whole text
+fn add_run_chars(unicode_buffer: &mut rustybuzz::UnicodeBuffer, chars: &[char], run_start: usize) {
+    for (char_index, c) in chars.iter().enumerate() {
+        unicode_buffer.add(*c, (run_start + char_index) as u32);
+    }
+}
+
+// Combining marks, zero width joiners and variation selectors (the common ranges)
+fn is_cluster_continuation(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x200C..=0x200D
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F
+            | 0xFE20..=0xFE2F
+            | 0xE0100..=0xE01EF
+    )
+}
+
+/// Kind of line break opportunity before a character, see find_line_breaks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineBreak {
+    /// The line has to end here (after a line feed, carriage return or paragraph separator)
+    Mandatory,
+    /// The line can end here when the text needs to wrap
+    Allowed,
+}
+
+/// Returns the character indicies (with the kind of break) where a new line can start
+///
+/// Supports a subset of the Unicode line breaking algorithm (UAX #14) with these break classes:
+/// mandatory breaks (line feed, carriage return not broken from a following line feed, vertical tab,
+/// form feed, next line and the line and paragraph separators), breaks after spaces,
+/// breaks before and after CJK ideographs, kana and Hangul syllables, no break before closing
+/// punctuation (and small kana or the prolonged sound mark), no break after opening punctuation,
+/// no break around non-breaking (glue) characters and no break before combining marks.
+/// Every other character (like Latin letters) does not break from its neighbors so words stay whole.
+/// Complex scripts that need dictionary based breaking (like Thai, Lao and Khmer) are not handled
+/// and only break at spaces
+pub fn find_line_breaks(text: &str) -> Vec<(usize, LineBreak)> {
+    let chars: Vec<char> = text.chars().collect();
+    get_line_breaks(&chars)
+}
+
+fn get_line_breaks(chars: &[char]) -> Vec<(usize, LineBreak)> {
+    let mut breaks = Vec::new();
+    let mut classes = chars.iter().map(|c| get_break_class(*c));
+    let mut before = match classes.next() {
+        Some(c) => c,
+        None => return breaks,
+    };
+    for (char_index, after) in classes.enumerate().map(|(i, c)| (i + 1, c)) {
+        if let Some(line_break) = get_pair_break(before, after) {
+            breaks.push((char_index, line_break));
+        }
+        // Combining marks take the class of the character they combine with
+        if after != BreakClass::Combining {
+            before = after;
+        }
+    }
+    if matches!(
+        before,
+        BreakClass::Mandatory | BreakClass::CarriageReturn | BreakClass::LineFeed
+    ) {
+        breaks.push((chars.len(), LineBreak::Mandatory));
+    }
+    breaks
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BreakClass {
+    Mandatory,
+    CarriageReturn,
+    LineFeed,
+    Space,
+    Glue,
+    Ideographic,
+    Open,
+    Close,
+    Combining,
+    Alphabetic,
+}
+
+fn get_break_class(c: char) -> BreakClass {
+    match c {
+        '\n' => BreakClass::LineFeed,
+        '\r' => BreakClass::CarriageReturn,
+        '\u{000B}' | '\u{000C}' | '\u{0085}' | '\u{2028}' | '\u{2029}' => BreakClass::Mandatory,
+        ' ' | '\t' | '\u{3000}' => BreakClass::Space,
+        '\u{00A0}' | '\u{2007}' | '\u{202F}' | '\u{2060}' | '\u{FEFF}' => BreakClass::Glue,
+        '(' | '[' | '{' | '\u{3008}' | '\u{300A}' | '\u{300C}' | '\u{300E}' | '\u{3010}'
+        | '\u{3014}' | '\u{3016}' | '\u{3018}' | '\u{301A}' | '\u{FF08}' | '\u{FF3B}'
+        | '\u{FF5B}' => BreakClass::Open,
+        ')' | ']' | '}' | ',' | '.' | '!' | '?' | ':' | ';' | '\u{3001}' | '\u{3002}'
+        | '\u{3009}' | '\u{300B}' | '\u{300D}' | '\u{300F}' | '\u{3011}' | '\u{3015}'
+        | '\u{3017}' | '\u{3019}' | '\u{301B}' | '\u{3005}' | '\u{303B}' | '\u{30FC}'
+        | '\u{FF01}' | '\u{FF09}' | '\u{FF0C}' | '\u{FF0E}' | '\u{FF1A}' | '\u{FF1B}'
+        | '\u{FF1F}' | '\u{FF3D}' | '\u{FF5D}' => BreakClass::Close,
+        // Small kana
+        '\u{3041}' | '\u{3043}' | '\u{3045}' | '\u{3047}' | '\u{3049}' | '\u{3063}'
+        | '\u{3083}' | '\u{3085}' | '\u{3087}' | '\u{308E}' | '\u{30A1}' | '\u{30A3}'
+        | '\u{30A5}' | '\u{30A7}' | '\u{30A9}' | '\u{30C3}' | '\u{30E3}' | '\u{30E5}'
+        | '\u{30E7}' | '\u{30EE}' | '\u{30F5}' | '\u{30F6}' => BreakClass::Close,
+        _ if is_cluster_continuation(c) => BreakClass::Combining,
+        _ => match c as u32 {
+            0x2E80..=0x2FFF
+            | 0x3040..=0x30FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xAC00..=0xD7AF
+            | 0xF900..=0xFAFF
+            | 0xFF00..=0xFFEF
+            | 0x20000..=0x3FFFF => BreakClass::Ideographic,
+            _ => BreakClass::Alphabetic,
+        },
+    }
+}
+
+// Returns the break opportunity in between two characters of the classes (in rule order)
+fn get_pair_break(before: BreakClass, after: BreakClass) -> Option<LineBreak> {
+    match (before, after) {
+        (BreakClass::CarriageReturn, BreakClass::LineFeed) => None,
+        (BreakClass::Mandatory | BreakClass::CarriageReturn | BreakClass::LineFeed, _) => {
+            Some(LineBreak::Mandatory)
+        }
+        (
+            _,
+            BreakClass::Mandatory
+            | BreakClass::CarriageReturn
+            | BreakClass::LineFeed
+            | BreakClass::Space
+            | BreakClass::Combining,
+        ) => None,
+        (BreakClass::Glue, _) | (_, BreakClass::Glue) => None,
+        (_, BreakClass::Close) | (BreakClass::Open, _) => None,
+        (BreakClass::Space, _) | (BreakClass::Close, BreakClass::Open) => Some(LineBreak::Allowed),
+        (BreakClass::Ideographic, _) | (_, BreakClass::Ideographic) => Some(LineBreak::Allowed),
+        _ => None,
     }
 }
 
 #[derive(Default)]
 pub struct TextBuffer {
     unicode_buffer: rustybuzz::UnicodeBuffer,
+    chars: Vec<char>, // Kept for splitting the text up into runs when shaping with multiple faces
+    text_len: u32,
+    tab_clusters: Vec<u32>,
 }
 
 // impl Default for TextBuffer {
@@ -845,23 +1294,467 @@ pub struct TextBuffer {
 // }
 
 impl TextBuffer {
+    /// Adds text to be shaped together with any previously added text
+    ///
+    /// Shaping works on grapheme clusters so a base character with combining marks (like e followed by
+    /// a combining acute accent) and emoji sequences joined with zero width joiners or modifiers end up
+    /// in one cluster, the font decides whether that cluster is drawn with a single composed glyph or
+    /// with positioned mark glyphs and emoji sequences only become one glyph when the font has a
+    /// ligature for them. Grapheme clusters are kept together when drawing along paths and arcs.
     pub fn add_text(&mut self, text: &str) {
-        self.unicode_buffer.push_str(text);
+        // Characters are added individually so that the clusters are character indicies that keep
+        //  increasing across calls and the tab characters can be found again after shaping
+        for c in text.chars() {
+            if c == '\t' {
+                self.tab_clusters.push(self.text_len);
+            }
+            self.unicode_buffer.add(c, self.text_len);
+            self.chars.push(c);
+            self.text_len += 1;
+        }
     }
 }
 
-pub struct GlyphBufferRenderInfo<'a> {
+/// How draw_glyphs places each glyph on the pixel grid
+///
+/// Subpixel keeps the exact shaped positions for the most accurate spacing (better for prose),
+/// glyphs can then land on different pixel fractions so the same character can look slightly
+/// different (softer) from one spot to the next.
+/// PixelSnapped rounds the final position of each glyph (the pen position plus the glyph offsets)
+/// to a whole pixel so every copy of a glyph gets rendered the same and small text looks crisper
+/// (better for UI), at the cost of up to half a pixel of spacing error per glyph
+/// (the advance itself is not rounded so the error does not accumulate)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GlyphPositioning {
+    #[default]
+    Subpixel,
+    PixelSnapped,
+}
+
+// Glyphs shaped with a single face in text order
+pub(super) struct GlyphRun<'a> {
     pub(super) glyph_buffer: rustybuzz::GlyphBuffer,
     pub(super) scale: f32,
     pub(super) dp: f32,
     pub(super) outline_index_offset: u32,
     pub(super) outline_indicies: &'a [GlyphOutlineData],
+    pub(super) outline_lookup: &'a HashMap<u32, u32>,
+}
+
+impl<'a> GlyphRun<'a> {
+    pub(super) fn get_outline_index(&self, glyph_id: u32) -> Option<usize> {
+        self.outline_lookup.get(&glyph_id).map(|ind| *ind as usize)
+    }
+}
+
+pub struct GlyphBufferRenderInfo<'a> {
+    pub(super) runs: Vec<GlyphRun<'a>>,
+    ascender: f32,
+    descender: f32,
+    chars: Vec<char>,
+    pub(super) tab_clusters: Vec<u32>,
+    pub(super) tab_width: f32,
+    pub(super) positioning: GlyphPositioning,
 }
 
 impl<'a> GlyphBufferRenderInfo<'a> {
-    pub fn get_text_buffer(self) -> TextBuffer {
+    pub fn get_text_buffer(mut self) -> TextBuffer {
+        self.tab_clusters.clear();
+        self.chars.clear();
         TextBuffer {
-            unicode_buffer: self.glyph_buffer.clear(),
+            unicode_buffer: self.runs.swap_remove(0).glyph_buffer.clear(),
+            chars: self.chars,
+            text_len: 0,
+            tab_clusters: self.tab_clusters,
         }
     }
+
+    /// Sets the distance in pixels between tab stops
+    ///
+    /// When drawn a tab character advances to the next tab stop (measured from the start of the line)
+    /// instead of by its own advance, a tab width of 0.0 (the default) disables the tab stops
+    pub fn set_tab_width(&mut self, tab_width: f32) {
+        self.tab_width = tab_width.max(0.0);
+    }
+
+    /// Sets how draw_glyphs places each glyph on the pixel grid, Subpixel is the default
+    pub fn set_positioning(&mut self, positioning: GlyphPositioning) {
+        self.positioning = positioning;
+    }
+
+    pub(super) fn is_tab(&self, cluster: u32) -> bool {
+        (self.tab_width > 0.0) && self.tab_clusters.binary_search(&cluster).is_ok()
+    }
+
+    // Returns the line x distance of the next tab stop after the line x distance
+    pub(super) fn get_next_tab_stop(&self, line_x: f32) -> f32 {
+        ((line_x / self.tab_width).floor() + 1.0) * self.tab_width
+    }
+    /// Returns the width in pixels of the whole line when drawn
+    pub fn get_line_width(&self) -> f32 {
+        self.get_line_distance_before(u32::MAX)
+    }
+
+    /// Returns the (width, height) in pixels of the line when drawn without drawing it,
+    /// the height is the ascender plus the descender like the selection_rect height
+    pub fn measure(&self) -> (f32, f32) {
+        (self.get_line_width(), self.ascender + self.descender)
+    }
+
+    /// Returns the pixel x of the start of the line when drawn at x with the justification
+    pub fn get_line_start_x(&self, x: f32, justification: DrawJustification) -> f32 {
+        match justification {
+            DrawJustification::Left => x,
+            DrawJustification::Right => x - self.get_line_width(),
+            DrawJustification::Center => x - (self.get_line_width() * 0.5),
+        }
+    }
+
+    /// Returns the pixel x of a text caret placed before the character at char_index
+    /// when the line is drawn at x with the justification
+    ///
+    /// A char_index at or past the end of the text places the caret after the last glyph
+    /// and a char_index inside of a ligature (or another multiple character cluster) places the caret
+    /// after the cluster like the distances used for wrapping
+    pub fn get_caret_x(&self, x: f32, justification: DrawJustification, char_index: usize) -> f32 {
+        let char_index = char_index.min(u32::MAX as usize) as u32;
+        self.get_line_start_x(x, justification) + self.get_line_distance_before(char_index)
+    }
+
+    /// Returns the (top_left, dimensions) pixel rectangle that covers the characters from
+    /// start_index up to (but not including) end_index when the line is drawn at p0 with the justification
+    ///
+    /// The rectangle spans from the font ascender to the font descender so that it can be drawn
+    /// as a highlight behind the selected glyphs before drawing the text, reversed ranges are swapped
+    pub fn selection_rect(
+        &self,
+        p0: &crate::vulkan::PrimitivePosition,
+        justification: DrawJustification,
+        start_index: usize,
+        end_index: usize,
+    ) -> ((f32, f32), (f32, f32)) {
+        let (start_index, end_index) = if end_index < start_index {
+            (end_index, start_index)
+        } else {
+            (start_index, end_index)
+        };
+        let x0 = self.get_caret_x(p0.x, justification, start_index);
+        let x1 = self.get_caret_x(p0.x, justification, end_index);
+        (
+            (x0, p0.y - self.ascender),
+            (x1 - x0, self.ascender + self.descender),
+        )
+    }
+
+    /// Returns the character indicies where each line starts when the text wraps at max_width pixels,
+    /// the first line always starts at 0 (see find_line_breaks for where lines can break)
+    ///
+    /// Trailing spaces of a line do not count towards its width and a line without a break
+    /// opportunity that is wider than max_width stays whole. The widths come from shaping the text as
+    /// a single line so each wrapped line should be shaped again (the text in between two starts)
+    /// before drawing it, tab stops are measured from the start of the text
+    pub fn get_wrapped_line_starts(&self, max_width: f32) -> Vec<usize> {
+        let distances = self.get_char_distances();
+        let line_width = |start: usize, end: usize| {
+            distances[self.get_trimmed_line_end(start, end)] - distances[start]
+        };
+
+        let mut line_starts = vec![0];
+        let mut line_start = 0;
+        let mut last_fit = None;
+        let mut breaks = get_line_breaks(&self.chars);
+        // The end of the text is checked like an allowed break that never starts a new line
+        breaks.push((self.chars.len(), LineBreak::Allowed));
+        let last_ind = breaks.len() - 1;
+        for (ind, (char_index, line_break)) in breaks.into_iter().enumerate() {
+            if line_width(line_start, char_index) > max_width {
+                if let Some(fit) = last_fit.take() {
+                    line_starts.push(fit);
+                    line_start = fit;
+                }
+            }
+            if ind == last_ind {
+                break;
+            }
+            match line_break {
+                LineBreak::Mandatory => {
+                    line_starts.push(char_index);
+                    line_start = char_index;
+                    last_fit = None;
+                }
+                LineBreak::Allowed => last_fit = Some(char_index),
+            }
+        }
+        line_starts
+    }
+
+    /// Same as get_wrapped_line_starts except that a line without a break opportunity that is wider
+    /// than max_width gets broken in between grapheme clusters (at least one per line)
+    pub fn get_hard_wrapped_line_starts(&self, max_width: f32) -> Vec<usize> {
+        let distances = self.get_char_distances();
+        let soft_line_starts = self.get_wrapped_line_starts(max_width);
+        let mut line_starts = Vec::with_capacity(soft_line_starts.len());
+        for (ind, soft_start) in soft_line_starts.iter().enumerate() {
+            let line_end = match soft_line_starts.get(ind + 1) {
+                Some(next_start) => *next_start,
+                None => self.chars.len(),
+            };
+            let mut line_start = *soft_start;
+            line_starts.push(line_start);
+            while (distances[self.get_trimmed_line_end(line_start, line_end)]
+                - distances[line_start])
+                > max_width
+            {
+                let mut break_index = None;
+                for char_index in (line_start + 1)..line_end {
+                    if is_cluster_continuation(self.chars[char_index]) {
+                        continue;
+                    }
+                    if (distances[char_index] - distances[line_start]) > max_width {
+                        if break_index.is_none() {
+                            break_index = Some(char_index);
+                        }
+                        break;
+                    }
+                    break_index = Some(char_index);
+                }
+                match break_index {
+                    Some(b) => {
+                        line_starts.push(b);
+                        line_start = b;
+                    }
+                    None => break, // A single grapheme cluster wider than max_width
+                }
+            }
+        }
+        line_starts
+    }
+
+    // Returns the end of the line without the trailing spaces that do not count towards its width
+    pub(super) fn get_trimmed_line_end(&self, start: usize, end: usize) -> usize {
+        let mut end = end.min(self.chars.len());
+        while (end > start) && (get_break_class(self.chars[end - 1]) == BreakClass::Space) {
+            end -= 1;
+        }
+        end
+    }
+
+    pub(super) fn get_num_chars(&self) -> usize {
+        self.chars.len()
+    }
+
+    // Returns the line x distance before every character index (including the end of the text)
+    pub(super) fn get_char_distances(&self) -> Vec<f32> {
+        let mut distances = Vec::with_capacity(self.chars.len() + 1);
+        let mut line_x = 0.0;
+        for run in &self.runs {
+            let glyph_infos = run.glyph_buffer.glyph_infos();
+            let glyph_positions = run.glyph_buffer.glyph_positions();
+            for (gp_ind, gp) in glyph_positions.iter().enumerate() {
+                let cluster = glyph_infos[gp_ind].cluster as usize;
+                // Characters inside of a cluster get the distance after it like get_line_distance_before
+                while distances.len() <= cluster.min(self.chars.len()) {
+                    distances.push(line_x);
+                }
+                if self.is_tab(cluster as u32) {
+                    line_x = self.get_next_tab_stop(line_x);
+                } else {
+                    line_x += (gp.x_advance as f32) * run.scale;
+                }
+            }
+        }
+        while distances.len() <= self.chars.len() {
+            distances.push(line_x);
+        }
+        distances
+    }
+
+    // Returns the line x distance covered by the glyphs of the characters before char_index
+    fn get_line_distance_before(&self, char_index: u32) -> f32 {
+        let mut line_x = 0.0;
+        for run in &self.runs {
+            let glyph_infos = run.glyph_buffer.glyph_infos();
+            let glyph_positions = run.glyph_buffer.glyph_positions();
+            for (gp_ind, gp) in glyph_positions.iter().enumerate() {
+                let cluster = glyph_infos[gp_ind].cluster;
+                if cluster >= char_index {
+                    // Clusters keep increasing across the runs
+                    return line_x;
+                }
+                if self.is_tab(cluster) {
+                    line_x = self.get_next_tab_stop(line_x);
+                } else {
+                    line_x += (gp.x_advance as f32) * run.scale;
+                }
+            }
+        }
+        line_x
+    }
+}
+
+/// Text wrapped into lines no wider than a max width where every line is shaped on its own
+///
+/// Unlike GlyphBufferRenderInfo::draw_paragraph the kerning and ligatures of a line never
+/// depend on the text of the neighboring lines and tab stops start again at every line,
+/// create one with GlyphFaceShaper::create_paragraph_layout (or the fallback shaper version)
+pub struct ParagraphLayout<'a> {
+    pub(super) lines: Vec<GlyphBufferRenderInfo<'a>>,
+    pub(super) ascender: f32,
+    pub(super) descender: f32,
+    pub(super) line_advance: f32,
+}
+
+impl<'a> ParagraphLayout<'a> {
+    // The whole text gets shaped once to find the line wraps and then each line gets shaped again
+    fn new(
+        text: &str,
+        max_width: f32,
+        line_spacing: f32,
+        shape: impl Fn(TextBuffer) -> GlyphBufferRenderInfo<'a>,
+    ) -> Self {
+        let mut text_buffer = TextBuffer::default();
+        text_buffer.add_text(text);
+        let whole = shape(text_buffer);
+        let line_starts = whole.get_hard_wrapped_line_starts(max_width);
+
+        let mut lines = Vec::with_capacity(line_starts.len());
+        for (ind, line_start) in line_starts.iter().enumerate() {
+            let line_end = match line_starts.get(ind + 1) {
+                Some(next_start) => *next_start,
+                None => whole.chars.len(),
+            };
+            // The trailing spaces and line break characters are not drawn
+            let mut line_end = line_end;
+            while (line_end > *line_start)
+                && matches!(
+                    get_break_class(whole.chars[line_end - 1]),
+                    BreakClass::Space
+                        | BreakClass::Mandatory
+                        | BreakClass::CarriageReturn
+                        | BreakClass::LineFeed
+                )
+            {
+                line_end -= 1;
+            }
+            let line_text: String = whole.chars[*line_start..line_end].iter().collect();
+            let mut line_buffer = TextBuffer::default();
+            line_buffer.add_text(&line_text);
+            lines.push(shape(line_buffer));
+        }
+
+        Self {
+            lines,
+            ascender: whole.ascender,
+            descender: whole.descender,
+            line_advance: (whole.ascender + whole.descender) * line_spacing,
+        }
+    }
+
+    /// Returns the shaped lines in order from the top
+    pub fn get_lines(&self) -> &[GlyphBufferRenderInfo<'a>] {
+        &self.lines
+    }
+
+    /// Returns the pixel distance from one baseline to the next
+    pub fn get_line_advance(&self) -> f32 {
+        self.line_advance
+    }
+
+    /// Sets the distance in pixels between tab stops of every line, see GlyphBufferRenderInfo::set_tab_width
+    pub fn set_tab_width(&mut self, tab_width: f32) {
+        for line in &mut self.lines {
+            line.set_tab_width(tab_width);
+        }
+    }
+
+    /// Sets how every line places its glyphs on the pixel grid, see GlyphBufferRenderInfo::set_positioning
+    pub fn set_positioning(&mut self, positioning: GlyphPositioning) {
+        for line in &mut self.lines {
+            line.set_positioning(positioning);
+        }
+    }
+
+    /// Returns the (width, height) in pixels of all of the lines when drawn, the width is the
+    /// widest line and the height goes from the ascender of the first line to the descender of the last
+    pub fn measure(&self) -> (f32, f32) {
+        let width = self
+            .lines
+            .iter()
+            .fold(0.0_f32, |width, line| width.max(line.get_line_width()));
+        let height = match self.lines.len() {
+            0 => 0.0,
+            num_lines => {
+                (self.line_advance * ((num_lines - 1) as f32)) + self.ascender + self.descender
+            }
+        };
+        (width, height)
+    }
+}
+
+/// Returns true if a blinking caret should be visible after the elapsed time
+/// since the caret last moved for a full blink period (on and then off)
+///
+/// The caret starts visible so that it shows immediately after moving
+pub fn is_caret_visible(elapsed: std::time::Duration, blink_period: std::time::Duration) -> bool {
+    let period_nanos = blink_period.as_nanos();
+    if period_nanos == 0 {
+        return true;
+    }
+    (elapsed.as_nanos() % period_nanos) < (period_nanos >> 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../bin/font/firasans/FiraSans-Regular.ttf"
+    );
+
+    #[test]
+    fn caret_inside_of_a_ligature_goes_after_it() {
+        let glyphs = Glyphs::new_from_font_file(FONT_PATH, 0, 2, "en").unwrap();
+        let shaper = glyphs.get_font_face_shaper(0).unwrap();
+        let mut text_buffer = TextBuffer::default();
+        text_buffer.add_text("fi");
+        let render_info = shaper.create_glyph_buffer_render_info(12, 96.0, text_buffer);
+        // Fira Sans draws fi with a single ligature glyph
+        assert_eq!(render_info.runs[0].glyph_buffer.len(), 1);
+
+        let line_width = render_info.get_line_width();
+        assert!(line_width > 0.0);
+        let caret_x =
+            |char_index| render_info.get_caret_x(0.0, DrawJustification::Left, char_index);
+        assert_eq!(caret_x(0), 0.0);
+        assert_eq!(caret_x(1), line_width);
+        assert_eq!(caret_x(2), line_width);
+        assert_eq!(
+            render_info.get_char_distances(),
+            vec![0.0, line_width, line_width]
+        );
+    }
+
+    #[test]
+    fn combining_mark_stays_in_the_grapheme_cluster() {
+        let glyphs = Glyphs::new_from_font_file(FONT_PATH, 0, 2, "en").unwrap();
+        let shaper = glyphs.get_font_face_shaper(0).unwrap();
+        let mut text_buffer = TextBuffer::default();
+        // e followed by a combining acute accent and then x
+        text_buffer.add_text("e\u{301}x");
+        let render_info = shaper.create_glyph_buffer_render_info(12, 96.0, text_buffer);
+
+        // The accent either composes with the e or gets a mark glyph in the same cluster
+        let glyph_infos = render_info.runs[0].glyph_buffer.glyph_infos();
+        let clusters: Vec<u32> = glyph_infos.iter().map(|info| info.cluster).collect();
+        assert_eq!(clusters.last(), Some(&2));
+        assert!(clusters[..clusters.len() - 1].iter().all(|&c| c == 0));
+
+        let caret_x =
+            |char_index| render_info.get_caret_x(0.0, DrawJustification::Left, char_index);
+        assert!(caret_x(1) > 0.0);
+        assert_eq!(caret_x(1), caret_x(2));
+        // Hard wrapping never breaks in between the e and its accent
+        assert_eq!(render_info.get_hard_wrapped_line_starts(1.0), vec![0, 2]);
+    }
 }
@@ -0,0 +1,156 @@
+//Media Enhanced Swiftlet Graphics Rust Library using Vulkan
+//MIT License
+//Copyright (c) 2024 Jared Loewenthal
+//
+//Permission is hereby granted, free of charge, to any person obtaining a copy
+//of this software and associated documentation files (the "Software"), to deal
+//in the Software without restriction, including without limitation the rights
+//to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+//copies of the Software, and to permit persons to whom the Software is
+//furnished to do so, subject to the following conditions:
+//
+//The above copyright notice and this permission notice shall be included in all
+//copies or substantial portions of the Software.
+//
+//THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+//IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+//OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//SOFTWARE.
+
+use std::time::Duration;
+
+// Fixed bucket layout keeps the memory use bounded no matter how long the render loop runs
+const BUCKET_WIDTH_US: u32 = 250;
+const BUCKET_COUNT: usize = 400; // Covers frame times up to 100 ms, longer frames go in the last bucket
+const WINDOW_SIZE: usize = 1024; // Number of the most recent frames that are tracked
+
+/// Summary of the frame times currently in the rolling window
+#[derive(Clone, Copy, Debug)]
+pub struct FrameTimeReport {
+    pub frames: u32,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    pub over_budget: u32,
+}
+
+/// Rolling histogram of the last 1024 frame times with fixed 0.25 ms buckets
+///
+/// Percentiles are rounded up to the end of their bucket and anything at or above 100 ms
+/// is reported as the longest frame time in the window
+pub struct FrameTimeHistogram {
+    buckets: [u16; BUCKET_COUNT + 1],
+    recent_us: [u32; WINDOW_SIZE],
+    recent_next: usize,
+    recent_len: usize,
+    budget_us: u32,
+    over_budget: u32,
+}
+
+impl FrameTimeHistogram {
+    /// Frames that take longer than the budget get counted as over budget
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            buckets: [0; BUCKET_COUNT + 1],
+            recent_us: [0; WINDOW_SIZE],
+            recent_next: 0,
+            recent_len: 0,
+            budget_us: duration_to_us(budget),
+            over_budget: 0,
+        }
+    }
+
+    /// Adds the time between two presented frames, the oldest frame leaves the window when it is full
+    pub fn add_frame(&mut self, frame_time: Duration) {
+        let frame_us = duration_to_us(frame_time);
+        if self.recent_len == WINDOW_SIZE {
+            let oldest_us = self.recent_us[self.recent_next];
+            self.buckets[get_bucket_index(oldest_us)] -= 1;
+            if oldest_us > self.budget_us {
+                self.over_budget -= 1;
+            }
+        } else {
+            self.recent_len += 1;
+        }
+        self.recent_us[self.recent_next] = frame_us;
+        self.recent_next = (self.recent_next + 1) % WINDOW_SIZE;
+        self.buckets[get_bucket_index(frame_us)] += 1;
+        if frame_us > self.budget_us {
+            self.over_budget += 1;
+        }
+    }
+
+    /// Clears all of the tracked frames but keeps the budget
+    pub fn reset(&mut self) {
+        self.buckets = [0; BUCKET_COUNT + 1];
+        self.recent_next = 0;
+        self.recent_len = 0;
+        self.over_budget = 0;
+    }
+
+    /// Changes the budget and recounts the tracked frames that are over it
+    pub fn set_budget(&mut self, budget: Duration) {
+        self.budget_us = duration_to_us(budget);
+        self.over_budget = self.recent_us[..self.recent_len]
+            .iter()
+            .filter(|us| **us > self.budget_us)
+            .count() as u32;
+    }
+
+    pub fn get_frame_count(&self) -> u32 {
+        self.recent_len as u32
+    }
+
+    pub fn get_over_budget_count(&self) -> u32 {
+        self.over_budget
+    }
+
+    /// Returns the frame time that the given fraction (0.0 to 1.0) of the tracked frames are at or below
+    pub fn get_percentile(&self, fraction: f32) -> Duration {
+        if self.recent_len == 0 {
+            return Duration::ZERO;
+        }
+        let target = ((fraction.clamp(0.0, 1.0) * (self.recent_len as f32)).ceil() as u32).max(1);
+        let mut count = 0;
+        for (bucket_index, bucket_count) in self.buckets[..BUCKET_COUNT].iter().enumerate() {
+            count += *bucket_count as u32;
+            if count >= target {
+                let bucket_end_us = ((bucket_index as u32) + 1) * BUCKET_WIDTH_US;
+                return Duration::from_micros(bucket_end_us as u64);
+            }
+        }
+        self.get_max()
+    }
+
+    pub fn get_max(&self) -> Duration {
+        let max_us = self.recent_us[..self.recent_len]
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or(0);
+        Duration::from_micros(max_us as u64)
+    }
+
+    pub fn get_report(&self) -> FrameTimeReport {
+        FrameTimeReport {
+            frames: self.get_frame_count(),
+            p50: self.get_percentile(0.50),
+            p95: self.get_percentile(0.95),
+            p99: self.get_percentile(0.99),
+            max: self.get_max(),
+            over_budget: self.over_budget,
+        }
+    }
+}
+
+fn duration_to_us(duration: Duration) -> u32 {
+    duration.as_micros().min(u32::MAX as u128) as u32
+}
+
+fn get_bucket_index(frame_us: u32) -> usize {
+    ((frame_us / BUCKET_WIDTH_US) as usize).min(BUCKET_COUNT)
+}
@@ -30,12 +30,20 @@ pub mod vulkan;
 #[cfg_attr(target_os = "macos", path = "mac/os.rs")]
 mod os;
 pub use os::KeyCode;
+pub use os::KeyModifiers;
+pub use os::MouseButton;
 pub use os::OsEventSignaler;
+pub use vulkan::BackgroundFit;
+pub use vulkan::BlendMode;
+pub use vulkan::DebugMessageSeverity;
 use vulkan::GlyphSegment;
+pub use vulkan::LatencyMode;
+pub use vulkan::PresentMode;
 //use os::{AudioInput, AudioOutput, AudioOwner};
 
 pub mod color;
 pub mod font;
+pub mod frame_time;
 
 // #[cfg(feature = "opus")]
 // pub mod opus;
@@ -45,6 +53,153 @@ pub enum Error {
     VulkanError(vulkan::Error),
     OsError(os::OsError),
     CannotFindPhysicalDevice,
+    DeviceLost,
+    InvalidMonitor(usize),
+    NoGlyphData, // The glyphs have no outlines to draw such as when the font did not load any glyphs
+}
+
+/// Bounds are in virtual screen pixels where the primary monitor starts at (0, 0)
+#[derive(Debug, Clone, Copy)]
+pub struct MonitorInfo {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+    pub is_primary: bool,
+}
+
+impl MonitorInfo {
+    // Area of the given rectangle that is visible on this monitor
+    fn get_overlap(&self, x: i32, y: i32, width: u32, height: u32) -> u64 {
+        let x_min = x.max(self.x) as i64;
+        let y_min = y.max(self.y) as i64;
+        let x_max = (x as i64 + width as i64).min(self.x as i64 + self.width as i64);
+        let y_max = (y as i64 + height as i64).min(self.y as i64 + self.height as i64);
+        if x_max > x_min && y_max > y_min {
+            ((x_max - x_min) * (y_max - y_min)) as u64
+        } else {
+            0
+        }
+    }
+
+    // Moves the given rectangle the least amount to fit inside this monitor
+    fn clamp_position(&self, x: i32, y: i32, width: u32, height: u32) -> (i32, i32) {
+        let x_max = self.x + (self.width.saturating_sub(width) as i32);
+        let y_max = self.y + (self.height.saturating_sub(height) as i32);
+        (x.clamp(self.x, x_max), y.clamp(self.y, y_max))
+    }
+}
+
+/// Lists every connected monitor with its bounds and DPI
+pub fn get_monitors() -> Result<Vec<MonitorInfo>, Error> {
+    match os::get_monitors() {
+        Ok(m) => Ok(m),
+        Err(e) => Err(Error::OsError(e)),
+    }
+}
+
+/// What the renderers can run on, reported without opening a window
+#[derive(Debug)]
+pub struct SupportReport {
+    pub instance_error: Option<vulkan::Error>, // Set when Vulkan itself could not be loaded or started
+    pub devices: Vec<vulkan::DeviceSupport>,
+}
+
+impl SupportReport {
+    /// True when at least one device has what every renderer (2D, triglyph, CPU pixel and offscreen) needs
+    pub fn is_supported(&self) -> bool {
+        self.devices.iter().any(|d| d.unsupported_reason.is_none())
+    }
+
+    /// Index of the first supported device that can be given to Vulkan2dWindow::new
+    pub fn first_supported_device_index(&self) -> Option<usize> {
+        self.devices
+            .iter()
+            .find(|d| d.unsupported_reason.is_none())
+            .map(|d| d.index)
+    }
+}
+
+/// Checks whether the hardware supports the renderers before a window gets opened
+///
+/// Every renderer needs the same device capabilities so there is a single report for all paths.
+/// Surface formats and present modes still get checked when a window is created since they need a surface.
+pub fn check_support() -> SupportReport {
+    match vulkan::check_device_support() {
+        Ok(devices) => SupportReport {
+            instance_error: None,
+            devices,
+        },
+        Err(e) => SupportReport {
+            instance_error: Some(e),
+            devices: Vec::new(),
+        },
+    }
+}
+
+/// Where a new window is placed when it opens
+#[derive(Debug, Clone, Copy)]
+pub enum WindowPosition {
+    /// Lets the OS decide
+    Default,
+    /// Top left corner of the window in virtual screen pixels, a position that is no longer
+    /// visible (such as a saved position on an unplugged monitor) gets clamped to a monitor
+    At(i32, i32),
+    /// Centered on the monitor at this index from get_monitors
+    CenteredOn(usize),
+}
+
+fn create_os_window(
+    position: WindowPosition,
+    width: u32,
+    height: u32,
+) -> Result<os::OsWindow, Error> {
+    let (x, y) = match position {
+        WindowPosition::Default => {
+            return match os::OsWindow::new(width, height) {
+                Ok(w) => Ok(w),
+                Err(e) => Err(Error::OsError(e)),
+            }
+        }
+        WindowPosition::At(x, y) => {
+            let monitors = get_monitors()?;
+            let mut best_monitor = None;
+            let mut best_overlap = 0;
+            for m in &monitors {
+                let overlap = m.get_overlap(x, y, width, height);
+                if overlap > best_overlap {
+                    best_overlap = overlap;
+                    best_monitor = Some(m);
+                }
+            }
+            if best_monitor.is_none() {
+                best_monitor = match monitors.iter().find(|m| m.is_primary) {
+                    Some(m) => Some(m),
+                    None => monitors.first(),
+                };
+            }
+            match best_monitor {
+                Some(m) => m.clamp_position(x, y, width, height),
+                None => (x, y),
+            }
+        }
+        WindowPosition::CenteredOn(monitor) => {
+            let monitors = get_monitors()?;
+            let m = match monitors.get(monitor) {
+                Some(m) => m,
+                None => return Err(Error::InvalidMonitor(monitors.len())),
+            };
+            let x = m.x + ((m.width as i32 - width as i32) / 2);
+            let y = m.y + ((m.height as i32 - height as i32) / 2);
+            m.clamp_position(x, y, width, height)
+        }
+    };
+
+    match os::OsWindow::new_at(x, y, width, height) {
+        Ok(w) => Ok(w),
+        Err(e) => Err(Error::OsError(e)),
+    }
 }
 
 pub struct BasicWindow {
@@ -54,10 +209,15 @@ pub struct BasicWindow {
 
 impl BasicWindow {
     pub fn new(width: u32, height: u32) -> Result<(Self, os::OsEventSignaler), Error> {
-        let window = match os::OsWindow::new(width, height) {
-            Ok(w) => w,
-            Err(e) => return Err(Error::OsError(e)),
-        };
+        Self::new_at(WindowPosition::Default, width, height)
+    }
+
+    pub fn new_at(
+        position: WindowPosition,
+        width: u32,
+        height: u32,
+    ) -> Result<(Self, os::OsEventSignaler), Error> {
+        let window = create_os_window(position, width, height)?;
 
         let signal_watcher = match os::OsEvent::new() {
             Ok(t) => t,
@@ -109,6 +269,7 @@ pub struct VulkanWindow {
     draw_trigger_external: os::OsEvent,
     render_width: u32,
     render_height: u32,
+    is_minimized: bool, // Drawing pauses while the window has no area to present to
 }
 
 impl VulkanWindow {
@@ -158,7 +319,12 @@ impl VulkanWindow {
         let signaler = draw_trigger_external.create_signaler();
 
         let surface_parameters = window.get_surface_parameters();
-        let swapchain = match vulkan::Swapchain::new(physical_device, surface_parameters) {
+        let swapchain = match vulkan::Swapchain::new(
+            physical_device,
+            surface_parameters,
+            vulkan::LatencyMode::LowLatency,
+            window.get_size(),
+        ) {
             Ok(s) => s,
             Err(e) => return Err(Error::VulkanError(e)),
         };
@@ -175,6 +341,7 @@ impl VulkanWindow {
                 draw_trigger_external,
                 render_width: width,
                 render_height: height,
+                is_minimized: false,
             },
             signaler,
         ))
@@ -193,11 +360,13 @@ impl VulkanWindow {
                 Ok(os::OsWindowState::ShouldDrop) => {
                     break;
                 }
+                Ok(os::OsWindowState::Resized(window_size)) => self.resize(window_size)?,
                 Ok(_) => {}
                 Err(e) => return Err(Error::OsError(e)),
             }
             match self.draw_trigger_external.check() {
                 Ok(false) => {}
+                Ok(true) if self.is_minimized => {}
                 Ok(true) => match self.swapchain_cpu_render.get_buffer() {
                     Ok(data) => {
                         callback.draw(data, self.render_width, self.render_height);
@@ -211,6 +380,9 @@ impl VulkanWindow {
                                 //     Err(e) => return Err(Error::VulkanError(e)),
                                 // }
                             }
+                            Err(e) if e.is_swapchain_out_of_date() => {
+                                self.resize(self.window.get_size())?
+                            }
                             Err(e) => return Err(Error::VulkanError(e)),
                         }
                     }
@@ -222,9 +394,27 @@ impl VulkanWindow {
 
         Ok(())
     }
+
+    // Recreates the swapchain for the new window size or pauses drawing while it is zero
+    fn resize(&mut self, window_size: (u32, u32)) -> Result<(), Error> {
+        if window_size.0 == 0 || window_size.1 == 0 {
+            self.is_minimized = true;
+            return Ok(());
+        }
+        match self.swapchain_cpu_render.resize(window_size) {
+            Ok(()) => self.is_minimized = false,
+            Err(vulkan::Error::ZeroSizeSurface) => self.is_minimized = true,
+            Err(e) => return Err(Error::VulkanError(e)),
+        }
+        Ok(())
+    }
 }
 
-fn create_glyph_data_from_glyph_outline_data(
+/// Converts font glyph outlines into the GPU glyph data of the 2d renderers
+///
+/// The outlines of every font are packed one after another (in the order of Glyphs::get_glyph_outline_data)
+/// so primitives from different fonts can share a batch by adding Glyphs::get_font_outline_offset
+pub fn create_glyph_data_from_glyph_outline_data(
     glyph_outline_data: &[font::GlyphOutlineData],
     rays_per_outline_po2: u8,
 ) -> vulkan::GlyphData {
@@ -284,6 +474,14 @@ fn create_glyph_data_from_glyph_outline_data(
     }
 }
 
+/// Kind of key event reported to the key_event callbacks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyState {
+    Pressed,
+    Repeated, // Auto-repeat while the key is held down
+    Released,
+}
+
 pub trait VulkanTriglyphCallbacks {
     fn draw(
         &mut self,
@@ -294,6 +492,10 @@ pub trait VulkanTriglyphCallbacks {
 
     fn key_pressed(&mut self, key_code: KeyCode) -> bool;
 
+    /// Called for every key press, auto-repeat and release with the held down modifiers
+    /// before key_pressed gets called (only for fresh presses)
+    fn key_event(&mut self, _key_code: &KeyCode, _modifiers: KeyModifiers, _state: KeyState) {}
+
     fn tick(&mut self) -> bool;
 }
 
@@ -303,15 +505,20 @@ pub struct VulkanTriglyph {
     draw_trigger_external: os::OsEvent,
     render_width: u32,
     render_height: u32,
+    is_minimized: bool, // Drawing pauses while the window has no area to present to
 }
 
 impl VulkanTriglyph {
     /// max_triangles needs to be a multiple of 8
+    ///
+    /// The latency mode picks the swapchain present mode where LatencyMode::Throughput (FIFO)
+    /// avoids tearing and LatencyMode::Explicit falls back to FIFO when the mode is not supported
     pub fn new(
         width: u32,
         height: u32,
         max_triangles: u32,
         glyph_outline_data: (&[font::GlyphOutlineData], u8),
+        latency_mode: vulkan::LatencyMode,
         use_validation_layers: bool,
     ) -> Result<(Self, os::OsEventSignaler), Error> {
         let layer_names = if use_validation_layers {
@@ -364,7 +571,12 @@ impl VulkanTriglyph {
         let signaler = draw_trigger_external.create_signaler();
 
         let surface_parameters = window.get_surface_parameters();
-        let swapchain = match vulkan::Swapchain::new(physical_device, surface_parameters) {
+        let swapchain = match vulkan::Swapchain::new(
+            physical_device,
+            surface_parameters,
+            latency_mode,
+            window.get_size(),
+        ) {
             Ok(s) => s,
             Err(e) => return Err(Error::VulkanError(e)),
         };
@@ -378,13 +590,16 @@ impl VulkanTriglyph {
                 Err(e) => return Err(Error::VulkanError(e)),
             };
 
+        let (render_width, render_height) = swapchain_triangle_render.get_size();
+
         Ok((
             VulkanTriglyph {
                 swapchain_triglyph_render: swapchain_triangle_render,
                 window,
                 draw_trigger_external,
-                render_width: width,
-                render_height: height,
+                render_width,
+                render_height,
+                is_minimized: false,
             },
             signaler,
         ))
@@ -403,7 +618,12 @@ impl VulkanTriglyph {
                 Ok(os::OsWindowState::ShouldDrop) => {
                     break;
                 }
-                Ok(os::OsWindowState::KeyPressed(key_code)) => {
+                Ok(os::OsWindowState::KeyPressed(key_code, modifiers, is_repeat)) => {
+                    if is_repeat {
+                        callback.key_event(&key_code, modifiers, KeyState::Repeated);
+                        continue;
+                    }
+                    callback.key_event(&key_code, modifiers, KeyState::Pressed);
                     if callback.key_pressed(key_code) {
                         if let Err(e) = self.window.close_window() {
                             return Err(Error::OsError(e));
@@ -411,23 +631,30 @@ impl VulkanTriglyph {
                     }
                     continue;
                 }
+                Ok(os::OsWindowState::KeyReleased(key_code, modifiers)) => {
+                    callback.key_event(&key_code, modifiers, KeyState::Released);
+                    continue;
+                }
+                Ok(os::OsWindowState::Resized(window_size)) => self.resize(window_size)?,
                 Ok(_) => {}
                 Err(e) => return Err(Error::OsError(e)),
             }
             let should_draw = callback.tick();
-            if should_draw {
+            if should_draw && !self.is_minimized {
                 match self.swapchain_triglyph_render.get_data() {
                     Ok(mut input_data) => {
                         let (num_verticies, num_triangles) =
                             callback.draw(&mut input_data, self.render_width, self.render_height);
-                        if let Err(e) = self.swapchain_triglyph_render.render(
+                        // An out of date swapchain gets recreated for the current window size
+                        // instead of the size of the old swapchain
+                        let (window_width, window_height) = self.window.get_size();
+                        let result = self.swapchain_triglyph_render.render(
                             num_verticies,
                             num_triangles,
-                            self.render_width,
-                            self.render_height,
-                        ) {
-                            return Err(Error::VulkanError(e));
-                        }
+                            window_width,
+                            window_height,
+                        );
+                        self.render_result(result)?;
                     }
                     Err(e) => return Err(Error::VulkanError(e)),
                 }
@@ -435,7 +662,7 @@ impl VulkanTriglyph {
             match self.draw_trigger_external.check() {
                 Ok(false) => {}
                 Ok(true) => {
-                    if !should_draw {
+                    if !should_draw && !self.is_minimized {
                         match self.swapchain_triglyph_render.get_data() {
                             Ok(mut input_data) => {
                                 let (num_verticies, num_triangles) = callback.draw(
@@ -443,14 +670,13 @@ impl VulkanTriglyph {
                                     self.render_width,
                                     self.render_height,
                                 );
-                                if let Err(e) = self.swapchain_triglyph_render.render(
+                                let result = self.swapchain_triglyph_render.render(
                                     num_verticies,
                                     num_triangles,
                                     self.render_width,
                                     self.render_height,
-                                ) {
-                                    return Err(Error::VulkanError(e));
-                                }
+                                );
+                                self.render_result(result)?;
                             }
                             Err(e) => return Err(Error::VulkanError(e)),
                         }
@@ -462,6 +688,36 @@ impl VulkanTriglyph {
 
         Ok(())
     }
+
+    // Recreates the swapchain for the new window size or pauses drawing while it is zero
+    fn resize(&mut self, window_size: (u32, u32)) -> Result<(), Error> {
+        if window_size.0 == 0 || window_size.1 == 0 {
+            self.is_minimized = true;
+            return Ok(());
+        }
+        match self.swapchain_triglyph_render.resize(window_size) {
+            Ok(()) => self.is_minimized = false,
+            Err(vulkan::Error::ZeroSizeSurface) => self.is_minimized = true,
+            Err(e) => return Err(Error::VulkanError(e)),
+        }
+        (self.render_width, self.render_height) = self.swapchain_triglyph_render.get_size();
+        Ok(())
+    }
+
+    // Render already recreates an out of date swapchain so only the new size gets picked up here
+    fn render_result(&mut self, result: Result<(), vulkan::Error>) -> Result<(), Error> {
+        match result {
+            Ok(()) => {
+                (self.render_width, self.render_height) = self.swapchain_triglyph_render.get_size();
+                Ok(())
+            }
+            Err(vulkan::Error::ZeroSizeSurface) => {
+                self.is_minimized = true;
+                Ok(())
+            }
+            Err(e) => Err(Error::VulkanError(e)),
+        }
+    }
 }
 
 // pub enum TickActions<'a> {
@@ -472,6 +728,7 @@ impl VulkanTriglyph {
 //     //New Icons, New Images
 // }
 
+#[derive(Clone, Copy)]
 pub enum DrawJustification {
     Left,
     Center,
@@ -489,7 +746,7 @@ impl font::Glyphs {
         icon: u32,
         height: f32,
         justification: DrawJustification,
-    ) {
+    ) -> Option<vulkan::PrimitiveRect> {
         let icon_comp = icon as usize;
         if icon_comp < self.num_icons {
             let mut tex_min = (0.0, 0.0);
@@ -525,13 +782,19 @@ impl font::Glyphs {
                     tex_max,
                     icon | glyph_index_bits,
                     dp,
-                )
+                );
+                return primitives.get_last_bounds();
             }
         }
+        None
     }
 }
 
 impl<'a> font::GlyphFaceShaper<'a> {
+    /// Draws the font glyph of a single Unicode scalar value without any shaping
+    ///
+    /// Combining marks and emoji sequences need more than one scalar value so they should be
+    /// drawn from a shaped TextBuffer with GlyphBufferRenderInfo::draw_glyphs instead
     // Baseline Center Point
     pub fn draw_glyph(
         &self,
@@ -543,16 +806,10 @@ impl<'a> font::GlyphFaceShaper<'a> {
         dpi: f32,
         code_point: char,
         justification: DrawJustification,
-    ) {
+    ) -> Option<vulkan::PrimitiveRect> {
         if let Some(glyph_id_w) = self.font_face.glyph_index(code_point) {
             let glyph_id = glyph_id_w.0 as u32;
-            let outline_index = match self
-                .outline_indicies
-                .binary_search_by(|od| od.glyph_id.cmp(&glyph_id))
-            {
-                Ok(found_ind) => found_ind,
-                Err(_insert_ind) => return,
-            };
+            let outline_index = self.get_outline_index(glyph_id)?;
 
             let mut tex_min = (0.0, 0.0);
             let mut tex_max = (0.0, 0.0);
@@ -585,39 +842,197 @@ impl<'a> font::GlyphFaceShaper<'a> {
                     tex_max,
                     ((self.outline_index_offset + outline_index) as u32) | glyph_index_bits,
                     dp,
-                )
+                );
+                return primitives.get_last_bounds();
             }
         }
+        None
     }
 }
 
-impl<'a> font::GlyphBufferRenderInfo<'a> {
-    pub fn draw_glyphs(
+impl<'a> font::GlyphFallbackShaper<'a> {
+    /// Draws the font glyph of a single Unicode scalar value with the first face that has a glyph for it
+    pub fn draw_glyph(
         &self,
         primitives: &mut vulkan::Primitives2d,
         p0: &vulkan::PrimitivePosition,
         color: &vulkan::PrimitiveColor,
         rays_per_outline_po2: u32,
+        pt_size: u32,
+        dpi: f32,
+        code_point: char,
         justification: DrawJustification,
-    ) {
-        let glyph_infos = self.glyph_buffer.glyph_infos();
-        let glyph_positions = self.glyph_buffer.glyph_positions();
+    ) -> Option<vulkan::PrimitiveRect> {
+        let face = self.get_char_face(code_point, 0);
+        self.face_shapers[face].draw_glyph(
+            primitives,
+            p0,
+            color,
+            rays_per_outline_po2,
+            pt_size,
+            dpi,
+            code_point,
+            justification,
+        )
+    }
+}
 
-        let mut line_width = 0.0;
-        for gp in glyph_positions {
-            line_width += (gp.x_advance as f32) * self.scale;
+impl<'a> font::ParagraphLayout<'a> {
+    /// Draws the lines inside of the bounding box with the first baseline one ascender below its top,
+    /// lines get justified to the left edge, the center or the right edge of the box
+    ///
+    /// Lines that would go past the bottom of the box are not drawn,
+    /// returns the pixel bounds of the drawn lines
+    pub fn draw(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        bounding_box: &vulkan::PrimitiveRect,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+        justification: DrawJustification,
+    ) -> vulkan::PrimitiveRect {
+        let x = match justification {
+            DrawJustification::Left => bounding_box.x,
+            DrawJustification::Center => bounding_box.x + (bounding_box.w * 0.5),
+            DrawJustification::Right => bounding_box.x + bounding_box.w,
+        };
+        let box_bottom = bounding_box.y + bounding_box.h;
+        let mut bounds: Option<vulkan::PrimitiveRect> = None;
+        for (line_ind, line) in self.lines.iter().enumerate() {
+            let baseline = bounding_box.y + self.ascender + (self.line_advance * (line_ind as f32));
+            if (baseline + self.descender) > box_bottom {
+                break;
+            }
+            let line_bounds = line.draw_glyphs(
+                primitives,
+                &vulkan::PrimitivePosition { x, y: baseline },
+                color,
+                rays_per_outline_po2,
+                justification,
+            );
+            bounds = match bounds {
+                Some(b) => Some(b.union(&line_bounds)),
+                None => Some(line_bounds),
+            };
+        }
+        match bounds {
+            Some(b) => b,
+            None => vulkan::PrimitiveRect {
+                x,
+                y: bounding_box.y,
+                w: 0.0,
+                h: 0.0,
+            },
         }
+    }
+}
 
-        let mut baseline_p0 = match justification {
-            DrawJustification::Left => vulkan::PrimitivePosition { x: p0.x, y: p0.y },
-            DrawJustification::Right => vulkan::PrimitivePosition {
-                x: p0.x - line_width,
+impl<'a> font::GlyphBufferRenderInfo<'a> {
+    /// Returns the pixel bounds of the line which covers the whole advance width of the
+    /// line starting from the baseline and all of the drawn glyphs
+    pub fn draw_glyphs(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        p0: &vulkan::PrimitivePosition,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+        justification: DrawJustification,
+    ) -> vulkan::PrimitiveRect {
+        let line_start_x = self.get_line_start_x(p0.x, justification);
+        self.draw_glyph_range(
+            primitives,
+            &vulkan::PrimitivePosition {
+                x: line_start_x,
                 y: p0.y,
             },
-            DrawJustification::Center => vulkan::PrimitivePosition {
-                x: p0.x - (line_width * 0.5),
+            line_start_x,
+            self.get_line_width(),
+            color,
+            rays_per_outline_po2,
+            0..usize::MAX,
+        )
+    }
+
+    /// Draws the text wrapped into lines no wider than max_width pixels (breaking long words
+    /// when they do not fit on their own line) with p0 being on the baseline of the first line
+    ///
+    /// Each line gets justified to p0.x like draw_glyphs and the baseline moves down by the
+    /// ascender plus descender times the line_height multiplier for every line.
+    /// The lines keep the shaping of the whole text as a single line (see get_wrapped_line_starts),
+    /// returns the pixel bounds of all of the lines
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_paragraph(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        p0: &vulkan::PrimitivePosition,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+        max_width: f32,
+        line_height: f32,
+        justification: DrawJustification,
+    ) -> vulkan::PrimitiveRect {
+        let (_line_width, font_height) = self.measure();
+        let line_advance = font_height * line_height;
+        let distances = self.get_char_distances();
+        let line_starts = self.get_hard_wrapped_line_starts(max_width);
+
+        let mut bounds: Option<vulkan::PrimitiveRect> = None;
+        for (line_ind, line_start) in line_starts.iter().enumerate() {
+            let line_end = match line_starts.get(line_ind + 1) {
+                Some(next_start) => *next_start,
+                None => self.get_num_chars(),
+            };
+            let line_width = distances[self.get_trimmed_line_end(*line_start, line_end)]
+                - distances[*line_start];
+            let line_x = match justification {
+                DrawJustification::Left => p0.x,
+                DrawJustification::Right => p0.x - line_width,
+                DrawJustification::Center => p0.x - (line_width * 0.5),
+            };
+            let line_bounds = self.draw_glyph_range(
+                primitives,
+                &vulkan::PrimitivePosition {
+                    x: line_x - distances[*line_start],
+                    y: p0.y + (line_advance * (line_ind as f32)),
+                },
+                line_x,
+                line_width,
+                color,
+                rays_per_outline_po2,
+                *line_start..line_end,
+            );
+            bounds = match bounds {
+                Some(b) => Some(b.union(&line_bounds)),
+                None => Some(line_bounds),
+            };
+        }
+        match bounds {
+            Some(b) => b,
+            None => vulkan::PrimitiveRect {
+                x: p0.x,
                 y: p0.y,
+                w: 0.0,
+                h: 0.0,
             },
+        }
+    }
+
+    // Draws the glyphs of the characters in the char range where the origin is the pen position
+    // of the start of the text (tab stops are measured from it) and the line bounds start at line_x
+    #[allow(clippy::too_many_arguments)]
+    fn draw_glyph_range(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        origin: &vulkan::PrimitivePosition,
+        line_x: f32,
+        line_width: f32,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+        char_range: std::ops::Range<usize>,
+    ) -> vulkan::PrimitiveRect {
+        let mut baseline_p0 = vulkan::PrimitivePosition {
+            x: origin.x,
+            y: origin.y,
         };
         //println!("Dp: {}", self.dp);
 
@@ -625,55 +1040,291 @@ impl<'a> font::GlyphBufferRenderInfo<'a> {
 
         let mut tex_min = (0.0, 0.0);
         let mut tex_max = (0.0, 0.0);
-        for (gp_ind, gp) in glyph_positions.iter().enumerate() {
-            let glyph_id = glyph_infos[gp_ind].glyph_id;
-            // Could cache certain high probability glyphs in future
-
-            let outline_index = match self
-                .outline_indicies
-                .binary_search_by(|od| od.glyph_id.cmp(&glyph_id))
-            {
-                Ok(found_ind) => found_ind,
-                Err(_insert_ind) => {
-                    baseline_p0.x += (gp.x_advance as f32) * self.scale;
+        let line_start_x = origin.x;
+        let mut bounds = vulkan::PrimitiveRect {
+            x: line_x,
+            y: origin.y,
+            w: line_width,
+            h: 0.0,
+        };
+        // Each run continues from the pen position of the previous run
+        for run in &self.runs {
+            let glyph_infos = run.glyph_buffer.glyph_infos();
+            let glyph_positions = run.glyph_buffer.glyph_positions();
+            for (gp_ind, gp) in glyph_positions.iter().enumerate() {
+                if self.is_tab(glyph_infos[gp_ind].cluster) {
+                    baseline_p0.x =
+                        line_start_x + self.get_next_tab_stop(baseline_p0.x - line_start_x);
+                    continue;
+                }
+                // Glyphs outside of the range still advance the pen
+                if !char_range.contains(&(glyph_infos[gp_ind].cluster as usize)) {
+                    baseline_p0.x += (gp.x_advance as f32) * run.scale;
                     continue;
                 }
+
+                let glyph_id = glyph_infos[gp_ind].glyph_id;
+                let outline_index = match run.get_outline_index(glyph_id) {
+                    Some(ind) => ind,
+                    None => {
+                        baseline_p0.x += (gp.x_advance as f32) * run.scale;
+                        continue;
+                    }
+                };
+
+                if run.outline_indicies[outline_index].set_render_info(&mut tex_min, &mut tex_max) {
+                    let mut offsets = (
+                        tex_min.0 + (gp.x_offset as f32),
+                        tex_min.1 + (gp.y_offset as f32),
+                    );
+                    offsets.0 = (offsets.0 * run.scale) + 1.0;
+                    offsets.1 = (offsets.1 * run.scale) + 1.0;
+                    let mut dimensions = (tex_max.0 - tex_min.0, tex_max.1 - tex_min.1);
+                    // println!(
+                    //     "Glyph Dims: {}, {}, {}",
+                    //     dimensions.0, dimensions.1, run.scale
+                    // );
+                    dimensions.0 = (dimensions.0 * run.scale) + 2.0;
+                    dimensions.1 = (dimensions.1 * run.scale) + 2.0;
+
+                    tex_min.0 -= run.dp;
+                    tex_min.1 -= run.dp;
+                    tex_max.0 += run.dp;
+                    tex_max.1 += run.dp;
+
+                    let glyph_p0 = match self.positioning {
+                        font::GlyphPositioning::Subpixel => vulkan::PrimitivePosition {
+                            x: baseline_p0.x,
+                            y: baseline_p0.y,
+                        },
+                        font::GlyphPositioning::PixelSnapped => {
+                            // The final glyph corner gets snapped so the offsets are moved into the position
+                            let glyph_p0 = vulkan::PrimitivePosition {
+                                x: (baseline_p0.x + offsets.0).round(),
+                                y: (baseline_p0.y - offsets.1).round(),
+                            };
+                            offsets = (0.0, 0.0);
+                            glyph_p0
+                        }
+                    };
+
+                    //println!("GP: {}, {}", gp_ind, outline_index);
+                    let glyph_bounds = primitives.add_glyph(
+                        &glyph_p0,
+                        color,
+                        offsets,
+                        dimensions,
+                        tex_min,
+                        tex_max,
+                        (run.outline_index_offset + (outline_index as u32)) | glyph_index_bits,
+                        run.dp,
+                    );
+                    bounds = bounds.union(&glyph_bounds);
+                }
+                baseline_p0.x += (gp.x_advance as f32) * run.scale;
+            }
+        }
+        bounds
+    }
+
+    /// Draws the glyphs along a baseline path made of connected line segments (in pixels)
+    /// starting start_distance pixels from the first path point
+    ///
+    /// Each grapheme cluster is centered on the path at the middle of its advance and rotated to the path
+    /// tangent at that point which requires the per glyph rotation of Primitives2d::add_glyph_rotated
+    /// The advance accumulates along the arc length and glyphs past the end of the path are not drawn
+    ///
+    /// Sharp corners and tight curves make neighboring glyphs overlap on the inside of the curve
+    ///
+    /// Returns the pixel bounds of the drawn glyphs or None if no glyphs were drawn
+    pub fn draw_glyphs_on_path(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        path: &[vulkan::PrimitivePosition],
+        start_distance: f32,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+    ) -> Option<vulkan::PrimitiveRect> {
+        if path.len() < 2 {
+            return None;
+        }
+
+        let mut segment_index = 0;
+        let mut segment_start_distance = 0.0;
+        self.draw_glyphs_along(
+            primitives,
+            start_distance,
+            color,
+            rays_per_outline_po2,
+            |distance| {
+                // Distances only increase so the path walk can continue from the last segment
+                while segment_index < (path.len() - 1) {
+                    let dx = path[segment_index + 1].x - path[segment_index].x;
+                    let dy = path[segment_index + 1].y - path[segment_index].y;
+                    let segment_length = ((dx * dx) + (dy * dy)).sqrt();
+                    let segment_distance = distance - segment_start_distance;
+                    if segment_distance <= segment_length {
+                        if segment_length <= 0.0 {
+                            segment_index += 1;
+                            continue;
+                        }
+                        let tangent = (dx / segment_length, dy / segment_length);
+                        let position = vulkan::PrimitivePosition {
+                            x: path[segment_index].x + (tangent.0 * segment_distance),
+                            y: path[segment_index].y + (tangent.1 * segment_distance),
+                        };
+                        return Some((position, tangent));
+                    }
+                    segment_start_distance += segment_length;
+                    segment_index += 1;
+                }
+                None
+            },
+        )
+    }
+
+    /// Draws the glyphs clockwise along the outside of a circle (in pixels) starting at start_angle
+    /// (in radians, 0.0 is to the right of center and positive is clockwise on screen)
+    ///
+    /// Each glyph is rotated to the circle tangent which requires the per glyph rotation of
+    /// Primitives2d::add_glyph_rotated and the advance accumulates along the arc length
+    ///
+    /// The radius should be at least twice the pixel height of the text (pt_size * dpi / 72)
+    /// for the text to stay legible, below that the glyphs overlap near the baseline
+    ///
+    /// Returns the pixel bounds of the drawn glyphs or None if no glyphs were drawn
+    pub fn draw_glyphs_on_arc(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        center: &vulkan::PrimitivePosition,
+        radius: f32,
+        start_angle: f32,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+    ) -> Option<vulkan::PrimitiveRect> {
+        if radius <= 0.0 {
+            return None;
+        }
+
+        let circumference = radius * std::f32::consts::TAU;
+        self.draw_glyphs_along(primitives, 0.0, color, rays_per_outline_po2, |distance| {
+            if distance > circumference {
+                return None;
+            }
+            let angle = start_angle + (distance / radius);
+            let (sin, cos) = angle.sin_cos();
+            let position = vulkan::PrimitivePosition {
+                x: center.x + (radius * cos),
+                y: center.y + (radius * sin),
             };
+            Some((position, (-sin, cos)))
+        })
+    }
 
-            if self.outline_indicies[outline_index].set_render_info(&mut tex_min, &mut tex_max) {
-                let mut offsets = (
-                    tex_min.0 + (gp.x_offset as f32),
-                    tex_min.1 + (gp.y_offset as f32),
-                );
-                offsets.0 = (offsets.0 * self.scale) + 1.0;
-                offsets.1 = (offsets.1 * self.scale) + 1.0;
-                let mut dimensions = (tex_max.0 - tex_min.0, tex_max.1 - tex_min.1);
-                // println!(
-                //     "Glyph Dims: {}, {}, {}",
-                //     dimensions.0, dimensions.1, self.scale
-                // );
-                dimensions.0 = (dimensions.0 * self.scale) + 2.0;
-                dimensions.1 = (dimensions.1 * self.scale) + 2.0;
-
-                tex_min.0 -= self.dp;
-                tex_min.1 -= self.dp;
-                tex_max.0 += self.dp;
-                tex_max.1 += self.dp;
-
-                //println!("GP: {}, {}", gp_ind, outline_index);
-                primitives.add_glyph(
-                    &baseline_p0,
-                    color,
-                    offsets,
-                    dimensions,
-                    tex_min,
-                    tex_max,
-                    (self.outline_index_offset + (outline_index as u32)) | glyph_index_bits,
-                    self.dp,
-                )
+    // Shared glyph placement for the path based drawing functions
+    // The placement function returns the baseline position and unit tangent at a distance along the path
+    fn draw_glyphs_along(
+        &self,
+        primitives: &mut vulkan::Primitives2d,
+        start_distance: f32,
+        color: &vulkan::PrimitiveColor,
+        rays_per_outline_po2: u32,
+        mut placement: impl FnMut(f32) -> Option<(vulkan::PrimitivePosition, (f32, f32))>,
+    ) -> Option<vulkan::PrimitiveRect> {
+        let glyph_index_bits = rays_per_outline_po2 << 30;
+
+        let mut bounds: Option<vulkan::PrimitiveRect> = None;
+        let mut distance = start_distance;
+        let mut tex_min = (0.0, 0.0);
+        let mut tex_max = (0.0, 0.0);
+        for run in &self.runs {
+            let glyph_infos = run.glyph_buffer.glyph_infos();
+            let glyph_positions = run.glyph_buffer.glyph_positions();
+            let mut cluster_start = 0;
+            while cluster_start < glyph_positions.len() {
+                // All of the glyphs in a grapheme cluster (such as a base letter and its combining marks)
+                // share a single placement so that they stay together on curves
+                let cluster = glyph_infos[cluster_start].cluster;
+                let mut cluster_end = cluster_start + 1;
+                while (cluster_end < glyph_positions.len())
+                    && (glyph_infos[cluster_end].cluster == cluster)
+                {
+                    cluster_end += 1;
+                }
+                let cluster_advance: f32 = glyph_positions[cluster_start..cluster_end]
+                    .iter()
+                    .map(|gp| (gp.x_advance as f32) * run.scale)
+                    .sum();
+                let half_cluster_advance = cluster_advance * 0.5;
+
+                let (middle, tangent) = match placement(distance + half_cluster_advance) {
+                    Some(p) => p,
+                    None => return bounds,
+                };
+
+                let mut cluster_pen = 0.0;
+                for gp_ind in cluster_start..cluster_end {
+                    let gp = &glyph_positions[gp_ind];
+                    let advance = (gp.x_advance as f32) * run.scale;
+
+                    let glyph_id = glyph_infos[gp_ind].glyph_id;
+                    let outline_index = match run.get_outline_index(glyph_id) {
+                        Some(ind) => ind,
+                        None => {
+                            cluster_pen += advance;
+                            continue;
+                        }
+                    };
+
+                    if run.outline_indicies[outline_index]
+                        .set_render_info(&mut tex_min, &mut tex_max)
+                    {
+                        let mut offsets = (
+                            tex_min.0 + (gp.x_offset as f32),
+                            tex_min.1 + (gp.y_offset as f32),
+                        );
+                        offsets.0 = (offsets.0 * run.scale) + 1.0;
+                        offsets.1 = (offsets.1 * run.scale) + 1.0;
+                        let mut dimensions = (tex_max.0 - tex_min.0, tex_max.1 - tex_min.1);
+                        dimensions.0 = (dimensions.0 * run.scale) + 2.0;
+                        dimensions.1 = (dimensions.1 * run.scale) + 2.0;
+
+                        tex_min.0 -= run.dp;
+                        tex_min.1 -= run.dp;
+                        tex_max.0 += run.dp;
+                        tex_max.1 += run.dp;
+
+                        // The glyph origin sits back along the tangent from the cluster middle point
+                        // by what is left of the first half of the cluster advance
+                        let back_distance = half_cluster_advance - cluster_pen;
+                        let baseline_p0 = vulkan::PrimitivePosition {
+                            x: middle.x - (tangent.0 * back_distance),
+                            y: middle.y - (tangent.1 * back_distance),
+                        };
+
+                        let glyph_bounds = primitives.add_glyph_rotated(
+                            &baseline_p0,
+                            color,
+                            offsets,
+                            dimensions,
+                            tex_min,
+                            tex_max,
+                            (run.outline_index_offset + (outline_index as u32)) | glyph_index_bits,
+                            run.dp,
+                            tangent,
+                        );
+                        bounds = match bounds {
+                            Some(b) => Some(b.union(&glyph_bounds)),
+                            None => Some(glyph_bounds),
+                        };
+                    }
+                    cluster_pen += advance;
+                }
+                distance += cluster_advance;
+                cluster_start = cluster_end;
             }
-            baseline_p0.x += (gp.x_advance as f32) * self.scale;
         }
+        bounds
     }
 }
 
@@ -683,33 +1334,202 @@ pub trait Vulkan2dWindowCallbacks {
     /// Return true if the window should be closed
     fn key_pressed(&mut self, key_code: KeyCode) -> bool;
 
-    /// Return true if the draw callback should be called
+    /// Called for every key press, auto-repeat and release with the held down modifiers
+    /// before key_pressed gets called (only for fresh presses)
+    fn key_event(&mut self, _key_code: &KeyCode, _modifiers: KeyModifiers, _state: KeyState) {}
+
+    /// Called with each typed character after the keyboard layout, dead keys and input methods
+    /// composed it (typing e after an acute accent dead key gives a single 'é'), control characters
+    /// are not included so use key_pressed for them and for shortcuts
+    fn char_input(&mut self, _c: char) {}
+
+    /// Called when the mouse moves with the pixel position in the same pixel space as Primitives2d
+    /// (the top left of the window is 0.0, 0.0), positions can be outside of the window while a button is held
+    fn mouse_moved(&mut self, _x: f32, _y: f32) {}
+
+    fn mouse_pressed(&mut self, _button: MouseButton) {}
+
+    fn mouse_released(&mut self, _button: MouseButton) {}
+
+    /// Called with the scrolled amount in wheel notches which is positive when scrolling up (away from the user)
+    fn mouse_scrolled(&mut self, _delta: f32) {}
+
+    /// Called with the horizontally scrolled amount in wheel notches (such as from a tilting wheel
+    /// or a touchpad) which is positive when scrolling to the right
+    fn mouse_scrolled_horizontal(&mut self, _delta: f32) {}
+
+    /// Return true if the draw callback should be called, new glyph outlines can be loaded
+    /// here with Glyphs::load_missing_chars before the characters get drawn
     fn tick(&mut self, glyphs: &mut font::Glyphs) -> bool;
+
+    /// Called after tick returned true, return a pixel region to only redraw that region in dirty
+    /// region mode (see Vulkan2dWindow::set_dirty_region_mode) while the rest of the window keeps
+    /// its previous pixels, the region gets combined with the regions marked by Primitives2d::mark_dirty
+    fn tick_dirty_region(&mut self) -> Option<vulkan::PrimitiveRect> {
+        None
+    }
+
+    /// Called before each attempt at recreating the renderer after the GPU device was lost
+    /// (such as from a driver reset), the attempt count starts at 1 for every device loss
+    fn device_lost(&mut self, _recovery_attempt: u32) {}
+
+    /// Called after every presented frame once the frame time histogram was updated
+    fn frame_presented(&mut self, _frame_times: &frame_time::FrameTimeHistogram) {}
 }
 
+// Frames longer than this count as over budget until changed with set_frame_time_budget
+const DEFAULT_FRAME_TIME_BUDGET: std::time::Duration = std::time::Duration::from_micros(16667);
+
+// Bounds the recreation attempts for a single device loss before giving up
+const MAX_DEVICE_LOST_RECOVERY_ATTEMPTS: u32 = 5;
+// Drivers can take a couple of seconds to reset so give them time between attempts
+const DEVICE_LOST_RECOVERY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
 pub struct Vulkan2dWindow {
     //jpegxl decomp
     //icons
     glyphs: font::Glyphs,
-    render: vulkan::TwoDimensionRender,
+    render: Option<vulkan::TwoDimensionRender>,
+    reserved_cpu_mem: usize,
+    mode: Vulkan2dWindowMode,
+    latency_mode: vulkan::LatencyMode,
+    frames_in_flight: u32,
+    device_index: Option<usize>, // GPU adapter that gets picked again after a device loss
+    dirty_region_mode: bool,
+    blend_mode: vulkan::BlendMode,
+    glyph_coverage_gamma: f32,
+    is_minimized: bool, // Drawing pauses while the window has no area to present to
+    frame_times: frame_time::FrameTimeHistogram,
+    last_frame_instant: Option<std::time::Instant>,
     //draw_trigger_external: os::OsEvent,
     window: os::OsWindow,
 }
 
+#[derive(Clone, Copy)]
 pub enum Vulkan2dWindowMode {
     Normal,
+    /// Enables the validation layers, their messages go to vulkan::set_debug_message_callback
     ValidationDebug,
 }
 
 impl Vulkan2dWindow {
+    /// reserved_cpu_mem is the staging buffer size for each frame in flight which has to fit the
+    /// primitive data of a frame, glyph data larger than it gets uploaded in multiple passes
+    /// as long as it is at least vulkan::GLYPH_UPLOAD_MIN_STAGING_SIZE
+    ///
+    /// frames_in_flight of None uses LatencyMode::get_frames_in_flight of the latency mode
+    /// so that one setting tunes the present mode and the frames in flight together
+    ///
+    /// device_index picks a GPU adapter from vulkan::PhysicalDevice::enumerate,
+    /// None uses the adapter that the window is on (or the first one)
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         width: u32,
         height: u32,
         reserved_cpu_mem: usize,
         glyphs: font::Glyphs,
         mode: Vulkan2dWindowMode,
+        latency_mode: vulkan::LatencyMode,
+        frames_in_flight: Option<u32>,
+        device_index: Option<usize>,
         //) -> Result<(Self, os::OsEventSignaler), Error> {
     ) -> Result<(Self, u32), Error> {
+        Self::new_at(
+            WindowPosition::Default,
+            width,
+            height,
+            reserved_cpu_mem,
+            glyphs,
+            mode,
+            latency_mode,
+            frames_in_flight,
+            device_index,
+        )
+    }
+
+    /// Same as new but opens the window at the given position, such as a restored placement
+    /// or centered on a secondary monitor
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_at(
+        position: WindowPosition,
+        width: u32,
+        height: u32,
+        reserved_cpu_mem: usize,
+        glyphs: font::Glyphs,
+        mode: Vulkan2dWindowMode,
+        latency_mode: vulkan::LatencyMode,
+        frames_in_flight: Option<u32>,
+        device_index: Option<usize>,
+    ) -> Result<(Self, u32), Error> {
+        // Checked before anything gets created since the GPU glyph buffers cannot be empty
+        if glyphs.is_empty() {
+            return Err(Error::NoGlyphData);
+        }
+        let frames_in_flight = match frames_in_flight {
+            Some(f) => f,
+            None => latency_mode.get_frames_in_flight(),
+        };
+        let window = create_os_window(position, width, height)?;
+        let window_dpi = window.get_dpi();
+        //println!("Window DPI: {}", window.get_dpi());
+
+        // let draw_trigger_external = match os::OsEvent::new() {
+        //     Ok(t) => t,
+        //     Err(e) => return Err(Error::OsError(e)),
+        // };
+        // let signaler = draw_trigger_external.create_signaler();
+
+        let render = Self::create_render(
+            &window,
+            &glyphs,
+            reserved_cpu_mem,
+            mode,
+            latency_mode,
+            frames_in_flight,
+            device_index,
+        )?;
+
+        // Ok((
+        //     Vulkan2dWindow {
+        //         glyphs,
+        //         render,
+        //         //draw_trigger_external,
+        //         window,
+        //     },
+        //     signaler,
+        // ))
+
+        Ok((
+            Vulkan2dWindow {
+                glyphs,
+                render: Some(render),
+                reserved_cpu_mem,
+                mode,
+                latency_mode,
+                frames_in_flight,
+                device_index,
+                dirty_region_mode: false,
+                blend_mode: vulkan::BlendMode::Over,
+                glyph_coverage_gamma: vulkan::DEFAULT_GLYPH_COVERAGE_GAMMA,
+                is_minimized: false,
+                frame_times: frame_time::FrameTimeHistogram::new(DEFAULT_FRAME_TIME_BUDGET),
+                last_frame_instant: None,
+                window,
+            },
+            window_dpi,
+        ))
+    }
+
+    // Creates every Vulkan object from the instance up and uploads the glyph data
+    fn create_render(
+        window: &os::OsWindow,
+        glyphs: &font::Glyphs,
+        reserved_cpu_mem: usize,
+        mode: Vulkan2dWindowMode,
+        latency_mode: vulkan::LatencyMode,
+        frames_in_flight: u32,
+        device_index: Option<usize>,
+    ) -> Result<vulkan::TwoDimensionRender, Error> {
         let (layer_names, extension_names) = match mode {
             Vulkan2dWindowMode::Normal => (
                 vec![],
@@ -738,35 +1558,35 @@ impl Vulkan2dWindow {
             Err(e) => return Err(Error::VulkanError(e)),
         };
 
-        let physical_device = match os::get_device_luid() {
-            Ok(Some(luid)) => match vulkan::PhysicalDevice::new_from_luid(instance, luid) {
-                Ok(Some(d)) => d,
-                Ok(None) => return Err(Error::CannotFindPhysicalDevice),
-                Err(e) => return Err(Error::VulkanError(e)),
-            },
-            Ok(None) => match vulkan::PhysicalDevice::new(instance) {
+        let physical_device = if let Some(index) = device_index {
+            match vulkan::PhysicalDevice::new_from_index(instance, index) {
                 Ok(Some(d)) => d,
                 Ok(None) => return Err(Error::CannotFindPhysicalDevice),
                 Err(e) => return Err(Error::VulkanError(e)),
-            },
-            Err(e) => return Err(Error::OsError(e)),
-        };
-
-        let window = match os::OsWindow::new(width, height) {
-            Ok(w) => w,
-            Err(e) => return Err(Error::OsError(e)),
+            }
+        } else {
+            match os::get_device_luid() {
+                Ok(Some(luid)) => match vulkan::PhysicalDevice::new_from_luid(instance, luid) {
+                    Ok(Some(d)) => d,
+                    Ok(None) => return Err(Error::CannotFindPhysicalDevice),
+                    Err(e) => return Err(Error::VulkanError(e)),
+                },
+                Ok(None) => match vulkan::PhysicalDevice::new(instance) {
+                    Ok(Some(d)) => d,
+                    Ok(None) => return Err(Error::CannotFindPhysicalDevice),
+                    Err(e) => return Err(Error::VulkanError(e)),
+                },
+                Err(e) => return Err(Error::OsError(e)),
+            }
         };
-        let window_dpi = window.get_dpi();
-        //println!("Window DPI: {}", window.get_dpi());
-
-        // let draw_trigger_external = match os::OsEvent::new() {
-        //     Ok(t) => t,
-        //     Err(e) => return Err(Error::OsError(e)),
-        // };
-        // let signaler = draw_trigger_external.create_signaler();
 
         let surface_parameters = window.get_surface_parameters();
-        let swapchain = match vulkan::Swapchain::new(physical_device, surface_parameters) {
+        let swapchain = match vulkan::Swapchain::new(
+            physical_device,
+            surface_parameters,
+            latency_mode,
+            window.get_size(),
+        ) {
             Ok(s) => s,
             Err(e) => return Err(Error::VulkanError(e)),
         };
@@ -775,30 +1595,143 @@ impl Vulkan2dWindow {
         let glyph_data =
             create_glyph_data_from_glyph_outline_data(glyph_outline_data.0, glyph_outline_data.1);
 
-        let render = match vulkan::TwoDimensionRender::new(swapchain, reserved_cpu_mem, glyph_data)
-        {
-            Ok(s) => s,
-            Err(e) => return Err(Error::VulkanError(e)),
+        match vulkan::TwoDimensionRender::new(
+            swapchain,
+            reserved_cpu_mem,
+            glyph_data,
+            frames_in_flight,
+        ) {
+            Ok(r) => Ok(r),
+            Err(e) => Err(Error::VulkanError(e)),
+        }
+    }
+
+    fn draw_frame(
+        render: &mut vulkan::TwoDimensionRender,
+        callback: &mut impl Vulkan2dWindowCallbacks,
+        glyphs: &font::Glyphs,
+        tick_dirty_region: Option<vulkan::PrimitiveRect>,
+    ) -> Result<(), vulkan::Error> {
+        let mut primitives = render.get_primitives()?;
+        if let Some(region) = tick_dirty_region {
+            primitives.mark_dirty(region);
+        }
+        callback.draw(&mut primitives, glyphs);
+        let (num_verticies, num_triangles) = primitives.get_num_verts_and_tris();
+        let dirty_region = primitives.get_dirty_region();
+        let regions = primitives.get_regions();
+        render.render_with_regions(num_verticies, num_triangles, dirty_region, &regions)
+    }
+
+    /// Only redraw the regions marked with Primitives2d::mark_dirty in the draw callback
+    /// to save GPU power for mostly static content
+    pub fn set_dirty_region_mode(&mut self, enabled: bool) {
+        self.dirty_region_mode = enabled;
+        if let Some(render) = &mut self.render {
+            render.set_dirty_region_mode(enabled);
+        }
+    }
+
+    /// Selects how the primitives of the draw callback are blended, Over is the default
+    pub fn set_blend_mode(&mut self, blend_mode: vulkan::BlendMode) {
+        self.blend_mode = blend_mode;
+        if let Some(render) = &mut self.render {
+            render.set_blend_mode(blend_mode);
+        }
+    }
+
+    /// Darkens (gamma below 1.0) or lightens the glyph coverage for small text,
+    /// see vulkan::TwoDimensionRender::set_glyph_coverage_gamma for the tradeoff
+    pub fn set_glyph_coverage_gamma(&mut self, gamma: f32) -> Result<(), Error> {
+        self.glyph_coverage_gamma = gamma;
+        if let Some(render) = &mut self.render {
+            if let Err(e) = render.set_glyph_coverage_gamma(gamma) {
+                return Err(Error::VulkanError(e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Frame times are measured between presented frames of consecutive ticks that drew
+    pub fn get_frame_times(&self) -> &frame_time::FrameTimeHistogram {
+        &self.frame_times
+    }
+
+    pub fn reset_frame_times(&mut self) {
+        self.frame_times.reset();
+        self.last_frame_instant = None;
+    }
+
+    pub fn set_frame_time_budget(&mut self, budget: std::time::Duration) {
+        self.frame_times.set_budget(budget);
+    }
+
+    // Recreates the swapchain for the new window size or pauses drawing while it is zero
+    fn resize(
+        &mut self,
+        callback: &mut impl Vulkan2dWindowCallbacks,
+        timer: &os::OsWait,
+        window_size: (u32, u32),
+    ) -> Result<(), Error> {
+        if window_size.0 == 0 || window_size.1 == 0 {
+            self.is_minimized = true;
+            return Ok(());
+        }
+        let render = match &mut self.render {
+            Some(r) => r,
+            None => return Err(Error::DeviceLost),
         };
+        match render.resize(window_size) {
+            Ok(()) => self.is_minimized = false,
+            Err(vulkan::Error::ZeroSizeSurface) => self.is_minimized = true,
+            Err(e) if e.is_device_lost() => {
+                // The recreated renderer already uses the new window size
+                self.recover_from_device_loss(callback, timer)?;
+                self.is_minimized = false;
+            }
+            Err(e) => return Err(Error::VulkanError(e)),
+        }
+        Ok(())
+    }
 
-        // Ok((
-        //     Vulkan2dWindow {
-        //         glyphs,
-        //         render,
-        //         //draw_trigger_external,
-        //         window,
-        //     },
-        //     signaler,
-        // ))
+    fn recover_from_device_loss(
+        &mut self,
+        callback: &mut impl Vulkan2dWindowCallbacks,
+        timer: &os::OsWait,
+    ) -> Result<(), Error> {
+        for recovery_attempt in 1..=MAX_DEVICE_LOST_RECOVERY_ATTEMPTS {
+            callback.device_lost(recovery_attempt);
+
+            // The old objects need to be destroyed before the window can get a new surface
+            self.render = None;
+            match Self::create_render(
+                &self.window,
+                &self.glyphs,
+                self.reserved_cpu_mem,
+                self.mode,
+                self.latency_mode,
+                self.frames_in_flight,
+                self.device_index,
+            ) {
+                Ok(mut r) => {
+                    r.set_dirty_region_mode(self.dirty_region_mode);
+                    r.set_blend_mode(self.blend_mode);
+                    if let Err(e) = r.set_glyph_coverage_gamma(self.glyph_coverage_gamma) {
+                        return Err(Error::VulkanError(e));
+                    }
+                    self.render = Some(r);
+                    return Ok(());
+                }
+                Err(Error::VulkanError(e)) if e.is_device_lost() => {}
+                Err(e) => return Err(e),
+            }
 
-        Ok((
-            Vulkan2dWindow {
-                glyphs,
-                render,
-                window,
-            },
-            window_dpi,
-        ))
+            if let Err(e) = timer.wait(DEVICE_LOST_RECOVERY_DELAY) {
+                return Err(Error::OsError(e));
+            }
+        }
+
+        Err(Error::DeviceLost)
     }
 
     pub fn run(
@@ -822,7 +1755,12 @@ impl Vulkan2dWindow {
                 Ok(os::OsWindowState::ShouldDrop) => {
                     break;
                 }
-                Ok(os::OsWindowState::KeyPressed(key_code)) => {
+                Ok(os::OsWindowState::KeyPressed(key_code, modifiers, is_repeat)) => {
+                    if is_repeat {
+                        callback.key_event(&key_code, modifiers, KeyState::Repeated);
+                        continue;
+                    }
+                    callback.key_event(&key_code, modifiers, KeyState::Pressed);
                     if callback.key_pressed(key_code) {
                         if let Err(e) = self.window.close_window() {
                             return Err(Error::OsError(e));
@@ -830,22 +1768,94 @@ impl Vulkan2dWindow {
                     }
                     continue;
                 }
+                Ok(os::OsWindowState::KeyReleased(key_code, modifiers)) => {
+                    callback.key_event(&key_code, modifiers, KeyState::Released);
+                    continue;
+                }
+                Ok(os::OsWindowState::CharInput(c)) => {
+                    callback.char_input(c);
+                    continue;
+                }
+                Ok(os::OsWindowState::MouseMoved { x, y }) => {
+                    callback.mouse_moved(x, y);
+                    continue;
+                }
+                Ok(os::OsWindowState::MousePressed(button)) => {
+                    callback.mouse_pressed(button);
+                    continue;
+                }
+                Ok(os::OsWindowState::MouseReleased(button)) => {
+                    callback.mouse_released(button);
+                    continue;
+                }
+                Ok(os::OsWindowState::MouseScrolled(delta)) => {
+                    callback.mouse_scrolled(delta);
+                    continue;
+                }
+                Ok(os::OsWindowState::MouseScrolledHorizontal(delta)) => {
+                    callback.mouse_scrolled_horizontal(delta);
+                    continue;
+                }
+                Ok(os::OsWindowState::Resized(window_size)) => {
+                    self.resize(callback, &timer, window_size)?;
+                }
                 Ok(_) => {}
                 Err(e) => return Err(Error::OsError(e)),
             }
             let next_process_instant =
                 std::time::Instant::now() + min_time_between_processing_window_msgs;
-            if callback.tick(&mut self.glyphs) {
-                match self.render.get_primitives() {
-                    Ok(mut primitives) => {
-                        callback.draw(&mut primitives, &self.glyphs);
-                        let (num_verticies, num_triangles) = primitives.get_num_verts_and_tris();
-                        if let Err(e) = self.render.render(num_verticies, num_triangles) {
-                            return Err(Error::VulkanError(e));
+            let should_draw = callback.tick(&mut self.glyphs);
+            if self.glyphs.take_outlines_changed() {
+                // Glyph outlines were added during the tick so the GPU needs them before drawing
+                let render = match &mut self.render {
+                    Some(r) => r,
+                    None => return Err(Error::DeviceLost),
+                };
+                let glyph_outline_data = self.glyphs.get_glyph_outline_data();
+                let glyph_data = create_glyph_data_from_glyph_outline_data(
+                    glyph_outline_data.0,
+                    glyph_outline_data.1,
+                );
+                match render.update_glyph_data(glyph_data) {
+                    Ok(()) => {}
+                    Err(e) if e.is_device_lost() => {
+                        // The recreated renderer already includes the new outlines
+                        self.recover_from_device_loss(callback, &timer)?;
+                        self.last_frame_instant = None;
+                    }
+                    Err(e) => return Err(Error::VulkanError(e)),
+                }
+            }
+            if should_draw && !self.is_minimized {
+                let tick_dirty_region = callback.tick_dirty_region();
+                let render = match &mut self.render {
+                    Some(r) => r,
+                    None => return Err(Error::DeviceLost),
+                };
+                match Self::draw_frame(render, callback, &self.glyphs, tick_dirty_region) {
+                    Ok(()) => {
+                        let frame_instant = std::time::Instant::now();
+                        if let Some(last_frame_instant) = self.last_frame_instant {
+                            self.frame_times
+                                .add_frame(frame_instant - last_frame_instant);
+                            callback.frame_presented(&self.frame_times);
                         }
+                        self.last_frame_instant = Some(frame_instant);
+                    }
+                    Err(e) if e.is_device_lost() => {
+                        self.recover_from_device_loss(callback, &timer)?;
+                        // The recovery time is not a frame time
+                        self.last_frame_instant = None;
+                    }
+                    Err(e) if e.is_swapchain_out_of_date() => {
+                        self.resize(callback, &timer, self.window.get_size())?;
+                        self.last_frame_instant = None;
                     }
                     Err(e) => return Err(Error::VulkanError(e)),
                 }
+            } else {
+                // Idle time without drawing is not a frame time either
+                self.last_frame_instant = None;
             }
             let current_instant = std::time::Instant::now();
             if current_instant < next_process_instant {
@@ -31,6 +31,7 @@ pub enum OsError {
     EventSetup,
     EventCheck,
     EventSignal,
+    NoScreens,
 }
 
 pub(super) fn get_device_luid() -> Result<Option<[u32; 2]>, OsError> {
@@ -38,7 +39,7 @@ pub(super) fn get_device_luid() -> Result<Option<[u32; 2]>, OsError> {
 }
 
 use objc2_app_kit::{
-    NSApplication, NSApplicationActivationPolicy, NSApplicationDelegate, NSBackingStoreType, NSEvent, NSEventMask, NSEventModifierFlags, NSEventType, NSWindow, NSWindowDelegate, NSWindowStyleMask, CAMetalLayer
+    NSApplication, NSApplicationActivationPolicy, NSApplicationDelegate, NSBackingStoreType, NSEvent, NSEventMask, NSEventModifierFlags, NSEventType, NSScreen, NSWindow, NSWindowDelegate, NSWindowStyleMask, CAMetalLayer
 };
 use objc2_foundation::{
     ns_string, CGFloat, MainThreadMarker, NSDefaultRunLoopMode, NSNotification, NSObject, NSObjectProtocol, NSPoint, NSRect,  NSSize};
@@ -145,6 +146,37 @@ pub(super) struct OsWindow {
     app: Id<NSApplication>,
     window: Id<NSWindow>,
     layer: Id<CAMetalLayer>,
+    last_size: (u32, u32), // Content view size in pixels from the last resize check
+}
+
+fn get_primary_screen_height(mtm: MainThreadMarker) -> Result<CGFloat, OsError> {
+    match NSScreen::screens(mtm).first() {
+        Some(s) => Ok(s.frame().size.height),
+        None => Err(OsError::NoScreens),
+    }
+}
+
+pub(super) fn get_monitors() -> Result<Vec<super::MonitorInfo>, OsError> {
+    let mtm = match MainThreadMarker::new() {
+        Some(m) => m,
+        None => return Err(OsError::NotMainThread),
+    };
+    let primary_height = get_primary_screen_height(mtm)?;
+
+    let screens = NSScreen::screens(mtm);
+    let mut monitors = Vec::with_capacity(screens.len());
+    for (ind, screen) in screens.iter().enumerate() {
+        let frame = screen.frame();
+        monitors.push(super::MonitorInfo {
+            x: frame.origin.x as i32,
+            y: (primary_height - (frame.origin.y + frame.size.height)) as i32,
+            width: frame.size.width as u32,
+            height: frame.size.height as u32,
+            dpi: (72.0 * screen.backingScaleFactor()) as u32,
+            is_primary: ind == 0,
+        });
+    }
+    Ok(monitors)
 }
 
 pub(super) enum OsWindowState {
@@ -152,10 +184,20 @@ pub(super) enum OsWindowState {
     CloseAttempt,
     Closing,
     ShouldDrop,
+    Resized((u32, u32)),
 }
 
 impl OsWindow {
     pub(super) fn new(width: u32, height: u32) -> Result<Self, OsError> {
+        Self::create(None, width, height)
+    }
+
+    // The x and y position is the top left corner of the window in virtual screen pixels
+    pub(super) fn new_at(x: i32, y: i32, width: u32, height: u32) -> Result<Self, OsError> {
+        Self::create(Some((x, y)), width, height)
+    }
+
+    fn create(position: Option<(i32, i32)>, width: u32, height: u32) -> Result<Self, OsError> {
         let mtm = match MainThreadMarker::new() {
             Some(m) => m,
             None => return Err(OsError::NotMainThread),
@@ -196,7 +238,14 @@ impl OsWindow {
         window.setDelegate(Some(object));
 
 
-        window.center();
+        match position {
+            Some((x, y)) => {
+                // AppKit screen coordinates start at the bottom left of the primary screen
+                let primary_height = get_primary_screen_height(mtm)?;
+                window.setFrameTopLeftPoint(NSPoint::new(x as f64, primary_height - (y as f64)));
+            }
+            None => window.center(),
+        }
         window.setTitle(ns_string!("Window Title"));
         window.makeKeyAndOrderFront(None);
 
@@ -212,7 +261,9 @@ impl OsWindow {
             }
         };
 
-        Ok(OsWindow { mtm, app, window, layer })
+        let mut os_window = OsWindow { mtm, app, window, layer, last_size: (0, 0) };
+        os_window.last_size = os_window.get_size();
+        Ok(os_window)
     }
 
     pub(super) fn get_surface_parameters(&self) -> super::vulkan::api::CAMetalLayerPtr {
@@ -241,11 +292,29 @@ impl OsWindow {
                     }
                 }
             } else {
+                // Checked once the queue is empty so a burst of size changes is reported once
+                let size = self.get_size();
+                if size != self.last_size {
+                    self.last_size = size;
+                    return Ok(OsWindowState::Resized(size));
+                }
                 return Ok(OsWindowState::Normal);
             }
         }
     }
 
+    // Returns the content view size in pixels
+    pub(super) fn get_size(&self) -> (u32, u32) {
+        match self.window.contentView() {
+            Some(cv) => {
+                let size = cv.frame().size;
+                let scale = self.window.backingScaleFactor();
+                ((size.width * scale) as u32, (size.height * scale) as u32)
+            }
+            None => (0, 0),
+        }
+    }
+
     pub(super) fn close_window(&mut self) -> Result<(), OsError> {
         //unsafe { self.app.terminate(None) };
         self.window.close();
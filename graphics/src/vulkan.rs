@@ -78,6 +78,33 @@ pub enum Error {
     InvalidMapPtr,
     Spirv(spirv::Error),
     GlyphBufferSizeTooBig,
+    BadFramesInFlight,
+    ZeroSizeSurface,
+    SwapchainOutOfDate,
+    ZeroSizeImage,
+    NonSrgbRenderTarget,
+    NoGlyphData,
+    GlyphDataExceedsStorageRange, // A part of the glyph data is larger than a storage buffer descriptor can cover
+    ImageTooSmall,                // The image has fewer bytes than its size needs
+    ImageExceedsStorageRange,
+    ShaderFileRead,
+    GlyphCacheFileWrite,
+    GlyphCacheFileRead,
+    GlyphCacheInvalid, // The cache file is truncated, corrupt or from a different format version
+}
+
+impl Error {
+    /// Returns true when the GPU device was lost (such as from a driver reset)
+    /// and every Vulkan object needs to be recreated before rendering can resume
+    pub fn is_device_lost(&self) -> bool {
+        matches!(self, Error::VkResult(api::RESULT_ERROR_DEVICE_LOST))
+    }
+
+    /// Returns true when the swapchain no longer matches the surface (such as after a window
+    /// resize) and needs to be recreated before the next frame
+    pub fn is_swapchain_out_of_date(&self) -> bool {
+        matches!(self, Error::SwapchainOutOfDate)
+    }
 }
 
 pub const LAYER_NAME_VALIDATION: &str = "VK_LAYER_KHRONOS_validation";
@@ -91,15 +118,65 @@ pub const INSTANCE_EXTENSION_NAME_OS_SURFACE: &str = "VK_EXT_metal_surface";
 pub const INSTANCE_EXTENSION_NAME_DEBUG: &str = "VK_EXT_debug_utils";
 
 pub const FUNCTION_EXTENSION_NAME_CREATE_DEBUG: &str = "vkCreateDebugUtilsMessengerEXT";
+pub const FUNCTION_EXTENSION_NAME_DESTROY_DEBUG: &str = "vkDestroyDebugUtilsMessengerEXT";
+
+/// Severity of a validation layer debug message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugMessageSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl DebugMessageSeverity {
+    fn from_flags(message_severity: api::DebugUtilsMessageSeverityFlags) -> Self {
+        if (message_severity & api::DebugUtilsMessageSeverityFlagBit::Error as u32) > 0 {
+            DebugMessageSeverity::Error
+        } else if (message_severity & api::DebugUtilsMessageSeverityFlagBit::Warning as u32) > 0 {
+            DebugMessageSeverity::Warning
+        } else if (message_severity & api::DebugUtilsMessageSeverityFlagBit::Info as u32) > 0 {
+            DebugMessageSeverity::Info
+        } else {
+            DebugMessageSeverity::Verbose
+        }
+    }
+}
+
+/// Callback that receives the validation layer debug messages
+pub type DebugMessageCallback = Box<dyn Fn(DebugMessageSeverity, &str) + Send>;
+
+// Validation layers can call back from any thread so the callback is process wide
+static DEBUG_MESSAGE_CALLBACK: std::sync::Mutex<Option<DebugMessageCallback>> =
+    std::sync::Mutex::new(None);
+
+/// Sets (or clears with None) where the validation layer debug messages go for every instance,
+/// such as a debug pane of a terminal UI that printing would garble.
+/// Without a callback the messages get printed
+pub fn set_debug_message_callback(callback: Option<DebugMessageCallback>) {
+    let mut callback_guard = match DEBUG_MESSAGE_CALLBACK.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *callback_guard = callback;
+}
 
 fn debug_print(
-    _message_severity: api::DebugUtilsMessageSeverityFlags,
+    message_severity: api::DebugUtilsMessageSeverityFlags,
     _message_types: api::DebugUtilsMessageTypeFlags,
     p_callback_data: *const api::DebugUtilsMessengerCallbackData,
     _user_data: *const c_void,
 ) -> api::Bool32 {
     let message_cstr = unsafe { CStr::from_ptr((*p_callback_data).p_message) };
-    println!("Debug Msg: {:?}\n", message_cstr);
+    let severity = DebugMessageSeverity::from_flags(message_severity);
+    let callback_guard = match DEBUG_MESSAGE_CALLBACK.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    match &*callback_guard {
+        Some(callback) => callback(severity, &message_cstr.to_string_lossy()),
+        None => println!("Debug Msg: {:?}\n", message_cstr),
+    }
 
     BOOL_FALSE
 }
@@ -209,6 +286,19 @@ impl Instance {
             handle,
         })
     }
+
+    fn destroy(&self) {
+        if let Some(debug_create) = self.debug_create {
+            if let Ok(fn_name_cstr) = CString::new(FUNCTION_EXTENSION_NAME_DESTROY_DEBUG) {
+                let void_fn =
+                    unsafe { api::vkGetInstanceProcAddr(self.handle, fn_name_cstr.as_ptr()) };
+                let destroy_debug_fn: api::DestroyDebugUtilsMessenger =
+                    unsafe { mem::transmute(void_fn) };
+                unsafe { destroy_debug_fn(self.handle, debug_create, ptr::null()) };
+            }
+        }
+        unsafe { api::vkDestroyInstance(self.handle, ptr::null()) };
+    }
 }
 
 pub struct PhysicalDevice {
@@ -217,19 +307,63 @@ pub struct PhysicalDevice {
     local_only_memory_type_index: u32,
     basic_cpu_access_memory_type_index: u32,
     rdma_capable_memory_type_index: Option<u32>,
+    max_storage_buffer_range: u32,
 }
 
 impl PhysicalDevice {
     pub fn new(instance: Instance) -> Result<Option<Self>, Error> {
+        Self::new_from_index(instance, 0)
+    }
+
+    /// Lists the index and device name of every physical device (GPU adapter) in the order
+    /// that new_from_index uses
+    pub fn enumerate(instance: &Instance) -> Result<Vec<(usize, String)>, Error> {
         let devices = [ptr::null(); 32];
-        let device_count = devices.len() as u32;
+        // The device count gets written with the number of devices
+        let mut device_count = devices.len() as u32;
         let result = unsafe {
-            api::vkEnumeratePhysicalDevices(instance.handle, &device_count, devices.as_ptr())
+            api::vkEnumeratePhysicalDevices(
+                instance.handle,
+                ptr::addr_of_mut!(device_count),
+                devices.as_ptr(),
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let mut device_names = Vec::with_capacity(device_count as usize);
+        for (ind, device) in devices[..device_count as usize].iter().enumerate() {
+            let device_properties = PhysicalDeviceProperties2::default();
+            unsafe { api::vkGetPhysicalDeviceProperties2(*device, &device_properties) };
+            device_names.push((ind, device_properties.get_device_name()));
+        }
+        Ok(device_names)
+    }
+
+    /// Picks the physical device at an index from enumerate, returns None when there is no device at that index
+    pub fn new_from_index(instance: Instance, index: usize) -> Result<Option<Self>, Error> {
+        let devices = [ptr::null(); 32];
+        // The device count gets written with the number of devices
+        let mut device_count = devices.len() as u32;
+        let result = unsafe {
+            api::vkEnumeratePhysicalDevices(
+                instance.handle,
+                ptr::addr_of_mut!(device_count),
+                devices.as_ptr(),
+            )
         };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
-        let handle = devices[0];
+        if index >= (device_count as usize) {
+            return Ok(None);
+        }
+        let handle = devices[index];
+
+        let device_properties = PhysicalDeviceProperties2::default();
+        unsafe { api::vkGetPhysicalDeviceProperties2(handle, &device_properties) };
+        let max_storage_buffer_range = device_properties.get_max_storage_buffer_range();
 
         let format_properties = api::FormatProperties::default();
         unsafe {
@@ -292,6 +426,7 @@ impl PhysicalDevice {
             local_only_memory_type_index,
             basic_cpu_access_memory_type_index,
             rdma_capable_memory_type_index,
+            max_storage_buffer_range,
         }))
     }
 
@@ -341,6 +476,7 @@ impl PhysicalDevice {
             return Ok(None);
         }
         let handle = devices[device_index];
+        let max_storage_buffer_range = device_properties.get_max_storage_buffer_range();
 
         let format_properties = api::FormatProperties::default();
         unsafe {
@@ -403,8 +539,106 @@ impl PhysicalDevice {
             local_only_memory_type_index,
             basic_cpu_access_memory_type_index,
             rdma_capable_memory_type_index,
+            max_storage_buffer_range,
         }))
     }
+
+    // Returns the first queue family with graphics and transfer support (presentation is not checked)
+    fn find_graphics_queue_family(&self) -> Option<u32> {
+        let queue_family_property_count = 0;
+        unsafe {
+            api::vkGetPhysicalDeviceQueueFamilyProperties2(
+                self.handle,
+                &queue_family_property_count,
+                ptr::null(),
+            )
+        };
+        let mut queue_family_properties = Vec::with_capacity(queue_family_property_count as usize);
+        for _i in 0..queue_family_property_count {
+            queue_family_properties.push(api::QueueFamilyProperties2::default());
+        }
+        unsafe {
+            api::vkGetPhysicalDeviceQueueFamilyProperties2(
+                self.handle,
+                &queue_family_property_count,
+                queue_family_properties.as_ptr(),
+            )
+        };
+
+        for (ind, qf) in queue_family_properties.iter().enumerate() {
+            let flags = qf.queue_flags;
+            if ((flags & api::QueueFlagBit::Graphics as api::QueueFlags) > 0)
+                && ((flags & api::QueueFlagBit::Transfer as api::QueueFlags) > 0)
+            {
+                return Some(ind as u32);
+            }
+        }
+        None
+    }
+
+    fn supports_synchronization2(&self) -> bool {
+        let mut physical_device_features = api::PhysicalDeviceFeatures2::default();
+        let sync_fetures = api::PhysicalDeviceSynchronization2Features::default();
+        physical_device_features
+            .header
+            .set_next_structure(ptr::addr_of!(sync_fetures) as *const c_void);
+        unsafe { api::vkGetPhysicalDeviceFeatures2(self.handle, &physical_device_features) };
+        sync_fetures.synchronization2 == BOOL_TRUE
+    }
+}
+
+/// Support check result of a physical device (GPU adapter) from check_device_support
+#[derive(Debug)]
+pub struct DeviceSupport {
+    pub index: usize, // Index for PhysicalDevice::new_from_index
+    pub name: String,
+    pub unsupported_reason: Option<Error>, // None when the device has what the renderers need
+}
+
+/// Checks every physical device for what the renderers need without a window or surface:
+/// the blit and sampling format features, synchronization2 and a graphics queue family
+///
+/// Uses a throwaway instance that is destroyed before returning.
+/// Every renderer (2D, triglyph, CPU pixel and offscreen) needs the same device capabilities,
+/// the surface formats and present modes of the windowed renderers can only be checked with a window
+pub fn check_device_support() -> Result<Vec<DeviceSupport>, Error> {
+    let instance = Instance::new("SwiftletSupportCheck", "SwiftletSupportCheck", &[], &[])?;
+    let device_names = match PhysicalDevice::enumerate(&instance) {
+        Ok(d) => d,
+        Err(e) => {
+            instance.destroy();
+            return Err(e);
+        }
+    };
+
+    let mut device_support = Vec::with_capacity(device_names.len());
+    for (index, name) in device_names {
+        // The physical device gets a non-owning copy of the instance handle that is never destroyed
+        let instance_handle_copy = Instance {
+            debug_create: None,
+            handle: instance.handle,
+        };
+        let unsupported_reason = match PhysicalDevice::new_from_index(instance_handle_copy, index) {
+            Ok(Some(physical_device)) => {
+                if !physical_device.supports_synchronization2() {
+                    Some(Error::Synchronization2NotSupported)
+                } else if physical_device.find_graphics_queue_family().is_none() {
+                    Some(Error::NoQueueFamily)
+                } else {
+                    None
+                }
+            }
+            Ok(None) => continue,
+            Err(e) => Some(e),
+        };
+        device_support.push(DeviceSupport {
+            index,
+            name,
+            unsupported_reason,
+        });
+    }
+    instance.destroy();
+    Ok(device_support)
 }
 
 const DEVICE_EXTENSION_NAME_SYNCHRONIZATION: &str = "VK_KHR_synchronization2"; // Shouldn't need this function
@@ -495,18 +729,198 @@ impl Device {
             Err(Error::BadQueueFamilyIndex)
         }
     }
+
+    fn destroy(&self) {
+        unsafe { api::vkDestroyDevice(self.handle, ptr::null()) };
+        self.physical_device.instance.destroy();
+    }
 }
 
-const SWAPCHAIN_PRESENT_MODE: api::PresentMode = api::PresentMode::Immediate;
 const SWAPCHAIN_IMAGE_COUNT: u32 = 3;
 
+/// Upper bound for the frames in flight of a TwoDimensionRender
+///
+/// With 1 frame in flight the CPU waits for the GPU to finish the previous frame before
+/// preparing the next one which gives the lowest latency, with 2 or more the CPU prepares the
+/// next frame while the GPU is still rendering which improves throughput at the cost of
+/// each frame being shown later (and a CPU staging buffer for every frame in flight)
+pub const MAX_FRAMES_IN_FLIGHT: u32 = SWAPCHAIN_IMAGE_COUNT;
+
+/// Tradeoff between input latency and smooth presentation of every frame
+///
+/// Throughput: Uses FIFO presentation (vsync) where every rendered frame is queued and shown
+/// in order without tearing, FIFO is guaranteed by Vulkan so it is available on every platform.
+/// Defaults to 2 frames in flight so the CPU prepares the next frame while the GPU renders
+///
+/// LowLatency: Uses Mailbox presentation where the most recently rendered frame replaces any
+/// queued frame that has not been shown yet which discards older frames without tearing
+/// On Windows most drivers support Mailbox and MoltenVK on macOS does not, when Mailbox is
+/// unavailable Immediate presentation is used instead which has the lowest latency but can tear
+/// and FIFO is the last fallback. Defaults to 1 frame in flight
+///
+/// Explicit: Uses the given present mode, when the surface does not support it FIFO is used
+/// instead since it is always available. Defaults to 2 frames in flight for FIFO and 1 otherwise
+///
+/// The default frames in flight get used when a Vulkan2dWindow is created without a number
+#[derive(Clone, Copy, Debug)]
+pub enum LatencyMode {
+    Throughput,
+    LowLatency,
+    Explicit(PresentMode),
+}
+
+/// Swapchain presentation mode for LatencyMode::Explicit
+///
+/// Immediate: Frames are shown right away which has the lowest latency but tears
+///
+/// Fifo: Frames are queued and shown in order at the display refresh (vsync) without tearing,
+/// it also saves power since rendering gets limited to the refresh rate
+///
+/// Mailbox: The most recently rendered frame replaces a queued frame without tearing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresentMode {
+    Immediate,
+    Fifo,
+    Mailbox,
+}
+
+impl LatencyMode {
+    /// Returns the frames in flight that go with the latency mode (see the LatencyMode docs)
+    pub fn get_frames_in_flight(&self) -> u32 {
+        match self {
+            LatencyMode::Throughput | LatencyMode::Explicit(PresentMode::Fifo) => 2,
+            LatencyMode::LowLatency
+            | LatencyMode::Explicit(PresentMode::Immediate)
+            | LatencyMode::Explicit(PresentMode::Mailbox) => 1,
+        }
+    }
+
+    // Ordered from most to least preferred and always ending with the guaranteed FIFO
+    fn get_present_modes(&self) -> &'static [api::PresentMode] {
+        match self {
+            LatencyMode::Throughput => &[api::PresentMode::Fifo],
+            LatencyMode::LowLatency => &[
+                api::PresentMode::Mailbox,
+                api::PresentMode::Immediate,
+                api::PresentMode::Fifo,
+            ],
+            LatencyMode::Explicit(PresentMode::Immediate) => {
+                &[api::PresentMode::Immediate, api::PresentMode::Fifo]
+            }
+            LatencyMode::Explicit(PresentMode::Fifo) => &[api::PresentMode::Fifo],
+            LatencyMode::Explicit(PresentMode::Mailbox) => {
+                &[api::PresentMode::Mailbox, api::PresentMode::Fifo]
+            }
+        }
+    }
+}
+
+// A surface current extent of this value means the swapchain decides the surface size
+const SURFACE_EXTENT_UNDEFINED: u32 = 0xFFFFFFFF;
+
+// A zero size surface extent (such as from a minimized window) cannot have a swapchain
+// so the caller should try again later when the window has a size
+fn choose_surface_extent(
+    surface_capabilities: &api::SurfaceCapabilities,
+    window_extent: api::Extent2d,
+) -> Result<api::Extent2d, Error> {
+    let extent = if surface_capabilities.current_extent.width == SURFACE_EXTENT_UNDEFINED {
+        api::Extent2d {
+            width: window_extent.width.clamp(
+                surface_capabilities.min_image_extent.width,
+                surface_capabilities.max_image_extent.width,
+            ),
+            height: window_extent.height.clamp(
+                surface_capabilities.min_image_extent.height,
+                surface_capabilities.max_image_extent.height,
+            ),
+        }
+    } else {
+        surface_capabilities.current_extent
+    };
+    if extent.width == 0 || extent.height == 0 {
+        Err(Error::ZeroSizeSurface)
+    } else {
+        Ok(extent)
+    }
+}
+
+// The queue and command buffer used for one off copies through a staging buffer
+#[derive(Clone, Copy)]
+struct CopyQueue {
+    queue: OpaqueHandle,
+    command_buffer: OpaqueHandle,
+}
+
+impl CopyQueue {
+    // Waits for the queue to be idle so the copied staging data can be overwritten afterwards
+    fn copy_buffer(&self, copy_buffer_info: &api::CopyBufferInfo2) -> Result<(), Error> {
+        let cmd_buffer_begin_info = api::CommandBufferBeginInfo {
+            header: StructureHeader::new(StructureType::CommandBufferBeginInfo),
+            flags: api::CommandBufferUsageFlagBit::None as api::CommandBufferUsageFlags,
+            inheritance_info: ptr::null(),
+        };
+
+        let result =
+            unsafe { api::vkBeginCommandBuffer(self.command_buffer, &cmd_buffer_begin_info) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        unsafe { api::vkCmdCopyBuffer2(self.command_buffer, copy_buffer_info) };
+
+        let result = unsafe { api::vkEndCommandBuffer(self.command_buffer) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let cmd_buffer_submit_info = api::CommandBufferSubmitInfo {
+            header: StructureHeader::new(StructureType::CommandBufferSubmitInfo),
+            command_buffer: self.command_buffer,
+            device_mask: 0,
+        };
+        let submit_info = api::SubmitInfo2 {
+            header: StructureHeader::new(StructureType::SubmitInfo2),
+            flags: api::SubmitFlagBit::None as api::SubmitFlags,
+            wait_semaphore_info_count: 0,
+            wait_semaphore_infos: ptr::null(),
+            command_buffer_info_count: 1,
+            command_buffer_infos: &cmd_buffer_submit_info,
+            signal_semaphore_info_count: 0,
+            signal_semaphore_infos: ptr::null(),
+        };
+        let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &submit_info, ptr::null()) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let result = unsafe { api::vkQueueWaitIdle(self.queue) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        Ok(())
+    }
+}
+
+// Out of date and suboptimal both mean the swapchain needs to be recreated
+fn acquire_present_error(result: i32) -> Error {
+    if result == api::RESULT_ERROR_OUT_OF_DATE || result == api::RESULT_SUBOPTIMAL {
+        Error::SwapchainOutOfDate
+    } else {
+        Error::VkResult(result)
+    }
+}
+
 pub struct Swapchain {
     width: u32,
     height: u32,
+    window_extent: api::Extent2d, // Only used when the surface leaves the size up to the swapchain
     present_info: api::PresentInfo,
     submit_info: api::SubmitInfo2,
-    signal_semaphore_submit_info: api::SemaphoreSubmitInfo,
-    wait_semaphore_submit_info: api::SemaphoreSubmitInfo,
+    signal_semaphore_submit_infos: [api::SemaphoreSubmitInfo; SWAPCHAIN_IMAGE_COUNT as usize],
+    wait_semaphore_submit_infos: [api::SemaphoreSubmitInfo; SWAPCHAIN_IMAGE_COUNT as usize],
+    frame_index: usize, // Selects the semaphores used by the next acquire and submit
     cmd_buffer_submit_infos: [api::CommandBufferSubmitInfo; SWAPCHAIN_IMAGE_COUNT as usize],
     command_pool: OpaqueHandle,
     //image_memory_barrier: api::ImageMemoryBarrier2,
@@ -521,6 +935,8 @@ impl Swapchain {
     fn create(
         physical_device: PhysicalDevice,
         surface_handle: OpaqueHandle,
+        latency_mode: LatencyMode,
+        window_extent: api::Extent2d,
     ) -> Result<Self, Error> {
         let surface_format_count = 0;
         let result = unsafe {
@@ -581,7 +997,7 @@ impl Swapchain {
 
         let mut present_modes = Vec::with_capacity(present_mode_count as usize);
         for _i in 0..present_mode_count {
-            present_modes.push(SWAPCHAIN_PRESENT_MODE);
+            present_modes.push(api::PresentMode::Fifo);
         }
         let result = unsafe {
             api::vkGetPhysicalDeviceSurfacePresentModesKHR(
@@ -595,16 +1011,25 @@ impl Swapchain {
             return Err(Error::VkResult(result));
         }
 
-        let mut found_presentation = false;
-        for pm in present_modes {
-            if (SWAPCHAIN_PRESENT_MODE as u32) == (pm as u32) {
-                found_presentation = true;
+        let mut found_presentation = None;
+        for preferred_mode in latency_mode.get_present_modes() {
+            if present_modes
+                .iter()
+                .any(|pm| (*preferred_mode as u32) == (*pm as u32))
+            {
+                found_presentation = Some(*preferred_mode);
                 break;
             }
         }
-        if !found_presentation {
-            return Err(Error::NoPresentationMode);
-        }
+        // Only a surface that breaks the Vulkan guarantee of FIFO support ends up without a
+        // preferred mode so the first supported mode gets used instead of failing
+        let present_mode = match found_presentation {
+            Some(pm) => pm,
+            None => match present_modes.first() {
+                Some(pm) => *pm,
+                None => return Err(Error::NoPresentationMode),
+            },
+        };
         //println!("Found Presentation!");
 
         let queue_family_property_count = 0;
@@ -716,6 +1141,7 @@ impl Swapchain {
             "Surface Width | Height: {} | {}",
             surface_capabilities.current_extent.width, surface_capabilities.current_extent.height
         );
+        let image_extent = choose_surface_extent(&surface_capabilities, window_extent)?;
 
         let swapchain_create_info = api::SwapchainCreateInfo {
             header: StructureHeader::new(StructureType::SwapchainCreateInfo),
@@ -724,10 +1150,7 @@ impl Swapchain {
             min_image_count: SWAPCHAIN_IMAGE_COUNT,
             image_format: api::Format::B8G8R8A8srgb,
             image_color_space: api::ColorSpace::SrgbNonlinear,
-            image_extent: api::Extent2d {
-                width: surface_capabilities.current_extent.width,
-                height: surface_capabilities.current_extent.height,
-            },
+            image_extent,
             image_array_layers: 1,
             image_usage: (api::ImageUsageFlagBit::TransferDst as api::ImageUsageFlags)
                 | (api::ImageUsageFlagBit::ColorAttachment as api::ImageUsageFlags),
@@ -736,7 +1159,7 @@ impl Swapchain {
             p_queue_family_indices: ptr::null(),
             pre_transform: surface_capabilities.current_transform,
             composite_alpha: api::CompositeAlphaFlagBit::Opaque as api::CompositeAlphaFlags,
-            present_mode: SWAPCHAIN_PRESENT_MODE,
+            present_mode,
             clipped: BOOL_TRUE,
             old_swapchain: ptr::null(),
         };
@@ -846,50 +1269,44 @@ impl Swapchain {
             header: StructureHeader::new(StructureType::SemaphoreCreateInfo),
             flags: 0,
         };
-        let wait_semaphore = ptr::null();
-        let result = unsafe {
-            api::vkCreateSemaphore(
-                device.handle,
-                &semaphore_create_info,
-                ptr::null(),
-                &wait_semaphore,
-            )
-        };
-        if result != 0 {
-            return Err(Error::VkResult(result));
-        }
-        let signal_semaphore = ptr::null();
-        let result = unsafe {
-            api::vkCreateSemaphore(
-                device.handle,
-                &semaphore_create_info,
-                ptr::null(),
-                &signal_semaphore,
-            )
-        };
-        if result != 0 {
-            return Err(Error::VkResult(result));
+        // One set of semaphores for each possible frame in flight
+        let wait_semaphores = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+        let signal_semaphores = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+        for semaphore in wait_semaphores.iter().chain(signal_semaphores.iter()) {
+            let result = unsafe {
+                api::vkCreateSemaphore(
+                    device.handle,
+                    &semaphore_create_info,
+                    ptr::null(),
+                    semaphore,
+                )
+            };
+            if result != 0 {
+                return Err(Error::VkResult(result));
+            }
         }
         println!(
             "Wait | Signal Sempahores: {:?} | {:?}",
-            wait_semaphore, signal_semaphore
+            wait_semaphores, signal_semaphores
         );
 
-        let wait_semaphore_submit_info = api::SemaphoreSubmitInfo {
-            header: StructureHeader::new(StructureType::SemaphoreSubmitInfo),
-            semaphore: wait_semaphore,
-            value: 0,
-            stage_mask: api::PipelineStageFlag2Bit::AllCommands as api::PipelineStageFlags2,
-            device_index: 0,
-        };
-
-        let signal_semaphore_submit_info = api::SemaphoreSubmitInfo {
-            header: StructureHeader::new(StructureType::SemaphoreSubmitInfo),
-            semaphore: signal_semaphore,
-            value: 0,
-            stage_mask: api::PipelineStageFlag2Bit::AllCommands as api::PipelineStageFlags2,
-            device_index: 0,
-        };
+        let wait_semaphore_submit_infos =
+            wait_semaphores.map(|semaphore| api::SemaphoreSubmitInfo {
+                header: StructureHeader::new(StructureType::SemaphoreSubmitInfo),
+                semaphore,
+                value: 0,
+                stage_mask: api::PipelineStageFlag2Bit::AllCommands as api::PipelineStageFlags2,
+                device_index: 0,
+            });
+
+        let signal_semaphore_submit_infos =
+            signal_semaphores.map(|semaphore| api::SemaphoreSubmitInfo {
+                header: StructureHeader::new(StructureType::SemaphoreSubmitInfo),
+                semaphore,
+                value: 0,
+                stage_mask: api::PipelineStageFlag2Bit::AllCommands as api::PipelineStageFlags2,
+                device_index: 0,
+            });
 
         let submit_info = api::SubmitInfo2 {
             header: StructureHeader::new(StructureType::SubmitInfo2),
@@ -923,16 +1340,17 @@ impl Swapchain {
         if result != 0 {
             return Err(Error::VkResult(result));
         }
-        let width = surface_capabilities.current_extent.width;
-        let height = surface_capabilities.current_extent.height;
+        let extent = choose_surface_extent(&surface_capabilities, window_extent)?;
 
         Ok(Swapchain {
-            width,
-            height,
+            width: extent.width,
+            height: extent.height,
+            window_extent,
             present_info,
             submit_info,
-            signal_semaphore_submit_info,
-            wait_semaphore_submit_info,
+            signal_semaphore_submit_infos,
+            wait_semaphore_submit_infos,
+            frame_index: 0,
             cmd_buffer_submit_infos,
             command_pool,
             //image_memory_barrier,
@@ -951,6 +1369,8 @@ impl Swapchain {
             windows::Win32::Foundation::HINSTANCE,
             windows::Win32::Foundation::HWND,
         ),
+        latency_mode: LatencyMode,
+        window_size: (u32, u32),
     ) -> Result<Self, Error> {
         let surface_create_info = api::SurfaceCreateInfoWin32 {
             header: StructureHeader::new(StructureType::SurfaceCreateInfoWin32),
@@ -974,13 +1394,19 @@ impl Swapchain {
 
         println!("Got Surface!");
 
-        Swapchain::create(physical_device, surface_handle)
+        let window_extent = api::Extent2d {
+            width: window_size.0,
+            height: window_size.1,
+        };
+        Swapchain::create(physical_device, surface_handle, latency_mode, window_extent)
     }
 
     #[cfg(target_os = "macos")]
     pub fn new(
         physical_device: PhysicalDevice,
         surface_parameters: api::CAMetalLayerPtr,
+        latency_mode: LatencyMode,
+        window_size: (u32, u32),
     ) -> Result<Self, Error> {
         let surface_create_info = api::SurfaceCreateInfoMetal {
             header: StructureHeader::new(StructureType::SurfaceCreateInfoMetal),
@@ -1003,7 +1429,11 @@ impl Swapchain {
 
         println!("Got Surface!");
 
-        Swapchain::create(physical_device, surface_handle)
+        let window_extent = api::Extent2d {
+            width: window_size.0,
+            height: window_size.1,
+        };
+        Swapchain::create(physical_device, surface_handle, latency_mode, window_extent)
     }
 
     fn update_current_size(&mut self) -> Result<(), Error> {
@@ -1018,8 +1448,9 @@ impl Swapchain {
         if result != 0 {
             return Err(Error::VkResult(result));
         }
-        self.width = surface_capabilities.current_extent.width;
-        self.height = surface_capabilities.current_extent.height;
+        let extent = choose_surface_extent(&surface_capabilities, self.window_extent)?;
+        self.width = extent.width;
+        self.height = extent.height;
 
         Ok(())
     }
@@ -1033,20 +1464,23 @@ impl Swapchain {
                 self.device.handle,
                 self.handle,
                 timeout,
-                self.wait_semaphore_submit_info.semaphore,
+                self.wait_semaphore_submit_infos[self.frame_index].semaphore,
                 ptr::null(),
                 &next_image_index,
             )
         };
-        if result != 0 {
-            return Err(Error::VkResult(result));
+        if result != 0 && result != api::RESULT_SUBOPTIMAL {
+            // Nothing gets submitted so the fence is signalled here for the next wait
+            self.signal_fence(fence)?;
+            return Err(acquire_present_error(result));
         }
         //println!("Next Image Index: {}", next_image_index);
 
-        self.submit_info.wait_semaphore_infos = &self.wait_semaphore_submit_info;
+        self.submit_info.wait_semaphore_infos = &self.wait_semaphore_submit_infos[self.frame_index];
         self.submit_info.command_buffer_infos =
             &(self.cmd_buffer_submit_infos[next_image_index as usize]);
-        self.submit_info.signal_semaphore_infos = &self.signal_semaphore_submit_info;
+        self.submit_info.signal_semaphore_infos =
+            &self.signal_semaphore_submit_infos[self.frame_index];
         let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &self.submit_info, fence) };
         if result != 0 {
             return Err(Error::VkResult(result));
@@ -1058,12 +1492,13 @@ impl Swapchain {
         // }
         // self.present_info.wait_semaphore_count = 0;
 
-        self.present_info.wait_semaphores = &self.signal_semaphore_submit_info.semaphore;
+        self.present_info.wait_semaphores =
+            &self.signal_semaphore_submit_infos[self.frame_index].semaphore;
         self.present_info.swapchains = &self.handle;
         self.present_info.image_indicies = &next_image_index;
         let result = unsafe { api::vkQueuePresentKHR(self.queue, &self.present_info) };
         if result != 0 {
-            return Err(Error::VkResult(result));
+            return Err(acquire_present_error(result));
         }
 
         Ok(())
@@ -1078,92 +1513,179 @@ impl Swapchain {
                 self.device.handle,
                 self.handle,
                 timeout,
-                self.wait_semaphore_submit_info.semaphore,
+                self.wait_semaphore_submit_infos[self.frame_index].semaphore,
                 ptr::null(),
                 &next_image_index,
             )
         };
-        if result == 0 {
+        // A suboptimal image can still be rendered to and the present reports it afterwards
+        if result == 0 || result == api::RESULT_SUBOPTIMAL {
             Ok(next_image_index)
         } else {
-            Err(Error::VkResult(result))
+            Err(acquire_present_error(result))
         }
     }
 
-    fn submit_queue_and_present(
-        &mut self,
-        next_image_index: u32,
-        fence: OpaqueHandle,
-    ) -> Result<(), Error> {
-        self.submit_info.wait_semaphore_infos = &self.wait_semaphore_submit_info;
-        self.submit_info.command_buffer_infos =
-            &(self.cmd_buffer_submit_infos[next_image_index as usize]);
-        self.submit_info.signal_semaphore_infos = &self.signal_semaphore_submit_info;
-        let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &self.submit_info, fence) };
-        if result != 0 {
-            return Err(Error::VkResult(result));
-        }
-
-        self.present_info.wait_semaphores = &self.signal_semaphore_submit_info.semaphore;
-        self.present_info.swapchains = &self.handle;
-        self.present_info.image_indicies = &next_image_index;
-        let result = unsafe { api::vkQueuePresentKHR(self.queue, &self.present_info) };
+    // Signals a fence without submitting any commands such as when no image was acquired
+    fn signal_fence(&self, fence: OpaqueHandle) -> Result<(), Error> {
+        let submit_info = api::SubmitInfo2 {
+            header: StructureHeader::new(StructureType::SubmitInfo2),
+            flags: api::SubmitFlagBit::None as api::SubmitFlags,
+            wait_semaphore_info_count: 0,
+            wait_semaphore_infos: ptr::null(),
+            command_buffer_info_count: 0,
+            command_buffer_infos: ptr::null(),
+            signal_semaphore_info_count: 0,
+            signal_semaphore_infos: ptr::null(),
+        };
+        let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &submit_info, fence) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
-
         Ok(())
     }
 
-    fn stage_buffer_copy(&mut self, copy_buffer_info: &api::CopyBufferInfo2) -> Result<(), Error> {
-        let cmd_buffer_begin_info = api::CommandBufferBeginInfo {
-            header: StructureHeader::new(StructureType::CommandBufferBeginInfo),
-            flags: api::CommandBufferUsageFlagBit::None as api::CommandBufferUsageFlags,
-            inheritance_info: ptr::null(),
-        };
-
-        let cmd_buffer = &self.cmd_buffer_submit_infos[0];
-        let result =
-            unsafe { api::vkBeginCommandBuffer(cmd_buffer.command_buffer, &cmd_buffer_begin_info) };
+    /// Recreates the swapchain images at the current surface size for the given window size
+    /// (in pixels), the surface itself is kept so its parameters are not needed again.
+    /// Anything created from the old images (image views, framebuffers and recorded command
+    /// buffers) needs to be recreated by the caller afterwards.
+    pub fn recreate(&mut self, window_size: (u32, u32)) -> Result<(), Error> {
+        let result = unsafe { api::vkDeviceWaitIdle(self.device.handle) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        unsafe { api::vkCmdCopyBuffer2(cmd_buffer.command_buffer, copy_buffer_info) };
-
-        let result = unsafe { api::vkEndCommandBuffer(cmd_buffer.command_buffer) };
+        let surface_capabilities = api::SurfaceCapabilities::default();
+        let result = unsafe {
+            api::vkGetPhysicalDeviceSurfaceCapabilitiesKHR(
+                self.device.physical_device.handle,
+                self.swapchain_create_info.surface,
+                &surface_capabilities,
+            )
+        };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
+        self.window_extent = api::Extent2d {
+            width: window_size.0,
+            height: window_size.1,
+        };
+        let image_extent = choose_surface_extent(&surface_capabilities, self.window_extent)?;
 
-        self.submit_info.wait_semaphore_info_count = 0;
-        self.submit_info.command_buffer_infos = cmd_buffer;
-        self.submit_info.signal_semaphore_info_count = 0;
-        let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &self.submit_info, ptr::null()) };
+        self.swapchain_create_info.image_extent = image_extent;
+        self.swapchain_create_info.pre_transform = surface_capabilities.current_transform;
+        self.swapchain_create_info.old_swapchain = self.handle;
+        let handle = ptr::null();
+        let result = unsafe {
+            api::vkCreateSwapchainKHR(
+                self.device.handle,
+                &self.swapchain_create_info,
+                ptr::null(),
+                &handle,
+            )
+        };
+        self.swapchain_create_info.old_swapchain = ptr::null();
         if result != 0 {
             return Err(Error::VkResult(result));
         }
+        unsafe { api::vkDestroySwapchainKHR(self.device.handle, self.handle, ptr::null()) };
+        self.handle = handle;
 
-        let result = unsafe { api::vkQueueWaitIdle(self.queue) };
+        let swapchain_image_count = SWAPCHAIN_IMAGE_COUNT;
+        let result = unsafe {
+            api::vkGetSwapchainImagesKHR(
+                self.device.handle,
+                self.handle,
+                &swapchain_image_count,
+                self.image_handles.as_ptr(),
+            )
+        };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
-        self.submit_info.wait_semaphore_info_count = 1;
-        self.submit_info.signal_semaphore_info_count = 1;
+        if swapchain_image_count != SWAPCHAIN_IMAGE_COUNT {
+            return Err(Error::BadSwapchainImageCount);
+        }
 
+        self.width = image_extent.width;
+        self.height = image_extent.height;
         Ok(())
     }
-}
 
-pub struct SwapchainCpuRender {
-    img_buffer_mem: OpaqueHandle,
-    img_buffer_check: OpaqueHandle,
-    fence: OpaqueHandle,
-    cpu_buffer_size: u64,
-    cpu_buffer_img_mem: OpaqueHandle,
-    cpu_buffer_image: OpaqueHandle,
-    gpu_img_mem: OpaqueHandle,
-    gpu_image: OpaqueHandle,
+    // The command buffer index is the one that the commands were recorded into which does not
+    // need to match the presented image index (such as with fewer frames in flight than images)
+    fn submit_queue_and_present(
+        &mut self,
+        cmd_buffer_index: usize,
+        next_image_index: u32,
+        fence: OpaqueHandle,
+    ) -> Result<(), Error> {
+        self.submit_info.wait_semaphore_infos = &self.wait_semaphore_submit_infos[self.frame_index];
+        self.submit_info.command_buffer_infos = &(self.cmd_buffer_submit_infos[cmd_buffer_index]);
+        self.submit_info.signal_semaphore_infos =
+            &self.signal_semaphore_submit_infos[self.frame_index];
+        let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &self.submit_info, fence) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        self.present_info.wait_semaphores =
+            &self.signal_semaphore_submit_infos[self.frame_index].semaphore;
+        self.present_info.swapchains = &self.handle;
+        self.present_info.image_indicies = &next_image_index;
+        let result = unsafe { api::vkQueuePresentKHR(self.queue, &self.present_info) };
+        if result != 0 {
+            return Err(acquire_present_error(result));
+        }
+
+        Ok(())
+    }
+
+    fn get_copy_queue(&self) -> CopyQueue {
+        CopyQueue {
+            queue: self.queue,
+            command_buffer: self.cmd_buffer_submit_infos[0].command_buffer,
+        }
+    }
+
+    fn stage_buffer_copy(&mut self, copy_buffer_info: &api::CopyBufferInfo2) -> Result<(), Error> {
+        self.get_copy_queue().copy_buffer(copy_buffer_info)
+    }
+
+    // Destroys everything down to the instance so the device must be idle before calling this
+    fn destroy(&self) {
+        let device = self.device.handle;
+        unsafe {
+            for semaphore_submit_info in self
+                .signal_semaphore_submit_infos
+                .iter()
+                .chain(self.wait_semaphore_submit_infos.iter())
+            {
+                api::vkDestroySemaphore(device, semaphore_submit_info.semaphore, ptr::null());
+            }
+            api::vkDestroyCommandPool(device, self.command_pool, ptr::null());
+            api::vkDestroySwapchainKHR(device, self.handle, ptr::null());
+            api::vkDestroySurfaceKHR(
+                self.device.physical_device.instance.handle,
+                self.swapchain_create_info.surface,
+                ptr::null(),
+            );
+        }
+        self.device.destroy();
+    }
+}
+
+pub struct SwapchainCpuRender {
+    width: u32, // Size of the CPU image which stays the same when the window is resized
+    height: u32,
+    img_buffer_mem: OpaqueHandle,
+    img_buffer_check: OpaqueHandle,
+    fence: OpaqueHandle,
+    cpu_buffer_size: u64,
+    cpu_buffer_img_mem: OpaqueHandle,
+    cpu_buffer_image: OpaqueHandle,
+    gpu_img_mem: OpaqueHandle,
+    gpu_image: OpaqueHandle,
     swapchain: Swapchain,
 }
 
@@ -1311,11 +1833,12 @@ impl SwapchainCpuRender {
                 base_array_layer: 0,
                 layer_count: 1,
             },
+            // Scales the image to the swapchain size which differs after a window resize
             dst_offsets: [
                 api::Offset3d::default(),
                 api::Offset3d {
-                    x: width as i32,
-                    y: height as i32,
+                    x: self.swapchain.width as i32,
+                    y: self.swapchain.height as i32,
                     z: 1,
                 },
             ],
@@ -1673,6 +2196,8 @@ impl SwapchainCpuRender {
         }
 
         let mut scr = SwapchainCpuRender {
+            width,
+            height,
             img_buffer_mem,
             img_buffer_check,
             fence,
@@ -1741,6 +2266,14 @@ impl SwapchainCpuRender {
         Ok(())
     }
 
+    /// Recreates the swapchain for the new window size (in pixels) and records the command
+    /// buffers again for the new swapchain images, call this after a window resize or when
+    /// rendering returns an out of date swapchain error
+    pub fn resize(&mut self, window_size: (u32, u32)) -> Result<(), Error> {
+        self.swapchain.recreate(window_size)?;
+        self.write_command_buffers(self.width, self.height)
+    }
+
     pub fn buffer_check(&mut self, width: u32, height: u32) -> Result<(), Error> {
         std::thread::sleep(std::time::Duration::from_millis(100));
         let data_ptr = ptr::null_mut();
@@ -1775,6 +2308,30 @@ impl SwapchainCpuRender {
 
 const MAIN_DATA: [i8; 5] = [109, 97, 105, 110, 0];
 
+// The SPIR-V gets read from disk once and is then shared by every renderer that gets created
+// (including recreations after a device loss)
+// The shader modules themselves stay with each renderer since every renderer creates its own device
+// and a shader module can only be used (and has to be destroyed) with the device that created it
+static TRIGLYPH_SHADER_BYTES: std::sync::OnceLock<(Vec<u8>, Vec<u8>)> = std::sync::OnceLock::new();
+
+fn get_triglyph_shader_bytes() -> Result<(&'static [u8], &'static [u8]), Error> {
+    if let Some((vertex_bytes, fragment_bytes)) = TRIGLYPH_SHADER_BYTES.get() {
+        return Ok((vertex_bytes, fragment_bytes));
+    }
+    // A failed read is not cached so that a later renderer creation can try again
+    let vertex_bytes = match std::fs::read(std::path::Path::new("shader/triglyph-vert.spv")) {
+        Ok(b) => b,
+        Err(_) => return Err(Error::ShaderFileRead),
+    };
+    let fragment_bytes = match std::fs::read(std::path::Path::new("shader/triglyph-frag.spv")) {
+        Ok(b) => b,
+        Err(_) => return Err(Error::ShaderFileRead),
+    };
+    let (vertex_bytes, fragment_bytes) =
+        TRIGLYPH_SHADER_BYTES.get_or_init(|| (vertex_bytes, fragment_bytes));
+    Ok((vertex_bytes, fragment_bytes))
+}
+
 fn create_shader_stage_from_bytes(
     device: OpaqueHandle,
     data: &[u8],
@@ -1971,6 +2528,152 @@ pub struct GlyphData {
     pub segment_data: Vec<GlyphSegment>,
 }
 
+// Bump the version whenever the cache layout or the way glyph data gets created changes
+// so that caches written by an older crate version get rejected instead of drawing garbage
+const GLYPH_CACHE_MAGIC: [u8; 8] = *b"SWGLYPHS";
+const GLYPH_CACHE_VERSION: u32 = 1;
+const GLYPH_CACHE_HEADER_LEN: usize = 8 + (5 * 4);
+const GLYPH_SEGMENT_LEN: usize = 8 * 4;
+
+impl GlyphData {
+    /// Returns true when there are no glyph segments which would create empty GPU buffers
+    pub fn is_empty(&self) -> bool {
+        (self.num_glyphs == 0) || self.segment_data.is_empty()
+    }
+
+    /// Writes the glyph data to a binary cache file with a version header
+    /// so a fixed font set can skip the outline extraction on later launches
+    pub fn save(&self, path: &std::path::Path) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(
+            GLYPH_CACHE_HEADER_LEN
+                + (self.segment_offsets.len() * 4)
+                + (self.segment_data.len() * GLYPH_SEGMENT_LEN),
+        );
+        bytes.extend_from_slice(&GLYPH_CACHE_MAGIC);
+        let num_offsets = match u32::try_from(self.segment_offsets.len()) {
+            Ok(n) => n,
+            Err(_) => return Err(Error::GlyphCacheInvalid),
+        };
+        let num_segments = match u32::try_from(self.segment_data.len()) {
+            Ok(n) => n,
+            Err(_) => return Err(Error::GlyphCacheInvalid),
+        };
+        let header = [
+            GLYPH_CACHE_VERSION,
+            self.num_glyphs,
+            self.num_aliasing,
+            num_offsets,
+            num_segments,
+        ];
+        for value in header {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for offset in &self.segment_offsets {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+        for segment in &self.segment_data {
+            let values = [
+                segment.is_quad,
+                segment.y0,
+                segment.y1,
+                segment.yq,
+                segment.xmax,
+                segment.x0,
+                segment.x1,
+                segment.xq,
+            ];
+            for value in values {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        match std::fs::write(path, bytes) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(Error::GlyphCacheFileWrite),
+        }
+    }
+
+    /// Reads glyph data that was previously written with save
+    /// A cache from a different format version returns GlyphCacheInvalid so it can be recreated
+    pub fn load(path: &std::path::Path) -> Result<Self, Error> {
+        let bytes = match std::fs::read(path) {
+            Ok(b) => b,
+            Err(_) => return Err(Error::GlyphCacheFileRead),
+        };
+        if (bytes.len() < GLYPH_CACHE_HEADER_LEN) || (bytes[..8] != GLYPH_CACHE_MAGIC) {
+            return Err(Error::GlyphCacheInvalid);
+        }
+        let read_u32 = |index: usize| {
+            let start = 8 + (index * 4);
+            u32::from_le_bytes([
+                bytes[start],
+                bytes[start + 1],
+                bytes[start + 2],
+                bytes[start + 3],
+            ])
+        };
+        if read_u32(0) != GLYPH_CACHE_VERSION {
+            return Err(Error::GlyphCacheInvalid);
+        }
+        let num_glyphs = read_u32(1);
+        let num_aliasing = read_u32(2);
+        let num_offsets = read_u32(3) as usize;
+        let num_segments = read_u32(4) as usize;
+
+        let segments_start = GLYPH_CACHE_HEADER_LEN + (num_offsets * 4);
+        let expected_len = segments_start + (num_segments * GLYPH_SEGMENT_LEN);
+        if bytes.len() != expected_len {
+            return Err(Error::GlyphCacheInvalid);
+        }
+        let mut words = bytes[GLYPH_CACHE_HEADER_LEN..]
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2], c[3]]);
+
+        let mut segment_offsets = Vec::with_capacity(num_offsets);
+        for _ in 0..num_offsets {
+            match words.next() {
+                Some(w) => segment_offsets.push(u32::from_le_bytes(w)),
+                None => return Err(Error::GlyphCacheInvalid),
+            }
+        }
+        let mut segment_data = Vec::with_capacity(num_segments);
+        for _ in 0..num_segments {
+            let mut values = [0.0; 8];
+            for value in &mut values {
+                match words.next() {
+                    Some(w) => *value = f32::from_le_bytes(w),
+                    None => return Err(Error::GlyphCacheInvalid),
+                }
+            }
+            segment_data.push(GlyphSegment {
+                is_quad: values[0],
+                y0: values[1],
+                y1: values[2],
+                yq: values[3],
+                xmax: values[4],
+                x0: values[5],
+                x1: values[6],
+                xq: values[7],
+            });
+        }
+
+        // Every glyph needs its start and end offset and the offsets have to stay inside the segment data
+        if (segment_offsets.len() <= (num_glyphs as usize))
+            || segment_offsets
+                .iter()
+                .any(|offset| (*offset as usize) > segment_data.len())
+        {
+            return Err(Error::GlyphCacheInvalid);
+        }
+
+        Ok(Self {
+            num_glyphs,
+            num_aliasing,
+            segment_offsets,
+            segment_data,
+        })
+    }
+}
+
 pub struct SwapchainTriglyphRender {
     fence: OpaqueHandle,
     descriptor_set: OpaqueHandle,
@@ -2055,6 +2758,7 @@ impl SwapchainTriglyphRender {
                     self.graphics_pipeline,
                 )
             };
+            self.record_viewport_and_scissor(cmd_buffer.command_buffer);
             unsafe {
                 api::vkCmdBindVertexBuffers(
                     cmd_buffer.command_buffer,
@@ -2084,6 +2788,18 @@ impl SwapchainTriglyphRender {
                     ptr::null(),
                 )
             };
+            // Every draw starts at the first primitive
+            let primitive_offset: u32 = 0;
+            unsafe {
+                api::vkCmdPushConstants(
+                    cmd_buffer.command_buffer,
+                    self.pipeline_layout,
+                    api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+                    0,
+                    mem::size_of::<u32>() as u32,
+                    &primitive_offset as *const u32 as *const c_void,
+                )
+            };
             unsafe { api::vkCmdDrawIndexed(cmd_buffer.command_buffer, 0, 0, 0, 0, 0) };
 
             unsafe { api::vkCmdEndRenderPass(cmd_buffer.command_buffer) };
@@ -2102,6 +2818,9 @@ impl SwapchainTriglyphRender {
         max_triangles: u32,
         glyph_data: GlyphData,
     ) -> Result<Self, Error> {
+        if glyph_data.is_empty() {
+            return Err(Error::NoGlyphData);
+        }
         swapchain.update_current_size()?;
         let swapchain_format = swapchain.swapchain_create_info.image_format;
 
@@ -2741,18 +3460,15 @@ impl SwapchainTriglyphRender {
         swapchain.stage_buffer_copy(&copy_buffer_info)?;
 
         // Shader Stage Create
-        let vertex_shader_bytes =
-            std::fs::read(std::path::Path::new("shader/triglyph-vert.spv")).unwrap();
+        let (vertex_shader_bytes, fragment_shader_bytes) = get_triglyph_shader_bytes()?;
         let vertex_shader_stage = create_shader_stage_from_bytes(
             swapchain.device.handle,
-            &vertex_shader_bytes,
+            vertex_shader_bytes,
             api::ShaderStageFlagBit::Vertex,
         )?;
-        let fragment_shader_bytes =
-            std::fs::read(std::path::Path::new("shader/triglyph-frag.spv")).unwrap();
         let fragment_shader_stage = create_shader_stage_from_bytes(
             swapchain.device.handle,
-            &fragment_shader_bytes,
+            fragment_shader_bytes,
             api::ShaderStageFlagBit::Fragment,
         )?;
         // let fragment_shader_stage = create_shader_stage_from_word_stream(
@@ -2786,11 +3502,18 @@ impl SwapchainTriglyphRender {
                 stage_flags: api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
                 immutable_samplers: ptr::null(),
             },
+            api::DescriptorSetLayoutBinding {
+                binding: 4, // Image (of the shared fragment shader)
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                stage_flags: api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+                immutable_samplers: ptr::null(),
+            },
         ];
         let descriptor_set_layout_create_info = api::DescriptorSetLayoutCreateInfo {
             header: StructureHeader::new(StructureType::DescriptorSetLayoutCreateInfo),
             flags: 0,
-            binding_count: 3,
+            binding_count: 4,
             bindings: descriptor_set_layout_binding.as_ptr(),
         };
 
@@ -2807,13 +3530,20 @@ impl SwapchainTriglyphRender {
             return Err(Error::VkResult(result));
         }
 
+        // The fragment shader is shared with TwoDimensionRender which draws its regions
+        // starting at different primitives
+        let push_constant_range = api::PushConstantRange {
+            stage_flags: api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+            offset: 0,
+            size: mem::size_of::<u32>() as u32,
+        };
         let pipeline_layout_create_info = api::PipelineLayoutCreateInfo {
             header: StructureHeader::new(StructureType::PipelineLayoutCreateInfo),
             flags: 0,
             set_layout_count: 1,
             set_layouts: &descriptor_set_layout,
-            push_constant_range_count: 0,
-            push_constant_ranges: ptr::null(),
+            push_constant_range_count: 1,
+            push_constant_ranges: &push_constant_range,
         };
 
         let pipeline_layout = ptr::null();
@@ -2947,6 +3677,15 @@ impl SwapchainTriglyphRender {
             blend_constants: [0.0, 0.0, 0.0, 0.0],
         };
 
+        // Viewport and scissor follow the swapchain size after a resize
+        let dynamic_states = [api::DynamicState::Viewport, api::DynamicState::Scissor];
+        let dynamic_state_create_info = api::PipelineDynamicStateCreateInfo {
+            header: StructureHeader::new(StructureType::PipelineDynamicStateCreateInfo),
+            flags: 0,
+            dynamic_state_count: dynamic_states.len() as u32,
+            dynamic_states: dynamic_states.as_ptr(),
+        };
+
         let graphics_pipeline_create_info = api::GraphicsPipelineCreateInfo {
             header: StructureHeader::new(StructureType::GraphicsPipelineCreateInfo),
             flags: 0,
@@ -2960,7 +3699,7 @@ impl SwapchainTriglyphRender {
             multisample_state: &multisampling_create_info,
             depth_stencil_state: &depth_stencil_create_info,
             color_blend_state: &color_blend_create_info,
-            dynamic_state: ptr::null(),
+            dynamic_state: &dynamic_state_create_info,
             layout: pipeline_layout,
             render_pass,
             subpass: 0,
@@ -2985,7 +3724,7 @@ impl SwapchainTriglyphRender {
         // Descriptor{Pool, Set, etc} Create
         let descriptor_pool_size = api::DescriptorPoolSize {
             descriptor_type: api::DescriptorType::StorageBuffer,
-            descriptor_count: 3,
+            descriptor_count: 4,
         };
         let descriptor_pool_create_info = api::DescriptorPoolCreateInfo {
             header: StructureHeader::new(StructureType::DescriptorPoolCreateInfo),
@@ -3040,6 +3779,13 @@ impl SwapchainTriglyphRender {
             offset: glyph_info_offset_size,
             range: glyph_segment_data_size,
         };
+        // Only TwoDimensionRender has images so the glyph buffer stands in for one here
+        // that never gets read since no image primitives are drawn
+        let image_descriptor_buffer_info = api::DescriptorBufferInfo {
+            buffer: gpu_glyph_buffer,
+            offset: 0,
+            range: glyph_info_offset_size + glyph_segment_data_size,
+        };
         let write_descriptors = [
             api::WriteDescriptorSet {
                 header: StructureHeader::new(StructureType::WriteDescriptorSet),
@@ -3074,11 +3820,22 @@ impl SwapchainTriglyphRender {
                 buffer_info: &segment_descriptor_buffer_info,
                 texel_buffer_view: ptr::null(),
             },
+            api::WriteDescriptorSet {
+                header: StructureHeader::new(StructureType::WriteDescriptorSet),
+                dst_set: descriptor_set,
+                dst_binding: 4,
+                dst_array_element: 0,
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                image_info: ptr::null(),
+                buffer_info: &image_descriptor_buffer_info,
+                texel_buffer_view: ptr::null(),
+            },
         ];
         unsafe {
             api::vkUpdateDescriptorSets(
                 swapchain.device.handle,
-                3,
+                4,
                 write_descriptors.as_ptr(),
                 0,
                 ptr::null(),
@@ -3200,6 +3957,8 @@ impl SwapchainTriglyphRender {
         }
     }
 
+    /// Width and height are the current window size (in pixels) that gets used when the
+    /// swapchain is out of date, the frame always renders at the swapchain size (get_size)
     pub fn render(
         &mut self,
         num_verticies: u32,
@@ -3209,8 +3968,25 @@ impl SwapchainTriglyphRender {
     ) -> Result<(), Error> {
         unsafe { api::vkUnmapMemory(self.swapchain.device.handle, self.cpu_triangle_buffer_mem) };
 
-        // Resize if necessary here in future based on next image index results
-        let next_image_index = self.swapchain.get_next_image_index()? as usize;
+        // An out of date swapchain gets recreated once and the frame is retried on the new one
+        let next_image_index = match self.swapchain.get_next_image_index() {
+            Err(e) if e.is_swapchain_out_of_date() => self
+                .resize((width, height))
+                .and_then(|_| self.swapchain.get_next_image_index()),
+            r => r,
+        };
+        let next_image_index = match next_image_index {
+            Ok(i) => i as usize,
+            Err(e) => {
+                // Nothing gets submitted so the fence is signalled here for the next wait
+                self.swapchain.signal_fence(self.fence)?;
+                return Err(e);
+            }
+        };
+        let extent = api::Extent2d {
+            width: self.swapchain.width,
+            height: self.swapchain.height,
+        };
 
         let vertex_buffer_copy_region = api::BufferCopy2 {
             header: StructureHeader::new(StructureType::BufferCopy2),
@@ -3272,7 +4048,7 @@ impl SwapchainTriglyphRender {
             framebuffer: self.framebuffers[next_image_index],
             render_area: api::Rect2D {
                 offset: api::Offset2d::default(),
-                extent: api::Extent2d { width, height },
+                extent,
             },
             clear_value_count: 1,
             clear_values: &clear_value,
@@ -3309,6 +4085,7 @@ impl SwapchainTriglyphRender {
                 self.graphics_pipeline,
             )
         };
+        self.record_viewport_and_scissor(cmd_buffer);
         unsafe {
             api::vkCmdBindVertexBuffers(
                 cmd_buffer,
@@ -3333,6 +4110,18 @@ impl SwapchainTriglyphRender {
                 ptr::null(),
             )
         };
+        // Every draw starts at the first primitive
+        let primitive_offset: u32 = 0;
+        unsafe {
+            api::vkCmdPushConstants(
+                cmd_buffer,
+                self.pipeline_layout,
+                api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+                0,
+                mem::size_of::<u32>() as u32,
+                &primitive_offset as *const u32 as *const c_void,
+            )
+        };
         unsafe { api::vkCmdDrawIndexed(cmd_buffer, num_triangles * 3, 1, 0, 0, 0) };
 
         unsafe { api::vkCmdEndRenderPass(cmd_buffer) };
@@ -3342,14 +4131,72 @@ impl SwapchainTriglyphRender {
             return Err(Error::VkResult(result));
         }
 
-        self.swapchain
-            .submit_queue_and_present(next_image_index as u32, self.fence)?;
+        // The frame was already submitted so only the next frame uses the recreated swapchain
+        match self.swapchain.submit_queue_and_present(
+            next_image_index,
+            next_image_index as u32,
+            self.fence,
+        ) {
+            Err(e) if e.is_swapchain_out_of_date() => self.resize((width, height)),
+            r => r,
+        }
+    }
+
+    fn record_viewport_and_scissor(&self, cmd_buffer: OpaqueHandle) {
+        let viewport = api::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.swapchain.width as f32,
+            height: self.swapchain.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = api::Rect2D {
+            offset: api::Offset2d::default(),
+            extent: api::Extent2d {
+                width: self.swapchain.width,
+                height: self.swapchain.height,
+            },
+        };
+        unsafe { api::vkCmdSetViewport(cmd_buffer, 0, 1, &viewport) };
+        unsafe { api::vkCmdSetScissor(cmd_buffer, 0, 1, &scissor) };
+    }
+
+    /// Recreates the swapchain and its framebuffers for the new window size (in pixels),
+    /// render already does this when the swapchain is out of date
+    pub fn resize(&mut self, window_size: (u32, u32)) -> Result<(), Error> {
+        // Recreate waits for the device to be idle before anything gets destroyed
+        self.swapchain.recreate(window_size)?;
+
+        let device = self.swapchain.device.handle;
+        unsafe {
+            for framebuffer in self.framebuffers {
+                api::vkDestroyFramebuffer(device, framebuffer, ptr::null());
+            }
+            for image_view in self.swapchain_image_views {
+                api::vkDestroyImageView(device, image_view, ptr::null());
+            }
+        }
+        self.framebuffers = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+        self.swapchain_image_views = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+
+        let (swapchain_image_views, framebuffers) =
+            create_swapchain_framebuffers(&self.swapchain, self.render_pass)?;
+        self.swapchain_image_views = swapchain_image_views;
+        self.framebuffers = framebuffers;
         Ok(())
     }
+
+    /// Current swapchain size in pixels that draw positions should be based on
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.swapchain.width, self.swapchain.height)
+    }
 }
 
+/// GPU vertex layout of a Primitives2d vertex
+#[derive(Clone, Copy, Default)]
 #[repr(C)]
-struct TriVert {
+pub struct TriVert {
     x: f32,
     y: f32,
     tex_x: f32,
@@ -3359,15 +4206,19 @@ struct TriVert {
 const MAX_NUMBER_OF_VERTICIES: u64 = 1 << 16;
 const VERTEX_BUFFER_SIZE: u64 = (mem::size_of::<TriVert>() as u64) * MAX_NUMBER_OF_VERTICIES;
 
+/// GPU index layout of a Primitives2d triangle
+#[derive(Clone, Copy, Default)]
 #[repr(C)]
-struct TriInd {
+pub struct TriInd {
     p0: u16,
     p1: u16,
     p2: u16,
 }
 
+/// GPU color and glyph data layout of a Primitives2d triangle
+#[derive(Clone, Copy, Default)]
 #[repr(C)]
-struct TriPrimData {
+pub struct TriPrimData {
     linear_rgb: [f32; 3],
     linear_alpha: f32,
     glyph_index: u32, // If glyph: [rays_per_outline_po2 (2 bits), index (30 bits)], else [rect_type (2 bits), 0x3FFF_FFFF]
@@ -3378,10 +4229,28 @@ struct TriPrimData {
 
 #[repr(C)]
 struct UniformData {
-    x_mult: f32, // Normalized Horizontal Distance per Pixel
-    y_mult: f32, // Normalized Vertical Distance Per Pixel
-                 // "Texture" Modifiers here in future
+    x_mult: f32,               // Normalized Horizontal Distance per Pixel
+    y_mult: f32,               // Normalized Vertical Distance Per Pixel
+    glyph_coverage_gamma: f32, // Glyph coverage gets raised to this power (1.0 leaves it linear)
+    reserved: f32,
+}
+
+// Start of the image storage buffer, the pixel or plane bytes follow right after it
+#[repr(C)]
+#[derive(Default)]
+struct ImageHeader {
+    yuv_to_rgb: [[f32; 4]; 3], // Rows of [y, u, v, offset] from color::get_yuv_to_rgb_matrix
+    width: u32,                // Zero until the first image gets uploaded
+    height: u32,
+    format: u32,      // 0 for sRGB RGBA pixels and 1 for 8 bit 4:2:0 YUV planes
+    chroma_step: u32, // Byte step between neighbouring chroma values (2 for NV12)
+    chroma_width: u32,
+    u_offset: u32, // Byte offsets of the first U and V values after the header
+    v_offset: u32,
+    reserved: u32,
 }
+/// Glyph coverage gamma that leaves the ray based coverage as is
+pub const DEFAULT_GLYPH_COVERAGE_GAMMA: f32 = 1.0;
 
 pub struct Primitives2d<'a> {
     verticies: &'a mut [TriVert],
@@ -3391,6 +4260,63 @@ pub struct Primitives2d<'a> {
     num_triangles: usize,
     width: f32,
     height: f32,
+    last_bounds: Option<PrimitiveRect>,
+    dirty_region: Option<PrimitiveRect>,
+    regions: RenderRegions,
+    has_overflowed: bool, // Set once a primitive did not fit
+}
+
+/// Most begin_region and end_region calls that the primitives of a single frame can have
+pub const MAX_RENDER_REGIONS: usize = 16;
+
+// Triangles from first_triangle until the next region are clipped to the area
+#[derive(Clone, Copy, Default)]
+struct RenderRegion {
+    area: Option<PrimitiveRect>, // None draws to the whole render target
+    clear_color: Option<[f32; 4]>,
+    first_triangle: u32,
+}
+
+/// The viewport regions of a frame from Primitives2d::get_regions for TwoDimensionRender::render_with_regions
+#[derive(Clone, Copy, Default)]
+pub struct RenderRegions {
+    regions: [RenderRegion; MAX_RENDER_REGIONS],
+    num_regions: usize,
+}
+
+/// CPU memory backed primitive storage that creates Primitives2d without a renderer,
+/// meant for benchmarking the primitive building functions
+pub struct Primitives2dBuffer {
+    verticies: Vec<TriVert>,
+    indicies: Vec<TriInd>,
+    data: Vec<TriPrimData>,
+}
+
+impl Primitives2dBuffer {
+    pub fn new(max_triangles: usize) -> Self {
+        let mut verticies = Vec::new();
+        verticies.resize_with(MAX_NUMBER_OF_VERTICIES as usize, TriVert::default);
+        let mut indicies = Vec::new();
+        indicies.resize_with(max_triangles, TriInd::default);
+        let mut data = Vec::new();
+        data.resize_with(max_triangles, TriPrimData::default);
+        Self {
+            verticies,
+            indicies,
+            data,
+        }
+    }
+
+    // Width and height are the pixel size of the pretend render target
+    pub fn get_primitives(&mut self, width: u32, height: u32) -> Primitives2d<'_> {
+        Primitives2d::new_in_buffers(
+            &mut self.verticies,
+            &mut self.indicies,
+            &mut self.data,
+            width,
+            height,
+        )
+    }
 }
 
 #[derive(Default)]
@@ -3399,12 +4325,89 @@ pub struct PrimitivePosition {
     pub y: f32,
 }
 
+// Pixel bounding box with x, y being the top left corner
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PrimitiveRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl PrimitiveRect {
+    pub fn union(&self, other: &PrimitiveRect) -> PrimitiveRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        PrimitiveRect {
+            x,
+            y,
+            w: (self.x + self.w).max(other.x + other.w) - x,
+            h: (self.y + self.h).max(other.y + other.h) - y,
+        }
+    }
+}
+
+/// How a background image or video frame gets fitted into the render area
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BackgroundFit {
+    /// Keeps the aspect ratio and fits the whole content inside leaving bars on two sides
+    #[default]
+    Letterbox,
+    /// Keeps the aspect ratio and fills the whole area cropping the content on two sides
+    Crop,
+    /// Fills the whole area ignoring the aspect ratio of the content
+    Stretch,
+}
+
+impl BackgroundFit {
+    /// Returns the centered pixel rectangle that the content gets drawn into
+    ///
+    /// For crop the rectangle extends past the render area and gets clipped by the scissor
+    pub fn get_rect(
+        &self,
+        content_width: u32,
+        content_height: u32,
+        area_width: u32,
+        area_height: u32,
+    ) -> PrimitiveRect {
+        let area_w = area_width as f32;
+        let area_h = area_height as f32;
+        if content_width == 0 || content_height == 0 || *self == BackgroundFit::Stretch {
+            return PrimitiveRect {
+                x: 0.0,
+                y: 0.0,
+                w: area_w,
+                h: area_h,
+            };
+        }
+
+        let width_scale = area_w / (content_width as f32);
+        let height_scale = area_h / (content_height as f32);
+        let scale = match self {
+            BackgroundFit::Letterbox => width_scale.min(height_scale),
+            _ => width_scale.max(height_scale),
+        };
+        let w = (content_width as f32) * scale;
+        let h = (content_height as f32) * scale;
+        PrimitiveRect {
+            x: (area_w - w) * 0.5,
+            y: (area_h - h) * 0.5,
+            w,
+            h,
+        }
+    }
+}
+
+/// Premultiplied linear color used by the shaders, see the color module for the full color pipeline
 pub struct PrimitiveColor {
     linear_rgb: [f32; 3],
     linear_alpha: f32,
 }
 
 impl PrimitiveColor {
+    /// Premultiplies the linear rgb by the alpha which is what the default over blending expects
+    ///
+    /// With additive blending the premultiplied color adds light scaled by the alpha (like a fading glow)
     pub fn new_from_linear_rgb_and_alpha(mut linear_rgb: [f32; 3], mut alpha: f32) -> Self {
         alpha = alpha.max(0.0);
         alpha = alpha.min(1.0);
@@ -3417,6 +4420,43 @@ impl PrimitiveColor {
             linear_alpha: alpha,
         }
     }
+
+    /// Keeps the linear rgb as is without premultiplying it by the alpha
+    ///
+    /// Meant for additive blending where the full rgb gets added no matter what the alpha is,
+    /// using it with the default over blending makes translucent colors look too bright
+    pub fn new_raw(linear_rgb: [f32; 3], alpha: f32) -> Self {
+        Self {
+            linear_rgb,
+            linear_alpha: alpha.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Linearizes the sRGB bytes (like a designer color) before premultiplying by the alpha
+    ///
+    /// The render target does the sRGB encode so (255, 128, 0, 1.0) shows up as
+    /// the same orange that a color managed design tool shows for #FF8000
+    pub fn new_from_srgb8(r: u8, g: u8, b: u8, alpha: f32) -> Self {
+        let linear_rgb = [
+            crate::color::get_linear_rgb_float_from_srgb_byte(r),
+            crate::color::get_linear_rgb_float_from_srgb_byte(g),
+            crate::color::get_linear_rgb_float_from_srgb_byte(b),
+        ];
+        Self::new_from_linear_rgb_and_alpha(linear_rgb, alpha)
+    }
+
+    /// Parses a "#RRGGBB" or "#RRGGBBAA" sRGB hex color (the alpha byte is linear)
+    ///
+    /// Returns None when the text is not in one of those forms
+    pub fn new_from_hex(hex: &str) -> Option<Self> {
+        let bytes = crate::color::get_srgb_bytes_from_hex(hex)?;
+        Some(Self::new_from_srgb8(
+            bytes[0],
+            bytes[1],
+            bytes[2],
+            bytes[3] as f32 / 255.0,
+        ))
+    }
 }
 
 pub enum PrimitiveRectangleModifier {
@@ -3427,10 +4467,134 @@ pub enum PrimitiveRectangleModifier {
 }
 
 impl<'a> Primitives2d<'a> {
+    /// Creates empty primitives over caller owned memory such as for testing the primitive
+    /// building functions without a GPU, indicies and data hold one entry per triangle and
+    /// primitives that do not fit in the slices get skipped
+    ///
+    /// Width and height are the pixel size of the pretend render target
+    pub fn new_in_buffers(
+        verticies: &'a mut [TriVert],
+        indicies: &'a mut [TriInd],
+        data: &'a mut [TriPrimData],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Primitives2d {
+            verticies,
+            indicies,
+            data,
+            num_verticies: 0,
+            num_triangles: 0,
+            width: width as f32,
+            height: height as f32,
+            last_bounds: None,
+            dirty_region: None,
+            regions: RenderRegions::default(),
+            has_overflowed: false,
+        }
+    }
+
     pub(super) fn get_num_verts_and_tris(&self) -> (u16, u32) {
         (self.num_verticies as u16, self.num_triangles as u32)
     }
 
+    pub fn get_num_triangles(&self) -> u32 {
+        self.num_triangles as u32
+    }
+
+    /// Number of primitives (rectangles, lines or glyphs) that can still be added this frame
+    ///
+    /// Every primitive takes 4 verticies and 2 triangles, the verticies are also limited
+    /// by the u16 vertex indicies. Primitives added past this get skipped with a one time warning
+    /// and return empty bounds
+    pub fn remaining_capacity(&self) -> usize {
+        let max_verticies = self.verticies.len().min(u16::MAX as usize);
+        let max_triangles = self.indicies.len().min(self.data.len());
+        let remaining_verticies = max_verticies.saturating_sub(self.num_verticies) / 4;
+        let remaining_triangles = max_triangles.saturating_sub(self.num_triangles) / 2;
+        remaining_verticies.min(remaining_triangles)
+    }
+
+    /// Returns true once a primitive was skipped because there was no capacity left
+    pub fn has_overflowed(&self) -> bool {
+        self.has_overflowed
+    }
+
+    // Returns false (and sets has_overflowed) when another primitive does not fit
+    fn has_primitive_capacity(&mut self) -> bool {
+        if self.remaining_capacity() > 0 {
+            return true;
+        }
+        self.has_overflowed = true;
+        false
+    }
+
+    // Returns the pixel bounds of the most recently added primitive
+    pub fn get_last_bounds(&self) -> Option<PrimitiveRect> {
+        self.last_bounds
+    }
+
+    /// Marks a pixel region that changed since the previous frame for the dirty region mode
+    pub fn mark_dirty(&mut self, region: PrimitiveRect) {
+        self.dirty_region = match self.dirty_region {
+            Some(r) => Some(r.union(&region)),
+            None => Some(region),
+        };
+    }
+
+    pub(super) fn get_dirty_region(&self) -> Option<PrimitiveRect> {
+        self.dirty_region
+    }
+
+    /// Clips the primitives added from now on to the pixel area (like one pane of a split screen or
+    /// a picture in picture) until the next begin_region or end_region call
+    ///
+    /// With a clear color the area gets cleared right before its primitives are drawn so every region
+    /// clears independently, without one the primitives composite over what is already there
+    /// (the frame clear color and the regions drawn before it). For N regions call begin_region
+    /// before adding the primitives of each region (in back to front order), positions stay in
+    /// render target pixels so the region content should be offset to the area top left.
+    /// Returns false when there are already MAX_RENDER_REGIONS and the primitives are not clipped
+    pub fn begin_region(
+        &mut self,
+        area: PrimitiveRect,
+        clear_color: Option<&PrimitiveColor>,
+    ) -> bool {
+        let clear_color = clear_color.map(|c| {
+            [
+                c.linear_rgb[0],
+                c.linear_rgb[1],
+                c.linear_rgb[2],
+                c.linear_alpha,
+            ]
+        });
+        self.add_region(Some(area), clear_color)
+    }
+
+    /// Stops clipping so the primitives added from now on draw to the whole render target again,
+    /// returns false when there are already MAX_RENDER_REGIONS
+    pub fn end_region(&mut self) -> bool {
+        self.add_region(None, None)
+    }
+
+    fn add_region(&mut self, area: Option<PrimitiveRect>, clear_color: Option<[f32; 4]>) -> bool {
+        if self.regions.num_regions >= MAX_RENDER_REGIONS {
+            return false;
+        }
+        self.regions.regions[self.regions.num_regions] = RenderRegion {
+            area,
+            clear_color,
+            first_triangle: self.num_triangles as u32,
+        };
+        self.regions.num_regions += 1;
+        true
+    }
+
+    /// Returns the regions of begin_region and end_region calls for TwoDimensionRender::render_with_regions
+    pub fn get_regions(&self) -> RenderRegions {
+        self.regions
+    }
+
     pub fn get_position_from_percentage(&self, mut x: f32, mut y: f32) -> PrimitivePosition {
         x = x.max(0.0);
         y = y.max(0.0);
@@ -3459,7 +4623,15 @@ impl<'a> Primitives2d<'a> {
         dimensions: (f32, f32),
         color: &PrimitiveColor,
         modifier: PrimitiveRectangleModifier,
-    ) {
+    ) -> PrimitiveRect {
+        if !self.has_primitive_capacity() {
+            return PrimitiveRect {
+                x: top_left_pixels.0,
+                y: top_left_pixels.1,
+                w: 0.0,
+                h: 0.0,
+            };
+        }
         let tex_x_max = dimensions.0 + 1.0;
         let tex_y_max = dimensions.1 + 1.0;
         let x_min = top_left_pixels.0 - 1.0;
@@ -3529,25 +4701,384 @@ impl<'a> Primitives2d<'a> {
 
         self.num_verticies += 4;
         self.num_triangles += 2;
+
+        let bounds = PrimitiveRect {
+            x: top_left_pixels.0,
+            y: top_left_pixels.1,
+            w: dimensions.0,
+            h: dimensions.1,
+        };
+        self.last_bounds = Some(bounds);
+        bounds
     }
 
-    pub fn add_glyph(
+    /// Adds a straight line segment from p0 to p1 (in pixels) that is width pixels thick with rounded caps
+    ///
+    /// The line is a rounded corner rectangle rotated along the segment so its edges get the same
+    /// antialiasing as add_rectangle and the caps extend half of the width past both end points,
+    /// parts outside of the render target get clipped by the GPU
+    pub fn add_line(
         &mut self,
-        p0: &PrimitivePosition,
+        p0: (f32, f32),
+        p1: (f32, f32),
+        width: f32,
         color: &PrimitiveColor,
-        offsets: (f32, f32),
-        dimensions: (f32, f32),
-        tex_min: (f32, f32),
-        tex_max: (f32, f32),
-        glyph_index: u32,
-        texture_width: f32,
-    ) {
-        let x_min = p0.x + offsets.0;
-        let x_max = x_min + dimensions.0;
-        let y_max = p0.y - offsets.1;
-        let y_min = y_max - dimensions.1;
+    ) -> PrimitiveRect {
+        if !self.has_primitive_capacity() {
+            return PrimitiveRect {
+                x: p0.0,
+                y: p0.1,
+                w: 0.0,
+                h: 0.0,
+            };
+        }
+        let dx = p1.0 - p0.0;
+        let dy = p1.1 - p0.1;
+        let length = ((dx * dx) + (dy * dy)).sqrt();
+        // A zero length line becomes a dot
+        let (cos, sin) = if length > 0.0 {
+            (dx / length, dy / length)
+        } else {
+            (1.0, 0.0)
+        };
+        let half_width = width * 0.5;
+        let dimensions = (length + width, width);
+        let tex_x_max = dimensions.0 + 1.0;
+        let tex_y_max = dimensions.1 + 1.0;
 
-        self.verticies[self.num_verticies] = TriVert {
+        // Corners along (u) and across (v) the segment from p0 including the antialiasing border
+        let u_min = -half_width - 1.0;
+        let u_max = length + half_width + 1.0;
+        let v_min = -half_width - 1.0;
+        let v_max = half_width + 1.0;
+        let corners = [
+            (u_min, v_min, -1.0, -1.0),
+            (u_max, v_min, tex_x_max, -1.0),
+            (u_max, v_max, tex_x_max, tex_y_max),
+            (u_min, v_max, -1.0, tex_y_max),
+        ];
+        for (vert_ind, (u, v, tex_x, tex_y)) in corners.into_iter().enumerate() {
+            self.verticies[self.num_verticies + vert_ind] = TriVert {
+                x: p0.0 + (u * cos) - (v * sin),
+                y: p0.1 + (u * sin) + (v * cos),
+                tex_x,
+                tex_y,
+            };
+        }
+
+        self.indicies[self.num_triangles] = TriInd {
+            p0: self.num_verticies as u16,
+            p1: (self.num_verticies + 1) as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+        self.indicies[self.num_triangles + 1] = TriInd {
+            p0: (self.num_verticies + 3) as u16,
+            p1: self.num_verticies as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+
+        // Same as PrimitiveRectangleModifier::RoundedCorners with a radius of half of the width
+        self.data[self.num_triangles] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index: 0xBFFF_FFFF,
+            texture_width: dimensions.0,
+            texture_height: dimensions.1,
+            extra: half_width,
+        };
+        self.data[self.num_triangles + 1] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index: 0xBFFF_FFFF,
+            texture_width: dimensions.0,
+            texture_height: dimensions.1,
+            extra: half_width,
+        };
+
+        // Same as add_rectangle the bounds do not include the antialiasing border
+        let mut x_range = (f32::MAX, f32::MIN);
+        let mut y_range = (f32::MAX, f32::MIN);
+        for u in [-half_width, length + half_width] {
+            for v in [-half_width, half_width] {
+                let x = p0.0 + (u * cos) - (v * sin);
+                let y = p0.1 + (u * sin) + (v * cos);
+                x_range = (x_range.0.min(x), x_range.1.max(x));
+                y_range = (y_range.0.min(y), y_range.1.max(y));
+            }
+        }
+
+        self.num_verticies += 4;
+        self.num_triangles += 2;
+
+        let bounds = PrimitiveRect {
+            x: x_range.0,
+            y: y_range.0,
+            w: x_range.1 - x_range.0,
+            h: y_range.1 - y_range.0,
+        };
+        self.last_bounds = Some(bounds);
+        bounds
+    }
+
+    /// Adds the image from TwoDimensionRender::set_image (or the video frame from set_video_frame)
+    /// stretched over the given pixel rectangle (see BackgroundFit::get_rect to keep its aspect ratio)
+    ///
+    /// The color tints the image and its alpha fades it so white keeps the image as is.
+    /// Nothing gets drawn before the first image is set, returns the covered pixel bounds
+    pub fn add_image(
+        &mut self,
+        top_left_pixels: (f32, f32),
+        dimensions: (f32, f32),
+        color: &PrimitiveColor,
+    ) -> PrimitiveRect {
+        if !self.has_primitive_capacity() {
+            return PrimitiveRect {
+                x: top_left_pixels.0,
+                y: top_left_pixels.1,
+                w: 0.0,
+                h: 0.0,
+            };
+        }
+        let x_min = top_left_pixels.0;
+        let y_min = top_left_pixels.1;
+        let x_max = top_left_pixels.0 + dimensions.0;
+        let y_max = top_left_pixels.1 + dimensions.1;
+
+        // Texture coordinates go from 0.0 to 1.0 across the image whatever its size is
+        self.verticies[self.num_verticies] = TriVert {
+            x: x_min,
+            y: y_min,
+            tex_x: 0.0,
+            tex_y: 0.0,
+        };
+        self.verticies[self.num_verticies + 1] = TriVert {
+            x: x_max,
+            y: y_min,
+            tex_x: 1.0,
+            tex_y: 0.0,
+        };
+        self.verticies[self.num_verticies + 2] = TriVert {
+            x: x_max,
+            y: y_max,
+            tex_x: 1.0,
+            tex_y: 1.0,
+        };
+        self.verticies[self.num_verticies + 3] = TriVert {
+            x: x_min,
+            y: y_max,
+            tex_x: 0.0,
+            tex_y: 1.0,
+        };
+
+        self.indicies[self.num_triangles] = TriInd {
+            p0: self.num_verticies as u16,
+            p1: (self.num_verticies + 1) as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+        self.indicies[self.num_triangles + 1] = TriInd {
+            p0: (self.num_verticies + 3) as u16,
+            p1: self.num_verticies as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+
+        // Outline index 0x3E000000 of the last primitive type is the image
+        let glyph_index = 0xFE00_0000;
+        self.data[self.num_triangles] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index,
+            texture_width: dimensions.0,
+            texture_height: dimensions.1,
+            extra: 0.0,
+        };
+        self.data[self.num_triangles + 1] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index,
+            texture_width: dimensions.0,
+            texture_height: dimensions.1,
+            extra: 0.0,
+        };
+
+        self.num_verticies += 4;
+        self.num_triangles += 2;
+
+        let bounds = PrimitiveRect {
+            x: x_min,
+            y: y_min,
+            w: dimensions.0,
+            h: dimensions.1,
+        };
+        self.last_bounds = Some(bounds);
+        bounds
+    }
+
+    /// Adds a soft drop shadow of an element (such as a card or a tooltip) with the given bounds,
+    /// the shadow needs to be added before the element so that it gets drawn behind it
+    ///
+    /// The shadow is the element rectangle (with the corner radius like PrimitiveRectangleModifier::RoundedCorners)
+    /// moved by the offset where the edge fades out over blur pixels on both sides.
+    /// It is computed in the fragment shader from the signed distance to the rounded rectangle
+    /// with a smooth falloff that approximates a Gaussian blur, so it only costs one distance
+    /// calculation per covered pixel and no render to texture or blur pass.
+    /// The blur gets rounded to a quarter pixel (up to 16383.75 pixels), returns the covered pixel bounds
+    pub fn add_shadow(
+        &mut self,
+        bounds: &PrimitiveRect,
+        corner_radius: f32,
+        blur: f32,
+        offset: (f32, f32),
+        color: &PrimitiveColor,
+    ) -> PrimitiveRect {
+        if !self.has_primitive_capacity() {
+            return PrimitiveRect {
+                x: bounds.x + offset.0,
+                y: bounds.y + offset.1,
+                w: 0.0,
+                h: 0.0,
+            };
+        }
+        let blur_quarters = (blur.max(0.0) * 4.0).round().min(65535.0) as u32;
+        let border = ((blur_quarters as f32) * 0.25) + 1.0;
+        let tex_x_max = bounds.w + border;
+        let tex_y_max = bounds.h + border;
+        let x_min = bounds.x + offset.0 - border;
+        let y_min = bounds.y + offset.1 - border;
+        let x_max = bounds.x + offset.0 + tex_x_max;
+        let y_max = bounds.y + offset.1 + tex_y_max;
+
+        self.verticies[self.num_verticies] = TriVert {
+            x: x_min,
+            y: y_min,
+            tex_x: -border,
+            tex_y: -border,
+        };
+        self.verticies[self.num_verticies + 1] = TriVert {
+            x: x_max,
+            y: y_min,
+            tex_x: tex_x_max,
+            tex_y: -border,
+        };
+        self.verticies[self.num_verticies + 2] = TriVert {
+            x: x_max,
+            y: y_max,
+            tex_x: tex_x_max,
+            tex_y: tex_y_max,
+        };
+        self.verticies[self.num_verticies + 3] = TriVert {
+            x: x_min,
+            y: y_max,
+            tex_x: -border,
+            tex_y: tex_y_max,
+        };
+
+        self.indicies[self.num_triangles] = TriInd {
+            p0: self.num_verticies as u16,
+            p1: (self.num_verticies + 1) as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+        self.indicies[self.num_triangles + 1] = TriInd {
+            p0: (self.num_verticies + 3) as u16,
+            p1: self.num_verticies as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+
+        // Rounded corners with the blur in the low bits of the outline index
+        let glyph_index = 0xBF00_0000 | blur_quarters;
+        self.data[self.num_triangles] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index,
+            texture_width: bounds.w,
+            texture_height: bounds.h,
+            extra: corner_radius,
+        };
+        self.data[self.num_triangles + 1] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index,
+            texture_width: bounds.w,
+            texture_height: bounds.h,
+            extra: corner_radius,
+        };
+
+        self.num_verticies += 4;
+        self.num_triangles += 2;
+
+        let shadow_bounds = PrimitiveRect {
+            x: x_min,
+            y: y_min,
+            w: x_max - x_min,
+            h: y_max - y_min,
+        };
+        self.last_bounds = Some(shadow_bounds);
+        shadow_bounds
+    }
+
+    /// Adds connected line segments through the points (like a waveform) with add_line
+    ///
+    /// The rounded caps of neighboring segments overlap so the joints are rounded without gaps,
+    /// a translucent color gets blended twice where the segments overlap.
+    /// Returns the bounds of all of the segments (stops early when out of primitive capacity)
+    pub fn add_polyline(
+        &mut self,
+        points: &[(f32, f32)],
+        width: f32,
+        color: &PrimitiveColor,
+    ) -> PrimitiveRect {
+        if points.len() == 1 {
+            return self.add_line(points[0], points[0], width, color);
+        }
+        let mut bounds: Option<PrimitiveRect> = None;
+        for segment in points.windows(2) {
+            if !self.has_primitive_capacity() {
+                break;
+            }
+            let segment_bounds = self.add_line(segment[0], segment[1], width, color);
+            bounds = match bounds {
+                Some(b) => Some(b.union(&segment_bounds)),
+                None => Some(segment_bounds),
+            };
+        }
+        match bounds {
+            Some(b) => {
+                self.last_bounds = Some(b);
+                b
+            }
+            None => PrimitiveRect {
+                x: 0.0,
+                y: 0.0,
+                w: 0.0,
+                h: 0.0,
+            },
+        }
+    }
+
+    pub fn add_glyph(
+        &mut self,
+        p0: &PrimitivePosition,
+        color: &PrimitiveColor,
+        offsets: (f32, f32),
+        dimensions: (f32, f32),
+        tex_min: (f32, f32),
+        tex_max: (f32, f32),
+        glyph_index: u32,
+        texture_width: f32,
+    ) -> PrimitiveRect {
+        if !self.has_primitive_capacity() {
+            return PrimitiveRect {
+                x: p0.x,
+                y: p0.y,
+                w: 0.0,
+                h: 0.0,
+            };
+        }
+        let x_min = p0.x + offsets.0;
+        let x_max = x_min + dimensions.0;
+        let y_max = p0.y - offsets.1;
+        let y_min = y_max - dimensions.1;
+
+        self.verticies[self.num_verticies] = TriVert {
             x: x_min,
             y: y_max,
             tex_x: tex_min.0,
@@ -3602,62 +5133,544 @@ impl<'a> Primitives2d<'a> {
 
         self.num_verticies += 4;
         self.num_triangles += 2;
-    }
-}
 
-pub struct TwoDimensionRender {
-    cmd_buffer_begin_info: api::CommandBufferBeginInfo,
-    clear_value: ClearValue,
-    fence: OpaqueHandle,
-    descriptor_set: OpaqueHandle,
-    descriptor_pool: OpaqueHandle,
-    graphics_pipeline: OpaqueHandle,
-    pipeline_layout: OpaqueHandle,
-    descriptor_set_layout: OpaqueHandle,
-    shader_stages: [api::PipelineShaderStageCreateInfo; 2],
-    gpu_glyph_buffer_mem: OpaqueHandle,
-    gpu_glyph_buffer: OpaqueHandle,
-    gpu_uniform_buffer_mem: OpaqueHandle,
-    gpu_uniform_buffer: OpaqueHandle,
-    gpu_triangle_buffer_mem: OpaqueHandle,
-    gpu_triangle_buffer: OpaqueHandle,
-    gpu_primitive_data_offset: u64,
-    max_triangles: u64,
-    cpu_staging_buffer_mapped_ptr: *mut c_void,
-    cpu_staging_buffer_size: u64,
-    cpu_staging_buffer_mem: OpaqueHandle,
-    cpu_staging_buffer: OpaqueHandle,
-    framebuffers: [OpaqueHandle; SWAPCHAIN_IMAGE_COUNT as usize],
-    swapchain_image_views: [OpaqueHandle; SWAPCHAIN_IMAGE_COUNT as usize],
-    render_pass_begin_info: RenderPassBeginInfo, // Contains the render_pass OpaqueHandle in it which is why it is here on the list!
-    swapchain: Swapchain,
-}
+        let bounds = PrimitiveRect {
+            x: x_min,
+            y: y_min,
+            w: dimensions.0,
+            h: dimensions.1,
+        };
+        self.last_bounds = Some(bounds);
+        bounds
+    }
 
-impl TwoDimensionRender {
-    pub fn new(
-        mut swapchain: Swapchain,
-        staging_buffer_size: usize,
-        glyph_data: GlyphData,
-    ) -> Result<Self, Error> {
-        swapchain.update_current_size()?;
-        let swapchain_format = swapchain.swapchain_create_info.image_format;
+    // Same as add_glyph but the glyph quad is rotated around p0 (the baseline origin) by the
+    //  provided rotation given as (cos(angle), sin(angle)) where a positive angle is clockwise on screen
+    // The texture coordinates stay the same so the outline rendering in the shader is unaffected
+    pub fn add_glyph_rotated(
+        &mut self,
+        p0: &PrimitivePosition,
+        color: &PrimitiveColor,
+        offsets: (f32, f32),
+        dimensions: (f32, f32),
+        tex_min: (f32, f32),
+        tex_max: (f32, f32),
+        glyph_index: u32,
+        texture_width: f32,
+        rotation: (f32, f32),
+    ) -> PrimitiveRect {
+        if !self.has_primitive_capacity() {
+            return PrimitiveRect {
+                x: p0.x,
+                y: p0.y,
+                w: 0.0,
+                h: 0.0,
+            };
+        }
+        let x_min = offsets.0;
+        let x_max = x_min + dimensions.0;
+        let y_max = -offsets.1;
+        let y_min = y_max - dimensions.1;
+        let (cos, sin) = rotation;
 
-        // Create Renderpass:
-        let color_attachment_description = api::AttachmentDescription2 {
-            header: StructureHeader::new(StructureType::AttachmentDescription2),
-            flags: 0,
-            format: swapchain_format,
-            samples: 1,
-            load_op: api::AttachmentLoadOp::Clear,
-            store_op: api::AttachmentStoreOp::Store,
-            stencil_load_op: api::AttachmentLoadOp::DontCare,
-            stencil_store_op: api::AttachmentStoreOp::DontCare,
-            initial_layout: api::ImageLayout::Undefined,
-            final_layout: api::ImageLayout::PresentSrc,
+        self.verticies[self.num_verticies] = TriVert {
+            x: p0.x + (x_min * cos) - (y_max * sin),
+            y: p0.y + (x_min * sin) + (y_max * cos),
+            tex_x: tex_min.0,
+            tex_y: tex_min.1,
         };
-
-        let color_attachment_reference = api::AttachmentReference2 {
-            header: StructureHeader::new(StructureType::AttachmentReference2),
+        self.verticies[self.num_verticies + 1] = TriVert {
+            x: p0.x + (x_max * cos) - (y_max * sin),
+            y: p0.y + (x_max * sin) + (y_max * cos),
+            tex_x: tex_max.0,
+            tex_y: tex_min.1,
+        };
+        self.verticies[self.num_verticies + 2] = TriVert {
+            x: p0.x + (x_max * cos) - (y_min * sin),
+            y: p0.y + (x_max * sin) + (y_min * cos),
+            tex_x: tex_max.0,
+            tex_y: tex_max.1,
+        };
+        self.verticies[self.num_verticies + 3] = TriVert {
+            x: p0.x + (x_min * cos) - (y_min * sin),
+            y: p0.y + (x_min * sin) + (y_min * cos),
+            tex_x: tex_min.0,
+            tex_y: tex_max.1,
+        };
+
+        self.indicies[self.num_triangles] = TriInd {
+            p0: self.num_verticies as u16,
+            p1: (self.num_verticies + 1) as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+        self.indicies[self.num_triangles + 1] = TriInd {
+            p0: (self.num_verticies + 3) as u16,
+            p1: self.num_verticies as u16,
+            p2: (self.num_verticies + 2) as u16,
+        };
+
+        self.data[self.num_triangles] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index,
+            texture_width,
+            texture_height: 0.0,
+            extra: 0.0,
+        };
+        self.data[self.num_triangles + 1] = TriPrimData {
+            linear_rgb: color.linear_rgb,
+            linear_alpha: color.linear_alpha,
+            glyph_index,
+            texture_width,
+            texture_height: 0.0,
+            extra: 0.0,
+        };
+
+        let mut x_range = (f32::MAX, f32::MIN);
+        let mut y_range = (f32::MAX, f32::MIN);
+        for v in &self.verticies[self.num_verticies..(self.num_verticies + 4)] {
+            x_range = (x_range.0.min(v.x), x_range.1.max(v.x));
+            y_range = (y_range.0.min(v.y), y_range.1.max(v.y));
+        }
+
+        self.num_verticies += 4;
+        self.num_triangles += 2;
+
+        let bounds = PrimitiveRect {
+            x: x_range.0,
+            y: y_range.0,
+            w: x_range.1 - x_range.0,
+            h: y_range.1 - y_range.0,
+        };
+        self.last_bounds = Some(bounds);
+        bounds
+    }
+}
+
+/// Raw Vulkan handles (VkDevice, VkCommandBuffer and VkRenderPass) given to the custom draw
+/// of TwoDimensionRender::render_with along with the pixel area being rendered
+pub struct CustomDrawContext {
+    pub device: *const c_void,
+    pub command_buffer: *const c_void,
+    pub render_pass: *const c_void,
+    pub render_area: PrimitiveRect,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Creates the GPU glyph buffer and copies the glyph data into it through the start of the
+// staging buffer so nothing else can be using the staging buffer at the same time
+// Glyph data larger than a single frame of the staging buffer gets copied in multiple passes
+fn create_glyph_buffer(
+    device: &Device,
+    copy_queue: CopyQueue,
+    glyph_data: &GlyphData,
+    cpu_staging_buffer: OpaqueHandle,
+    cpu_staging_buffer_mapped_ptr: *mut c_void,
+    cpu_staging_buffer_size: u64,
+) -> Result<(OpaqueHandle, OpaqueHandle, u64, u64, u64), Error> {
+    if glyph_data.is_empty() {
+        return Err(Error::NoGlyphData);
+    }
+    let glyph_info_offset_size = ((glyph_data.segment_offsets.len() + 4) << 2) as u64;
+    let glyph_segment_data_size =
+        (glyph_data.segment_data.len() * mem::size_of::<GlyphSegment>()) as u64;
+    let glyph_buffer_size = glyph_info_offset_size + glyph_segment_data_size;
+    println!("Glyph Buffer Size: {}", glyph_buffer_size);
+    if cpu_staging_buffer_size < GLYPH_UPLOAD_MIN_STAGING_SIZE {
+        return Err(Error::GlyphBufferSizeTooBig);
+    }
+    // Each part of the glyph buffer is bound with its own storage buffer descriptor
+    let max_storage_buffer_range = device.physical_device.max_storage_buffer_range as u64;
+    if (glyph_info_offset_size > max_storage_buffer_range)
+        || (glyph_segment_data_size > max_storage_buffer_range)
+    {
+        return Err(Error::GlyphDataExceedsStorageRange);
+    }
+    let (gpu_glyph_buffer, gpu_glyph_buffer_mem, glyph_buffer_mem_reqs_size) =
+        create_storage_buffer(device, glyph_buffer_size)?;
+
+    // Add glyph data to staging buffer
+    let mut glyph_info_offsets = Vec::with_capacity(glyph_data.segment_offsets.len() + 4);
+    glyph_info_offsets.extend_from_slice(&[glyph_data.num_glyphs, glyph_data.num_aliasing, 0, 0]);
+    glyph_info_offsets.extend_from_slice(&glyph_data.segment_offsets);
+    let glyph_info_bytes = unsafe {
+        std::slice::from_raw_parts(
+            glyph_info_offsets.as_ptr() as *const u8,
+            glyph_info_offset_size as usize,
+        )
+    };
+    let glyph_segment_bytes = unsafe {
+        std::slice::from_raw_parts(
+            glyph_data.segment_data.as_ptr() as *const u8,
+            glyph_segment_data_size as usize,
+        )
+    };
+
+    let mut glyph_bytes = Vec::with_capacity(glyph_buffer_size as usize);
+    glyph_bytes.extend_from_slice(glyph_info_bytes);
+    glyph_bytes.extend_from_slice(glyph_segment_bytes);
+
+    upload_buffer_data(
+        copy_queue,
+        cpu_staging_buffer,
+        cpu_staging_buffer_mapped_ptr,
+        cpu_staging_buffer_size,
+        gpu_glyph_buffer,
+        0,
+        &glyph_bytes,
+    )?;
+
+    Ok((
+        gpu_glyph_buffer,
+        gpu_glyph_buffer_mem,
+        glyph_info_offset_size,
+        glyph_segment_data_size,
+        glyph_buffer_mem_reqs_size,
+    ))
+}
+
+// Creates a GPU only storage buffer that gets filled through copies from the staging buffer
+// and returns it with its memory and the allocated memory size
+fn create_storage_buffer(
+    device: &Device,
+    size: u64,
+) -> Result<(OpaqueHandle, OpaqueHandle, u64), Error> {
+    let mem_reqs = api::MemoryRequirements2::default();
+    let buffer_create_info = api::BufferCreateInfo {
+        header: StructureHeader::new(StructureType::BufferCreateInfo),
+        flags: api::BufferCreateFlagBit::None as api::BufferCreateFlags,
+        size,
+        usage: (api::BufferUsageFlagBit::TransferDst as api::BufferUsageFlags)
+            | (api::BufferUsageFlagBit::StorageBuffer as api::BufferUsageFlags),
+        sharing_mode: api::SharingMode::Exclusive,
+        queue_family_index_count: 0, // Exclusive to zero here
+        p_queue_family_indices: ptr::null(),
+    };
+
+    let buffer = ptr::null();
+    let result: i32 =
+        unsafe { api::vkCreateBuffer(device.handle, &buffer_create_info, ptr::null(), &buffer) };
+    if result != 0 {
+        return Err(Error::VkResult(result));
+    }
+
+    let buf_mem_reqs_info = api::BufferMemoryRequirementsInfo2 {
+        header: StructureHeader::new(StructureType::BufferMemoryRequirementsInfo2),
+        buffer,
+    };
+    unsafe { api::vkGetBufferMemoryRequirements2(device.handle, &buf_mem_reqs_info, &mem_reqs) };
+
+    let mem_alloc_info = api::MemoryAllocateInfo {
+        header: StructureHeader::new(StructureType::MemoryAllocateInfo),
+        allocation_size: mem_reqs.size,
+        memory_type_index: device.physical_device.local_only_memory_type_index,
+    };
+    let buffer_mem = ptr::null();
+    let result =
+        unsafe { api::vkAllocateMemory(device.handle, &mem_alloc_info, ptr::null(), &buffer_mem) };
+    if result != 0 {
+        return Err(Error::VkResult(result));
+    }
+    let bind_buf_mem_info = api::BindBufferMemoryInfo {
+        header: StructureHeader::new(StructureType::BindBufferMemoryInfo),
+        buffer,
+        memory: buffer_mem,
+        memory_offset: 0,
+    };
+    let result = unsafe { api::vkBindBufferMemory2(device.handle, 1, &bind_buf_mem_info) };
+    if result != 0 {
+        return Err(Error::VkResult(result));
+    }
+
+    Ok((buffer, buffer_mem, mem_reqs.size))
+}
+
+// Copies the bytes into the destination buffer through the start of the staging buffer
+// so nothing else can be using the staging buffer at the same time
+// Bytes larger than a single frame of the staging buffer get copied in multiple passes
+fn upload_buffer_data(
+    copy_queue: CopyQueue,
+    cpu_staging_buffer: OpaqueHandle,
+    cpu_staging_buffer_mapped_ptr: *mut c_void,
+    cpu_staging_buffer_size: u64,
+    dst_buffer: OpaqueHandle,
+    mut dst_offset: u64,
+    bytes: &[u8],
+) -> Result<(), Error> {
+    // Chunk sizes stay a multiple of 4 bytes to keep the copy offsets aligned
+    let chunk_size = (cpu_staging_buffer_size & !0x3) as usize;
+    for chunk in bytes.chunks(chunk_size) {
+        let staging_data = unsafe {
+            std::slice::from_raw_parts_mut(cpu_staging_buffer_mapped_ptr as *mut u8, chunk.len())
+        };
+        staging_data.copy_from_slice(chunk);
+
+        let copy_buffer_region = api::BufferCopy2 {
+            header: StructureHeader::new(StructureType::BufferCopy2),
+            src_offset: 0,
+            dst_offset,
+            size: chunk.len() as u64,
+        };
+        let copy_buffer_info = api::CopyBufferInfo2 {
+            header: StructureHeader::new(StructureType::CopyBufferInfo2),
+            src_buffer: cpu_staging_buffer,
+            dst_buffer,
+            region_count: 1,
+            regions: &copy_buffer_region,
+        };
+
+        // Waits for the copy to finish before the staging data gets overwritten by the next chunk
+        copy_queue.copy_buffer(&copy_buffer_info)?;
+        dst_offset += chunk.len() as u64;
+    }
+    Ok(())
+}
+
+/// The smallest staging buffer (reserved_cpu_mem) that can upload glyph data, larger staging
+/// buffers need fewer copy passes when uploading large glyph data
+///
+/// Every copy pass (chunk) holds at least 256 outline segments so large glyph data does not
+/// need thousands of passes that each wait for the copy queue
+pub const GLYPH_UPLOAD_MIN_STAGING_SIZE: u64 = (mem::size_of::<GlyphSegment>() as u64) * 256;
+
+// Creates an image view and framebuffer for each swapchain image at the current swapchain size
+fn create_swapchain_framebuffers(
+    swapchain: &Swapchain,
+    render_pass: OpaqueHandle,
+) -> Result<
+    (
+        [OpaqueHandle; SWAPCHAIN_IMAGE_COUNT as usize],
+        [OpaqueHandle; SWAPCHAIN_IMAGE_COUNT as usize],
+    ),
+    Error,
+> {
+    let swapchain_format = swapchain.swapchain_create_info.image_format;
+    let swapchain_image_views = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+    let mut image_view_create_info = api::ImageViewCreateInfo {
+        header: StructureHeader::new(StructureType::ImageViewCreateInfo),
+        flags: 0,
+        image: ptr::null(),
+        view_type: api::ImageViewType::TwoDimensions,
+        format: swapchain_format,
+        components: api::ComponentMapping {
+            r: api::ComponentSwizzle::Identity,
+            g: api::ComponentSwizzle::Identity,
+            b: api::ComponentSwizzle::Identity,
+            a: api::ComponentSwizzle::Identity,
+        },
+        subresource_range: api::ImageSubresourceRange {
+            aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+    };
+    for (ind, iv) in swapchain_image_views.iter().enumerate() {
+        image_view_create_info.image = swapchain.image_handles[ind];
+        let result = unsafe {
+            api::vkCreateImageView(
+                swapchain.device.handle,
+                &image_view_create_info,
+                ptr::null(),
+                iv,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+    }
+
+    let framebuffers: [*const _; 3] = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+    let mut framebuffer_create_info = api::FramebufferCreateInfo {
+        header: StructureHeader::new(StructureType::FramebufferCreateInfo),
+        flags: 0,
+        render_pass,
+        attachment_count: 1,
+        attachments: ptr::null(),
+        width: swapchain.width,
+        height: swapchain.height,
+        layers: 1,
+    };
+    for (ind, fb) in framebuffers.iter().enumerate() {
+        framebuffer_create_info.attachments = &swapchain_image_views[ind];
+        let result = unsafe {
+            api::vkCreateFramebuffer(
+                swapchain.device.handle,
+                &framebuffer_create_info,
+                ptr::null(),
+                fb,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+    }
+
+    Ok((swapchain_image_views, framebuffers))
+}
+
+// The render passes, graphics pipeline and buffers shared by every 2d render target
+struct TwoDimensionPipeline {
+    clear_value: ClearValue,
+    width: u32,
+    height: u32,
+    descriptor_set: OpaqueHandle,
+    descriptor_pool: OpaqueHandle,
+    blend_mode: BlendMode,
+    glyph_coverage_gamma: f32,
+    graphics_pipelines: [OpaqueHandle; BLEND_MODE_COUNT], // One for each blend mode
+    pipeline_layout: OpaqueHandle,
+    descriptor_set_layout: OpaqueHandle,
+    shader_stages: [api::PipelineShaderStageCreateInfo; 2],
+    gpu_glyph_buffer_mem_size: u64,
+    gpu_glyph_buffer_mem: OpaqueHandle,
+    gpu_glyph_buffer: OpaqueHandle,
+    gpu_uniform_buffer_mem_size: u64,
+    gpu_uniform_buffer_mem: OpaqueHandle,
+    gpu_uniform_buffer: OpaqueHandle,
+    gpu_image_buffer_size: u64, // Largest header and image that fit (grows for larger images)
+    gpu_image_buffer_mem_size: u64,
+    gpu_image_buffer_mem: OpaqueHandle,
+    gpu_image_buffer: OpaqueHandle,
+    image_size: (u32, u32), // Zero until the first image gets uploaded
+    background_fit: Option<BackgroundFit>,
+    gpu_triangle_buffer_mem_size: u64,
+    gpu_triangle_buffer_mem: OpaqueHandle,
+    gpu_triangle_buffer: OpaqueHandle,
+    gpu_primitive_data_offset: u64,
+    max_triangles: u64,
+    cpu_staging_buffer_mapped_ptr: *mut c_void,
+    cpu_staging_buffer_size: u64, // Size for a single frame in flight
+    cpu_staging_buffer_mem_size: u64, // Covers every frame in flight
+    cpu_staging_buffer_mem: OpaqueHandle,
+    cpu_staging_buffer: OpaqueHandle,
+    load_render_pass: OpaqueHandle, // Keeps the previous image contents for dirty regions
+    clear_render_pass: OpaqueHandle,
+}
+
+/// How the primitives of a 2d renderer are blended into the render target
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BlendMode {
+    /// Premultiplied over blending for PrimitiveColor::new_from_linear_rgb_and_alpha colors
+    #[default]
+    Over,
+    /// Adds the color onto the render target (for glow and particle effects) and keeps the alpha,
+    /// use PrimitiveColor::new_raw to add the full color no matter what the alpha is
+    Additive,
+    /// Only writes the alpha channel (with over blending) and keeps the colors for masking
+    AlphaWrite,
+}
+
+const BLEND_MODE_COUNT: usize = 3;
+
+/// Device memory allocated by a 2d renderer in bytes (rounded up to the allocation requirements)
+/// and the number of Vulkan objects it created, useful for tracking down leaks and for sizing
+/// the staging buffer (reserved_cpu_mem) with the resulting max_triangles headroom
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryReport {
+    /// GPU only vertex, index and per triangle color buffer
+    pub triangle_buffer_size: u64,
+    /// GPU only glyph outline buffer
+    pub glyph_buffer_size: u64,
+    /// GPU only uniform buffer
+    pub uniform_buffer_size: u64,
+    /// GPU only image buffer (grows to the largest image or video frame set so far)
+    pub image_buffer_size: u64,
+    /// CPU visible staging buffer for every frame in flight
+    pub staging_buffer_size: u64,
+    /// Sum of every size above (the swapchain images are owned by the swapchain and not counted)
+    pub total_size: u64,
+    /// Most triangles that can be drawn in a single frame
+    pub max_triangles: u64,
+    pub num_buffers: u32,
+    pub num_image_views: u32,
+    pub num_framebuffers: u32,
+    pub num_render_passes: u32,
+    pub num_pipelines: u32,
+}
+
+/// How the alpha of pixels read back from a render target gets stored
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PixelAlpha {
+    /// The rgb is already multiplied by the alpha like the renderer blends it (no conversion)
+    #[default]
+    Premultiplied,
+    /// The rgb is divided by the alpha (where the alpha is not zero) for straight alpha image formats like PNG
+    Straight,
+}
+
+// Destroyed handles get set back to null (like the framebuffers on resize) so this counts the live objects
+fn get_live_handle_count(handles: &[OpaqueHandle]) -> u32 {
+    handles.iter().filter(|handle| !handle.is_null()).count() as u32
+}
+
+// Returns the part of the pixel area inside of the render area or None when they do not overlap
+fn get_clipped_render_area(render_area: &api::Rect2D, area: &PrimitiveRect) -> Option<api::Rect2D> {
+    let render_x_max = (render_area.offset.x as u32) + render_area.extent.width;
+    let render_y_max = (render_area.offset.y as u32) + render_area.extent.height;
+    let x_min = (area.x.max(0.0).floor() as u32).max(render_area.offset.x as u32);
+    let y_min = (area.y.max(0.0).floor() as u32).max(render_area.offset.y as u32);
+    let x_max = ((area.x + area.w).ceil().max(0.0) as u32).min(render_x_max);
+    let y_max = ((area.y + area.h).ceil().max(0.0) as u32).min(render_y_max);
+    if x_max <= x_min || y_max <= y_min {
+        return None;
+    }
+    Some(api::Rect2D {
+        offset: api::Offset2d {
+            x: x_min as i32,
+            y: y_min as i32,
+        },
+        extent: api::Extent2d {
+            width: x_max - x_min,
+            height: y_max - y_min,
+        },
+    })
+}
+
+fn get_format_color_space(format: api::Format) -> crate::color::ColorSpace {
+    match format {
+        api::Format::R8G8B8A8srgb | api::Format::B8G8R8A8srgb => crate::color::ColorSpace::Srgb,
+        _ => crate::color::ColorSpace::Linear,
+    }
+}
+
+impl TwoDimensionPipeline {
+    // The render passes end with the image in final_layout so the load render pass expects
+    // the image to already be in that layout
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &Device,
+        copy_queue: CopyQueue,
+        format: api::Format,
+        final_layout: api::ImageLayout,
+        (width, height): (u32, u32),
+        staging_buffer_size: usize,
+        glyph_data: &GlyphData,
+        frames_in_flight: u32,
+    ) -> Result<Self, Error> {
+        // The shaders output linear colors so the format has to do the sRGB encode
+        if get_format_color_space(format) != crate::color::RENDER_TARGET_COLOR_SPACE {
+            return Err(Error::NonSrgbRenderTarget);
+        }
+
+        // Create Renderpass:
+        let color_attachment_description = api::AttachmentDescription2 {
+            header: StructureHeader::new(StructureType::AttachmentDescription2),
+            flags: 0,
+            format,
+            samples: 1,
+            load_op: api::AttachmentLoadOp::Clear,
+            store_op: api::AttachmentStoreOp::Store,
+            stencil_load_op: api::AttachmentLoadOp::DontCare,
+            stencil_store_op: api::AttachmentStoreOp::DontCare,
+            initial_layout: api::ImageLayout::Undefined,
+            final_layout,
+        };
+
+        let color_attachment_reference = api::AttachmentReference2 {
+            header: StructureHeader::new(StructureType::AttachmentReference2),
             attachment: 0,
             layout: api::ImageLayout::ColorAttachmentOptimal,
             aspect_mask: 0,
@@ -3708,7 +5721,7 @@ impl TwoDimensionRender {
         let render_pass = ptr::null();
         let result = unsafe {
             api::vkCreateRenderPass2(
-                swapchain.device.handle,
+                device.handle,
                 &render_pass_create_info,
                 ptr::null(),
                 &render_pass,
@@ -3718,75 +5731,41 @@ impl TwoDimensionRender {
             return Err(Error::VkResult(result));
         }
 
-        // Create Swapchain ImageViews and Corresponding Framebuffers
-        let swapchain_image_views = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
-        let mut image_view_create_info = api::ImageViewCreateInfo {
-            header: StructureHeader::new(StructureType::ImageViewCreateInfo),
-            flags: 0,
-            image: ptr::null(),
-            view_type: api::ImageViewType::TwoDimensions,
-            format: swapchain_format,
-            components: api::ComponentMapping {
-                r: api::ComponentSwizzle::Identity,
-                g: api::ComponentSwizzle::Identity,
-                b: api::ComponentSwizzle::Identity,
-                a: api::ComponentSwizzle::Identity,
-            },
-            subresource_range: api::ImageSubresourceRange {
-                aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
-                base_mip_level: 0,
-                level_count: 1,
-                base_array_layer: 0,
-                layer_count: 1,
-            },
+        // Compatible render pass that keeps the previous contents for the dirty region mode
+        let load_color_attachment_description = api::AttachmentDescription2 {
+            load_op: api::AttachmentLoadOp::Load,
+            initial_layout: final_layout,
+            ..color_attachment_description
         };
-        for (ind, iv) in swapchain_image_views.iter().enumerate() {
-            image_view_create_info.image = swapchain.image_handles[ind];
-            let result = unsafe {
-                api::vkCreateImageView(
-                    swapchain.device.handle,
-                    &image_view_create_info,
-                    ptr::null(),
-                    iv,
-                )
-            };
-            if result != 0 {
-                return Err(Error::VkResult(result));
-            }
-        }
-
-        let framebuffers: [*const _; 3] = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
-        let mut framebuffer_create_info = api::FramebufferCreateInfo {
-            header: StructureHeader::new(StructureType::FramebufferCreateInfo),
-            flags: 0,
-            render_pass,
-            attachment_count: 1,
-            attachments: ptr::null(),
-            width: swapchain.width,
-            height: swapchain.height,
-            layers: 1,
+        let load_dependency = api::SubpassDependency2 {
+            dst_access_mask: (api::AccessFlagBit::ColorAttachmentRead as api::AccessFlags)
+                | (api::AccessFlagBit::ColorAttachmentWrite as api::AccessFlags),
+            ..dependency
         };
-        for (ind, fb) in framebuffers.iter().enumerate() {
-            framebuffer_create_info.attachments = &swapchain_image_views[ind];
-            let result = unsafe {
-                api::vkCreateFramebuffer(
-                    swapchain.device.handle,
-                    &framebuffer_create_info,
-                    ptr::null(),
-                    fb,
-                )
-            };
-            if result != 0 {
-                return Err(Error::VkResult(result));
-            }
+        let load_render_pass_create_info = api::RenderPassCreateInfo2 {
+            attachments: &load_color_attachment_description,
+            dependencies: &load_dependency,
+            ..render_pass_create_info
+        };
+        let load_render_pass = ptr::null();
+        let result = unsafe {
+            api::vkCreateRenderPass2(
+                device.handle,
+                &load_render_pass_create_info,
+                ptr::null(),
+                &load_render_pass,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
         }
 
-        // Create Staging Buffer
-        let cpu_staging_buffer_size = staging_buffer_size as u64;
+        // Create Staging Buffer (one section for each frame in flight)
+        let cpu_staging_buffer_size = ((staging_buffer_size as u64) + 0xFF) & (!0xFF);
         let staging_buffer_create_info = api::BufferCreateInfo {
             header: StructureHeader::new(StructureType::BufferCreateInfo),
             flags: api::BufferCreateFlagBit::None as api::BufferCreateFlags,
-            size: cpu_staging_buffer_size,
+            size: cpu_staging_buffer_size * (frames_in_flight as u64),
             usage: api::BufferUsageFlagBit::TransferSrc as api::BufferUsageFlags,
             sharing_mode: api::SharingMode::Exclusive,
             queue_family_index_count: 0, // Exclusive to zero here
@@ -3796,7 +5775,7 @@ impl TwoDimensionRender {
         let cpu_staging_buffer = ptr::null();
         let result: i32 = unsafe {
             api::vkCreateBuffer(
-                swapchain.device.handle,
+                device.handle,
                 &staging_buffer_create_info,
                 ptr::null(),
                 &cpu_staging_buffer,
@@ -3812,26 +5791,19 @@ impl TwoDimensionRender {
             buffer: cpu_staging_buffer,
         };
         unsafe {
-            api::vkGetBufferMemoryRequirements2(
-                swapchain.device.handle,
-                &buf_mem_reqs_info,
-                &mem_reqs,
-            )
+            api::vkGetBufferMemoryRequirements2(device.handle, &buf_mem_reqs_info, &mem_reqs)
         };
         let staging_buffer_mem_reqs_size = mem_reqs.size;
 
         let mem_alloc_info = api::MemoryAllocateInfo {
             header: StructureHeader::new(StructureType::MemoryAllocateInfo),
             allocation_size: staging_buffer_mem_reqs_size,
-            memory_type_index: swapchain
-                .device
-                .physical_device
-                .basic_cpu_access_memory_type_index,
+            memory_type_index: device.physical_device.basic_cpu_access_memory_type_index,
         };
         let cpu_staging_buffer_mem = ptr::null();
         let result = unsafe {
             api::vkAllocateMemory(
-                swapchain.device.handle,
+                device.handle,
                 &mem_alloc_info,
                 ptr::null(),
                 &cpu_staging_buffer_mem,
@@ -3846,8 +5818,7 @@ impl TwoDimensionRender {
             memory: cpu_staging_buffer_mem,
             memory_offset: 0,
         };
-        let result =
-            unsafe { api::vkBindBufferMemory2(swapchain.device.handle, 1, &bind_buf_mem_info) };
+        let result = unsafe { api::vkBindBufferMemory2(device.handle, 1, &bind_buf_mem_info) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
@@ -3855,7 +5826,7 @@ impl TwoDimensionRender {
         let cpu_staging_buffer_mapped_ptr = ptr::null_mut();
         let result = unsafe {
             api::vkMapMemory(
-                swapchain.device.handle,
+                device.handle,
                 cpu_staging_buffer_mem,
                 0,
                 staging_buffer_mem_reqs_size,
@@ -3892,7 +5863,7 @@ impl TwoDimensionRender {
         let gpu_triangle_buffer = ptr::null();
         let result: i32 = unsafe {
             api::vkCreateBuffer(
-                swapchain.device.handle,
+                device.handle,
                 &triangle_buffer_create_info,
                 ptr::null(),
                 &gpu_triangle_buffer,
@@ -3907,26 +5878,19 @@ impl TwoDimensionRender {
             buffer: gpu_triangle_buffer,
         };
         unsafe {
-            api::vkGetBufferMemoryRequirements2(
-                swapchain.device.handle,
-                &buf_mem_reqs_info,
-                &mem_reqs,
-            )
+            api::vkGetBufferMemoryRequirements2(device.handle, &buf_mem_reqs_info, &mem_reqs)
         };
         let gpu_triangle_buffer_mem_size = mem_reqs.size;
 
         let mem_alloc_info = api::MemoryAllocateInfo {
             header: StructureHeader::new(StructureType::MemoryAllocateInfo),
             allocation_size: gpu_triangle_buffer_mem_size,
-            memory_type_index: swapchain
-                .device
-                .physical_device
-                .local_only_memory_type_index,
+            memory_type_index: device.physical_device.local_only_memory_type_index,
         };
         let gpu_triangle_buffer_mem = ptr::null();
         let result = unsafe {
             api::vkAllocateMemory(
-                swapchain.device.handle,
+                device.handle,
                 &mem_alloc_info,
                 ptr::null(),
                 &gpu_triangle_buffer_mem,
@@ -3942,8 +5906,7 @@ impl TwoDimensionRender {
             memory: gpu_triangle_buffer_mem,
             memory_offset: 0,
         };
-        let result =
-            unsafe { api::vkBindBufferMemory2(swapchain.device.handle, 1, &bind_buf_mem_info) };
+        let result = unsafe { api::vkBindBufferMemory2(device.handle, 1, &bind_buf_mem_info) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
@@ -3964,7 +5927,7 @@ impl TwoDimensionRender {
         let gpu_uniform_buffer = ptr::null();
         let result: i32 = unsafe {
             api::vkCreateBuffer(
-                swapchain.device.handle,
+                device.handle,
                 &uniform_buffer_create_info,
                 ptr::null(),
                 &gpu_uniform_buffer,
@@ -3979,26 +5942,19 @@ impl TwoDimensionRender {
             buffer: gpu_uniform_buffer,
         };
         unsafe {
-            api::vkGetBufferMemoryRequirements2(
-                swapchain.device.handle,
-                &buf_mem_reqs_info,
-                &mem_reqs,
-            )
+            api::vkGetBufferMemoryRequirements2(device.handle, &buf_mem_reqs_info, &mem_reqs)
         };
         let uniform_buffer_mem_reqs_size = mem_reqs.size;
 
         let mem_alloc_info = api::MemoryAllocateInfo {
             header: StructureHeader::new(StructureType::MemoryAllocateInfo),
             allocation_size: uniform_buffer_mem_reqs_size,
-            memory_type_index: swapchain
-                .device
-                .physical_device
-                .local_only_memory_type_index,
+            memory_type_index: device.physical_device.local_only_memory_type_index,
         };
         let gpu_uniform_buffer_mem = ptr::null();
         let result = unsafe {
             api::vkAllocateMemory(
-                swapchain.device.handle,
+                device.handle,
                 &mem_alloc_info,
                 ptr::null(),
                 &gpu_uniform_buffer_mem,
@@ -4013,178 +5969,51 @@ impl TwoDimensionRender {
             memory: gpu_uniform_buffer_mem,
             memory_offset: 0,
         };
-        let result =
-            unsafe { api::vkBindBufferMemory2(swapchain.device.handle, 1, &bind_buf_mem_info) };
+        let result = unsafe { api::vkBindBufferMemory2(device.handle, 1, &bind_buf_mem_info) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        // Fill in Initial Uniform Data Buffer
-        let uniform_data = unsafe {
-            std::slice::from_raw_parts_mut(cpu_staging_buffer_mapped_ptr as *mut UniformData, 1)
-        };
-        uniform_data[0].x_mult = 2.0 / (swapchain.width as f32);
-        uniform_data[0].y_mult = 2.0 / (swapchain.height as f32);
-        println!(
-            "Uniform Data: {}, {}",
-            uniform_data[0].x_mult, uniform_data[0].y_mult
-        );
-
-        let copy_buffer_region = api::BufferCopy2 {
-            header: StructureHeader::new(StructureType::BufferCopy2),
-            src_offset: 0,
-            dst_offset: 0,
-            size: uniform_buffer_size,
-        };
-        let copy_buffer_info = api::CopyBufferInfo2 {
-            header: StructureHeader::new(StructureType::CopyBufferInfo2),
-            src_buffer: cpu_staging_buffer,
-            dst_buffer: gpu_uniform_buffer,
-            region_count: 1,
-            regions: &copy_buffer_region,
-        };
-
-        swapchain.stage_buffer_copy(&copy_buffer_info)?;
-
         // Glyph Data Buffer Create
-        let glyph_info_offset_size = ((glyph_data.segment_offsets.len() + 4) << 2) as u64;
-        let glyph_segment_data_size =
-            (glyph_data.segment_data.len() * mem::size_of::<GlyphSegment>()) as u64;
-        let glyph_buffer_size = glyph_info_offset_size + glyph_segment_data_size;
-        let glyph_buffer_create_info = api::BufferCreateInfo {
-            header: StructureHeader::new(StructureType::BufferCreateInfo),
-            flags: api::BufferCreateFlagBit::None as api::BufferCreateFlags,
-            size: glyph_buffer_size,
-            usage: (api::BufferUsageFlagBit::TransferDst as api::BufferUsageFlags)
-                | (api::BufferUsageFlagBit::StorageBuffer as api::BufferUsageFlags),
-            sharing_mode: api::SharingMode::Exclusive,
-            queue_family_index_count: 0, // Exclusive to zero here
-            p_queue_family_indices: ptr::null(),
-        };
-
-        let gpu_glyph_buffer = ptr::null();
-        let result: i32 = unsafe {
-            api::vkCreateBuffer(
-                swapchain.device.handle,
-                &glyph_buffer_create_info,
-                ptr::null(),
-                &gpu_glyph_buffer,
-            )
-        };
-        if result != 0 {
-            return Err(Error::VkResult(result));
-        }
-
-        let buf_mem_reqs_info = api::BufferMemoryRequirementsInfo2 {
-            header: StructureHeader::new(StructureType::BufferMemoryRequirementsInfo2),
-            buffer: gpu_glyph_buffer,
-        };
-        unsafe {
-            api::vkGetBufferMemoryRequirements2(
-                swapchain.device.handle,
-                &buf_mem_reqs_info,
-                &mem_reqs,
-            )
-        };
-        let glyph_buffer_mem_reqs_size = mem_reqs.size;
-
-        let mem_alloc_info = api::MemoryAllocateInfo {
-            header: StructureHeader::new(StructureType::MemoryAllocateInfo),
-            allocation_size: glyph_buffer_mem_reqs_size,
-            memory_type_index: swapchain
-                .device
-                .physical_device
-                .local_only_memory_type_index,
-        };
-        let gpu_glyph_buffer_mem = ptr::null();
-        let result = unsafe {
-            api::vkAllocateMemory(
-                swapchain.device.handle,
-                &mem_alloc_info,
-                ptr::null(),
-                &gpu_glyph_buffer_mem,
-            )
-        };
-        if result != 0 {
-            return Err(Error::VkResult(result));
-        }
-        let bind_buf_mem_info = api::BindBufferMemoryInfo {
-            header: StructureHeader::new(StructureType::BindBufferMemoryInfo),
-            buffer: gpu_glyph_buffer,
-            memory: gpu_glyph_buffer_mem,
-            memory_offset: 0,
-        };
-        let result =
-            unsafe { api::vkBindBufferMemory2(swapchain.device.handle, 1, &bind_buf_mem_info) };
-        if result != 0 {
-            return Err(Error::VkResult(result));
-        }
-
-        // Add glyph data to staging buffer
-        println!("Glyph Buffer Size: {}", glyph_buffer_size);
-        if glyph_buffer_size > cpu_staging_buffer_size {
-            return Err(Error::GlyphBufferSizeTooBig);
-        }
-
-        let glyph_info_offsets_len = glyph_data.segment_offsets.len() + 4;
-        let glyph_info_offsets = unsafe {
-            std::slice::from_raw_parts_mut(
-                cpu_staging_buffer_mapped_ptr as *mut u32,
-                glyph_info_offsets_len,
-            )
-        };
-        let index_offset = unsafe { cpu_staging_buffer_mapped_ptr.add(glyph_info_offsets_len * 4) };
-        let glyph_segments = unsafe {
-            std::slice::from_raw_parts_mut(
-                index_offset as *mut GlyphSegment,
-                glyph_data.segment_data.len(),
-            )
-        };
-
-        glyph_info_offsets[0] = glyph_data.num_glyphs;
-        glyph_info_offsets[1] = glyph_data.num_aliasing;
-        glyph_info_offsets[2] = 0;
-        glyph_info_offsets[3] = 0;
-        glyph_info_offsets[4..].copy_from_slice(&glyph_data.segment_offsets);
-        glyph_segments.copy_from_slice(&glyph_data.segment_data);
-
-        let copy_buffer_region = api::BufferCopy2 {
-            header: StructureHeader::new(StructureType::BufferCopy2),
-            src_offset: 0,
-            dst_offset: 0,
-            size: glyph_buffer_size,
-        };
-        let copy_buffer_info = api::CopyBufferInfo2 {
-            header: StructureHeader::new(StructureType::CopyBufferInfo2),
-            src_buffer: cpu_staging_buffer,
-            dst_buffer: gpu_glyph_buffer,
-            region_count: 1,
-            regions: &copy_buffer_region,
-        };
+        let (
+            gpu_glyph_buffer,
+            gpu_glyph_buffer_mem,
+            glyph_info_offset_size,
+            glyph_segment_data_size,
+            gpu_glyph_buffer_mem_size,
+        ) = create_glyph_buffer(
+            device,
+            copy_queue,
+            glyph_data,
+            cpu_staging_buffer,
+            cpu_staging_buffer_mapped_ptr,
+            cpu_staging_buffer_size,
+        )?;
 
-        swapchain.stage_buffer_copy(&copy_buffer_info)?;
+        // Image Buffer Create
+        // Only holds the empty image header until the first image gets set
+        let gpu_image_buffer_size = mem::size_of::<ImageHeader>() as u64;
+        let (gpu_image_buffer, gpu_image_buffer_mem, gpu_image_buffer_mem_size) =
+            create_storage_buffer(device, gpu_image_buffer_size)?;
 
         // Shader Stage Create
-        let vertex_shader_bytes =
-            std::fs::read(std::path::Path::new("shader/triglyph-vert.spv")).unwrap();
+        let (vertex_shader_bytes, fragment_shader_bytes) = get_triglyph_shader_bytes()?;
         let vertex_shader_stage = create_shader_stage_from_bytes(
-            swapchain.device.handle,
-            &vertex_shader_bytes,
+            device.handle,
+            vertex_shader_bytes,
             api::ShaderStageFlagBit::Vertex,
         )?;
-        let fragment_shader_bytes =
-            std::fs::read(std::path::Path::new("shader/triglyph-frag.spv")).unwrap();
         let fragment_shader_stage = create_shader_stage_from_bytes(
-            swapchain.device.handle,
-            &fragment_shader_bytes,
+            device.handle,
+            fragment_shader_bytes,
             api::ShaderStageFlagBit::Fragment,
         )?;
         // let fragment_shader_stage = create_shader_stage_from_word_stream(
-        //     swapchain.device.handle,
+        //     device.handle,
         //     &COLOR_FRAGMENT_SHADER_DATA,
         //     api::ShaderStageFlagBit::Fragment,
         // )?;
-        //let _fragment_shader_stage_alt = create_basic_fragment_shader(swapchain.device.handle)?;
+        //let _fragment_shader_stage_alt = create_basic_fragment_shader(device.handle)?;
         let shader_stages = [vertex_shader_stage, fragment_shader_stage];
 
         // Create Pipeline Layout
@@ -4218,18 +6047,25 @@ impl TwoDimensionRender {
                     | (api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags),
                 immutable_samplers: ptr::null(),
             },
+            api::DescriptorSetLayoutBinding {
+                binding: 4, // Image Buffer
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                stage_flags: api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+                immutable_samplers: ptr::null(),
+            },
         ];
         let descriptor_set_layout_create_info = api::DescriptorSetLayoutCreateInfo {
             header: StructureHeader::new(StructureType::DescriptorSetLayoutCreateInfo),
             flags: 0,
-            binding_count: 4,
+            binding_count: 5,
             bindings: descriptor_set_layout_binding.as_ptr(),
         };
 
         let descriptor_set_layout = ptr::null();
         let result = unsafe {
             api::vkCreateDescriptorSetLayout(
-                swapchain.device.handle,
+                device.handle,
                 &descriptor_set_layout_create_info,
                 ptr::null(),
                 &descriptor_set_layout,
@@ -4239,19 +6075,25 @@ impl TwoDimensionRender {
             return Err(Error::VkResult(result));
         }
 
+        // The first primitive of each region draw call
+        let push_constant_range = api::PushConstantRange {
+            stage_flags: api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+            offset: 0,
+            size: mem::size_of::<u32>() as u32,
+        };
         let pipeline_layout_create_info = api::PipelineLayoutCreateInfo {
             header: StructureHeader::new(StructureType::PipelineLayoutCreateInfo),
             flags: 0,
             set_layout_count: 1,
             set_layouts: &descriptor_set_layout,
-            push_constant_range_count: 0,
-            push_constant_ranges: ptr::null(),
+            push_constant_range_count: 1,
+            push_constant_ranges: &push_constant_range,
         };
 
         let pipeline_layout = ptr::null();
         let result = unsafe {
             api::vkCreatePipelineLayout(
-                swapchain.device.handle,
+                device.handle,
                 &pipeline_layout_create_info,
                 ptr::null(),
                 &pipeline_layout,
@@ -4298,17 +6140,14 @@ impl TwoDimensionRender {
         let viewport = api::Viewport {
             x: 0.0,
             y: 0.0,
-            width: swapchain.width as f32,
-            height: swapchain.height as f32,
+            width: width as f32,
+            height: height as f32,
             min_depth: 0.0,
             max_depth: 1.0,
         };
         let scissor = api::Rect2D {
             offset: api::Offset2d::default(),
-            extent: api::Extent2d {
-                width: swapchain.width,
-                height: swapchain.height,
-            },
+            extent: api::Extent2d { width, height },
         };
         let viewport_create_info = api::PipelineViewportStateCreateInfo {
             header: StructureHeader::new(StructureType::PipelineViewportStateCreateInfo),
@@ -4359,55 +6198,90 @@ impl TwoDimensionRender {
             max_depth_bounds: 1.0,
         };
 
-        let color_blend_attachment = api::PipelineColorBlendAttachmentState {
-            blend_enable: BOOL_TRUE,
-            src_color_blend_factor: api::BlendFactor::One,
-            dst_color_blend_factor: api::BlendFactor::OneMinusSrcAlpha,
-            color_blend_op: api::BlendOp::Add,
-            src_alpha_blend_factor: api::BlendFactor::One,
-            dst_alpha_blend_factor: api::BlendFactor::OneMinusSrcAlpha,
-            alpha_blend_op: api::BlendOp::Add,
-            color_write_mask: api::ColorComponentFlagBit::All as api::ColorComponentFlags,
-        };
-        let color_blend_create_info = api::PipelineColorBlendStateCreateInfo {
-            header: StructureHeader::new(StructureType::PipelineColorBlendStateCreateInfo),
+        // In BlendMode order
+        let color_blend_attachments = [
+            api::PipelineColorBlendAttachmentState {
+                blend_enable: BOOL_TRUE,
+                src_color_blend_factor: api::BlendFactor::One,
+                dst_color_blend_factor: api::BlendFactor::OneMinusSrcAlpha,
+                color_blend_op: api::BlendOp::Add,
+                src_alpha_blend_factor: api::BlendFactor::One,
+                dst_alpha_blend_factor: api::BlendFactor::OneMinusSrcAlpha,
+                alpha_blend_op: api::BlendOp::Add,
+                color_write_mask: api::ColorComponentFlagBit::All as api::ColorComponentFlags,
+            },
+            api::PipelineColorBlendAttachmentState {
+                blend_enable: BOOL_TRUE,
+                src_color_blend_factor: api::BlendFactor::One,
+                dst_color_blend_factor: api::BlendFactor::One,
+                color_blend_op: api::BlendOp::Add,
+                src_alpha_blend_factor: api::BlendFactor::Zero,
+                dst_alpha_blend_factor: api::BlendFactor::One,
+                alpha_blend_op: api::BlendOp::Add,
+                color_write_mask: api::ColorComponentFlagBit::All as api::ColorComponentFlags,
+            },
+            api::PipelineColorBlendAttachmentState {
+                blend_enable: BOOL_TRUE,
+                src_color_blend_factor: api::BlendFactor::One,
+                dst_color_blend_factor: api::BlendFactor::OneMinusSrcAlpha,
+                color_blend_op: api::BlendOp::Add,
+                src_alpha_blend_factor: api::BlendFactor::One,
+                dst_alpha_blend_factor: api::BlendFactor::OneMinusSrcAlpha,
+                alpha_blend_op: api::BlendOp::Add,
+                color_write_mask: api::ColorComponentFlagBit::A as api::ColorComponentFlags,
+            },
+        ];
+        let color_blend_create_infos: [api::PipelineColorBlendStateCreateInfo; BLEND_MODE_COUNT] =
+            std::array::from_fn(|i| api::PipelineColorBlendStateCreateInfo {
+                header: StructureHeader::new(StructureType::PipelineColorBlendStateCreateInfo),
+                flags: 0,
+                logic_op_enable: BOOL_FALSE,
+                logic_op: api::LogicOp::Copy,
+                attachment_count: 1,
+                attachments: &color_blend_attachments[i],
+                blend_constants: [0.0, 0.0, 0.0, 0.0],
+            });
+
+        // Viewport follows the target size after a resize and scissor follows the render area
+        // which is smaller than the target for dirty regions
+        let dynamic_states = [api::DynamicState::Viewport, api::DynamicState::Scissor];
+        let dynamic_state_create_info = api::PipelineDynamicStateCreateInfo {
+            header: StructureHeader::new(StructureType::PipelineDynamicStateCreateInfo),
             flags: 0,
-            logic_op_enable: BOOL_FALSE,
-            logic_op: api::LogicOp::Copy,
-            attachment_count: 1,
-            attachments: &color_blend_attachment,
-            blend_constants: [0.0, 0.0, 0.0, 0.0],
+            dynamic_state_count: dynamic_states.len() as u32,
+            dynamic_states: dynamic_states.as_ptr(),
         };
 
-        let graphics_pipeline_create_info = api::GraphicsPipelineCreateInfo {
-            header: StructureHeader::new(StructureType::GraphicsPipelineCreateInfo),
-            flags: 0,
-            stage_count: 2,
-            stages: shader_stages.as_ptr(),
-            vertex_input_state: &vertex_input_create_info,
-            input_assembly_state: &input_assembly_create_info,
-            tessellation_state: &tessilation_create_info,
-            viewport_state: &viewport_create_info,
-            rasterization_state: &rasterization_create_info,
-            multisample_state: &multisampling_create_info,
-            depth_stencil_state: &depth_stencil_create_info,
-            color_blend_state: &color_blend_create_info,
-            dynamic_state: ptr::null(),
-            layout: pipeline_layout,
-            render_pass,
-            subpass: 0,
-            base_pipeline_handle: ptr::null(),
-            base_pipeline_index: -1,
-        };
-        let graphics_pipeline = ptr::null();
+        let graphics_pipeline_create_infos: [api::GraphicsPipelineCreateInfo; BLEND_MODE_COUNT] =
+            std::array::from_fn(|i| api::GraphicsPipelineCreateInfo {
+                header: StructureHeader::new(StructureType::GraphicsPipelineCreateInfo),
+                flags: 0,
+                stage_count: 2,
+                stages: shader_stages.as_ptr(),
+                vertex_input_state: &vertex_input_create_info,
+                input_assembly_state: &input_assembly_create_info,
+                tessellation_state: &tessilation_create_info,
+                viewport_state: &viewport_create_info,
+                rasterization_state: &rasterization_create_info,
+                multisample_state: &multisampling_create_info,
+                depth_stencil_state: &depth_stencil_create_info,
+                color_blend_state: &color_blend_create_infos[i],
+                dynamic_state: &dynamic_state_create_info,
+                layout: pipeline_layout,
+                render_pass,
+                subpass: 0,
+                base_pipeline_handle: ptr::null(),
+                base_pipeline_index: -1,
+            });
+        let graphics_pipelines = [ptr::null(); BLEND_MODE_COUNT];
         let result = unsafe {
             api::vkCreateGraphicsPipelines(
-                swapchain.device.handle,
+                device.handle,
                 ptr::null(),
-                1,
-                &graphics_pipeline_create_info,
+                BLEND_MODE_COUNT as u32,
+                graphics_pipeline_create_infos.as_ptr(),
                 ptr::null(),
-                &graphics_pipeline,
+                graphics_pipelines.as_ptr(),
             )
         };
         if result != 0 {
@@ -4418,7 +6292,7 @@ impl TwoDimensionRender {
         let descriptor_pool_sizes = [
             api::DescriptorPoolSize {
                 descriptor_type: api::DescriptorType::StorageBuffer,
-                descriptor_count: 3,
+                descriptor_count: 4,
             },
             api::DescriptorPoolSize {
                 descriptor_type: api::DescriptorType::UniformBuffer,
@@ -4435,7 +6309,7 @@ impl TwoDimensionRender {
         let descriptor_pool = ptr::null();
         let result = unsafe {
             api::vkCreateDescriptorPool(
-                swapchain.device.handle,
+                device.handle,
                 &descriptor_pool_create_info,
                 ptr::null(),
                 &descriptor_pool,
@@ -4454,7 +6328,7 @@ impl TwoDimensionRender {
         let descriptor_set = ptr::null();
         let result = unsafe {
             api::vkAllocateDescriptorSets(
-                swapchain.device.handle,
+                device.handle,
                 &descriptor_set_allocate_info,
                 &descriptor_set,
             )
@@ -4483,6 +6357,11 @@ impl TwoDimensionRender {
             offset: 0,
             range: uniform_buffer_size,
         };
+        let image_descriptor_buffer_info = api::DescriptorBufferInfo {
+            buffer: gpu_image_buffer,
+            offset: 0,
+            range: gpu_image_buffer_size,
+        };
         let write_descriptors = [
             api::WriteDescriptorSet {
                 header: StructureHeader::new(StructureType::WriteDescriptorSet),
@@ -4528,293 +6407,2021 @@ impl TwoDimensionRender {
                 buffer_info: &uniform_descriptor_buffer_info,
                 texel_buffer_view: ptr::null(),
             },
+            api::WriteDescriptorSet {
+                header: StructureHeader::new(StructureType::WriteDescriptorSet),
+                dst_set: descriptor_set,
+                dst_binding: 4,
+                dst_array_element: 0,
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                image_info: ptr::null(),
+                buffer_info: &image_descriptor_buffer_info,
+                texel_buffer_view: ptr::null(),
+            },
         ];
         unsafe {
             api::vkUpdateDescriptorSets(
-                swapchain.device.handle,
-                4,
+                device.handle,
+                5,
                 write_descriptors.as_ptr(),
                 0,
                 ptr::null(),
             )
         };
 
-        // Fence Create
-        let fence_create_info = api::FenceCreateInfo {
-            header: StructureHeader::new(StructureType::FenceCreateInfo),
-            flags: 0,
-        };
-        let fence = ptr::null();
-        let result = unsafe {
-            api::vkCreateFence(
-                swapchain.device.handle,
-                &fence_create_info,
-                ptr::null(),
-                &fence,
-            )
-        };
-        if result != 0 {
-            return Err(Error::VkResult(result));
-        }
-
-        // Command Buffer Constants Setup
-        let render_pass_begin_info = api::RenderPassBeginInfo {
-            header: StructureHeader::new(StructureType::RenderPassBeginInfo),
-            render_pass,
-            framebuffer: ptr::null(),
-            render_area: api::Rect2D {
-                offset: api::Offset2d::default(),
-                extent: api::Extent2d {
-                    width: swapchain.width,
-                    height: swapchain.height,
-                },
-            },
-            clear_value_count: 1,
-            clear_values: ptr::null(),
-        };
-
         let clear_value = api::ClearValue {
             color: api::ClearColorValue {
                 float32: [0.0, 0.0, 0.0, 0.0],
             },
         };
 
-        let cmd_buffer_begin_info = api::CommandBufferBeginInfo {
-            header: StructureHeader::new(StructureType::CommandBufferBeginInfo),
-            flags: api::CommandBufferUsageFlagBit::None as api::CommandBufferUsageFlags,
-            inheritance_info: ptr::null(),
-        };
-
-        let mut tdr = Self {
-            cmd_buffer_begin_info,
+        let mut pipeline = Self {
             clear_value,
-            fence,
+            width,
+            height,
             descriptor_set,
             descriptor_pool,
-            graphics_pipeline,
+            blend_mode: BlendMode::Over,
+            glyph_coverage_gamma: DEFAULT_GLYPH_COVERAGE_GAMMA,
+            graphics_pipelines,
             pipeline_layout,
             descriptor_set_layout,
             shader_stages,
+            gpu_glyph_buffer_mem_size,
             gpu_glyph_buffer_mem,
             gpu_glyph_buffer,
+            gpu_uniform_buffer_mem_size: uniform_buffer_mem_reqs_size,
             gpu_uniform_buffer_mem,
             gpu_uniform_buffer,
+            gpu_image_buffer_size,
+            gpu_image_buffer_mem_size,
+            gpu_image_buffer_mem,
+            gpu_image_buffer,
+            image_size: (0, 0),
+            background_fit: None,
+            gpu_triangle_buffer_mem_size,
             gpu_triangle_buffer_mem,
             gpu_triangle_buffer,
             gpu_primitive_data_offset,
             max_triangles,
             cpu_staging_buffer_mapped_ptr,
             cpu_staging_buffer_size,
+            cpu_staging_buffer_mem_size: staging_buffer_mem_reqs_size,
             cpu_staging_buffer_mem,
             cpu_staging_buffer,
-            framebuffers,
-            swapchain_image_views,
-            render_pass_begin_info,
-            swapchain,
+            load_render_pass,
+            clear_render_pass: render_pass,
         };
 
-        tdr.render_clear()?; // Render a clear for a sanity test and to allow the fence to be signalled
-        Ok(tdr)
+        // Fill in Initial Uniform Data Buffer
+        pipeline.upload_uniform_data(copy_queue, (width, height))?;
+        pipeline.upload_image(device, copy_queue, &ImageHeader::default(), &[])?;
+        Ok(pipeline)
     }
 
-    fn render_clear(&mut self) -> Result<(), Error> {
-        let next_image_index = self.swapchain.get_next_image_index()? as usize;
-        self.render_pass_begin_info.framebuffer = self.framebuffers[next_image_index];
-        self.render_pass_begin_info.clear_values = &self.clear_value;
+    // Writes the pixel to normalized coordinate multipliers for the given target size,
+    // the staging buffer start gets overwritten so no frame in flight can be using it
+    fn upload_uniform_data(
+        &mut self,
+        copy_queue: CopyQueue,
+        (width, height): (u32, u32),
+    ) -> Result<(), Error> {
+        self.width = width;
+        self.height = height;
+        let uniform_data = unsafe {
+            std::slice::from_raw_parts_mut(
+                self.cpu_staging_buffer_mapped_ptr as *mut UniformData,
+                1,
+            )
+        };
+        uniform_data[0].x_mult = 2.0 / (width as f32);
+        uniform_data[0].y_mult = 2.0 / (height as f32);
+        uniform_data[0].glyph_coverage_gamma = self.glyph_coverage_gamma;
+        uniform_data[0].reserved = 0.0;
+        println!(
+            "Uniform Data: {}, {}",
+            uniform_data[0].x_mult, uniform_data[0].y_mult
+        );
 
-        let cmd_buffer = self.swapchain.cmd_buffer_submit_infos[next_image_index].command_buffer;
+        let copy_buffer_region = api::BufferCopy2 {
+            header: StructureHeader::new(StructureType::BufferCopy2),
+            src_offset: 0,
+            dst_offset: 0,
+            size: mem::size_of::<UniformData>() as u64,
+        };
+        let copy_buffer_info = api::CopyBufferInfo2 {
+            header: StructureHeader::new(StructureType::CopyBufferInfo2),
+            src_buffer: self.cpu_staging_buffer,
+            dst_buffer: self.gpu_uniform_buffer,
+            region_count: 1,
+            regions: &copy_buffer_region,
+        };
 
-        let result = unsafe { api::vkBeginCommandBuffer(cmd_buffer, &self.cmd_buffer_begin_info) };
+        copy_queue.copy_buffer(&copy_buffer_info)
+    }
+
+    // Copies the staged primitives of the given frame and draws them inside the given render pass
+    #[allow(clippy::too_many_arguments)]
+    fn record_draw_commands(
+        &self,
+        device: &Device,
+        cmd_buffer: OpaqueHandle,
+        render_pass_begin_info: &RenderPassBeginInfo,
+        frame_index: usize,
+        num_verticies: u16,
+        num_triangles: u32,
+        regions: &RenderRegions,
+        custom_draw: impl FnOnce(&CustomDrawContext),
+    ) {
+        let staging_offset = self.get_staging_offset(frame_index);
+        let triangle_buffer_copy_regions = [
+            api::BufferCopy2 {
+                header: StructureHeader::new(StructureType::BufferCopy2),
+                src_offset: staging_offset,
+                dst_offset: 0,
+                size: (num_verticies as u64) * (mem::size_of::<TriVert>() as u64),
+            },
+            api::BufferCopy2 {
+                header: StructureHeader::new(StructureType::BufferCopy2),
+                src_offset: staging_offset + VERTEX_BUFFER_SIZE,
+                dst_offset: VERTEX_BUFFER_SIZE,
+                size: (num_triangles as u64) * (mem::size_of::<TriInd>() as u64),
+            },
+            api::BufferCopy2 {
+                header: StructureHeader::new(StructureType::BufferCopy2),
+                src_offset: staging_offset + self.gpu_primitive_data_offset,
+                dst_offset: self.gpu_primitive_data_offset,
+                size: (num_triangles as u64) * (mem::size_of::<TriPrimData>() as u64),
+            },
+        ];
+        let triangle_copy_buffer_info = api::CopyBufferInfo2 {
+            header: StructureHeader::new(StructureType::CopyBufferInfo2),
+            src_buffer: self.cpu_staging_buffer,
+            dst_buffer: self.gpu_triangle_buffer,
+            region_count: 3,
+            regions: triangle_buffer_copy_regions.as_ptr(),
+        };
+
+        // A previous frame in flight can still be reading the triangle buffer
+        let triangle_buffer_barrier = api::MemoryBarrier2 {
+            header: StructureHeader::new(StructureType::MemoryBarrier2),
+            src_stage_mask: (api::PipelineStageFlag2Bit::VertexInput as api::PipelineStageFlags2)
+                | (api::PipelineStageFlag2Bit::VertexShader as api::PipelineStageFlags2)
+                | (api::PipelineStageFlag2Bit::FragmentShader as api::PipelineStageFlags2),
+            src_access_mask: api::AccessFlag2Bit::None as api::AccessFlags2,
+            dst_stage_mask: api::PipelineStageFlag2Bit::Copy as api::PipelineStageFlags2,
+            dst_access_mask: api::AccessFlag2Bit::TransferWrite as api::AccessFlags2,
+        };
+        let dependency_info = api::DependencyInfo {
+            header: StructureHeader::new(StructureType::DependencyInfo),
+            dependency_flags: api::DependencyFlagBit::None as api::DependencyFlags,
+            memory_barrier_count: 1,
+            memory_barriers: &triangle_buffer_barrier,
+            buffer_memory_barrier_count: 0,
+            buffer_memory_barriers: ptr::null(),
+            image_memory_barrier_count: 0,
+            image_memory_barriers: ptr::null(),
+        };
+
+        let vertex_buffers = [self.gpu_triangle_buffer];
+        let vertex_offsets = [0];
+
+        unsafe { api::vkCmdPipelineBarrier2(cmd_buffer, &dependency_info) };
+        unsafe { api::vkCmdCopyBuffer2(cmd_buffer, &triangle_copy_buffer_info) };
+
+        unsafe {
+            api::vkCmdBeginRenderPass(
+                cmd_buffer,
+                render_pass_begin_info,
+                api::SubpassContents::Inline,
+            )
+        };
+
+        let keep_contents = render_pass_begin_info.render_pass == self.load_render_pass;
+        if keep_contents {
+            // The load render pass keeps the old contents so the dirty region is cleared here
+            let clear_attachment = api::ClearAttachment {
+                aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
+                color_attachment: 0,
+                clear_value: self.clear_value,
+            };
+            let clear_rect = api::ClearRect {
+                rect: render_pass_begin_info.render_area,
+                base_array_layer: 0,
+                layer_count: 1,
+            };
+            unsafe { api::vkCmdClearAttachments(cmd_buffer, 1, &clear_attachment, 1, &clear_rect) };
+        }
+
+        unsafe {
+            api::vkCmdBindPipeline(
+                cmd_buffer,
+                api::PipelineBindPoint::Graphics,
+                self.graphics_pipelines[self.blend_mode as usize],
+            )
+        };
+        let viewport = api::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.width as f32,
+            height: self.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        unsafe { api::vkCmdSetViewport(cmd_buffer, 0, 1, &viewport) };
+        unsafe { api::vkCmdSetScissor(cmd_buffer, 0, 1, &render_pass_begin_info.render_area) };
+        unsafe {
+            api::vkCmdBindVertexBuffers(
+                cmd_buffer,
+                0,
+                1,
+                vertex_buffers.as_ptr(),
+                vertex_offsets.as_ptr(),
+            )
+        };
+        unsafe {
+            api::vkCmdBindIndexBuffer(
+                cmd_buffer,
+                self.gpu_triangle_buffer,
+                VERTEX_BUFFER_SIZE,
+                api::IndexType::Uint16,
+            )
+        };
+        unsafe {
+            api::vkCmdBindDescriptorSets(
+                cmd_buffer,
+                api::PipelineBindPoint::Graphics,
+                self.pipeline_layout,
+                0,
+                1,
+                &self.descriptor_set,
+                0,
+                ptr::null(),
+            )
+        };
+        self.record_region_draws(
+            cmd_buffer,
+            &render_pass_begin_info.render_area,
+            regions,
+            num_triangles,
+        );
+
+        let render_area = &render_pass_begin_info.render_area;
+        custom_draw(&CustomDrawContext {
+            device: device.handle as *const c_void,
+            command_buffer: cmd_buffer as *const c_void,
+            render_pass: render_pass_begin_info.render_pass as *const c_void,
+            render_area: PrimitiveRect {
+                x: render_area.offset.x as f32,
+                y: render_area.offset.y as f32,
+                w: render_area.extent.width as f32,
+                h: render_area.extent.height as f32,
+            },
+            width: self.width,
+            height: self.height,
+        });
+
+        unsafe { api::vkCmdEndRenderPass(cmd_buffer) };
+    }
+
+    // The device must be idle since the old glyph buffer gets destroyed
+    fn update_glyph_data(
+        &mut self,
+        device: &Device,
+        copy_queue: CopyQueue,
+        glyph_data: &GlyphData,
+    ) -> Result<(), Error> {
+        let (
+            gpu_glyph_buffer,
+            gpu_glyph_buffer_mem,
+            glyph_info_offset_size,
+            glyph_segment_data_size,
+            gpu_glyph_buffer_mem_size,
+        ) = create_glyph_buffer(
+            device,
+            copy_queue,
+            glyph_data,
+            self.cpu_staging_buffer,
+            self.cpu_staging_buffer_mapped_ptr,
+            self.cpu_staging_buffer_size,
+        )?;
+
+        let font_descriptor_buffer_info = api::DescriptorBufferInfo {
+            buffer: gpu_glyph_buffer,
+            offset: 0,
+            range: glyph_info_offset_size,
+        };
+        let segment_descriptor_buffer_info = api::DescriptorBufferInfo {
+            buffer: gpu_glyph_buffer,
+            offset: glyph_info_offset_size,
+            range: glyph_segment_data_size,
+        };
+        let write_descriptors = [
+            api::WriteDescriptorSet {
+                header: StructureHeader::new(StructureType::WriteDescriptorSet),
+                dst_set: self.descriptor_set,
+                dst_binding: 1,
+                dst_array_element: 0,
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                image_info: ptr::null(),
+                buffer_info: &font_descriptor_buffer_info,
+                texel_buffer_view: ptr::null(),
+            },
+            api::WriteDescriptorSet {
+                header: StructureHeader::new(StructureType::WriteDescriptorSet),
+                dst_set: self.descriptor_set,
+                dst_binding: 2,
+                dst_array_element: 0,
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                image_info: ptr::null(),
+                buffer_info: &segment_descriptor_buffer_info,
+                texel_buffer_view: ptr::null(),
+            },
+        ];
+        unsafe {
+            api::vkUpdateDescriptorSets(
+                device.handle,
+                2,
+                write_descriptors.as_ptr(),
+                0,
+                ptr::null(),
+            )
+        };
+
+        let device = device.handle;
+        unsafe {
+            api::vkDestroyBuffer(device, self.gpu_glyph_buffer, ptr::null());
+            api::vkFreeMemory(device, self.gpu_glyph_buffer_mem, ptr::null());
+        }
+        self.gpu_glyph_buffer = gpu_glyph_buffer;
+        self.gpu_glyph_buffer_mem = gpu_glyph_buffer_mem;
+        self.gpu_glyph_buffer_mem_size = gpu_glyph_buffer_mem_size;
+
+        Ok(())
+    }
+
+    // The device must be idle since the image buffer gets recreated when the image does not fit
+    // and the header and image bytes are copied through the start of the staging buffer
+    fn upload_image(
+        &mut self,
+        device: &Device,
+        copy_queue: CopyQueue,
+        header: &ImageHeader,
+        image: &[u8],
+    ) -> Result<(), Error> {
+        let header_size = mem::size_of::<ImageHeader>() as u64;
+        // The shader reads the image bytes as whole u32 values
+        let image_buffer_size = (header_size + (image.len() as u64) + 3) & !0x3;
+        if image_buffer_size > device.physical_device.max_storage_buffer_range as u64 {
+            return Err(Error::ImageExceedsStorageRange);
+        }
+
+        if image_buffer_size > self.gpu_image_buffer_size {
+            let (gpu_image_buffer, gpu_image_buffer_mem, gpu_image_buffer_mem_size) =
+                create_storage_buffer(device, image_buffer_size)?;
+
+            let image_descriptor_buffer_info = api::DescriptorBufferInfo {
+                buffer: gpu_image_buffer,
+                offset: 0,
+                range: image_buffer_size,
+            };
+            let write_descriptor = api::WriteDescriptorSet {
+                header: StructureHeader::new(StructureType::WriteDescriptorSet),
+                dst_set: self.descriptor_set,
+                dst_binding: 4,
+                dst_array_element: 0,
+                descriptor_type: api::DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+                image_info: ptr::null(),
+                buffer_info: &image_descriptor_buffer_info,
+                texel_buffer_view: ptr::null(),
+            };
+            unsafe {
+                api::vkUpdateDescriptorSets(device.handle, 1, &write_descriptor, 0, ptr::null())
+            };
+
+            unsafe {
+                api::vkDestroyBuffer(device.handle, self.gpu_image_buffer, ptr::null());
+                api::vkFreeMemory(device.handle, self.gpu_image_buffer_mem, ptr::null());
+            }
+            self.gpu_image_buffer_size = image_buffer_size;
+            self.gpu_image_buffer = gpu_image_buffer;
+            self.gpu_image_buffer_mem = gpu_image_buffer_mem;
+            self.gpu_image_buffer_mem_size = gpu_image_buffer_mem_size;
+        }
+
+        self.image_size = (header.width, header.height);
+        let header_bytes = unsafe {
+            std::slice::from_raw_parts(
+                header as *const ImageHeader as *const u8,
+                header_size as usize,
+            )
+        };
+        upload_buffer_data(
+            copy_queue,
+            self.cpu_staging_buffer,
+            self.cpu_staging_buffer_mapped_ptr,
+            self.cpu_staging_buffer_size,
+            self.gpu_image_buffer,
+            0,
+            header_bytes,
+        )?;
+        upload_buffer_data(
+            copy_queue,
+            self.cpu_staging_buffer,
+            self.cpu_staging_buffer_mapped_ptr,
+            self.cpu_staging_buffer_size,
+            self.gpu_image_buffer,
+            header_size,
+            image,
+        )
+    }
+
+    // Draws the triangles before the first region to the whole render area and then the triangles of
+    // every region clipped to its area within the render area (after clearing it if needed)
+    fn record_region_draws(
+        &self,
+        cmd_buffer: OpaqueHandle,
+        render_area: &api::Rect2D,
+        regions: &RenderRegions,
+        num_triangles: u32,
+    ) {
+        let mut current = RenderRegion::default();
+        for ind in 0..=regions.num_regions {
+            let end_triangle = if ind < regions.num_regions {
+                regions.regions[ind].first_triangle.min(num_triangles)
+            } else {
+                num_triangles
+            };
+            self.record_region_draw(cmd_buffer, render_area, &current, end_triangle);
+            if ind < regions.num_regions {
+                current = regions.regions[ind];
+            }
+        }
+        // Custom draws get the whole render area again
+        unsafe { api::vkCmdSetScissor(cmd_buffer, 0, 1, render_area) };
+    }
+
+    fn record_region_draw(
+        &self,
+        cmd_buffer: OpaqueHandle,
+        render_area: &api::Rect2D,
+        region: &RenderRegion,
+        end_triangle: u32,
+    ) {
+        let scissor = match &region.area {
+            Some(area) => match get_clipped_render_area(render_area, area) {
+                Some(s) => s,
+                None => return,
+            },
+            None => *render_area,
+        };
+        if let Some(clear_color) = region.clear_color {
+            let clear_attachment = api::ClearAttachment {
+                aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
+                color_attachment: 0,
+                clear_value: api::ClearValue {
+                    color: api::ClearColorValue {
+                        float32: clear_color,
+                    },
+                },
+            };
+            let clear_rect = api::ClearRect {
+                rect: scissor,
+                base_array_layer: 0,
+                layer_count: 1,
+            };
+            unsafe { api::vkCmdClearAttachments(cmd_buffer, 1, &clear_attachment, 1, &clear_rect) };
+        }
+        if end_triangle <= region.first_triangle {
+            return;
+        }
+        unsafe { api::vkCmdSetScissor(cmd_buffer, 0, 1, &scissor) };
+        unsafe {
+            api::vkCmdPushConstants(
+                cmd_buffer,
+                self.pipeline_layout,
+                api::ShaderStageFlagBit::Fragment as api::ShaderStageFlags,
+                0,
+                mem::size_of::<u32>() as u32,
+                &region.first_triangle as *const u32 as *const c_void,
+            )
+        };
+        let num_triangles = end_triangle - region.first_triangle;
+        unsafe {
+            api::vkCmdDrawIndexed(
+                cmd_buffer,
+                num_triangles * 3,
+                1,
+                region.first_triangle * 3,
+                0,
+                0,
+            )
+        };
+    }
+
+    fn get_memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            triangle_buffer_size: self.gpu_triangle_buffer_mem_size,
+            glyph_buffer_size: self.gpu_glyph_buffer_mem_size,
+            uniform_buffer_size: self.gpu_uniform_buffer_mem_size,
+            image_buffer_size: self.gpu_image_buffer_mem_size,
+            staging_buffer_size: self.cpu_staging_buffer_mem_size,
+            total_size: self.gpu_triangle_buffer_mem_size
+                + self.gpu_glyph_buffer_mem_size
+                + self.gpu_uniform_buffer_mem_size
+                + self.gpu_image_buffer_mem_size
+                + self.cpu_staging_buffer_mem_size,
+            max_triangles: self.max_triangles,
+            num_buffers: get_live_handle_count(&[
+                self.gpu_glyph_buffer,
+                self.gpu_uniform_buffer,
+                self.gpu_image_buffer,
+                self.gpu_triangle_buffer,
+                self.cpu_staging_buffer,
+            ]),
+            num_image_views: 0,
+            num_framebuffers: 0,
+            num_render_passes: get_live_handle_count(&[
+                self.load_render_pass,
+                self.clear_render_pass,
+            ]),
+            num_pipelines: get_live_handle_count(&self.graphics_pipelines),
+        }
+    }
+
+    fn get_staging_offset(&self, frame_index: usize) -> u64 {
+        (frame_index as u64) * self.cpu_staging_buffer_size
+    }
+
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    // Same requirement as upload_uniform_data that no frame in flight can be using the staging buffer
+    fn set_glyph_coverage_gamma(&mut self, copy_queue: CopyQueue, gamma: f32) -> Result<(), Error> {
+        self.glyph_coverage_gamma = gamma.clamp(0.1, 4.0);
+        self.upload_uniform_data(copy_queue, (self.width, self.height))
+    }
+
+    // Clear values of sRGB formats are linear so the premultiplied linear color is used as is
+    fn set_clear_color(&mut self, color: &PrimitiveColor) {
+        self.clear_value = api::ClearValue {
+            color: api::ClearColorValue {
+                float32: [
+                    color.linear_rgb[0],
+                    color.linear_rgb[1],
+                    color.linear_rgb[2],
+                    color.linear_alpha,
+                ],
+            },
+        };
+    }
+
+    // The fence of the given frame must be signalled before its staging section gets written
+    fn get_primitives(&mut self, frame_index: usize) -> Primitives2d<'_> {
+        let frame_ptr = unsafe {
+            self.cpu_staging_buffer_mapped_ptr
+                .offset(self.get_staging_offset(frame_index) as isize)
+        };
+        let index_offset = unsafe { frame_ptr.offset(VERTEX_BUFFER_SIZE as isize) };
+        let index_offset_2 = unsafe { frame_ptr.offset(self.gpu_primitive_data_offset as isize) };
+        let mut primitives = Primitives2d {
+            verticies: unsafe {
+                std::slice::from_raw_parts_mut(
+                    frame_ptr as *mut TriVert,
+                    MAX_NUMBER_OF_VERTICIES as usize,
+                )
+            },
+            indicies: unsafe {
+                std::slice::from_raw_parts_mut(
+                    index_offset as *mut TriInd,
+                    self.max_triangles as usize,
+                )
+            },
+            data: unsafe {
+                std::slice::from_raw_parts_mut(
+                    index_offset_2 as *mut TriPrimData,
+                    self.max_triangles as usize,
+                )
+            },
+            num_verticies: 0,
+            num_triangles: 0,
+            width: self.width as f32,
+            height: self.height as f32,
+            last_bounds: None,
+            dirty_region: None,
+            regions: RenderRegions::default(),
+            has_overflowed: false,
+        };
+
+        // The background layer is the first primitive so everything else gets drawn on top of it
+        let (image_width, image_height) = self.image_size;
+        if let Some(fit) = self.background_fit {
+            if image_width > 0 {
+                let rect = fit.get_rect(image_width, image_height, self.width, self.height);
+                primitives.add_image(
+                    (rect.x, rect.y),
+                    (rect.w, rect.h),
+                    &PrimitiveColor::new_from_linear_rgb_and_alpha([1.0, 1.0, 1.0], 1.0),
+                );
+                primitives.last_bounds = None;
+            }
+        }
+        primitives
+    }
+
+    fn set_background_fit(&mut self, fit: Option<BackgroundFit>) {
+        self.background_fit = fit;
+    }
+
+    fn destroy(&self, device: OpaqueHandle) {
+        unsafe {
+            api::vkDestroyDescriptorPool(device, self.descriptor_pool, ptr::null());
+            for graphics_pipeline in &self.graphics_pipelines {
+                api::vkDestroyPipeline(device, *graphics_pipeline, ptr::null());
+            }
+            api::vkDestroyPipelineLayout(device, self.pipeline_layout, ptr::null());
+            api::vkDestroyDescriptorSetLayout(device, self.descriptor_set_layout, ptr::null());
+            for shader_stage in &self.shader_stages {
+                api::vkDestroyShaderModule(device, shader_stage.module, ptr::null());
+            }
+            api::vkDestroyBuffer(device, self.gpu_glyph_buffer, ptr::null());
+            api::vkFreeMemory(device, self.gpu_glyph_buffer_mem, ptr::null());
+            api::vkDestroyBuffer(device, self.gpu_uniform_buffer, ptr::null());
+            api::vkFreeMemory(device, self.gpu_uniform_buffer_mem, ptr::null());
+            api::vkDestroyBuffer(device, self.gpu_image_buffer, ptr::null());
+            api::vkFreeMemory(device, self.gpu_image_buffer_mem, ptr::null());
+            api::vkDestroyBuffer(device, self.gpu_triangle_buffer, ptr::null());
+            api::vkFreeMemory(device, self.gpu_triangle_buffer_mem, ptr::null());
+            api::vkUnmapMemory(device, self.cpu_staging_buffer_mem);
+            api::vkDestroyBuffer(device, self.cpu_staging_buffer, ptr::null());
+            api::vkFreeMemory(device, self.cpu_staging_buffer_mem, ptr::null());
+            api::vkDestroyRenderPass(device, self.load_render_pass, ptr::null());
+            api::vkDestroyRenderPass(device, self.clear_render_pass, ptr::null());
+        }
+    }
+}
+
+pub struct TwoDimensionRender {
+    cmd_buffer_begin_info: api::CommandBufferBeginInfo,
+    frame_index: usize,
+    frames_in_flight: usize,
+    fences: [OpaqueHandle; MAX_FRAMES_IN_FLIGHT as usize],
+    pipeline: TwoDimensionPipeline,
+    dirty_region_mode: bool,
+    image_contents_valid: [bool; SWAPCHAIN_IMAGE_COUNT as usize],
+    image_dirty_regions: [Option<PrimitiveRect>; SWAPCHAIN_IMAGE_COUNT as usize],
+    framebuffers: [OpaqueHandle; SWAPCHAIN_IMAGE_COUNT as usize],
+    swapchain_image_views: [OpaqueHandle; SWAPCHAIN_IMAGE_COUNT as usize],
+    render_pass_begin_info: RenderPassBeginInfo, // Switches between the clear and load render passes of the pipeline
+    swapchain: Swapchain,
+}
+
+impl TwoDimensionRender {
+    pub fn new(
+        mut swapchain: Swapchain,
+        staging_buffer_size: usize,
+        glyph_data: GlyphData,
+        frames_in_flight: u32,
+    ) -> Result<Self, Error> {
+        if frames_in_flight == 0 || frames_in_flight > MAX_FRAMES_IN_FLIGHT {
+            return Err(Error::BadFramesInFlight);
+        }
+        if glyph_data.is_empty() {
+            return Err(Error::NoGlyphData);
+        }
+        swapchain.update_current_size()?;
+
+        let pipeline = TwoDimensionPipeline::new(
+            &swapchain.device,
+            swapchain.get_copy_queue(),
+            swapchain.swapchain_create_info.image_format,
+            api::ImageLayout::PresentSrc,
+            (swapchain.width, swapchain.height),
+            staging_buffer_size,
+            &glyph_data,
+            frames_in_flight,
+        )?;
+
+        // Create Swapchain ImageViews and Corresponding Framebuffers
+        let (swapchain_image_views, framebuffers) =
+            create_swapchain_framebuffers(&swapchain, pipeline.clear_render_pass)?;
+
+        // Fence Create
+        // The first fence gets signalled by the initial clear and the rest start signalled
+        let fences = [ptr::null(); MAX_FRAMES_IN_FLIGHT as usize];
+        for (ind, fence) in fences.iter().take(frames_in_flight as usize).enumerate() {
+            let fence_create_info = api::FenceCreateInfo {
+                header: StructureHeader::new(StructureType::FenceCreateInfo),
+                flags: if ind == 0 {
+                    api::FenceCreateFlagBit::None as api::FenceCreateFlags
+                } else {
+                    api::FenceCreateFlagBit::Signaled as api::FenceCreateFlags
+                },
+            };
+            let result = unsafe {
+                api::vkCreateFence(
+                    swapchain.device.handle,
+                    &fence_create_info,
+                    ptr::null(),
+                    fence,
+                )
+            };
+            if result != 0 {
+                return Err(Error::VkResult(result));
+            }
+        }
+
+        // Command Buffer Constants Setup
+        let render_pass_begin_info = api::RenderPassBeginInfo {
+            header: StructureHeader::new(StructureType::RenderPassBeginInfo),
+            render_pass: pipeline.clear_render_pass,
+            framebuffer: ptr::null(),
+            render_area: api::Rect2D {
+                offset: api::Offset2d::default(),
+                extent: api::Extent2d {
+                    width: swapchain.width,
+                    height: swapchain.height,
+                },
+            },
+            clear_value_count: 1,
+            clear_values: ptr::null(),
+        };
+
+        let cmd_buffer_begin_info = api::CommandBufferBeginInfo {
+            header: StructureHeader::new(StructureType::CommandBufferBeginInfo),
+            flags: api::CommandBufferUsageFlagBit::None as api::CommandBufferUsageFlags,
+            inheritance_info: ptr::null(),
+        };
+
+        let mut tdr = Self {
+            cmd_buffer_begin_info,
+            frame_index: 0,
+            frames_in_flight: frames_in_flight as usize,
+            fences,
+            pipeline,
+            dirty_region_mode: false,
+            image_contents_valid: [false; SWAPCHAIN_IMAGE_COUNT as usize],
+            image_dirty_regions: [None; SWAPCHAIN_IMAGE_COUNT as usize],
+            framebuffers,
+            swapchain_image_views,
+            render_pass_begin_info,
+            swapchain,
+        };
+
+        tdr.render_clear()?; // Render a clear for a sanity test and to allow the fence to be signalled
+        Ok(tdr)
+    }
+
+    fn render_clear(&mut self) -> Result<(), Error> {
+        self.swapchain.frame_index = self.frame_index;
+        let next_image_index = self.swapchain.get_next_image_index()? as usize;
+        self.render_pass_begin_info.framebuffer = self.framebuffers[next_image_index];
+        self.render_pass_begin_info.clear_values = &self.pipeline.clear_value;
+
+        let cmd_buffer = self.swapchain.cmd_buffer_submit_infos[self.frame_index].command_buffer;
+
+        let result = unsafe { api::vkBeginCommandBuffer(cmd_buffer, &self.cmd_buffer_begin_info) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        unsafe {
+            api::vkCmdBeginRenderPass(
+                cmd_buffer,
+                &self.render_pass_begin_info,
+                api::SubpassContents::Inline,
+            )
+        };
+        unsafe {
+            api::vkCmdBindDescriptorSets(
+                cmd_buffer,
+                api::PipelineBindPoint::Graphics,
+                self.pipeline.pipeline_layout,
+                0,
+                1,
+                &self.pipeline.descriptor_set,
+                0,
+                ptr::null(),
+            )
+        };
+
+        unsafe { api::vkCmdEndRenderPass(cmd_buffer) };
+
+        let result = unsafe { api::vkEndCommandBuffer(cmd_buffer) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        self.swapchain.submit_queue_and_present(
+            self.frame_index,
+            next_image_index as u32,
+            self.fences[self.frame_index],
+        )?;
+
+        Ok(())
+    }
+
+    /// Dirty region is only used in dirty region mode and should contain every pixel region
+    /// that changed since the previous frame
+    pub fn render(
+        &mut self,
+        num_verticies: u16,
+        num_triangles: u32,
+        dirty_region: Option<PrimitiveRect>,
+    ) -> Result<(), Error> {
+        self.render_with_regions(
+            num_verticies,
+            num_triangles,
+            dirty_region,
+            &RenderRegions::default(),
+        )
+    }
+
+    /// Same as render but clips (and clears) the primitives of each region from Primitives2d::get_regions
+    /// to its own area for split screen layouts within a single frame, see Primitives2d::begin_region
+    ///
+    /// In dirty region mode each region only redraws where it overlaps the dirty region
+    pub fn render_with_regions(
+        &mut self,
+        num_verticies: u16,
+        num_triangles: u32,
+        dirty_region: Option<PrimitiveRect>,
+        regions: &RenderRegions,
+    ) -> Result<(), Error> {
+        // Safety: The empty custom draw does not record any commands
+        unsafe {
+            self.render_regions_with(num_verticies, num_triangles, dirty_region, regions, |_| {})
+        }
+    }
+
+    /// Same as render but calls custom_draw to record extra commands into the command buffer
+    /// right after the primitives are drawn and before the render pass ends, custom_draw is not
+    /// called when there is nothing to redraw in dirty region mode
+    ///
+    /// # Safety
+    ///
+    /// The commands recorded by custom_draw must:
+    /// - Be valid inside the active render pass and only use pipelines created for a render pass
+    ///   compatible with the given one (a single color attachment with the swapchain format)
+    /// - Not end the render pass, end or submit the command buffer, or wait on the device
+    /// - Stay within the render area by using a pipeline with a matching scissor
+    /// - Only reference Vulkan objects that stay alive until this frame's fence is signalled
+    ///   (which is guaranteed once get_primitives returns for the same frame slot again)
+    ///
+    /// The renderer's pipeline, vertex/index buffers and descriptor set are bound when
+    /// custom_draw starts and do not need to be restored
+    pub unsafe fn render_with(
+        &mut self,
+        num_verticies: u16,
+        num_triangles: u32,
+        dirty_region: Option<PrimitiveRect>,
+        custom_draw: impl FnOnce(&CustomDrawContext),
+    ) -> Result<(), Error> {
+        self.render_regions_with(
+            num_verticies,
+            num_triangles,
+            dirty_region,
+            &RenderRegions::default(),
+            custom_draw,
+        )
+    }
+
+    // Safety: Same as render_with
+    unsafe fn render_regions_with(
+        &mut self,
+        num_verticies: u16,
+        num_triangles: u32,
+        dirty_region: Option<PrimitiveRect>,
+        regions: &RenderRegions,
+        custom_draw: impl FnOnce(&CustomDrawContext),
+    ) -> Result<(), Error> {
+        if self.dirty_region_mode {
+            if let Some(region) = dirty_region {
+                for image_dirty_region in &mut self.image_dirty_regions {
+                    *image_dirty_region = match image_dirty_region {
+                        Some(r) => Some(r.union(&region)),
+                        None => Some(region),
+                    };
+                }
+            }
+            if self.image_contents_valid.iter().all(|v| *v)
+                && self.image_dirty_regions.iter().all(|r| r.is_none())
+            {
+                // Every swapchain image is already up to date
+                return self.signal_fence();
+            }
+        }
+
+        self.swapchain.frame_index = self.frame_index;
+        let next_image_index = match self.swapchain.get_next_image_index() {
+            Ok(i) => i as usize,
+            Err(e) => {
+                // Nothing gets submitted so the fence is signalled here for the next wait
+                self.signal_fence()?;
+                return Err(e);
+            }
+        };
+        self.render_pass_begin_info.framebuffer = self.framebuffers[next_image_index];
+        self.render_pass_begin_info.clear_values = &self.pipeline.clear_value;
+
+        let cmd_buffer = self.swapchain.cmd_buffer_submit_infos[self.frame_index].command_buffer;
+
+        let result = unsafe { api::vkBeginCommandBuffer(cmd_buffer, &self.cmd_buffer_begin_info) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        // When nothing changed in this image it gets presented again as is
+        if let Some((render_area, keep_contents)) = self.get_render_area(next_image_index) {
+            self.render_pass_begin_info.render_area = render_area;
+            self.render_pass_begin_info.render_pass = if keep_contents {
+                self.pipeline.load_render_pass
+            } else {
+                self.pipeline.clear_render_pass
+            };
+            self.pipeline.record_draw_commands(
+                &self.swapchain.device,
+                cmd_buffer,
+                &self.render_pass_begin_info,
+                self.frame_index,
+                num_verticies,
+                num_triangles,
+                regions,
+                custom_draw,
+            );
+        }
+
+        let result = unsafe { api::vkEndCommandBuffer(cmd_buffer) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        // The fence is submitted even when presenting reports an out of date swapchain
+        let result = self.swapchain.submit_queue_and_present(
+            self.frame_index,
+            next_image_index as u32,
+            self.fences[self.frame_index],
+        );
+        self.frame_index = (self.frame_index + 1) % self.frames_in_flight;
+        result
+    }
+
+    /// Replaces the GPU glyph data such as after new glyph outlines were loaded at runtime,
+    /// waits for the device to be idle so expect a short stall
+    ///
+    /// The GPU glyph buffer is recreated at the new size and the glyph data gets uploaded in
+    /// multiple passes when it is larger than the staging buffer. Glyph data that the GPU cannot
+    /// bind returns Error::GlyphDataExceedsStorageRange and the previous glyph data stays in use
+    pub fn update_glyph_data(&mut self, glyph_data: GlyphData) -> Result<(), Error> {
+        let result = unsafe { api::vkDeviceWaitIdle(self.swapchain.device.handle) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let copy_queue = self.swapchain.get_copy_queue();
+        self.pipeline
+            .update_glyph_data(&self.swapchain.device, copy_queue, &glyph_data)
+    }
+
+    /// Replaces the image that Primitives2d::add_image and the background layer (see set_background)
+    /// draw with tightly packed 8 bit sRGB RGBA pixels with straight alpha (like a decoded PNG),
+    /// waits for the device to be idle so expect a short stall
+    ///
+    /// The GPU image buffer grows when a larger image arrives, an image with fewer bytes than
+    /// its size needs returns Error::ImageTooSmall and the previous image stays in use.
+    /// In dirty region mode the image rectangles need to be marked dirty while the background
+    /// layer gets fully redrawn
+    pub fn set_image(&mut self, pixels: &[u8], (width, height): (u32, u32)) -> Result<(), Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::ZeroSizeImage);
+        }
+        let image_len = (width as usize) * (height as usize) * 4;
+        if pixels.len() < image_len {
+            return Err(Error::ImageTooSmall);
+        }
+        let header = ImageHeader {
+            width,
+            height,
+            ..Default::default()
+        };
+
+        let result = unsafe { api::vkDeviceWaitIdle(self.swapchain.device.handle) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let copy_queue = self.swapchain.get_copy_queue();
+        self.pipeline.upload_image(
+            &self.swapchain.device,
+            copy_queue,
+            &header,
+            &pixels[..image_len],
+        )?;
+        if self.pipeline.background_fit.is_some() {
+            // The background covers every pixel so no kept image contents are up to date
+            self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+            self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+        }
+        Ok(())
+    }
+
+    /// Replaces the image that Primitives2d::add_image and the background layer (see set_background)
+    /// draw with a decoded 8 bit 4:2:0 YUV video frame (tightly packed like color::YuvFormat::get_frame_len),
+    /// waits for the device to be idle so expect a short stall
+    ///
+    /// The planes get uploaded as is and the fragment shader samples them and applies the YUV to rgb
+    /// matrix of the given matrix and range so there is no CPU conversion pass. The same image buffer
+    /// as set_image is used so it grows the same way, a frame with fewer bytes than its size needs
+    /// returns Error::ImageTooSmall and the previous image stays in use
+    pub fn set_video_frame(
+        &mut self,
+        frame: &[u8],
+        (width, height): (u32, u32),
+        format: crate::color::YuvFormat,
+        matrix: crate::color::YuvMatrix,
+        range: crate::color::YuvRange,
+    ) -> Result<(), Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::ZeroSizeImage);
+        }
+        let frame_len = format.get_frame_len(width as usize, height as usize);
+        if frame.len() < frame_len {
+            return Err(Error::ImageTooSmall);
+        }
+        let (u_offset, v_offset, chroma_step) =
+            format.get_chroma_layout(width as usize, height as usize);
+        let header = ImageHeader {
+            yuv_to_rgb: crate::color::get_yuv_to_rgb_matrix(matrix, range),
+            width,
+            height,
+            format: 1,
+            chroma_step: chroma_step as u32,
+            chroma_width: width.div_ceil(2),
+            u_offset: u_offset as u32,
+            v_offset: v_offset as u32,
+            reserved: 0,
+        };
+
+        let result = unsafe { api::vkDeviceWaitIdle(self.swapchain.device.handle) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let copy_queue = self.swapchain.get_copy_queue();
+        self.pipeline.upload_image(
+            &self.swapchain.device,
+            copy_queue,
+            &header,
+            &frame[..frame_len],
+        )?;
+        if self.pipeline.background_fit.is_some() {
+            // The background covers every pixel so no kept image contents are up to date
+            self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+            self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+        }
+        Ok(())
+    }
+
+    /// Draws the image from set_image or set_video_frame as a background layer behind every primitive (such as the
+    /// remote video of a call), fitted into the whole render target with the given fit
+    /// or turned off with None (the default)
+    ///
+    /// The background is added as the first primitive of each frame and gets clipped like any
+    /// other primitive. Images are sRGB like the rest of the color pipeline and video frames from
+    /// set_video_frame are converted with their matrix and range and displayed with the sRGB curve
+    /// as the video transfer function, see color::get_linear_rgb_from_yuv.
+    /// Nothing gets drawn until an image or video frame is set
+    pub fn set_background(&mut self, fit: Option<BackgroundFit>) {
+        self.pipeline.set_background_fit(fit);
+        // Kept image contents still have the previous background
+        self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+        self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+    }
+
+    /// Returns the device memory allocated by the renderer by purpose and its Vulkan object counts
+    pub fn debug_memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            num_image_views: get_live_handle_count(&self.swapchain_image_views),
+            num_framebuffers: get_live_handle_count(&self.framebuffers),
+            ..self.pipeline.get_memory_report()
+        }
+    }
+
+    /// Background color that every frame starts from (transparent black by default),
+    /// its alpha does not show since the swapchain is presented as opaque
+    pub fn set_clear_color(&mut self, color: &PrimitiveColor) {
+        self.pipeline.set_clear_color(color);
+        // Kept image contents still have the previous background
+        self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+        self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+    }
+
+    /// Selects how the primitives of the next rendered frames are blended, Over is the default
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.pipeline.set_blend_mode(blend_mode);
+    }
+
+    /// Sets the power (clamped to 0.1 through 4.0) that the glyph coverage gets raised to
+    /// before blending, DEFAULT_GLYPH_COVERAGE_GAMMA (1.0) keeps the coverage linear
+    ///
+    /// Thin stems of small text (around 10 to 12 pixels on standard DPI displays) look too light
+    /// with linear coverage, a gamma below 1.0 (like 0.75) darkens the partially covered edge pixels
+    /// similar to stem darkening. Too low of a gamma makes text look bold and blobby with filled in
+    /// counters (the holes of letters like e and a) and it also affects large text, so it is best used
+    /// for windows that mostly show small text. Waits for the device to be idle.
+    pub fn set_glyph_coverage_gamma(&mut self, gamma: f32) -> Result<(), Error> {
+        let result = unsafe { api::vkDeviceWaitIdle(self.swapchain.device.handle) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let copy_queue = self.swapchain.get_copy_queue();
+        self.pipeline.set_glyph_coverage_gamma(copy_queue, gamma)?;
+        // Kept image contents still have the previous coverage
+        self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+        self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+        Ok(())
+    }
+
+    /// Opt-in mode for mostly static content that saves GPU power by only redrawing the
+    /// regions marked with Primitives2d::mark_dirty and keeping the rest of each swapchain image
+    /// from its previous frame, the draw callback still needs to add every primitive that
+    /// overlaps a dirty region
+    pub fn set_dirty_region_mode(&mut self, enabled: bool) {
+        self.dirty_region_mode = enabled;
+        // Every image gets fully redrawn once since its previous contents are unknown
+        self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+        self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+    }
+
+    // Returns the area to render and whether the image contents outside of it are kept
+    fn get_render_area(&mut self, image_index: usize) -> Option<(api::Rect2D, bool)> {
+        let full_render_area = api::Rect2D {
+            offset: api::Offset2d::default(),
+            extent: api::Extent2d {
+                width: self.swapchain.width,
+                height: self.swapchain.height,
+            },
+        };
+        if !self.dirty_region_mode {
+            return Some((full_render_area, false));
+        }
+        if !self.image_contents_valid[image_index] {
+            self.image_contents_valid[image_index] = true;
+            self.image_dirty_regions[image_index] = None;
+            return Some((full_render_area, false));
+        }
+
+        let region = self.image_dirty_regions[image_index].take()?;
+        let x_min = region.x.max(0.0).floor() as u32;
+        let y_min = region.y.max(0.0).floor() as u32;
+        let x_max = ((region.x + region.w).ceil().max(0.0) as u32).min(self.swapchain.width);
+        let y_max = ((region.y + region.h).ceil().max(0.0) as u32).min(self.swapchain.height);
+        if x_max <= x_min || y_max <= y_min {
+            return None;
+        }
+        let render_area = api::Rect2D {
+            offset: api::Offset2d {
+                x: x_min as i32,
+                y: y_min as i32,
+            },
+            extent: api::Extent2d {
+                width: x_max - x_min,
+                height: y_max - y_min,
+            },
+        };
+        Some((render_area, true))
+    }
+
+    // Signals the current frame fence without rendering or presenting anything
+    fn signal_fence(&mut self) -> Result<(), Error> {
+        self.swapchain.signal_fence(self.fences[self.frame_index])?;
+        self.frame_index = (self.frame_index + 1) % self.frames_in_flight;
+        Ok(())
+    }
+
+    /// Recreates the swapchain and everything that depends on its size for the new window size
+    /// (in pixels), call this after a window resize or when rendering returns an out of date
+    /// swapchain error
+    pub fn resize(&mut self, window_size: (u32, u32)) -> Result<(), Error> {
+        // Recreate waits for the device to be idle before anything gets destroyed
+        self.swapchain.recreate(window_size)?;
+
+        let device = self.swapchain.device.handle;
+        unsafe {
+            for framebuffer in self.framebuffers {
+                api::vkDestroyFramebuffer(device, framebuffer, ptr::null());
+            }
+            for image_view in self.swapchain_image_views {
+                api::vkDestroyImageView(device, image_view, ptr::null());
+            }
+        }
+        self.framebuffers = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+        self.swapchain_image_views = [ptr::null(); SWAPCHAIN_IMAGE_COUNT as usize];
+
+        let (swapchain_image_views, framebuffers) =
+            create_swapchain_framebuffers(&self.swapchain, self.pipeline.clear_render_pass)?;
+        self.swapchain_image_views = swapchain_image_views;
+        self.framebuffers = framebuffers;
+
+        let copy_queue = self.swapchain.get_copy_queue();
+        self.pipeline
+            .upload_uniform_data(copy_queue, (self.swapchain.width, self.swapchain.height))?;
+
+        // The new images have no previous contents to keep
+        self.image_contents_valid = [false; SWAPCHAIN_IMAGE_COUNT as usize];
+        self.image_dirty_regions = [None; SWAPCHAIN_IMAGE_COUNT as usize];
+        Ok(())
+    }
+
+    pub fn get_primitives(&mut self) -> Result<Primitives2d, Error> {
+        let result = unsafe {
+            api::vkWaitForFences(
+                self.swapchain.device.handle,
+                1,
+                &self.fences[self.frame_index],
+                BOOL_FALSE,
+                100000000, // 100 ms in nanoseconds
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+        let result = unsafe {
+            api::vkResetFences(
+                self.swapchain.device.handle,
+                1,
+                &self.fences[self.frame_index],
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        Ok(self.pipeline.get_primitives(self.frame_index))
+    }
+}
+
+impl Drop for TwoDimensionRender {
+    fn drop(&mut self) {
+        let device = self.swapchain.device.handle;
+        // The result is ignored since a lost device still needs its objects destroyed
+        let _ = unsafe { api::vkDeviceWaitIdle(device) };
+
+        unsafe {
+            for fence in self.fences {
+                api::vkDestroyFence(device, fence, ptr::null());
+            }
+            for framebuffer in self.framebuffers {
+                api::vkDestroyFramebuffer(device, framebuffer, ptr::null());
+            }
+            for image_view in self.swapchain_image_views {
+                api::vkDestroyImageView(device, image_view, ptr::null());
+            }
+        }
+        self.pipeline.destroy(device);
+
+        self.swapchain.destroy();
+    }
+}
+
+/// Renders 2d primitives into a single GPU image without an OS window or swapchain and copies
+/// the result back to CPU memory, useful for tests and server side thumbnails
+///
+/// The instance does not need any of the surface extensions.
+/// The image is R8G8B8A8 (instead of the B8G8R8A8 of the swapchain images) so the read back
+/// pixels are already in the RGBA byte order of image files, see get_pixels
+pub struct OffscreenRender {
+    cmd_buffer_begin_info: api::CommandBufferBeginInfo,
+    fence: OpaqueHandle,
+    pipeline: TwoDimensionPipeline,
+    cpu_readback_buffer_mapped_ptr: *mut c_void,
+    cpu_readback_buffer_mem: OpaqueHandle,
+    cpu_readback_buffer: OpaqueHandle,
+    framebuffer: OpaqueHandle,
+    gpu_image_view: OpaqueHandle,
+    gpu_image_mem: OpaqueHandle,
+    gpu_image: OpaqueHandle,
+    render_pass_begin_info: RenderPassBeginInfo,
+    command_buffer: OpaqueHandle,
+    command_pool: OpaqueHandle,
+    queue: OpaqueHandle,
+    device: Device,
+}
+
+impl OffscreenRender {
+    pub fn new(
+        physical_device: PhysicalDevice,
+        width: u32,
+        height: u32,
+        staging_buffer_size: usize,
+        glyph_data: GlyphData,
+    ) -> Result<Self, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::ZeroSizeImage);
+        }
+        if glyph_data.is_empty() {
+            return Err(Error::NoGlyphData);
+        }
+
+        // No presentation support is needed without a surface
+        let queue_family_index = match physical_device.find_graphics_queue_family() {
+            Some(i) => i,
+            None => return Err(Error::NoQueueFamily),
+        };
+
+        let queue_priority_data = [1.0];
+        let queue_create_info = api::DeviceQueueCreateInfo {
+            header: StructureHeader::new(StructureType::DeviceQueueCreateInfo),
+            flags: 0,
+            queue_family_index,
+            queue_count: 1,
+            queue_priorities: queue_priority_data.as_ptr(),
+        };
+
+        let queue_create_infos = [queue_create_info];
+        let extension_names = [DEVICE_EXTENSION_NAME_SYNCHRONIZATION];
+        let device = Device::new(physical_device, &queue_create_infos, &extension_names)?;
+
+        let queue = device.get_queue_handle(queue_family_index)?;
+
+        let command_pool_create_info = api::CommandPoolCreateInfo {
+            header: StructureHeader::new(StructureType::CommandPoolCreateInfo),
+            flags: api::CommandPoolCreateFlagBit::ResetCommandBufferBit
+                as api::CommandPoolCreateFlags,
+            queue_family_index,
+        };
+        let command_pool = ptr::null();
+        let result = unsafe {
+            api::vkCreateCommandPool(
+                device.handle,
+                &command_pool_create_info,
+                ptr::null(),
+                &command_pool,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let cmd_buffer_allocate_info = api::CommandBufferAllocateInfo {
+            header: StructureHeader::new(StructureType::CommandBufferAllocateInfo),
+            command_pool,
+            command_buffer_level: api::CommandBufferLevel::Primary,
+            command_buffer_count: 1,
+        };
+        let command_buffer = ptr::null();
+        let result = unsafe {
+            api::vkAllocateCommandBuffers(device.handle, &cmd_buffer_allocate_info, &command_buffer)
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        // Color Image Create (RGBA byte order so the pixels can be read back as is)
+        let image_format = api::Format::R8G8B8A8srgb;
+        let gpu_image_create_info = api::ImageCreateInfo {
+            header: StructureHeader::new(StructureType::ImageCreateInfo),
+            flags: api::ImageCreateFlagBit::None as api::ImageCreateFlags,
+            image_type: api::ImageTypeDimensions::Two,
+            format: image_format,
+            extent: api::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: 1,
+            tiling: api::ImageTiling::Optimal,
+            usage: (api::ImageUsageFlagBit::ColorAttachment as api::ImageUsageFlags)
+                | (api::ImageUsageFlagBit::TransferSrc as api::ImageUsageFlags),
+            sharing_mode: api::SharingMode::Exclusive,
+            queue_family_index_count: 0, // Exclusive to zero here
+            p_queue_family_indices: ptr::null(),
+            initial_layout: api::ImageLayout::Undefined,
+        };
+
+        let gpu_image = ptr::null();
+        let result = unsafe {
+            api::vkCreateImage(
+                device.handle,
+                &gpu_image_create_info,
+                ptr::null(),
+                &gpu_image,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let img_mem_reqs_info = api::ImageMemoryRequirementsInfo2 {
+            header: StructureHeader::new(StructureType::ImageMemoryRequirementsInfo2),
+            image: gpu_image,
+        };
+        let mem_reqs = api::MemoryRequirements2::default();
+        unsafe { api::vkGetImageMemoryRequirements2(device.handle, &img_mem_reqs_info, &mem_reqs) };
+
+        let mem_alloc_info = api::MemoryAllocateInfo {
+            header: StructureHeader::new(StructureType::MemoryAllocateInfo),
+            allocation_size: mem_reqs.size,
+            memory_type_index: device.physical_device.local_only_memory_type_index,
+        };
+        let gpu_image_mem = ptr::null();
+        let result = unsafe {
+            api::vkAllocateMemory(device.handle, &mem_alloc_info, ptr::null(), &gpu_image_mem)
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let bind_img_mem_info = api::BindImageMemoryInfo {
+            header: StructureHeader::new(StructureType::BindImageMemoryInfo),
+            image: gpu_image,
+            memory: gpu_image_mem,
+            memory_offset: 0,
+        };
+        let result = unsafe { api::vkBindImageMemory2(device.handle, 1, &bind_img_mem_info) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let image_view_create_info = api::ImageViewCreateInfo {
+            header: StructureHeader::new(StructureType::ImageViewCreateInfo),
+            flags: 0,
+            image: gpu_image,
+            view_type: api::ImageViewType::TwoDimensions,
+            format: image_format,
+            components: api::ComponentMapping {
+                r: api::ComponentSwizzle::Identity,
+                g: api::ComponentSwizzle::Identity,
+                b: api::ComponentSwizzle::Identity,
+                a: api::ComponentSwizzle::Identity,
+            },
+            subresource_range: api::ImageSubresourceRange {
+                aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+        };
+        let gpu_image_view = ptr::null();
+        let result = unsafe {
+            api::vkCreateImageView(
+                device.handle,
+                &image_view_create_info,
+                ptr::null(),
+                &gpu_image_view,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        // The image stays a color attachment between renders and only moves to a transfer
+        // layout for the copy back
+        let copy_queue = CopyQueue {
+            queue,
+            command_buffer,
+        };
+        let pipeline = TwoDimensionPipeline::new(
+            &device,
+            copy_queue,
+            image_format,
+            api::ImageLayout::ColorAttachmentOptimal,
+            (width, height),
+            staging_buffer_size,
+            &glyph_data,
+            1,
+        )?;
+
+        let framebuffer_create_info = api::FramebufferCreateInfo {
+            header: StructureHeader::new(StructureType::FramebufferCreateInfo),
+            flags: 0,
+            render_pass: pipeline.clear_render_pass,
+            attachment_count: 1,
+            attachments: &gpu_image_view,
+            width,
+            height,
+            layers: 1,
+        };
+        let framebuffer = ptr::null();
+        let result = unsafe {
+            api::vkCreateFramebuffer(
+                device.handle,
+                &framebuffer_create_info,
+                ptr::null(),
+                &framebuffer,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        // Readback Buffer Create (stays mapped for read_pixels)
+        let readback_buffer_create_info = api::BufferCreateInfo {
+            header: StructureHeader::new(StructureType::BufferCreateInfo),
+            flags: api::BufferCreateFlagBit::None as api::BufferCreateFlags,
+            size: (width as u64) * (height as u64) * 4,
+            usage: api::BufferUsageFlagBit::TransferDst as api::BufferUsageFlags,
+            sharing_mode: api::SharingMode::Exclusive,
+            queue_family_index_count: 0, // Exclusive to zero here
+            p_queue_family_indices: ptr::null(),
+        };
+
+        let cpu_readback_buffer = ptr::null();
+        let result: i32 = unsafe {
+            api::vkCreateBuffer(
+                device.handle,
+                &readback_buffer_create_info,
+                ptr::null(),
+                &cpu_readback_buffer,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+
+        let buf_mem_reqs_info = api::BufferMemoryRequirementsInfo2 {
+            header: StructureHeader::new(StructureType::BufferMemoryRequirementsInfo2),
+            buffer: cpu_readback_buffer,
+        };
+        unsafe {
+            api::vkGetBufferMemoryRequirements2(device.handle, &buf_mem_reqs_info, &mem_reqs)
+        };
+        let readback_buffer_mem_reqs_size = mem_reqs.size;
+
+        let mem_alloc_info = api::MemoryAllocateInfo {
+            header: StructureHeader::new(StructureType::MemoryAllocateInfo),
+            allocation_size: readback_buffer_mem_reqs_size,
+            memory_type_index: device.physical_device.basic_cpu_access_memory_type_index,
+        };
+        let cpu_readback_buffer_mem = ptr::null();
+        let result = unsafe {
+            api::vkAllocateMemory(
+                device.handle,
+                &mem_alloc_info,
+                ptr::null(),
+                &cpu_readback_buffer_mem,
+            )
+        };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+        let bind_buf_mem_info = api::BindBufferMemoryInfo {
+            header: StructureHeader::new(StructureType::BindBufferMemoryInfo),
+            buffer: cpu_readback_buffer,
+            memory: cpu_readback_buffer_mem,
+            memory_offset: 0,
+        };
+        let result = unsafe { api::vkBindBufferMemory2(device.handle, 1, &bind_buf_mem_info) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        unsafe {
-            api::vkCmdBeginRenderPass(
-                cmd_buffer,
-                &self.render_pass_begin_info,
-                api::SubpassContents::Inline,
+        let cpu_readback_buffer_mapped_ptr = ptr::null_mut();
+        let result = unsafe {
+            api::vkMapMemory(
+                device.handle,
+                cpu_readback_buffer_mem,
+                0,
+                readback_buffer_mem_reqs_size,
+                api::MemoryMapFlagBit::None as api::MemoryMapFlags,
+                &cpu_readback_buffer_mapped_ptr,
             )
         };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
+        if cpu_readback_buffer_mapped_ptr.is_null() {
+            return Err(Error::InvalidMapPtr);
+        }
+        // Reads back as transparent black until the first render
         unsafe {
-            api::vkCmdBindDescriptorSets(
-                cmd_buffer,
-                api::PipelineBindPoint::Graphics,
-                self.pipeline_layout,
-                0,
-                1,
-                &self.descriptor_set,
+            ptr::write_bytes(
+                cpu_readback_buffer_mapped_ptr as *mut u8,
                 0,
-                ptr::null(),
+                readback_buffer_mem_reqs_size as usize,
             )
         };
 
-        unsafe { api::vkCmdEndRenderPass(cmd_buffer) };
-
-        let result = unsafe { api::vkEndCommandBuffer(cmd_buffer) };
+        let fence_create_info = api::FenceCreateInfo {
+            header: StructureHeader::new(StructureType::FenceCreateInfo),
+            flags: api::FenceCreateFlagBit::None as api::FenceCreateFlags,
+        };
+        let fence = ptr::null();
+        let result =
+            unsafe { api::vkCreateFence(device.handle, &fence_create_info, ptr::null(), &fence) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        self.swapchain
-            .submit_queue_and_present(next_image_index as u32, self.fence)?;
-
-        Ok(())
-    }
-
-    pub fn render(&mut self, num_verticies: u16, num_triangles: u32) -> Result<(), Error> {
-        // Resize if necessary here in future based on next image index results
-        let next_image_index = self.swapchain.get_next_image_index()? as usize;
-        self.render_pass_begin_info.framebuffer = self.framebuffers[next_image_index];
-        self.render_pass_begin_info.clear_values = &self.clear_value;
-
-        let triangle_buffer_copy_regions = [
-            api::BufferCopy2 {
-                header: StructureHeader::new(StructureType::BufferCopy2),
-                src_offset: 0,
-                dst_offset: 0,
-                size: (num_verticies as u64) * (mem::size_of::<TriVert>() as u64),
-            },
-            api::BufferCopy2 {
-                header: StructureHeader::new(StructureType::BufferCopy2),
-                src_offset: VERTEX_BUFFER_SIZE,
-                dst_offset: VERTEX_BUFFER_SIZE,
-                size: (num_triangles as u64) * (mem::size_of::<TriInd>() as u64),
-            },
-            api::BufferCopy2 {
-                header: StructureHeader::new(StructureType::BufferCopy2),
-                src_offset: self.gpu_primitive_data_offset,
-                dst_offset: self.gpu_primitive_data_offset,
-                size: (num_triangles as u64) * (mem::size_of::<TriPrimData>() as u64),
+        // Command Buffer Constants Setup
+        let render_pass_begin_info = api::RenderPassBeginInfo {
+            header: StructureHeader::new(StructureType::RenderPassBeginInfo),
+            render_pass: pipeline.clear_render_pass,
+            framebuffer,
+            render_area: api::Rect2D {
+                offset: api::Offset2d::default(),
+                extent: api::Extent2d { width, height },
             },
-        ];
-        let triangle_copy_buffer_info = api::CopyBufferInfo2 {
-            header: StructureHeader::new(StructureType::CopyBufferInfo2),
-            src_buffer: self.cpu_staging_buffer,
-            dst_buffer: self.gpu_triangle_buffer,
-            region_count: 3,
-            regions: triangle_buffer_copy_regions.as_ptr(),
+            clear_value_count: 1,
+            clear_values: ptr::null(),
         };
 
-        let vertex_buffers = [self.gpu_triangle_buffer];
-        let vertex_offsets = [0];
+        let cmd_buffer_begin_info = api::CommandBufferBeginInfo {
+            header: StructureHeader::new(StructureType::CommandBufferBeginInfo),
+            flags: api::CommandBufferUsageFlagBit::None as api::CommandBufferUsageFlags,
+            inheritance_info: ptr::null(),
+        };
 
-        let cmd_buffer = self.swapchain.cmd_buffer_submit_infos[next_image_index].command_buffer;
+        Ok(Self {
+            cmd_buffer_begin_info,
+            fence,
+            pipeline,
+            cpu_readback_buffer_mapped_ptr,
+            cpu_readback_buffer_mem,
+            cpu_readback_buffer,
+            framebuffer,
+            gpu_image_view,
+            gpu_image_mem,
+            gpu_image,
+            render_pass_begin_info,
+            command_buffer,
+            command_pool,
+            queue,
+            device,
+        })
+    }
+
+    /// Calls draw to add the primitives for this frame, renders them and waits for the GPU to
+    /// copy the image back so read_pixels returns the new frame
+    pub fn render(&mut self, draw: impl FnOnce(&mut Primitives2d)) -> Result<(), Error> {
+        // The previous render has always finished so its primitive data can be overwritten
+        let mut primitives = self.pipeline.get_primitives(0);
+        draw(&mut primitives);
+        let (num_verticies, num_triangles) = primitives.get_num_verts_and_tris();
+        let regions = primitives.get_regions();
+
+        self.render_pass_begin_info.clear_values = &self.pipeline.clear_value;
+        let cmd_buffer = self.command_buffer;
 
         let result = unsafe { api::vkBeginCommandBuffer(cmd_buffer, &self.cmd_buffer_begin_info) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        unsafe { api::vkCmdCopyBuffer2(cmd_buffer, &triangle_copy_buffer_info) };
+        self.pipeline.record_draw_commands(
+            &self.device,
+            cmd_buffer,
+            &self.render_pass_begin_info,
+            0,
+            num_verticies,
+            num_triangles,
+            &regions,
+            |_| {},
+        );
 
-        unsafe {
-            api::vkCmdBeginRenderPass(
-                cmd_buffer,
-                &self.render_pass_begin_info,
-                api::SubpassContents::Inline,
-            )
+        let image_memory_barrier = api::ImageMemoryBarrier2 {
+            header: StructureHeader::new(StructureType::ImageMemoryBarrier2),
+            src_stage_mask: api::PipelineStageFlag2Bit::ColorAttachmentOutput
+                as api::PipelineStageFlags2,
+            src_access_mask: api::AccessFlag2Bit::ColorAttachmentWrite as api::AccessFlags2,
+            dst_stage_mask: api::PipelineStageFlag2Bit::Copy as api::PipelineStageFlags2,
+            dst_access_mask: api::AccessFlag2Bit::TransferRead as api::AccessFlags2,
+            old_layout: api::ImageLayout::ColorAttachmentOptimal,
+            new_layout: api::ImageLayout::TransferSrcOptimal,
+            src_queue_family_index: 0,
+            dst_queue_family_index: 0,
+            image: self.gpu_image,
+            subresource_range: api::ImageSubresourceRange {
+                aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
         };
+        let dependency_info = api::DependencyInfo {
+            header: StructureHeader::new(StructureType::DependencyInfo),
+            dependency_flags: api::DependencyFlagBit::None as api::DependencyFlags,
+            memory_barrier_count: 0,
+            memory_barriers: ptr::null(),
+            buffer_memory_barrier_count: 0,
+            buffer_memory_barriers: ptr::null(),
+            image_memory_barrier_count: 1,
+            image_memory_barriers: &image_memory_barrier,
+        };
+        unsafe { api::vkCmdPipelineBarrier2(cmd_buffer, &dependency_info) };
 
-        unsafe {
-            api::vkCmdBindPipeline(
-                cmd_buffer,
-                api::PipelineBindPoint::Graphics,
-                self.graphics_pipeline,
-            )
+        let buffer_image_copy = api::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0, // Tightly packed
+            buffer_image_height: 0,
+            image_subresource: api::ImageSubresourceLayers {
+                aspect_mask: api::ImageAspectFlagBit::Color as api::ImageAspectFlags,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: api::Offset3d::default(),
+            image_extent: api::Extent3d {
+                width: self.pipeline.width,
+                height: self.pipeline.height,
+                depth: 1,
+            },
         };
         unsafe {
-            api::vkCmdBindVertexBuffers(
+            api::vkCmdCopyImageToBuffer(
                 cmd_buffer,
-                0,
+                self.gpu_image,
+                api::ImageLayout::TransferSrcOptimal,
+                self.cpu_readback_buffer,
                 1,
-                vertex_buffers.as_ptr(),
-                vertex_offsets.as_ptr(),
+                &buffer_image_copy,
             )
         };
-        unsafe {
-            api::vkCmdBindIndexBuffer(
-                cmd_buffer,
-                self.gpu_triangle_buffer,
-                VERTEX_BUFFER_SIZE,
-                api::IndexType::Uint16,
-            )
+
+        // Makes the copied pixels visible to the CPU once the fence is signalled
+        let host_read_barrier = api::MemoryBarrier2 {
+            header: StructureHeader::new(StructureType::MemoryBarrier2),
+            src_stage_mask: api::PipelineStageFlag2Bit::Copy as api::PipelineStageFlags2,
+            src_access_mask: api::AccessFlag2Bit::TransferWrite as api::AccessFlags2,
+            dst_stage_mask: api::PipelineStageFlag2Bit::Host as api::PipelineStageFlags2,
+            dst_access_mask: api::AccessFlag2Bit::HostRead as api::AccessFlags2,
         };
-        unsafe {
-            api::vkCmdBindDescriptorSets(
-                cmd_buffer,
-                api::PipelineBindPoint::Graphics,
-                self.pipeline_layout,
-                0,
-                1,
-                &self.descriptor_set,
-                0,
-                ptr::null(),
-            )
+        let dependency_info = api::DependencyInfo {
+            header: StructureHeader::new(StructureType::DependencyInfo),
+            dependency_flags: api::DependencyFlagBit::None as api::DependencyFlags,
+            memory_barrier_count: 1,
+            memory_barriers: &host_read_barrier,
+            buffer_memory_barrier_count: 0,
+            buffer_memory_barriers: ptr::null(),
+            image_memory_barrier_count: 0,
+            image_memory_barriers: ptr::null(),
         };
-        unsafe { api::vkCmdDrawIndexed(cmd_buffer, num_triangles * 3, 1, 0, 0, 0) };
-
-        unsafe { api::vkCmdEndRenderPass(cmd_buffer) };
+        unsafe { api::vkCmdPipelineBarrier2(cmd_buffer, &dependency_info) };
 
         let result = unsafe { api::vkEndCommandBuffer(cmd_buffer) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        self.swapchain
-            .submit_queue_and_present(next_image_index as u32, self.fence)?;
-        Ok(())
-    }
+        let cmd_buffer_submit_info = api::CommandBufferSubmitInfo {
+            header: StructureHeader::new(StructureType::CommandBufferSubmitInfo),
+            command_buffer: cmd_buffer,
+            device_mask: 0,
+        };
+        let submit_info = api::SubmitInfo2 {
+            header: StructureHeader::new(StructureType::SubmitInfo2),
+            flags: api::SubmitFlagBit::None as api::SubmitFlags,
+            wait_semaphore_info_count: 0,
+            wait_semaphore_infos: ptr::null(),
+            command_buffer_info_count: 1,
+            command_buffer_infos: &cmd_buffer_submit_info,
+            signal_semaphore_info_count: 0,
+            signal_semaphore_infos: ptr::null(),
+        };
+        let result = unsafe { api::vkQueueSubmit2(self.queue, 1, &submit_info, self.fence) };
+        if result != 0 {
+            return Err(Error::VkResult(result));
+        }
 
-    pub fn get_primitives(&mut self) -> Result<Primitives2d, Error> {
         let result = unsafe {
             api::vkWaitForFences(
-                self.swapchain.device.handle,
+                self.device.handle,
                 1,
                 &self.fence,
                 BOOL_FALSE,
-                100000000, // 100 ms in nanoseconds
+                1000000000, // 1 second in nanoseconds
             )
         };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
-        let result = unsafe { api::vkResetFences(self.swapchain.device.handle, 1, &self.fence) };
+        let result = unsafe { api::vkResetFences(self.device.handle, 1, &self.fence) };
         if result != 0 {
             return Err(Error::VkResult(result));
         }
 
-        let index_offset = unsafe {
-            self.cpu_staging_buffer_mapped_ptr
-                .offset(VERTEX_BUFFER_SIZE as isize)
+        Ok(())
+    }
+
+    /// Borrows the most recently rendered frame without a copy, it stays valid until the next render
+    ///
+    /// The pixels are row by row from the top left and each one is RGBA in the sRGB color space
+    /// with red in the lowest byte (0xAABBGGRR) and premultiplied alpha. This is a different byte
+    /// order than the B8G8R8A8 swapchain images (0xAARRGGBB) since the offscreen image is R8G8B8A8.
+    pub fn get_pixels(&self) -> &[u32] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self.cpu_readback_buffer_mapped_ptr as *const u32,
+                (self.pipeline.width as usize) * (self.pipeline.height as usize),
+            )
+        }
+    }
+
+    /// Returns a copy of the most recently rendered frame in the same format as get_pixels,
+    /// same as read_pixels_with_alpha with the default of PixelAlpha::Premultiplied
+    pub fn read_pixels(&self) -> Vec<u32> {
+        self.read_pixels_with_alpha(PixelAlpha::Premultiplied)
+    }
+
+    /// Same as read_pixels but converts the pixels to straight alpha with PixelAlpha::Straight
+    /// so that frames rendered over a transparent clear color can be saved to a PNG directly
+    /// (the pixels are already in the RGBA byte order that PNG encoders expect)
+    pub fn read_pixels_with_alpha(&self, alpha: PixelAlpha) -> Vec<u32> {
+        let pixels = self.get_pixels();
+        match alpha {
+            PixelAlpha::Premultiplied => pixels.to_vec(),
+            PixelAlpha::Straight => pixels
+                .iter()
+                .map(|p| crate::color::get_straight_alpha_from_premultiplied_srgb_pixel(*p))
+                .collect(),
+        }
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.pipeline.width, self.pipeline.height)
+    }
+
+    /// Background color that every frame starts from (transparent black by default),
+    /// read_pixels returns it with the premultiplied alpha
+    pub fn set_clear_color(&mut self, color: &PrimitiveColor) {
+        self.pipeline.set_clear_color(color);
+    }
+
+    /// Selects how the primitives of the next renders are blended, Over is the default
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.pipeline.set_blend_mode(blend_mode);
+    }
+
+    /// Same as TwoDimensionRender::set_glyph_coverage_gamma
+    pub fn set_glyph_coverage_gamma(&mut self, gamma: f32) -> Result<(), Error> {
+        let copy_queue = CopyQueue {
+            queue: self.queue,
+            command_buffer: self.command_buffer,
         };
-        let index_offset_2 = unsafe {
-            self.cpu_staging_buffer_mapped_ptr
-                .offset(self.gpu_primitive_data_offset as isize)
+        self.pipeline.set_glyph_coverage_gamma(copy_queue, gamma)
+    }
+
+    /// Replaces the GPU glyph data such as after new glyph outlines were loaded at runtime
+    pub fn update_glyph_data(&mut self, glyph_data: GlyphData) -> Result<(), Error> {
+        let copy_queue = CopyQueue {
+            queue: self.queue,
+            command_buffer: self.command_buffer,
         };
-        Ok(Primitives2d {
-            verticies: unsafe {
-                std::slice::from_raw_parts_mut(
-                    self.cpu_staging_buffer_mapped_ptr as *mut TriVert,
-                    MAX_NUMBER_OF_VERTICIES as usize,
-                )
-            },
-            indicies: unsafe {
-                std::slice::from_raw_parts_mut(
-                    index_offset as *mut TriInd,
-                    self.max_triangles as usize,
-                )
-            },
-            data: unsafe {
-                std::slice::from_raw_parts_mut(
-                    index_offset_2 as *mut TriPrimData,
-                    self.max_triangles as usize,
-                )
-            },
-            num_verticies: 0,
-            num_triangles: 0,
-            width: self.swapchain.width as f32,
-            height: self.swapchain.height as f32,
-        })
+        self.pipeline
+            .update_glyph_data(&self.device, copy_queue, &glyph_data)
+    }
+}
+
+impl Drop for OffscreenRender {
+    fn drop(&mut self) {
+        let device = self.device.handle;
+        // The result is ignored since a lost device still needs its objects destroyed
+        let _ = unsafe { api::vkDeviceWaitIdle(device) };
+
+        unsafe {
+            api::vkDestroyFence(device, self.fence, ptr::null());
+            api::vkUnmapMemory(device, self.cpu_readback_buffer_mem);
+            api::vkDestroyBuffer(device, self.cpu_readback_buffer, ptr::null());
+            api::vkFreeMemory(device, self.cpu_readback_buffer_mem, ptr::null());
+            api::vkDestroyFramebuffer(device, self.framebuffer, ptr::null());
+            api::vkDestroyImageView(device, self.gpu_image_view, ptr::null());
+            api::vkDestroyImage(device, self.gpu_image, ptr::null());
+            api::vkFreeMemory(device, self.gpu_image_mem, ptr::null());
+        }
+        self.pipeline.destroy(device);
+
+        unsafe { api::vkDestroyCommandPool(device, self.command_pool, ptr::null()) };
+        self.device.destroy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_place_a_rectangle_right_of_another_without_overlap() {
+        let mut verticies = [TriVert::default(); 8];
+        let mut indicies = [TriInd::default(); 4];
+        let mut data = [TriPrimData::default(); 4];
+        let mut primitives =
+            Primitives2d::new_in_buffers(&mut verticies, &mut indicies, &mut data, 200, 100);
+        let color = PrimitiveColor::new_from_srgb8(255, 128, 0, 1.0);
+
+        let a = primitives.add_rectangle(
+            (10.0, 20.0),
+            (30.0, 15.0),
+            &color,
+            PrimitiveRectangleModifier::None,
+        );
+        let b = primitives.add_rectangle(
+            (a.x + a.w, a.y),
+            (20.0, a.h),
+            &color,
+            PrimitiveRectangleModifier::RoundedCorners(4.0),
+        );
+        assert_eq!((a.x, a.y, a.w, a.h), (10.0, 20.0, 30.0, 15.0));
+        assert_eq!((b.x, b.y, b.w, b.h), (40.0, 20.0, 20.0, 15.0));
+        assert!(b.x >= a.x + a.w);
+
+        let last = primitives.get_last_bounds().unwrap();
+        assert_eq!((last.x, last.w), (b.x, b.w));
+        let both = a.union(&b);
+        assert_eq!(both.w, a.w + b.w);
+    }
+
+    #[test]
+    fn rectangle_verticies_include_the_antialiasing_border() {
+        let mut verticies = [TriVert::default(); 4];
+        let mut indicies = [TriInd::default(); 2];
+        let mut data = [TriPrimData::default(); 2];
+        let mut primitives =
+            Primitives2d::new_in_buffers(&mut verticies, &mut indicies, &mut data, 200, 100);
+        let color = PrimitiveColor::new_from_srgb8(255, 255, 255, 1.0);
+        primitives.add_rectangle(
+            (10.0, 20.0),
+            (30.0, 15.0),
+            &color,
+            PrimitiveRectangleModifier::Ellipse,
+        );
+        assert_eq!(primitives.get_num_verts_and_tris(), (4, 2));
+        assert_eq!(primitives.remaining_capacity(), 0);
+        assert!(!primitives.has_overflowed());
+
+        let corners: Vec<(f32, f32)> = verticies.iter().map(|v| (v.x, v.y)).collect();
+        assert_eq!(
+            corners,
+            vec![(9.0, 19.0), (41.0, 19.0), (41.0, 36.0), (9.0, 36.0)]
+        );
+        let triangles: Vec<[u16; 3]> = indicies.iter().map(|i| [i.p0, i.p1, i.p2]).collect();
+        assert_eq!(triangles, vec![[0, 1, 2], [3, 0, 2]]);
+        assert_eq!(data[0].glyph_index, 0x7FFF_FFFF);
+        assert_eq!(
+            (data[1].texture_width, data[1].texture_height),
+            (30.0, 15.0)
+        );
+    }
+
+    #[test]
+    fn line_bounds_include_the_round_caps() {
+        let mut verticies = [TriVert::default(); 4];
+        let mut indicies = [TriInd::default(); 2];
+        let mut data = [TriPrimData::default(); 2];
+        let mut primitives =
+            Primitives2d::new_in_buffers(&mut verticies, &mut indicies, &mut data, 200, 100);
+        let color = PrimitiveColor::new_from_srgb8(255, 255, 255, 1.0);
+        let bounds = primitives.add_line((10.0, 10.0), (10.0, 30.0), 4.0, &color);
+        assert_eq!(
+            (bounds.x, bounds.y, bounds.w, bounds.h),
+            (8.0, 8.0, 4.0, 24.0)
+        );
+        // Rounded corners with a radius of half of the width over the length plus the caps
+        assert_eq!(data[0].glyph_index, 0xBFFF_FFFF);
+        assert_eq!(data[0].extra, 2.0);
+        assert_eq!((data[0].texture_width, data[0].texture_height), (24.0, 4.0));
+    }
+
+    #[test]
+    fn primitives_past_the_capacity_get_skipped() {
+        let mut verticies = [TriVert::default(); 8];
+        let mut indicies = [TriInd::default(); 4];
+        let mut data = [TriPrimData::default(); 4];
+        let mut primitives =
+            Primitives2d::new_in_buffers(&mut verticies, &mut indicies, &mut data, 200, 100);
+        let color = PrimitiveColor::new_from_srgb8(255, 255, 255, 1.0);
+        assert_eq!(primitives.remaining_capacity(), 2);
+        primitives.add_rectangle(
+            (0.0, 0.0),
+            (10.0, 10.0),
+            &color,
+            PrimitiveRectangleModifier::None,
+        );
+        let line_bounds = primitives.add_line((0.0, 20.0), (50.0, 20.0), 2.0, &color);
+        assert_eq!(primitives.remaining_capacity(), 0);
+        assert!(!primitives.has_overflowed());
+
+        let skipped = primitives.add_rectangle(
+            (60.0, 70.0),
+            (10.0, 10.0),
+            &color,
+            PrimitiveRectangleModifier::None,
+        );
+        assert!(primitives.has_overflowed());
+        assert_eq!(
+            (skipped.x, skipped.y, skipped.w, skipped.h),
+            (60.0, 70.0, 0.0, 0.0)
+        );
+        assert_eq!(primitives.get_num_triangles(), 4);
+        let last = primitives.get_last_bounds().unwrap();
+        assert_eq!((last.x, last.y), (line_bounds.x, line_bounds.y));
     }
 }
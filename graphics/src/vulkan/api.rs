@@ -34,6 +34,10 @@ type VoidFunction = unsafe extern "C" fn();
 pub(super) const BOOL_FALSE: u32 = 0;
 pub(super) const BOOL_TRUE: u32 = 1;
 
+pub(super) const RESULT_ERROR_DEVICE_LOST: i32 = -4;
+pub(super) const RESULT_SUBOPTIMAL: i32 = 1000001003;
+pub(super) const RESULT_ERROR_OUT_OF_DATE: i32 = -1000001004;
+
 #[macro_export]
 macro_rules! make_api_version {
     ($variant:expr, $major:expr, $minor:expr, $patch:expr) => {
@@ -301,6 +305,12 @@ pub(super) type CreateDebugUtilsMessenger = unsafe extern "C" fn(
     messenger_ptr: *const OpaqueHandle,
 ) -> i32;
 
+pub(super) type DestroyDebugUtilsMessenger = unsafe extern "C" fn(
+    instance: OpaqueHandle,
+    messenger: OpaqueHandle,
+    allocator: *const AllocationCallbacks,
+);
+
 #[repr(C)]
 pub(super) struct PhysicalDeviceIdProperties {
     pub(super) header: StructureHeader,
@@ -480,6 +490,21 @@ pub(super) struct PhysicalDeviceProperties2 {
     sparse_properties: PhysicalDeviceSparseProperties,
 }
 
+impl PhysicalDeviceProperties2 {
+    pub(super) fn get_max_storage_buffer_range(&self) -> u32 {
+        self.limits.max_storage_buffer_range
+    }
+
+    pub(super) fn get_device_name(&self) -> String {
+        let name_len = self
+            .device_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(self.device_name.len());
+        String::from_utf8_lossy(&self.device_name[..name_len]).into_owned()
+    }
+}
+
 impl Default for PhysicalDeviceProperties2 {
     fn default() -> Self {
         Self {
@@ -501,6 +526,7 @@ impl Default for PhysicalDeviceProperties2 {
 #[repr(C)]
 pub(super) enum Format {
     Undefined = 0,
+    R8G8B8A8srgb = 43,
     B8G8R8A8unorm = 44,
     B8G8R8A8srgb = 50,
     R32G32sfloat = 103,
@@ -618,6 +644,7 @@ impl Default for SurfaceFormat {
 }
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub(super) enum PresentMode {
     Immediate = 0,
     Mailbox = 1,
@@ -695,7 +722,7 @@ impl Default for QueueFamilyVideoProperties {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Default)]
 #[repr(C)]
 pub(super) struct Extent2d {
     pub(super) width: u32,
@@ -708,8 +735,8 @@ pub(super) struct SurfaceCapabilities {
     min_image_count: u32,
     pub(super) max_image_count: u32,
     pub(super) current_extent: Extent2d,
-    min_image_extent: Extent2d,
-    max_image_extent: Extent2d,
+    pub(super) min_image_extent: Extent2d,
+    pub(super) max_image_extent: Extent2d,
     max_image_array_layers: u32,
     pub(super) supported_transforms: u32,
     pub(super) current_transform: u32,
@@ -952,6 +979,7 @@ pub(super) enum ImageTiling {
     Linear = 1,
 }
 
+#[derive(Clone, Copy)]
 #[repr(u32)]
 pub(super) enum ImageLayout {
     Undefined = 0,
@@ -1064,7 +1092,10 @@ pub(super) enum PipelineStageFlag2Bit {
     TessellationEvaluationShader = 0x20,
     GeometryShader = 0x40,
     FragmentShader = 0x80,
+    ColorAttachmentOutput = 0x400,
+    Host = 0x4000,
     AllCommands = 0x00010000,
+    Copy = 0x100000000,
 }
 pub(super) type PipelineStageFlags2 = u64;
 
@@ -1075,8 +1106,10 @@ pub(super) enum AccessFlag2Bit {
     IndexRead = 0x02,
     VertexAttributeRead = 0x04,
     UniformRead = 0x08,
+    ColorAttachmentWrite = 0x100,
     TransferRead = 0x800,
     TransferWrite = 0x1000,
+    HostRead = 0x2000,
 }
 pub(super) type AccessFlags2 = u64;
 
@@ -1697,19 +1730,34 @@ pub(super) struct Viewport {
     pub(super) max_depth: f32,
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Default)]
 #[repr(C)]
 pub(super) struct Offset2d {
     pub(super) x: i32,
     pub(super) y: i32,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub(super) struct Rect2D {
     pub(super) offset: Offset2d,
     pub(super) extent: Extent2d,
 }
 
+#[repr(C)]
+pub(super) struct ClearAttachment {
+    pub(super) aspect_mask: ImageAspectFlags,
+    pub(super) color_attachment: u32,
+    pub(super) clear_value: ClearValue,
+}
+
+#[repr(C)]
+pub(super) struct ClearRect {
+    pub(super) rect: Rect2D,
+    pub(super) base_array_layer: u32,
+    pub(super) layer_count: u32,
+}
+
 #[repr(C)]
 pub(super) struct PipelineViewportStateCreateInfo {
     pub(super) header: StructureHeader,
@@ -2125,6 +2173,7 @@ pub(super) struct ClearDepthStencilValue {
     pub(super) stencil: u32,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub(super) union ClearValue {
     pub(super) color: ClearColorValue,
@@ -2509,6 +2558,37 @@ extern "C" {
         pipeline: OpaqueHandle,
     );
 
+    pub(super) fn vkCmdSetViewport(
+        cmd_buffer: OpaqueHandle,
+        first_viewport: u32,
+        viewport_count: u32,
+        viewports: *const Viewport,
+    );
+
+    pub(super) fn vkCmdSetScissor(
+        cmd_buffer: OpaqueHandle,
+        first_scissor: u32,
+        scissor_count: u32,
+        scissors: *const Rect2D,
+    );
+
+    pub(super) fn vkCmdClearAttachments(
+        cmd_buffer: OpaqueHandle,
+        attachment_count: u32,
+        attachments: *const ClearAttachment,
+        rect_count: u32,
+        rects: *const ClearRect,
+    );
+
+    pub(super) fn vkCmdPushConstants(
+        cmd_buffer: OpaqueHandle,
+        pipeline_layout: OpaqueHandle,
+        stage_flags: ShaderStageFlags,
+        offset: u32,
+        size: u32,
+        values: *const c_void,
+    );
+
     pub(super) fn vkCmdBindVertexBuffers(
         cmd_buffer: OpaqueHandle,
         first_binding: u32,
@@ -2543,4 +2623,106 @@ extern "C" {
         vertex_offset: i32,
         first_instance: u32,
     );
+
+    pub(super) fn vkDeviceWaitIdle(device: OpaqueHandle) -> i32;
+
+    pub(super) fn vkDestroyInstance(instance: OpaqueHandle, allocator: *const AllocationCallbacks);
+
+    pub(super) fn vkDestroySurfaceKHR(
+        instance: OpaqueHandle,
+        surface: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyDevice(device: OpaqueHandle, allocator: *const AllocationCallbacks);
+
+    pub(super) fn vkDestroySwapchainKHR(
+        device: OpaqueHandle,
+        swapchain: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyCommandPool(
+        device: OpaqueHandle,
+        command_pool: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroySemaphore(
+        device: OpaqueHandle,
+        semaphore: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyFence(
+        device: OpaqueHandle,
+        fence: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyBuffer(
+        device: OpaqueHandle,
+        buffer: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkFreeMemory(
+        device: OpaqueHandle,
+        memory: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyImage(
+        device: OpaqueHandle,
+        image: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyImageView(
+        device: OpaqueHandle,
+        image_view: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyFramebuffer(
+        device: OpaqueHandle,
+        framebuffer: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyRenderPass(
+        device: OpaqueHandle,
+        render_pass: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyShaderModule(
+        device: OpaqueHandle,
+        shader_module: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyDescriptorSetLayout(
+        device: OpaqueHandle,
+        descriptor_set_layout: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyDescriptorPool(
+        device: OpaqueHandle,
+        descriptor_pool: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyPipelineLayout(
+        device: OpaqueHandle,
+        pipeline_layout: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
+
+    pub(super) fn vkDestroyPipeline(
+        device: OpaqueHandle,
+        pipeline: OpaqueHandle,
+        allocator: *const AllocationCallbacks,
+    );
 }
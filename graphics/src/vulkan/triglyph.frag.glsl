@@ -9,7 +9,7 @@ struct PrimitiveInfo {
     uint glyphIndex; // Glyph Index where Most-Significant 2-bits contain additional information
     float textureWidth; // Pre-calculated texture width
     float textureHeight; // Pre-calculated texture height
-    float extra; // Extra Value (currently only used as radius for rounded rectangle)
+    float extra; // Extra Value (currently only used as radius for rounded rectangle and soft shadow)
 };
 
 layout(std430, set = 0, binding = 0) readonly buffer PrimDataBuffer {
@@ -33,10 +33,29 @@ layout(std430, set = 0, binding = 2) readonly buffer GlyphDataBuffer {
 	GlyphOutlineSegment data[];
 } glyphOutlineSegments;
 
+layout(push_constant) uniform PushData {
+	uint primitiveOffset; // First primitive of the current draw call since gl_PrimitiveID restarts at zero for every draw
+} push;
+
 layout(set = 0, binding = 3) uniform UniformData {
 	vec2 mult;
+	float glyphCoverageGamma; // Glyph coverage gets raised to this power (1.0 leaves it linear, below 1.0 darkens thin stems)
+	float reserved;
 } uni;
 
+layout(std430, set = 0, binding = 4) readonly buffer ImageBuffer {
+	vec4 yuvToRGB[3]; // Rows of [y, u, v, offset] that give non-linear R'G'B' for YUV video frames
+	uint width; // Zero until the first image gets uploaded
+	uint height;
+	uint format; // 0 => sRGB RGBA pixels | 1 => 8 bit 4:2:0 YUV planes
+	uint chromaStep; // 2 for the interleaved U and V of NV12 | 1 for the separate planes of I420
+	uint chromaWidth;
+	uint uOffset; // Byte offsets of the first U and V values within data
+	uint vOffset;
+	uint reserved;
+	uint data[]; // RGBA pixels with straight alpha (red is the lowest byte) or the Y plane and then the half resolution chroma plane(s) as packed bytes
+} image;
+
 const vec2 texZero = vec2(0.5, 0.5);
 const vec2 texHalf = vec2(0.5, 0.5);
 const vec2 texOne = vec2(0.5, 0.5);
@@ -47,9 +66,51 @@ const float sinPreCalc[8] = float[](0.0, 1.0, -0.707106781187, 0.707106781187, -
 const float xTestOffset[8] = float[](0.5, 0.5, 0.707106781187, 0.707106781187, 0.541196100146, 0.541196100146, 0.541196100146, 0.541196100146);
 const float kQuadraticEpsilon = 0.0001;
 
+vec3 getLinearRGB(vec3 srgb) {
+    return mix(srgb / 12.92, pow((srgb + 0.055) / 1.055, vec3(2.4)), greaterThan(srgb, vec3(0.04045)));
+}
+
+// Premultiplied linear RGBA of an image pixel
+vec4 readImagePixel(uint x, uint y) {
+    vec4 srgba = unpackUnorm4x8(image.data[(y * image.width) + x]);
+    return vec4(getLinearRGB(srgba.rgb) * srgba.a, srgba.a);
+}
+
+// Bilinear sample of the image where coord is in image pixels
+vec4 sampleImage(vec2 coord) {
+    vec2 maxCoord = vec2(float(image.width - 1), float(image.height - 1));
+    vec2 p = clamp(coord - texHalf, vec2(0.0, 0.0), maxCoord);
+    uvec2 p0 = uvec2(p);
+    uvec2 p1 = min(p0 + 1, uvec2(image.width - 1, image.height - 1));
+    vec2 f = p - vec2(p0);
+    vec4 v00 = readImagePixel(p0.x, p0.y);
+    vec4 v10 = readImagePixel(p1.x, p0.y);
+    vec4 v01 = readImagePixel(p0.x, p1.y);
+    vec4 v11 = readImagePixel(p1.x, p1.y);
+    return mix(mix(v00, v10, f.x), mix(v01, v11, f.x), f.y);
+}
+
+float readImageByte(uint byteIndex) {
+    return float((image.data[byteIndex >> 2] >> ((byteIndex & 3) * 8)) & 0xFF);
+}
+
+// Bilinear sample (0.0 to 1.0) of a YUV plane where coord is in plane pixels
+float sampleImagePlane(uint offset, uint step, uint planeWidth, uint planeHeight, vec2 coord) {
+    vec2 maxCoord = vec2(float(planeWidth - 1), float(planeHeight - 1));
+    vec2 p = clamp(coord - texHalf, vec2(0.0, 0.0), maxCoord);
+    uvec2 p0 = uvec2(p);
+    uvec2 p1 = min(p0 + 1, uvec2(planeWidth - 1, planeHeight - 1));
+    vec2 f = p - vec2(p0);
+    float v00 = readImageByte(offset + ((p0.y * planeWidth) + p0.x) * step);
+    float v10 = readImageByte(offset + ((p0.y * planeWidth) + p1.x) * step);
+    float v01 = readImageByte(offset + ((p1.y * planeWidth) + p0.x) * step);
+    float v11 = readImageByte(offset + ((p1.y * planeWidth) + p1.x) * step);
+    return mix(mix(v00, v10, f.x), mix(v01, v11, f.x), f.y) * (1.0 / 255.0);
+}
+
 void main()
 {   
-    PrimitiveInfo primitiveInfo = primitive.info[gl_PrimitiveID];
+    PrimitiveInfo primitiveInfo = primitive.info[gl_PrimitiveID + push.primitiveOffset];
     vec4 color = primitiveInfo.linearRGBA;
     uint outlineIndex = primitiveInfo.glyphIndex & 0x3FFFFFFF;
     uint additionalBits = primitiveInfo.glyphIndex >> 30;
@@ -94,6 +155,46 @@ void main()
             //Also check alpha * alpha theory
             preMultipliedAlphaColorOutput = color * alpha;
             
+        } else if (additionalBits == 2) {
+            // Is a Rounded Rectangle or the Soft Shadow of one (outline index 0x3F00XXXX holds the blur in quarter pixels)
+            vec2 halfDims = texDims * texHalf;
+            float radius = min(primitiveInfo.extra, min(halfDims.x, halfDims.y));
+            vec2 q = abs(textureCoord - halfDims) - halfDims + radius;
+            // Signed distance to the edge which is negative inside
+            float dist = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+
+            float alpha = clamp(0.5 - dist, 0.0, 1.0);
+            if ((outlineIndex & 0x3FFF0000) == 0x3F000000) {
+                // Approximates a Gaussian blur of the edge without a blur pass
+                float blur = max(float(outlineIndex & 0xFFFF) * 0.25, 0.5);
+                alpha = 1.0 - smoothstep(-blur, blur, dist);
+            }
+            preMultipliedAlphaColorOutput = color * alpha;
+
+        } else if ((outlineIndex & 0x3FFF0000) == 0x3E000000) {
+            // Is the Image where the texture coordinates go from 0.0 to 1.0 across it
+            if (image.width == 0) {
+                discard;
+            }
+            vec2 imageCoord = textureCoord * vec2(float(image.width), float(image.height));
+            vec4 imageColor;
+            if (image.format == 0) {
+                imageColor = sampleImage(imageCoord);
+            } else {
+                // Is a YUV Video Frame that gets converted with its matrix
+                uint chromaHeight = (image.height + 1) >> 1;
+                vec4 yuv = vec4(
+                    sampleImagePlane(0, 1, image.width, image.height, imageCoord),
+                    sampleImagePlane(image.uOffset, image.chromaStep, image.chromaWidth, chromaHeight, imageCoord * texHalf),
+                    sampleImagePlane(image.vOffset, image.chromaStep, image.chromaWidth, chromaHeight, imageCoord * texHalf),
+                    1.0);
+                vec3 rgb = clamp(vec3(dot(image.yuvToRGB[0], yuv), dot(image.yuvToRGB[1], yuv), dot(image.yuvToRGB[2], yuv)), 0.0, 1.0);
+                // Same sRGB curve approximation of the video transfer function as color::get_linear_rgb_from_yuv
+                imageColor = vec4(getLinearRGB(rgb), 1.0);
+            }
+            // The primitive color tints the image and its alpha fades it
+            preMultipliedAlphaColorOutput = imageColor * color;
+
         } else {
             discard;
         }
@@ -283,6 +384,7 @@ void main()
         //     debugPrintfEXT("TC %f\n", coverage);
         // }
         float alpha = clamp(abs(coverage) / avg_div , 0.0, 1.0);
+        alpha = pow(alpha, uni.glyphCoverageGamma);
         //Maybe discard in future if alpha is fully transparent!
         //Also check alpha * alpha theory
         preMultipliedAlphaColorOutput = color * alpha;
@@ -5,6 +5,8 @@ layout(location = 0) out vec2 textureCoord; // Which will be interpolated by gra
 
 layout(set = 0, binding = 3) uniform UniformData {
 	vec2 mult;
+	float glyphCoverageGamma; // Only used by the fragment shader
+	float reserved;
 } uni;
 
 const vec2 offset = vec2(-1.0, -1.0);
@@ -25,7 +25,7 @@ use windows::Win32::Foundation::{
     CloseHandle, GetLastError, BOOL, HANDLE, HINSTANCE, HWND, LPARAM, LRESULT, RECT, WAIT_FAILED,
     WAIT_OBJECT_0, WAIT_TIMEOUT, WPARAM,
 };
-use windows::Win32::Graphics::Gdi::{COLOR_BACKGROUND, HBRUSH};
+use windows::Win32::Graphics::Gdi::{self, COLOR_BACKGROUND, HBRUSH, HDC, HMONITOR};
 use windows::Win32::System::{LibraryLoader, Threading};
 use windows::Win32::UI::{HiDpi, Input::KeyboardAndMouse, WindowsAndMessaging};
 
@@ -41,6 +41,7 @@ pub enum OsError {
     Event(Error),
     UnexpectedEventCheckResult,
     TimerSet,
+    Monitor(Error),
 }
 
 pub(super) fn get_device_luid() -> Result<Option<[u32; 2]>, OsError> {
@@ -51,7 +52,60 @@ pub(super) fn get_device_luid() -> Result<Option<[u32; 2]>, OsError> {
     Ok(Some(interface.get_luid()))
 }
 
-#[derive(Debug)]
+unsafe extern "system" fn monitor_enum_callback(
+    hmonitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    lparam: LPARAM,
+) -> BOOL {
+    let monitors = unsafe { &mut *(lparam.0 as *mut Vec<HMONITOR>) };
+    monitors.push(hmonitor);
+    BOOL::from(true)
+}
+
+pub(super) fn get_monitors() -> Result<Vec<super::MonitorInfo>, OsError> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    let bool_res = unsafe {
+        Gdi::EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(monitor_enum_callback),
+            LPARAM(&mut handles as *mut Vec<HMONITOR> as isize),
+        )
+    };
+    if !bool_res.as_bool() {
+        return Err(OsError::Monitor(unsafe { GetLastError().into() }));
+    }
+
+    let mut monitors = Vec::with_capacity(handles.len());
+    for handle in handles {
+        let mut info = Gdi::MONITORINFO {
+            cbSize: std::mem::size_of::<Gdi::MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !unsafe { Gdi::GetMonitorInfoW(handle, &mut info) }.as_bool() {
+            return Err(OsError::Monitor(unsafe { GetLastError().into() }));
+        }
+        let mut dpi_x = 0;
+        let mut dpi_y = 0;
+        if let Err(e) = unsafe {
+            HiDpi::GetDpiForMonitor(handle, HiDpi::MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y)
+        } {
+            return Err(OsError::Monitor(e));
+        }
+        monitors.push(super::MonitorInfo {
+            x: info.rcMonitor.left,
+            y: info.rcMonitor.top,
+            width: (info.rcMonitor.right - info.rcMonitor.left) as u32,
+            height: (info.rcMonitor.bottom - info.rcMonitor.top) as u32,
+            dpi: dpi_x,
+            is_primary: (info.dwFlags & WindowsAndMessaging::MONITORINFOF_PRIMARY) != 0,
+        });
+    }
+    Ok(monitors)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum KeyCode {
     Unknown,
     LeftMouse, // Forgot if it is considered primary for switch buttons
@@ -142,6 +196,68 @@ impl KeyCode {
     }
 }
 
+/// Mouse button of a mouse press or release
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+/// Modifier keys held down during a key event
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyModifiers {
+    bits: u8,
+}
+
+impl KeyModifiers {
+    pub const SHIFT: Self = Self { bits: 0x1 };
+    pub const CONTROL: Self = Self { bits: 0x2 };
+    pub const ALT: Self = Self { bits: 0x4 };
+    pub const SUPER: Self = Self { bits: 0x8 }; // Either Windows logo key
+
+    /// Returns true if every modifier of other is held down
+    pub fn contains(&self, other: Self) -> bool {
+        (self.bits & other.bits) == other.bits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    // Reads the current modifier state of the thread which matches the message just retrieved
+    fn get_current() -> Self {
+        let mut bits = 0;
+        let modifiers = [
+            (KeyboardAndMouse::VK_SHIFT, Self::SHIFT),
+            (KeyboardAndMouse::VK_CONTROL, Self::CONTROL),
+            (KeyboardAndMouse::VK_MENU, Self::ALT),
+            (KeyboardAndMouse::VK_LWIN, Self::SUPER),
+            (KeyboardAndMouse::VK_RWIN, Self::SUPER),
+        ];
+        for (virtual_key, modifier) in modifiers {
+            // The high bit is set when the key is down
+            if unsafe { KeyboardAndMouse::GetKeyState(virtual_key.0 as i32) } < 0 {
+                bits |= modifier.bits;
+            }
+        }
+        Self { bits }
+    }
+}
+
+// Combines modifiers so shortcuts like Ctrl+Shift+S can be checked with a single contains
+impl std::ops::BitOr for KeyModifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            bits: self.bits | rhs.bits,
+        }
+    }
+}
+
 #[repr(isize)]
 enum CallbackResult {
     Destroy = 0,
@@ -182,6 +298,8 @@ pub(super) struct OsWindow {
     resize_disabled: bool,
     placement: WindowsAndMessaging::WINDOWPLACEMENT,
     msg: WindowsAndMessaging::MSG,
+    last_size: (u32, u32), // Client area size in pixels from the last resize check
+    high_surrogate: Option<u16>, // First half of a character input that needs two UTF-16 code units
 }
 
 pub(super) enum OsWindowState {
@@ -189,11 +307,33 @@ pub(super) enum OsWindowState {
     CloseAttempt,
     Closing,
     ShouldDrop,
-    KeyPressed(KeyCode),
+    KeyPressed(KeyCode, KeyModifiers, bool), // The bool is true for auto-repeats of a held down key
+    KeyReleased(KeyCode, KeyModifiers),
+    CharInput(char),
+    MouseMoved { x: f32, y: f32 },
+    MousePressed(MouseButton),
+    MouseReleased(MouseButton),
+    MouseScrolled(f32),
+    MouseScrolledHorizontal(f32),
+    Resized((u32, u32)),
 }
 
 impl OsWindow {
     pub(super) fn new(width: u32, height: u32) -> Result<Self, OsError> {
+        Self::create(
+            WindowsAndMessaging::CW_USEDEFAULT,
+            WindowsAndMessaging::CW_USEDEFAULT,
+            width,
+            height,
+        )
+    }
+
+    // The x and y position is the top left corner of the window frame in virtual screen pixels
+    pub(super) fn new_at(x: i32, y: i32, width: u32, height: u32) -> Result<Self, OsError> {
+        Self::create(x, y, width, height)
+    }
+
+    fn create(x: i32, y: i32, width: u32, height: u32) -> Result<Self, OsError> {
         //windows::Win32::
         let hinstance = match unsafe { LibraryLoader::GetModuleHandleW(None) } {
             Ok(i) => i.into(),
@@ -262,8 +402,8 @@ impl OsWindow {
                 PCWSTR::from_raw(class_name.as_ptr()),
                 PCWSTR::from_raw(window_name.as_ptr()),
                 style,
-                WindowsAndMessaging::CW_USEDEFAULT,
-                WindowsAndMessaging::CW_USEDEFAULT,
+                x,
+                y,
                 corrected_width,
                 corrected_height,
                 HWND::default(),
@@ -291,13 +431,17 @@ impl OsWindow {
 
         println!("Window Handle: {:?}", handle);
 
-        Ok(OsWindow {
+        let mut os_window = OsWindow {
             hinstance,
             handle,
             resize_disabled: false,
             placement,
             msg: WindowsAndMessaging::MSG::default(),
-        })
+            last_size: (0, 0),
+            high_surrogate: None,
+        };
+        os_window.last_size = os_window.get_size();
+        Ok(os_window)
     }
 
     pub(super) fn get_surface_parameters(&self) -> (HINSTANCE, HWND) {
@@ -316,18 +460,53 @@ impl OsWindow {
                 )
             };
             if bool_res.0 == 0 {
+                // Checked once the queue is empty so a burst of size changes is reported once
+                let size = self.get_size();
+                if size != self.last_size {
+                    self.last_size = size;
+                    return Ok(OsWindowState::Resized(size));
+                }
                 return Ok(OsWindowState::Normal);
             } else if self.msg.message != WindowsAndMessaging::WM_USER {
-                if self.msg.message == WindowsAndMessaging::WM_KEYDOWN {
-                    if (self.msg.lParam.0 & 0x40000000) == 0 {
-                        // Key was up previously
-                        let virtual_key_code = self.msg.wParam.0 as u32;
-                        let scan_code = (self.msg.lParam.0 >> 16) & 0xFF;
-                        return Ok(OsWindowState::KeyPressed(KeyCode::get_from_virtual_code(
-                            virtual_key_code,
-                            scan_code as u32,
-                        )));
+                let message = self.msg.message;
+                if let Some(state) = self.get_mouse_state(message) {
+                    return Ok(state);
+                }
+                if message == WindowsAndMessaging::WM_CHAR {
+                    if let Some(c) = self.get_char_input() {
+                        return Ok(OsWindowState::CharInput(c));
+                    }
+                    continue;
+                }
+                if (message == WindowsAndMessaging::WM_KEYDOWN)
+                    || (message == WindowsAndMessaging::WM_KEYUP)
+                    || (message == WindowsAndMessaging::WM_SYSKEYDOWN)
+                    || (message == WindowsAndMessaging::WM_SYSKEYUP)
+                {
+                    if (message == WindowsAndMessaging::WM_KEYDOWN)
+                        || (message == WindowsAndMessaging::WM_SYSKEYDOWN)
+                    {
+                        // Posts the WM_CHAR messages of the keyboard layout (including dead keys)
+                        let _res = unsafe { WindowsAndMessaging::TranslateMessage(&self.msg) };
+                    }
+                    let virtual_key_code = self.msg.wParam.0 as u32;
+                    if self.is_system_shortcut(message, virtual_key_code) {
+                        // Gets the default handling (like closing the window) and is not reported
+                        let _res = unsafe { WindowsAndMessaging::DispatchMessageW(&self.msg) };
+                        continue;
                     }
+                    let scan_code = (self.msg.lParam.0 >> 16) & 0xFF;
+                    let key_code =
+                        KeyCode::get_from_virtual_code(virtual_key_code, scan_code as u32);
+                    let modifiers = KeyModifiers::get_current();
+                    if (message == WindowsAndMessaging::WM_KEYDOWN)
+                        || (message == WindowsAndMessaging::WM_SYSKEYDOWN)
+                    {
+                        // Bit 30 is set when the key was already down
+                        let is_repeat = (self.msg.lParam.0 & 0x40000000) != 0;
+                        return Ok(OsWindowState::KeyPressed(key_code, modifiers, is_repeat));
+                    }
+                    return Ok(OsWindowState::KeyReleased(key_code, modifiers));
                 } else if self.msg.message != WindowsAndMessaging::WM_QUIT {
                     let _res = unsafe { WindowsAndMessaging::DispatchMessageW(&self.msg) };
                 } else {
@@ -340,6 +519,95 @@ impl OsWindow {
         }
     }
 
+    // Alt+F4 (close) and Alt+Space (window menu) are left to the system
+    // while the other keys held with alt get reported like any other key
+    fn is_system_shortcut(&self, message: u32, virtual_key_code: u32) -> bool {
+        let is_sys_key = (message == WindowsAndMessaging::WM_SYSKEYDOWN)
+            || (message == WindowsAndMessaging::WM_SYSKEYUP);
+        // Bit 29 is set when alt is held
+        let is_alt_down = (self.msg.lParam.0 & 0x20000000) != 0;
+        let key = KeyboardAndMouse::VIRTUAL_KEY(virtual_key_code as u16);
+        is_sys_key
+            && is_alt_down
+            && ((key == KeyboardAndMouse::VK_F4) || (key == KeyboardAndMouse::VK_SPACE))
+    }
+
+    // Characters arrive as UTF-16 code units so characters outside of the basic plane take two messages
+    // and text composed with an IME also arrives this way through the default IME message handling
+    fn get_char_input(&mut self) -> Option<char> {
+        let code_unit = self.msg.wParam.0 as u16;
+        let c = match code_unit {
+            0xD800..=0xDBFF => {
+                self.high_surrogate = Some(code_unit);
+                return None;
+            }
+            0xDC00..=0xDFFF => {
+                let high_surrogate = self.high_surrogate.take()?;
+                char::decode_utf16([high_surrogate, code_unit])
+                    .next()?
+                    .ok()?
+            }
+            _ => {
+                self.high_surrogate = None;
+                char::from_u32(code_unit as u32)?
+            }
+        };
+        // Control characters (like backspace, enter and tab) are left to the key events
+        if c.is_control() {
+            return None;
+        }
+        Some(c)
+    }
+
+    // Mouse positions are in client area pixels which is the same pixel space that the renderer uses
+    fn get_mouse_state(&self, message: u32) -> Option<OsWindowState> {
+        let x_button = match ((self.msg.wParam.0 >> 16) & 0xFFFF) as u16 {
+            WindowsAndMessaging::XBUTTON1 => MouseButton::X1,
+            _ => MouseButton::X2,
+        };
+        let state = match message {
+            WindowsAndMessaging::WM_MOUSEMOVE => OsWindowState::MouseMoved {
+                x: ((self.msg.lParam.0 & 0xFFFF) as i16) as f32,
+                y: (((self.msg.lParam.0 >> 16) & 0xFFFF) as i16) as f32,
+            },
+            WindowsAndMessaging::WM_LBUTTONDOWN => OsWindowState::MousePressed(MouseButton::Left),
+            WindowsAndMessaging::WM_RBUTTONDOWN => OsWindowState::MousePressed(MouseButton::Right),
+            WindowsAndMessaging::WM_MBUTTONDOWN => OsWindowState::MousePressed(MouseButton::Middle),
+            WindowsAndMessaging::WM_XBUTTONDOWN => OsWindowState::MousePressed(x_button),
+            WindowsAndMessaging::WM_LBUTTONUP => OsWindowState::MouseReleased(MouseButton::Left),
+            WindowsAndMessaging::WM_RBUTTONUP => OsWindowState::MouseReleased(MouseButton::Right),
+            WindowsAndMessaging::WM_MBUTTONUP => OsWindowState::MouseReleased(MouseButton::Middle),
+            WindowsAndMessaging::WM_XBUTTONUP => OsWindowState::MouseReleased(x_button),
+            WindowsAndMessaging::WM_MOUSEWHEEL => {
+                // Positive in notches when the wheel rotates forward (away from the user)
+                let delta = ((self.msg.wParam.0 >> 16) & 0xFFFF) as i16;
+                OsWindowState::MouseScrolled(
+                    (delta as f32) / (WindowsAndMessaging::WHEEL_DELTA as f32),
+                )
+            }
+            WindowsAndMessaging::WM_MOUSEHWHEEL => {
+                // Positive in notches when the wheel tilts to the right
+                let delta = ((self.msg.wParam.0 >> 16) & 0xFFFF) as i16;
+                OsWindowState::MouseScrolledHorizontal(
+                    (delta as f32) / (WindowsAndMessaging::WHEEL_DELTA as f32),
+                )
+            }
+            _ => return None,
+        };
+        match state {
+            OsWindowState::MousePressed(_) => {
+                // Keeps the mouse messages coming while a button is held outside of the window
+                let _previous = unsafe { KeyboardAndMouse::SetCapture(self.handle) };
+            }
+            // The low word holds the buttons that are still down
+            OsWindowState::MouseReleased(_) if (self.msg.wParam.0 & 0x73) == 0 => {
+                let _res = unsafe { KeyboardAndMouse::ReleaseCapture() };
+            }
+            _ => {}
+        }
+        Some(state)
+    }
+
     pub(super) fn close_window(&mut self) -> Result<(), OsError> {
         if let Err(e) = unsafe { WindowsAndMessaging::DestroyWindow(self.handle) } {
             Err(OsError::Window(e))
@@ -351,6 +619,15 @@ impl OsWindow {
     pub(super) fn get_dpi(&self) -> u32 {
         unsafe { HiDpi::GetDpiForWindow(self.handle) }
     }
+
+    // Returns the client area size in pixels which is (0, 0) when minimized
+    pub(super) fn get_size(&self) -> (u32, u32) {
+        let mut r = RECT::default();
+        if unsafe { WindowsAndMessaging::GetClientRect(self.handle, &mut r) }.is_err() {
+            return (0, 0);
+        }
+        ((r.right - r.left) as u32, (r.bottom - r.top) as u32)
+    }
 }
 
 pub(super) struct OsWait {
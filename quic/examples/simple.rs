@@ -78,6 +78,7 @@ fn server_thread(port: u16, server_name: String) {
         rt_recv_first_bytes: 0,
         initial_background_recv_size: BUFFER_SIZE_PER_CONNECTION,
         background_recv_first_bytes: MESSAGE_HEADER_SIZE,
+        rt_max_pending_segments: 4,
     };
 
     let mut server_endpoint =
@@ -121,6 +122,7 @@ fn client_thread(server_address: SocketAddr, user_name: String) {
         rt_recv_first_bytes: 0,
         initial_background_recv_size: BUFFER_SIZE_PER_CONNECTION,
         background_recv_first_bytes: MESSAGE_HEADER_SIZE,
+        rt_max_pending_segments: 4,
     };
 
     let mut client_endpoint = match Endpoint::new_client_with_first_connection(
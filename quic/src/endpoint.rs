@@ -37,6 +37,10 @@ use connection::{CloseInfo, CloseOrigin, Connection, RecvResult, SendResult, Str
 /// Used when creating a new Endpoint
 pub struct Config {
     /// The quic connection idle timeout in milliseconds.
+    ///
+    /// It is sent as the max_idle_timeout transport parameter and a connection that has not received
+    /// anything for the smaller timeout of both endpoints (but at least three probe timeouts) ends
+    /// with ConnectionEndReason::IdleTimeout, such as when the network of a peer went away.
     pub idle_timeout_in_ms: u64,
 
     /// The quic connection bidirectional stream receive buffer length in bytes.
@@ -56,6 +60,8 @@ pub struct Config {
     /// If there is a value and the duration has passed since the quic connection had recieved anything
     /// the quic connection will send out a PING to try and keep the connection alive.
     /// Any potential keep alives currently occur right before the tick callback function is called.
+    /// It should be well below the idle timeout (like a third of it) so that a lost PING
+    /// does not end a connection on a working network.
     pub keep_alive_timeout: Option<Duration>,
 
     /// The initial main stream recieve buffer size.
@@ -87,6 +93,36 @@ pub struct Config {
     ///
     /// If this value is set to 0 it will be changed to 1 during endpoint creation
     pub background_recv_first_bytes: usize,
+
+    /// The maximum number of finished real-time time segment streams that can still be in flight.
+    ///
+    /// A finished segment stream stays in flight until the peer acknowledged all of its data.
+    /// When finishing another segment would go over this limit the oldest in-flight segment
+    /// stream gets reset (dropped) since only the newest time segment matters for real-time data.
+    /// Drops are counted in the rt_segment_drops of the connection stats.
+    /// If this value is set to 0 it will be treated as 1
+    pub rt_max_pending_segments: usize,
+}
+
+/// Simulated network conditions for testing how an application handles an adverse network.
+///
+/// These only affect the outgoing packets of the endpoint they are set on and are only intended for testing.
+/// Set them on both endpoints of a test (such as a local server and client) to affect both directions.
+/// The same seed and the same sequence of outgoing packets always produce the same simulated results.
+/// Requires the network-simulation feature so that release builds never include it.
+#[cfg(feature = "network-simulation")]
+#[derive(Debug, Clone, Copy)]
+pub struct TestNetworkConditions {
+    /// The base one way delay added to every outgoing packet
+    pub latency: Duration,
+    /// The maximum random extra delay added on top of the latency for each outgoing packet
+    pub jitter: Duration,
+    /// The percentage (0 to 100) of outgoing packets that are dropped
+    pub loss_pct: f32,
+    /// The percentage (0 to 100) of outgoing packets that are held back to arrive after later packets
+    pub reorder_pct: f32,
+    /// The seed of the random number generator used for all of the above
+    pub seed: u64,
 }
 
 /// The Quic Endpoint structure
@@ -105,8 +141,14 @@ pub struct Endpoint {
     is_server: bool,
     conn_id_seed_key: ring::hmac::Key, // Value matters ONLY if is_server is true
     stats: Stats,
+    ready_queue: Vec<ConnectionId>, // Connections marked ready that still need the connection_ready callback
+    accept_stateless_messages: bool,
 }
 
+// Every stateless message datagram starts with this prefix. The first byte has the QUIC fixed bit
+// cleared so a stateless message can never be mistaken for a QUIC packet (and the other way around)
+const STATELESS_MESSAGE_PREFIX: [u8; 8] = [0x00, b'S', b'W', b'I', b'F', b'T', b'S', b'M'];
+
 /// Endpoint Stats
 #[derive(Debug)]
 pub struct Stats {
@@ -125,9 +167,74 @@ impl Stats {
     }
 }
 
+/// Connection health stats of a single connection, see Endpoint::get_connection_stats
+///
+/// The path values come from the active network path of the connection
+/// and are zero (or None) before the path has any estimates.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionStats {
+    /// Smoothed round trip time estimate
+    pub rtt: Duration,
+    /// Smallest round trip time seen so far
+    pub min_rtt: Option<Duration>,
+    /// Round trip time variation estimate
+    pub rtt_variance: Duration,
+    /// Current congestion window in bytes
+    pub congestion_window: usize,
+    /// Most recent delivery rate (bandwidth) estimate in bytes per second
+    pub delivery_rate: u64,
+    /// Total number of bytes sent
+    pub sent_bytes: u64,
+    /// Total number of bytes received
+    pub recv_bytes: u64,
+    /// Total number of bytes lost
+    pub lost_bytes: u64,
+    /// Total number of packets sent
+    pub sent_packets: usize,
+    /// Total number of packets received
+    pub recv_packets: usize,
+    /// Total number of packets lost
+    pub lost_packets: usize,
+    /// Total number of real-time time segments that were dropped before being fully delivered,
+    /// either by starting the next segment before the previous one was handed to the connection
+    /// or by going over the Config rt_max_pending_segments limit
+    pub rt_segment_drops: u64,
+}
+
+impl ConnectionStats {
+    /// Fraction of the sent packets that were lost from 0.0 to 1.0
+    pub fn get_packet_loss(&self) -> f32 {
+        if self.sent_packets == 0 {
+            0.0
+        } else {
+            self.lost_packets as f32 / self.sent_packets as f32
+        }
+    }
+}
+
 /// A Connection ID used to communicate with the endpoint about a specific connection.
 pub type ConnectionId = u64;
 
+/// A Stream ID used to communicate with the endpoint about a specific ad-hoc stream of a connection.
+pub type StreamId = u64;
+
+/// The delivery semantics of an ad-hoc stream chosen when it is opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamProfile {
+    /// Reliable bidirectional byte stream where data arrives in order (like TCP).
+    /// Both sides can send and each side finishes its own side.
+    ReliableOrdered,
+    /// Reliable one-way stream of messages where each send arrives in full as a single
+    /// stream_recv call, but messages can arrive in any order relative to each other.
+    /// Only the opening side can send. Messages can still arrive after the finishing one.
+    ReliableUnordered,
+    /// One-way stream of messages where only the newest message matters.
+    /// Sending a new message abandons the older messages that are not fully sent yet and
+    /// the receiver drops any message that arrives after a newer one. Messages that arrive are complete.
+    /// Only the opening side can send. A message still in flight can be dropped when the stream is finished.
+    Realtime,
+}
+
 /// Errors that the QUIC Endpoint can return
 #[derive(Debug)]
 pub enum Error {
@@ -166,6 +273,14 @@ pub enum Error {
     StreamSend,
     /// Error receiving data from the stream
     StreamRecv(connection::Error),
+    /// The stateless message does not fit in a single datagram
+    StatelessMessageTooLong,
+    /// The datagram is larger than the peer currently accepts, see get_max_datagram_len
+    DatagramTooLong,
+    /// The peer does not support datagrams (or the handshake has not finished yet)
+    DatagramNotSupported,
+    /// Error queueing the datagram (such as when the datagram send queue is full)
+    DatagramSend,
 }
 
 /// Based on combination of QUIC Transport Error Codes and Endpoint Error Codes
@@ -348,6 +463,9 @@ pub(super) enum RecvEvent {
     MainStreamReceived((ConnectionId, usize, Vec<u8>, usize)),
     RealtimeReceived(ConnectionId, usize, Vec<u8>, usize, u64),
     BackgroundStreamReceived((ConnectionId, usize, Vec<u8>, usize)),
+    StreamData((ConnectionId, StreamId, StreamProfile, Vec<u8>, usize, bool)),
+    StatelessReceived((SocketAddr, Vec<u8>)),
+    DatagramReceived((ConnectionId, Vec<u8>)),
 }
 
 pub(super) enum ReadInfo {
@@ -414,6 +532,8 @@ impl Endpoint {
                 is_server: true,
                 conn_id_seed_key,
                 stats: Stats::new(),
+                ready_queue: Vec::new(),
+                accept_stateless_messages: false,
             };
 
             Ok(endpoint_manager)
@@ -475,6 +595,8 @@ impl Endpoint {
                 is_server: false,
                 conn_id_seed_key,
                 stats: Stats::new(),
+                ready_queue: Vec::new(),
+                accept_stateless_messages: false,
             };
 
             Ok(endpoint_manager)
@@ -501,12 +623,26 @@ impl Endpoint {
     }
 
     fn send(&mut self, verified_index: usize) -> Result<Option<CloseInfo>, Error> {
+        self.send_with_pacing(verified_index, true)
+    }
+
+    // Without pacing the packets go out right away instead of at their paced send instant
+    fn send_with_pacing(
+        &mut self,
+        verified_index: usize,
+        pacing: bool,
+    ) -> Result<Option<CloseInfo>, Error> {
         //let mut immediate_sends = 0;
         //let mut delayed_sends = 0;
         loop {
             let packet_data = self.udp.get_next_send_data();
             match self.connections[verified_index].get_next_send_packet(packet_data) {
-                Ok(SendResult::DataToSend((packet_len, to_addr, instant))) => {
+                Ok(SendResult::DataToSend((packet_len, to_addr, paced_instant))) => {
+                    let instant = if pacing {
+                        paced_instant
+                    } else {
+                        Instant::now()
+                    };
                     match self.udp.done_with_send_data(to_addr, packet_len, instant) {
                         Ok(true) => {
                             //immediate_sends += 1;
@@ -622,13 +758,16 @@ impl Endpoint {
         if let Some(duration) = self.config.keep_alive_timeout {
             let before_instant = Instant::now() - duration;
             for verified_index in 0..self.connections.len() {
+                // Ending connections (like an idle timeout) get handled by the event loop instead
+                if self.connections[verified_index].get_close_info().is_some() {
+                    continue;
+                }
                 match self.connections[verified_index].send_ping_if_before_instant(before_instant) {
                     Ok(false) => {}
                     Ok(true) => {
-                        if self.send(verified_index)?.is_some() {
-                            return Err(Error::UnexpectedClose(1));
+                        if self.send(verified_index)?.is_none() {
+                            num_pings += 1;
                         }
-                        num_pings += 1;
                     }
                     Err(_) => {
                         return Err(Error::ConnectionPing);
@@ -639,6 +778,11 @@ impl Endpoint {
         Ok(num_pings)
     }
 
+    // Non-blocking check for received data that skips the tick and timeout handling of get_next_event
+    pub(super) fn has_recv_data(&mut self) -> bool {
+        self.udp.sleep_till_recv_data(Duration::ZERO)
+    }
+
     pub(super) fn get_next_event(
         &mut self,
         next_tick_instant: Instant,
@@ -821,6 +965,23 @@ impl Endpoint {
                 //     Err(Error::UnexpectedClose)
                 // }
             }
+            Ok(StreamResult::AdhocStreamReadable((
+                stream_id,
+                profile,
+                data_vec,
+                len,
+                is_finished,
+            ))) => Ok(RecvEvent::StreamData((
+                connection_id,
+                stream_id,
+                profile,
+                data_vec,
+                len,
+                is_finished,
+            ))),
+            Ok(StreamResult::DatagramReadable(data)) => {
+                Ok(RecvEvent::DatagramReceived((connection_id, data)))
+            }
             Ok(StreamResult::Nothing) => Ok(RecvEvent::NoUpdate),
             Ok(StreamResult::MainStreamFinished) => {
                 if let Some(close_info) =
@@ -872,7 +1033,15 @@ impl Endpoint {
             Ok((recv_data, from_addr)) => {
                 // Only bother to look at a datagram that is less than or equal to the target
                 if recv_data.len() <= self.max_payload_size {
-                    if let Some((dcid, new_conn_possibility)) =
+                    if recv_data.starts_with(&STATELESS_MESSAGE_PREFIX) {
+                        // Kept separate from the connections and ignored unless accepted
+                        if self.accept_stateless_messages {
+                            let message = recv_data[STATELESS_MESSAGE_PREFIX.len()..].to_vec();
+                            Ok(RecvEvent::StatelessReceived((from_addr, message)))
+                        } else {
+                            Ok(RecvEvent::NoUpdate)
+                        }
+                    } else if let Some((dcid, new_conn_possibility)) =
                         Connection::recv_header_analyze(recv_data, self.is_server)
                     {
                         let mut verified_index_opt = match self.last_recv_index {
@@ -971,10 +1140,15 @@ impl Endpoint {
                                                 self.config.rt_recv_first_bytes,
                                                 background_recv_data,
                                                 self.config.background_recv_first_bytes,
+                                                self.config.rt_max_pending_segments,
                                             )
                                             .is_ok()
                                         {
                                             self.last_valid_index = verified_index;
+                                            // Stream data that came with the establishment is processed
+                                            // (and anything sent from connection_started gets flushed) next
+                                            self.stream_process_index =
+                                                Some((conn_id, verified_index));
                                             Ok(RecvEvent::EstablishedOnce(conn_id))
                                         } else {
                                             Err(Error::StreamCreation)
@@ -1058,19 +1232,46 @@ impl Endpoint {
         }
     }
 
-    /// Close a connection with a given error code value
+    /// Close every connection that is not already closing without an error (such as when shutting down)
+    ///
+    /// The CONNECTION_CLOSE frames are sent out right away (without pacing).
+    /// Returns the number of connections that started closing
+    pub fn close_all_connections(&mut self) -> Result<usize, Error> {
+        let mut num_closing = 0;
+        for verified_index in 0..self.connections.len() {
+            // An error means that the connection was already closing
+            if self.connections[verified_index]
+                .close(EndpointCloseReason::NoError as u64, b"shutdown")
+                .is_ok()
+            {
+                self.send_with_pacing(verified_index, false)?;
+                num_closing += 1;
+            }
+        }
+        match self.udp.send_check() {
+            Ok(_) => Ok(num_closing),
+            Err(_) => Err(Error::SocketSend),
+        }
+    }
+
+    /// Close a connection with a given application error code value
+    ///
+    /// This sends a QUIC CONNECTION_CLOSE frame with the application error code to the peer right away
+    /// and can be called at any time the Endpoint is available, such as from the tick callback to kick a client.
+    /// The connection_ending_warning and connection_ended callbacks are then called from the event loop
+    /// as usual with a ConnectionEndReason::LocalApplication(error_code) reason.
     ///
     /// Returns true when connection close process has started
+    /// and false when the connection was already closing
     pub fn close_connection(&mut self, cid: &ConnectionId, error_code: u64) -> Result<bool, Error> {
         if let Some(verified_index) = self.find_connection_from_cid(*cid) {
             match self.connections[verified_index].app_close(error_code, b"app-reason") {
                 Ok(_) => {
-                    if self.send(verified_index)?.is_some() {
-                        Err(Error::UnexpectedClose(7))
-                    } else {
-                        Ok(true)
-                    }
+                    // The send reports the connection as closing now which the event loop picks up
+                    self.send(verified_index)?;
+                    Ok(true)
                 }
+                Err(connection::Error::Done) => Ok(false),
                 Err(_) => Err(Error::ConnectionClose),
             }
         } else {
@@ -1089,6 +1290,17 @@ impl Endpoint {
         }
     }
 
+    /// Get the current health stats of a connection such as the round trip time and packet loss
+    ///
+    /// The values are snapshots that are cheap to get so they can be polled every tick for live graphs.
+    pub fn get_connection_stats(&self, cid: &ConnectionId) -> Result<ConnectionStats, Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            Ok(self.connections[verified_index].get_stats())
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
     /// Send data over the main stream. This data is queued up if it cannot be sent immediately.
     ///
     /// The main stream is a reliable (ordered) stream that focuses on communicating
@@ -1103,7 +1315,8 @@ impl Endpoint {
         if let Some(verified_index) = self.find_connection_from_cid(*cid) {
             match self.connections[verified_index].main_stream_send(send_data) {
                 Ok(_) => {
-                    if self.send(verified_index)?.is_some() {
+                    let pacing = !self.connections[verified_index].get_main_stream_no_delay();
+                    if self.send_with_pacing(verified_index, pacing)?.is_some() {
                         Err(Error::UnexpectedClose(8))
                     } else {
                         Ok(())
@@ -1116,6 +1329,104 @@ impl Endpoint {
         }
     }
 
+    /// Send the same data over the main stream of every application ready connection (see mark_ready)
+    /// that is not ending, such as a chat message from the server to all of the clients.
+    ///
+    /// Connections that can no longer be sent on are skipped and a connection that starts closing
+    /// from the send gets picked up by the event loop as usual.
+    /// Returns the number of connections that the data was queued on
+    pub fn main_stream_broadcast(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let mut num_sent = 0;
+        for verified_index in 0..self.connections.len() {
+            let conn = &mut self.connections[verified_index];
+            if !conn.is_app_ready() || conn.get_close_info().is_some() {
+                continue;
+            }
+            if conn.main_stream_send(data.to_vec()).is_ok() {
+                let pacing = !conn.get_main_stream_no_delay();
+                if self.send_with_pacing(verified_index, pacing)?.is_none() {
+                    num_sent += 1;
+                }
+            }
+        }
+        Ok(num_sent)
+    }
+
+    /// Set whether main stream data of a connection is sent out immediately.
+    ///
+    /// By default the packets that carry main stream data are paced out with the rest of the connection
+    /// data, which smooths out bursts but can delay small latency sensitive messages (like chat or control messages).
+    /// With no_delay set to true the packets created by a main_stream_send call skip the pacing delay.
+    /// Those packets can also contain data from other streams that was waiting to be sent.
+    pub fn set_main_stream_no_delay(
+        &mut self,
+        cid: &ConnectionId,
+        no_delay: bool,
+    ) -> Result<(), Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            self.connections[verified_index].set_main_stream_no_delay(no_delay);
+            Ok(())
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Mark a connection as application ready once the application's own handshake
+    /// (like a name exchange) is done on top of the QUIC handshake.
+    ///
+    /// Connections go through two phases:
+    /// 1. Transport established: the connection_started callback was called. All of the stream send
+    ///    functions are valid but the data should only be part of the application's handshake.
+    /// 2. Application ready: this function was called and is_ready returns true from then on.
+    ///    The connection_ready callback gets called once the event that the current callback is
+    ///    part of is fully handled (like the whole receive pass with all of its stream callbacks),
+    ///    so other callbacks of that event can still come first.
+    ///    Other subsystems (like audio or the UI) should only treat the peer as active in this phase.
+    ///
+    /// Marking a connection that is already ready does nothing.
+    pub fn mark_ready(&mut self, cid: &ConnectionId) -> Result<(), Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            if self.connections[verified_index].set_app_ready() {
+                self.ready_queue.push(*cid);
+            }
+            Ok(())
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Returns true if the connection was marked as application ready with mark_ready
+    ///
+    /// Unknown connections are never ready.
+    pub fn is_ready(&self, cid: &ConnectionId) -> bool {
+        match self.find_connection_from_cid(*cid) {
+            Some(verified_index) => self.connections[verified_index].is_app_ready(),
+            None => false,
+        }
+    }
+
+    pub(super) fn take_ready_connections(&mut self) -> Vec<ConnectionId> {
+        std::mem::take(&mut self.ready_queue)
+    }
+
+    pub(super) fn main_stream_reply(
+        &mut self,
+        verified_index: usize,
+        send_data: Vec<u8>,
+    ) -> Result<(), Error> {
+        match self.connections[verified_index].main_stream_send(send_data) {
+            Ok(_) => {
+                let pacing = !self.connections[verified_index].get_main_stream_no_delay();
+                if self.send_with_pacing(verified_index, pacing)?.is_some() {
+                    Err(Error::UnexpectedClose(13))
+                } else {
+                    Ok(())
+                }
+            }
+            Err(_) => Err(Error::StreamSend),
+        }
+    }
+
     pub(super) fn main_stream_read(
         &mut self,
         verified_index: usize,
@@ -1210,6 +1521,34 @@ impl Endpoint {
         }
     }
 
+    /// Send the same data over the real-time stream of every application ready connection (see mark_ready)
+    /// that is not ending, with the same time segment behavior as rt_stream_send
+    ///
+    /// Connections that can no longer be sent on are skipped and a connection that starts closing
+    /// from the send gets picked up by the event loop as usual.
+    /// Returns the number of connections that the data was queued on
+    pub fn rt_stream_broadcast(
+        &mut self,
+        data: Option<&[u8]>,
+        last_send_of_time_segment: bool,
+    ) -> Result<usize, Error> {
+        let mut num_sent = 0;
+        for verified_index in 0..self.connections.len() {
+            let conn = &mut self.connections[verified_index];
+            if !conn.is_app_ready() || conn.get_close_info().is_some() {
+                continue;
+            }
+            if conn
+                .rt_stream_send(data.map(|d| d.to_vec()), last_send_of_time_segment)
+                .is_ok()
+                && self.send(verified_index)?.is_none()
+            {
+                num_sent += 1;
+            }
+        }
+        Ok(num_sent)
+    }
+
     pub(super) fn rt_stream_read(
         &mut self,
         verified_index: usize,
@@ -1237,6 +1576,20 @@ impl Endpoint {
     /// The background stream is a reliable (ordered) stream that focuses on communicating
     /// large(ish) messages between the server and client such as a file transfer.
     ///
+    /// The data of each call arrives at the peer after all of the data from previous background stream send calls
+    /// (including data that is still in flight or queued) and the byte order within the data is kept.
+    /// There is no ordering between the background stream and the other streams: main stream and ad-hoc stream data
+    /// has a higher send priority and can arrive before background data that was sent earlier.
+    ///
+    /// This can be called at any time the Endpoint is available, such as from the tick callback to push a file
+    /// proactively. Large data can be split up over multiple calls (such as one chunk per tick) to limit how much
+    /// is queued at once. A server that sends before the client has opened the background stream
+    /// has the data queued until the first background stream data from the client arrives.
+    /// The data also stays queued while the background stream is paused with set_background_paused.
+    ///
+    /// Returns Error::ConnectionNotFound if there is no connection for the Connection ID and
+    /// Error::StreamSend if the background stream of the connection can no longer be sent on.
+    ///
     /// A reminder that the Endpoint connection will be taking ownership of the data so it can be sent out when possible
     pub fn background_stream_send(
         &mut self,
@@ -1259,6 +1612,52 @@ impl Endpoint {
         }
     }
 
+    /// Pause (or resume) giving queued background stream data of a connection to the connection
+    ///
+    /// While paused the background_stream_send data stays queued in the endpoint so the main,
+    /// real-time and ad-hoc streams get all of the bandwidth (such as during congestion in a voice call).
+    /// Data that was already given to the connection before pausing can still go out.
+    /// Resuming gives the queued data to the connection and sends it out right away (with pacing).
+    /// The connection keeps sending ACKs and keep alives while paused so it does not get dropped.
+    pub fn set_background_paused(&mut self, cid: &ConnectionId, paused: bool) -> Result<(), Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            match self.connections[verified_index].set_bkgd_send_paused(paused) {
+                Ok(_) => {
+                    if self.send(verified_index)?.is_some() {
+                        Err(Error::UnexpectedClose(15))
+                    } else {
+                        Ok(())
+                    }
+                }
+                Err(_) => Err(Error::StreamSend),
+            }
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Give as much of the queued background stream data to the connection as its flow control allows
+    /// and send out the resulting packets without the pacing delay
+    ///
+    /// Nothing new is given to the connection while the background stream is paused.
+    /// Returns the number of queued bytes that still have to wait for the connection (or for resuming).
+    pub fn background_stream_flush(&mut self, cid: &ConnectionId) -> Result<usize, Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            match self.connections[verified_index].bkgd_stream_flush() {
+                Ok(queued_len) => {
+                    if self.send_with_pacing(verified_index, false)?.is_some() {
+                        Err(Error::UnexpectedClose(16))
+                    } else {
+                        Ok(queued_len)
+                    }
+                }
+                Err(_) => Err(Error::StreamSend),
+            }
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
     pub(super) fn background_stream_read(
         &mut self,
         verified_index: usize,
@@ -1313,11 +1712,181 @@ impl Endpoint {
         }
     }
 
+    /// Send a QUIC DATAGRAM frame on a connection right away (without pacing)
+    ///
+    /// Datagrams are unreliable and unordered: they are never retransmitted and
+    /// can arrive in any order or not at all, which suits the smallest latency sensitive
+    /// control messages. The peer gets them with the datagram_recv callback.
+    /// Each datagram has to fit in a single packet, see get_max_datagram_len.
+    pub fn send_datagram(&mut self, cid: &ConnectionId, data: &[u8]) -> Result<(), Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            match self.connections[verified_index].datagram_send(data) {
+                Ok(_) => {
+                    if self.send_with_pacing(verified_index, false)?.is_some() {
+                        Err(Error::UnexpectedClose(17))
+                    } else {
+                        Ok(())
+                    }
+                }
+                Err(connection::Error::BufferTooShort) => Err(Error::DatagramTooLong),
+                Err(connection::Error::InvalidState) => Err(Error::DatagramNotSupported),
+                Err(_) => Err(Error::DatagramSend),
+            }
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Get the largest datagram that can currently be sent on a connection
+    ///
+    /// Returns None if the peer does not support datagrams or the handshake has not finished yet.
+    /// The length can change during the connection (such as when the path MTU changes).
+    pub fn get_max_datagram_len(&self, cid: &ConnectionId) -> Result<Option<usize>, Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            Ok(self.connections[verified_index].get_max_datagram_len())
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Open a new ad-hoc stream on a connection with the given profile and return its Stream ID.
+    ///
+    /// Ad-hoc streams are streams that either side can open at any time in addition to
+    /// the main and background streams. Their send priority is between the main stream and
+    /// the background stream (Realtime profile messages go before the other ad-hoc streams).
+    /// See StreamProfile for the delivery semantics of each profile.
+    /// The peer learns about the stream right away with an empty stream_recv call that has the profile.
+    ///
+    /// Stream IDs are never reused on a connection. A ReliableOrdered stream lives until both sides
+    /// have finished it: the local side with a call to stream_finish and the peer side when
+    /// the stream_recv callback is called with finished set to true.
+    /// The one-way profiles only need to be finished by the opening side.
+    /// After that its Stream ID is cleaned up and becomes invalid.
+    /// Each side can only have a limited number of ad-hoc streams open at the same time
+    /// (every message in flight of the one-way profiles counts) so streams should be finished
+    /// when they are no longer needed, including ReliableOrdered streams that the peer opened.
+    pub fn open_stream(
+        &mut self,
+        cid: &ConnectionId,
+        profile: StreamProfile,
+    ) -> Result<StreamId, Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            match self.connections[verified_index].adhoc_stream_open(profile) {
+                Ok(stream_id) => {
+                    if self.send(verified_index)?.is_some() {
+                        Err(Error::UnexpectedClose(14))
+                    } else {
+                        Ok(stream_id)
+                    }
+                }
+                Err(_) => Err(Error::StreamCreation),
+            }
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Send data over an ad-hoc stream. This data is queued up if it cannot be sent immediately.
+    ///
+    /// The Stream ID can come from open_stream or from a stream_recv callback for a ReliableOrdered
+    /// stream the peer opened. With the one-way profiles each call sends one message.
+    /// The data is copied into the send queue.
+    pub fn stream_send(
+        &mut self,
+        cid: &ConnectionId,
+        stream_id: StreamId,
+        send_data: &[u8],
+    ) -> Result<(), Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            match self.connections[verified_index].adhoc_stream_send(stream_id, send_data.to_vec())
+            {
+                Ok(_) => {
+                    if self.send(verified_index)?.is_some() {
+                        Err(Error::UnexpectedClose(11))
+                    } else {
+                        Ok(())
+                    }
+                }
+                Err(_) => Err(Error::StreamSend),
+            }
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
+    /// Finish the local side of an ad-hoc stream after all of its queued up data is sent.
+    ///
+    /// No more data can be sent on the stream afterwards.
+    pub fn stream_finish(&mut self, cid: &ConnectionId, stream_id: StreamId) -> Result<(), Error> {
+        if let Some(verified_index) = self.find_connection_from_cid(*cid) {
+            match self.connections[verified_index].adhoc_stream_finish(stream_id) {
+                Ok(_) => {
+                    if self.send(verified_index)?.is_some() {
+                        Err(Error::UnexpectedClose(12))
+                    } else {
+                        Ok(())
+                    }
+                }
+                Err(_) => Err(Error::StreamSend),
+            }
+        } else {
+            Err(Error::ConnectionNotFound)
+        }
+    }
+
     // pub(super) fn connection_send(&mut self, verified_index: usize) -> Result<(), Error> {
     //     self.send(verified_index)?;
     //     Ok(())
     // }
 
+    /// Set (or clear with None) the simulated network conditions of the outgoing packets
+    ///
+    /// Only intended for testing, see TestNetworkConditions.
+    /// Packets that are already delayed (from pacing or a previous simulation) keep their send instant.
+    #[cfg(feature = "network-simulation")]
+    pub fn set_test_network_conditions(&mut self, conditions: Option<TestNetworkConditions>) {
+        self.udp.set_test_network_conditions(conditions);
+    }
+
+    /// Set whether received stateless messages get passed to the stateless_recv callback (off by default)
+    ///
+    /// Stateless messages are small application datagrams sent on the same UDP socket without a connection,
+    /// like a client probing for servers and the servers replying with their name and version.
+    /// They are unauthenticated, unencrypted and unreliable: anyone can send one with a spoofed source
+    /// address, so never trust their contents and keep replies no larger than the probe to avoid
+    /// the endpoint being used to amplify traffic towards a spoofed address.
+    pub fn set_accept_stateless_messages(&mut self, accept: bool) {
+        self.accept_stateless_messages = accept;
+    }
+
+    /// Get the largest message length that stateless_send can send
+    pub fn get_max_stateless_message_len(&self) -> usize {
+        self.max_payload_size - STATELESS_MESSAGE_PREFIX.len()
+    }
+
+    /// Send a stateless message to an address without a connection, see set_accept_stateless_messages
+    ///
+    /// The message is sent once in a single datagram and can get lost,
+    /// the receiving endpoint only sees it with stateless messages accepted.
+    /// An IPv4 endpoint can send to a broadcast address (like 255.255.255.255) to reach every
+    /// endpoint on the local network, IPv6 has no broadcast so an IPv6 endpoint can not.
+    pub fn stateless_send(&mut self, to_addr: SocketAddr, message: &[u8]) -> Result<(), Error> {
+        if message.len() > self.get_max_stateless_message_len() {
+            return Err(Error::StatelessMessageTooLong);
+        }
+        let data_len = STATELESS_MESSAGE_PREFIX.len() + message.len();
+        let send_data = self.udp.get_next_send_data();
+        send_data[..STATELESS_MESSAGE_PREFIX.len()].copy_from_slice(&STATELESS_MESSAGE_PREFIX);
+        send_data[STATELESS_MESSAGE_PREFIX.len()..data_len].copy_from_slice(message);
+        match self
+            .udp
+            .done_with_send_data(to_addr, data_len, Instant::now())
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Error::SocketSend),
+        }
+    }
+
     /// Get Endpoint Stats
     pub fn get_stats(&self) -> &Stats {
         &self.stats
@@ -1328,3 +1897,382 @@ impl Endpoint {
         self.stats = Stats::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EndpointEventCallbacks, EndpointHandler, PollStatus};
+
+    const ALPN_NAME: &[u8] = b"swiftlet-test";
+    const CERT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/security/cert.pem");
+    const PKEY_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/security/pkey.pem");
+
+    fn get_test_config() -> Config {
+        Config {
+            idle_timeout_in_ms: 5000,
+            reliable_stream_buffer: 65536,
+            unreliable_stream_buffer: 65536,
+            keep_alive_timeout: None,
+            initial_main_recv_size: 1024,
+            main_recv_first_bytes: 1,
+            initial_rt_recv_size: 1024,
+            rt_recv_first_bytes: 0,
+            initial_background_recv_size: 1024,
+            background_recv_first_bytes: 1,
+            rt_max_pending_segments: 4,
+        }
+    }
+
+    // Creates a local server (on a free port) and a client with a connection to it
+    fn create_endpoints() -> (Endpoint, Endpoint) {
+        let server =
+            Endpoint::new_server(false, 0, ALPN_NAME, CERT_PATH, PKEY_PATH, get_test_config())
+                .unwrap();
+        let server_addr = SocketAddr::V4(std::net::SocketAddrV4::new(
+            std::net::Ipv4Addr::LOCALHOST,
+            server.local_addr.port(),
+        ));
+        let client = Endpoint::new_client_with_first_connection(
+            false,
+            ALPN_NAME,
+            CERT_PATH,
+            server_addr,
+            "localhost",
+            get_test_config(),
+        )
+        .unwrap();
+        (server, client)
+    }
+
+    // Polls both endpoints in turn until is_done returns true or a few seconds passed
+    fn poll_until<S: EndpointEventCallbacks, C: EndpointEventCallbacks>(
+        server: &mut Endpoint,
+        server_events: &mut S,
+        client: &mut Endpoint,
+        client_events: &mut C,
+        is_done: impl Fn(&S, &C) -> bool,
+    ) -> bool {
+        let end_instant = Instant::now() + Duration::from_secs(5);
+        while !is_done(server_events, client_events) {
+            if Instant::now() > end_instant {
+                return false;
+            }
+            let deadline = Instant::now() + Duration::from_millis(1);
+            EndpointHandler::new(server, server_events)
+                .poll(deadline)
+                .unwrap();
+            EndpointHandler::new(client, client_events)
+                .poll(deadline)
+                .unwrap();
+        }
+        true
+    }
+
+    // Sends its message on the main stream from connection_started and keeps the received bytes
+    struct WelcomeEvents {
+        message: &'static [u8],
+        received: Vec<u8>,
+    }
+
+    impl EndpointEventCallbacks for WelcomeEvents {
+        fn connection_started(&mut self, endpoint: &mut Endpoint, cid: &ConnectionId) {
+            endpoint
+                .main_stream_send(cid, self.message.to_vec())
+                .unwrap();
+        }
+
+        fn connection_ended(
+            &mut self,
+            _endpoint: &mut Endpoint,
+            _cid: &ConnectionId,
+            _reason: ConnectionEndReason,
+            _remaining_connections: usize,
+        ) -> bool {
+            false
+        }
+
+        fn tick(&mut self, _endpoint: &mut Endpoint) -> bool {
+            false
+        }
+
+        fn main_stream_recv(
+            &mut self,
+            _endpoint: &mut Endpoint,
+            _cid: &ConnectionId,
+            read_data: &[u8],
+        ) -> Option<usize> {
+            self.received.extend_from_slice(read_data);
+            Some(1)
+        }
+    }
+
+    #[test]
+    fn main_stream_send_in_connection_started_reaches_the_peer() {
+        let (mut server, mut client) = create_endpoints();
+        let mut server_events = WelcomeEvents {
+            message: b"welcome from the server",
+            received: Vec::new(),
+        };
+        let mut client_events = WelcomeEvents {
+            message: b"hello from the client",
+            received: Vec::new(),
+        };
+
+        // The server welcome waits for the client to open the main stream with its hello
+        let expected_server = b"hello from the client".as_slice();
+        let expected_client = b"welcome from the server".as_slice();
+        assert!(poll_until(
+            &mut server,
+            &mut server_events,
+            &mut client,
+            &mut client_events,
+            |s, c| s.received.len() >= expected_server.len()
+                && c.received.len() >= expected_client.len(),
+        ));
+        assert_eq!(server_events.received, expected_server);
+        assert_eq!(client_events.received, expected_client);
+    }
+
+    #[test]
+    fn poll_returns_handled_until_the_deadline() {
+        let (mut server, _client) = create_endpoints();
+        let mut server_events = WelcomeEvents {
+            message: b"",
+            received: Vec::new(),
+        };
+        let mut handler = EndpointHandler::new(&mut server, &mut server_events);
+        handler.set_tick_duration(Duration::from_secs(10));
+
+        // Tick "0" happens right away and then nothing else is due before the deadline
+        let deadline = Instant::now() + Duration::from_millis(20);
+        assert_eq!(handler.poll(deadline).unwrap(), PollStatus::Handled);
+        assert_eq!(handler.get_current_tick(), 1);
+        let mut num_polls = 1;
+        while handler.poll(deadline).unwrap() == PollStatus::Handled {
+            num_polls += 1;
+            assert!(num_polls < 100);
+        }
+        assert!(Instant::now() >= deadline);
+        assert_eq!(handler.get_current_tick(), 1);
+    }
+
+    const STREAM_MESSAGES: [&[u8]; 3] = [b"one", b"two", b"three"];
+
+    // The client opens a stream with the profile in connection_started and sends the messages
+    // before finishing it, every stream_recv call gets kept (on the server)
+    struct StreamEvents {
+        profile: Option<StreamProfile>,
+        opened_id: Option<StreamId>,
+        received: Vec<(StreamId, StreamProfile, Vec<u8>, bool)>,
+    }
+
+    impl StreamEvents {
+        fn new(profile: Option<StreamProfile>) -> Self {
+            StreamEvents {
+                profile,
+                opened_id: None,
+                received: Vec::new(),
+            }
+        }
+
+        fn has_finished(&self) -> bool {
+            self.received.iter().any(|(_, _, _, finished)| *finished)
+        }
+
+        fn get_messages(&self) -> Vec<&[u8]> {
+            self.received
+                .iter()
+                .filter(|(_, _, data, _)| !data.is_empty())
+                .map(|(_, _, data, _)| data.as_slice())
+                .collect()
+        }
+    }
+
+    impl EndpointEventCallbacks for StreamEvents {
+        fn connection_started(&mut self, endpoint: &mut Endpoint, cid: &ConnectionId) {
+            if let Some(profile) = self.profile {
+                let stream_id = endpoint.open_stream(cid, profile).unwrap();
+                for message in STREAM_MESSAGES {
+                    endpoint.stream_send(cid, stream_id, message).unwrap();
+                }
+                endpoint.stream_finish(cid, stream_id).unwrap();
+                self.opened_id = Some(stream_id);
+            }
+        }
+
+        fn connection_ended(
+            &mut self,
+            _endpoint: &mut Endpoint,
+            _cid: &ConnectionId,
+            _reason: ConnectionEndReason,
+            _remaining_connections: usize,
+        ) -> bool {
+            false
+        }
+
+        fn tick(&mut self, _endpoint: &mut Endpoint) -> bool {
+            false
+        }
+
+        fn main_stream_recv(
+            &mut self,
+            _endpoint: &mut Endpoint,
+            _cid: &ConnectionId,
+            _read_data: &[u8],
+        ) -> Option<usize> {
+            Some(1)
+        }
+
+        fn stream_recv(
+            &mut self,
+            _endpoint: &mut Endpoint,
+            _cid: &ConnectionId,
+            stream_id: StreamId,
+            profile: StreamProfile,
+            read_data: &[u8],
+            finished: bool,
+        ) {
+            self.received
+                .push((stream_id, profile, read_data.to_vec(), finished));
+        }
+    }
+
+    // Returns the server side events of a client stream with the profile once is_done returns true
+    fn get_stream_recv_events(
+        profile: StreamProfile,
+        is_done: fn(&StreamEvents) -> bool,
+    ) -> StreamEvents {
+        let (mut server, mut client) = create_endpoints();
+        let mut server_events = StreamEvents::new(None);
+        let mut client_events = StreamEvents::new(Some(profile));
+        assert!(poll_until(
+            &mut server,
+            &mut server_events,
+            &mut client,
+            &mut client_events,
+            |s, _| is_done(s),
+        ));
+        let stream_id = client_events.opened_id.unwrap();
+        for (recv_id, recv_profile, _, _) in &server_events.received {
+            // The peer sees the same Stream ID and profile on every call
+            assert_eq!((*recv_id, *recv_profile), (stream_id, profile));
+        }
+        server_events
+    }
+
+    #[test]
+    fn reliable_ordered_stream_data_arrives_in_order() {
+        let server_events =
+            get_stream_recv_events(StreamProfile::ReliableOrdered, |s| s.has_finished());
+        let data: Vec<u8> = server_events
+            .received
+            .iter()
+            .flat_map(|(_, _, data, _)| data.clone())
+            .collect();
+        assert_eq!(data, b"onetwothree");
+        assert!(server_events.received.last().unwrap().3);
+    }
+
+    #[test]
+    fn reliable_unordered_stream_delivers_every_message_whole() {
+        // Messages can still arrive after the finishing one
+        let server_events = get_stream_recv_events(StreamProfile::ReliableUnordered, |s| {
+            s.has_finished() && (s.get_messages().len() == STREAM_MESSAGES.len())
+        });
+        let mut messages = server_events.get_messages();
+        messages.sort();
+        let mut expected = STREAM_MESSAGES.to_vec();
+        expected.sort();
+        assert_eq!(messages, expected);
+    }
+
+    #[test]
+    fn realtime_stream_never_delivers_an_older_message_after_a_newer_one() {
+        let server_events = get_stream_recv_events(StreamProfile::Realtime, |s| s.has_finished());
+        let message_indicies: Vec<usize> = server_events
+            .get_messages()
+            .iter()
+            .map(|message| {
+                STREAM_MESSAGES
+                    .iter()
+                    .position(|sent| sent == message)
+                    .unwrap()
+            })
+            .collect();
+        assert!(message_indicies.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    // Server side of an application handshake where the client name on the main stream marks
+    // the connection as ready, every lifecycle step gets recorded in order
+    struct ReadinessEvents {
+        name: Vec<u8>,
+        steps: Vec<&'static str>,
+    }
+
+    impl EndpointEventCallbacks for ReadinessEvents {
+        fn connection_started(&mut self, endpoint: &mut Endpoint, cid: &ConnectionId) {
+            assert!(!endpoint.is_ready(cid));
+            // Broadcasts only go to application ready connections
+            assert_eq!(endpoint.main_stream_broadcast(b"too early").unwrap(), 0);
+            self.steps.push("started");
+        }
+
+        fn connection_ready(&mut self, endpoint: &mut Endpoint, cid: &ConnectionId) {
+            assert!(endpoint.is_ready(cid));
+            assert_eq!(endpoint.main_stream_broadcast(b"welcome").unwrap(), 1);
+            self.steps.push("ready");
+        }
+
+        fn connection_ended(
+            &mut self,
+            _endpoint: &mut Endpoint,
+            _cid: &ConnectionId,
+            _reason: ConnectionEndReason,
+            _remaining_connections: usize,
+        ) -> bool {
+            false
+        }
+
+        fn tick(&mut self, _endpoint: &mut Endpoint) -> bool {
+            false
+        }
+
+        fn main_stream_recv(
+            &mut self,
+            endpoint: &mut Endpoint,
+            cid: &ConnectionId,
+            read_data: &[u8],
+        ) -> Option<usize> {
+            self.name.extend_from_slice(read_data);
+            if self.name == b"client name" {
+                endpoint.mark_ready(cid).unwrap();
+                // The connection_ready callback comes after this receive pass
+                self.steps.push("name received");
+                endpoint.mark_ready(cid).unwrap();
+            }
+            Some(1)
+        }
+    }
+
+    #[test]
+    fn connection_is_ready_after_the_application_handshake() {
+        let (mut server, mut client) = create_endpoints();
+        let mut server_events = ReadinessEvents {
+            name: Vec::new(),
+            steps: Vec::new(),
+        };
+        let mut client_events = WelcomeEvents {
+            message: b"client name",
+            received: Vec::new(),
+        };
+        assert!(poll_until(
+            &mut server,
+            &mut server_events,
+            &mut client,
+            &mut client_events,
+            |_, c| c.received == b"welcome",
+        ));
+        // Marking an already ready connection again does not call connection_ready again
+        assert_eq!(server_events.steps, ["started", "name received", "ready"]);
+    }
+}
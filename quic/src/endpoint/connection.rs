@@ -20,9 +20,11 @@
 //OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 //SOFTWARE.
 
+use crate::endpoint::ConnectionStats;
 use crate::endpoint::SocketAddr;
+use crate::endpoint::StreamProfile;
 use std::collections::VecDeque;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub(super) use quiche::Config;
 pub(super) use quiche::Error;
@@ -42,6 +44,69 @@ const CLIENT_REALTIME_START_ID: u64 = 2;
 const BACKGROUND_STREAM_ID: u64 = 4;
 const BACKGROUND_STREAM_PRIORITY: u8 = 200;
 
+// Ad-hoc Bidirectional Stream ID# start constants used for reliable application opened streams
+// Client started ad-hoc stream ids begin after the main and background stream ids
+// These streams have a send priority between the main and background streams
+const SERVER_ADHOC_START_ID: u64 = 1;
+const CLIENT_ADHOC_START_ID: u64 = 8;
+const ADHOC_STREAM_PRIORITY: u8 = 150;
+const REALTIME_ADHOC_STREAM_PRIORITY: u8 = 120; // Realtime profile messages go before the other ad-hoc streams
+const ADHOC_RECV_CHUNK_SIZE: usize = 65536;
+// Number of concurrent ad-hoc streams (including message streams) each side can open
+// Every unordered or realtime message takes a stream until its finish is acknowledged, so this leaves
+// room for a message every 10ms over a few hundred ms round trip time on top of the other streams
+const ADHOC_STREAM_LIMIT: u64 = 64;
+// Realtime streams opened by the peer that are remembered to drop their late messages,
+// bounded so the lookups stay short and a peer that opens streams forever cannot grow it
+const ADHOC_REALTIME_RECV_LIMIT: usize = ADHOC_STREAM_LIMIT as usize;
+// Largest unordered or realtime message that gets collected before the stream is stopped,
+// protects against a peer that never sends the finish of a message
+const ADHOC_MAX_MESSAGE_SIZE: usize = 1 << 22;
+
+// Every ad-hoc stream starts with a header from the opening side:
+// the profile byte (with the finish flag for the last message of a logical stream) and the logical stream id
+// The unordered and realtime profiles open a separate stream for each message with the same logical stream id
+const ADHOC_HEADER_LEN: usize = 9;
+const ADHOC_HEADER_FINISH_FLAG: u8 = 0x80;
+
+// Number of DATAGRAM frames that can wait in each of the send and receive queues
+// Based on 1 second of 10ms datagrams (same as the real-time streams)
+const DATAGRAM_QUEUE_LEN: usize = 100;
+
+fn create_adhoc_header(profile: StreamProfile, logical_id: u64, finishes_logical: bool) -> Vec<u8> {
+    let mut header = Vec::with_capacity(ADHOC_HEADER_LEN);
+    let profile_byte = match profile {
+        StreamProfile::ReliableOrdered => 0,
+        StreamProfile::ReliableUnordered => 1,
+        StreamProfile::Realtime => 2,
+    };
+    if finishes_logical {
+        header.push(profile_byte | ADHOC_HEADER_FINISH_FLAG);
+    } else {
+        header.push(profile_byte);
+    }
+    header.extend_from_slice(&logical_id.to_be_bytes());
+    header
+}
+
+// Returns the (profile, logical_id, finishes_logical) of an ad-hoc header or None for an unknown profile
+fn parse_adhoc_header(header: &[u8; ADHOC_HEADER_LEN]) -> Option<(StreamProfile, u64, bool)> {
+    let profile = match header[0] & !ADHOC_HEADER_FINISH_FLAG {
+        0 => StreamProfile::ReliableOrdered,
+        1 => StreamProfile::ReliableUnordered,
+        2 => StreamProfile::Realtime,
+        _ => return None,
+    };
+    let finishes_logical = (header[0] & ADHOC_HEADER_FINISH_FLAG) != 0;
+    let mut logical_id_bytes = [0; 8];
+    logical_id_bytes.copy_from_slice(&header[1..]);
+    Some((
+        profile,
+        u64::from_be_bytes(logical_id_bytes),
+        finishes_logical,
+    ))
+}
+
 struct StreamRecv {
     captured: usize,
     target: usize,
@@ -69,6 +134,67 @@ impl SendBuffer {
     }
 }
 
+struct AdhocStream {
+    id: u64,
+    logical_id: u64, // Stream ID used by the application (the same as the id for the ordered profile)
+    profile: StreamProfile,
+    is_local_message: bool, // Message stream opened by this side where only the peer finish is received
+    send_queue: VecDeque<SendBuffer>,
+    send_finished: bool, // True once the application finished the local side
+    fin_sent: bool,
+    recv_finished: bool,
+    header: [u8; ADHOC_HEADER_LEN],
+    header_len: usize, // Header bytes received so far (the full length for locally opened streams)
+    finishes_logical: bool,
+    is_outdated: bool, // Realtime message that a newer message of the same logical stream replaced
+    message: Vec<u8>,  // Received message data of the unordered and realtime profiles
+}
+
+impl AdhocStream {
+    // The profile of a stream opened by the peer is known once its header is received
+    fn new(id: u64) -> Self {
+        AdhocStream {
+            id,
+            logical_id: id,
+            profile: StreamProfile::ReliableOrdered,
+            is_local_message: false,
+            send_queue: VecDeque::with_capacity(4),
+            send_finished: false,
+            fin_sent: false,
+            recv_finished: false,
+            header: [0; ADHOC_HEADER_LEN],
+            header_len: 0,
+            finishes_logical: false,
+            is_outdated: false,
+            message: Vec::new(),
+        }
+    }
+
+    // Outdated realtime messages get stopped and finished right away
+    fn abandon_recv(&mut self, connection: &mut quiche::Connection) -> Result<(), Error> {
+        match connection.stream_shutdown(self.id, quiche::Shutdown::Read, 0) {
+            Ok(_) => {}
+            Err(Error::Done) => {}
+            Err(e) => return Err(e),
+        }
+        self.is_outdated = true;
+        self.recv_finished = true;
+        self.message = Vec::new();
+        if !self.send_finished {
+            self.send_queue.push_back(SendBuffer::new(Vec::new()));
+            self.send_finished = true;
+        }
+        Ok(())
+    }
+}
+
+// Newest message stream of a realtime stream opened by the peer
+struct AdhocRealtimeRecv {
+    logical_id: u64,
+    newest_id: u64,
+    is_finished: bool, // The finishing message arrived so only late messages can still come
+}
+
 struct RealtimeRecv {
     id: u64,
     captured: usize,
@@ -105,14 +231,26 @@ pub(super) struct Connection {
     last_send_instant: Instant, // Used for sending PING / ACK_Elicting if it's been a while
     next_timeout_instant: Option<Instant>,
     established_once: bool,
+    app_ready: bool, // Set by the application once its own handshake finished
     main_recv: StreamRecv,
     main_send_queue: VecDeque<SendBuffer>,
+    main_no_delay: bool,
     rt_recv: RealtimeRecv,
     rt_send_queue: VecDeque<SendBuffer>,
     rt_send_finished: bool,
     rt_send_stream_id: u64,
+    rt_send_pending: VecDeque<u64>, // Finished segment streams that can still have data in flight (oldest first)
+    rt_max_pending_segments: usize,
+    rt_segment_drops: u64,
     bkgd_recv: StreamRecv,
     bkgd_send_queue: VecDeque<SendBuffer>,
+    bkgd_send_paused: bool, // Queued background data is held back from the connection while paused
+    adhoc_streams: Vec<AdhocStream>,
+    adhoc_send_stream_id: u64, // Next ad-hoc stream id to open (never reused)
+    adhoc_message_streams: Vec<(u64, StreamProfile)>, // Unfinished unordered and realtime streams opened by this side
+    adhoc_realtime_recv: Vec<AdhocRealtimeRecv>, // Realtime streams opened by the peer (kept after the finish so late messages get dropped)
+    adhoc_realtime_recv_floor: u64, // Untracked realtime streams with a lower logical id were forgotten so their messages get dropped
+    adhoc_recv_data: Vec<u8>,       // Reused receive buffer of the ad-hoc streams
 }
 
 pub(super) enum CloseOrigin {
@@ -151,8 +289,10 @@ pub(super) enum StreamResult {
     MainStreamReadable((Vec<u8>, usize)),
     RealtimeStreamReadable((Vec<u8>, usize, u64)),
     BkgdStreamReadable((Vec<u8>, usize)),
+    AdhocStreamReadable((u64, StreamProfile, Vec<u8>, usize, bool)),
     MainStreamFinished,
     BkgdStreamFinished,
+    DatagramReadable(Vec<u8>),
 }
 
 impl Connection {
@@ -177,14 +317,14 @@ impl Connection {
             config.load_priv_key_from_pem_file(pkey_path)?;
             config.verify_peer(false);
 
-            config.set_initial_max_streams_bidi(2); // 1 For Main Communication, 2 and 3 for Unordered Alt Data (like file transfers)
+            config.set_initial_max_streams_bidi(2 + ADHOC_STREAM_LIMIT); // Main and Background streams plus the client ad-hoc streams
         } else {
             // Temporary solution for client to verify certificate
             // Maybe not return error immediately here?
             config.load_verify_locations_from_file(cert_path)?;
             config.verify_peer(true);
 
-            config.set_initial_max_streams_bidi(0);
+            config.set_initial_max_streams_bidi(ADHOC_STREAM_LIMIT); // Server ad-hoc streams
         }
 
         // Enable the ability to log the secret keys for wireshark debugging
@@ -210,6 +350,7 @@ impl Connection {
         config.set_disable_active_migration(true); // Temporary
 
         // Enable datagram frames for unreliable data to be sent
+        config.enable_dgram(true, DATAGRAM_QUEUE_LEN, DATAGRAM_QUEUE_LEN);
 
         Ok(config)
     }
@@ -276,14 +417,26 @@ impl Connection {
                 last_send_instant: Instant::now(),
                 next_timeout_instant: None,
                 established_once: false,
+                app_ready: false,
                 main_recv: StreamRecv::empty(),
                 main_send_queue: VecDeque::with_capacity(4),
+                main_no_delay: false,
                 rt_recv: RealtimeRecv::empty(false),
                 rt_send_queue: VecDeque::with_capacity(4),
                 rt_send_finished: false,
                 rt_send_stream_id: CLIENT_REALTIME_START_ID,
+                rt_send_pending: VecDeque::new(),
+                rt_max_pending_segments: 1,
+                rt_segment_drops: 0,
                 bkgd_recv: StreamRecv::empty(),
                 bkgd_send_queue: VecDeque::with_capacity(4),
+                bkgd_send_paused: false,
+                adhoc_streams: Vec::new(),
+                adhoc_send_stream_id: CLIENT_ADHOC_START_ID,
+                adhoc_message_streams: Vec::new(),
+                adhoc_realtime_recv: Vec::new(),
+                adhoc_realtime_recv_floor: 0,
+                adhoc_recv_data: vec![0; ADHOC_RECV_CHUNK_SIZE],
             };
 
             Ok(conn_mgr)
@@ -311,14 +464,26 @@ impl Connection {
                 last_send_instant: Instant::now(),
                 next_timeout_instant: None,
                 established_once: false,
+                app_ready: false,
                 main_recv: StreamRecv::empty(),
                 main_send_queue: VecDeque::with_capacity(4),
+                main_no_delay: false,
                 rt_recv: RealtimeRecv::empty(true),
                 rt_send_queue: VecDeque::with_capacity(4),
                 rt_send_finished: false,
                 rt_send_stream_id: SERVER_REALTIME_START_ID,
+                rt_send_pending: VecDeque::new(),
+                rt_max_pending_segments: 1,
+                rt_segment_drops: 0,
                 bkgd_recv: StreamRecv::empty(),
                 bkgd_send_queue: VecDeque::with_capacity(4),
+                bkgd_send_paused: false,
+                adhoc_streams: Vec::new(),
+                adhoc_send_stream_id: SERVER_ADHOC_START_ID,
+                adhoc_message_streams: Vec::new(),
+                adhoc_realtime_recv: Vec::new(),
+                adhoc_realtime_recv_floor: 0,
+                adhoc_recv_data: vec![0; ADHOC_RECV_CHUNK_SIZE],
             };
 
             Ok(conn_mgr)
@@ -479,6 +644,11 @@ impl Connection {
                     Err(Error::Done) => {
                         return Ok(total_bytes_sent);
                     }
+                    Err(Error::InvalidStreamState(_)) if self.connection.is_server() => {
+                        // The client has not opened this stream yet so the data stays queued
+                        // until the first stream data from the client arrives
+                        return Ok(total_bytes_sent);
+                    }
                     Err(e) => {
                         return Err(e);
                     }
@@ -506,8 +676,10 @@ impl Connection {
                         if send_buf.sent >= send_buf.data.len() {
                             self.rt_send_queue.pop_front();
                             if fin {
+                                self.rt_send_pending.push_back(self.rt_send_stream_id);
                                 self.rt_send_stream_id += 4;
                                 self.rt_send_finished = false;
+                                self.rt_limit_pending_segments()?;
                             }
                         } else {
                             return Ok(total_bytes_sent);
@@ -526,8 +698,32 @@ impl Connection {
         }
     }
 
+    // Oldest segments get dropped first since a newer time segment always replaces an older one
+    fn rt_limit_pending_segments(&mut self) -> Result<(), Error> {
+        // Completed streams (all data acknowledged) no longer exist in the connection
+        let connection = &self.connection;
+        self.rt_send_pending
+            .retain(|stream_id| connection.stream_capacity(*stream_id).is_ok());
+        while self.rt_send_pending.len() > self.rt_max_pending_segments {
+            if let Some(stream_id) = self.rt_send_pending.pop_front() {
+                match self
+                    .connection
+                    .stream_shutdown(stream_id, quiche::Shutdown::Write, stream_id)
+                {
+                    Ok(_) => self.rt_segment_drops += 1,
+                    Err(Error::Done) => {} // Completed in the meantime
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn bkgd_stream_send_next(&mut self) -> Result<usize, Error> {
         let mut total_bytes_sent = 0;
+        if self.bkgd_send_paused {
+            return Ok(total_bytes_sent);
+        }
         loop {
             if let Some(send_buf) = self.bkgd_send_queue.front_mut() {
                 match self.connection.stream_send(
@@ -547,6 +743,11 @@ impl Connection {
                     Err(Error::Done) => {
                         return Ok(total_bytes_sent);
                     }
+                    Err(Error::InvalidStreamState(_)) if self.connection.is_server() => {
+                        // The client has not opened this stream yet so the data stays queued
+                        // until the first stream data from the client arrives
+                        return Ok(total_bytes_sent);
+                    }
                     Err(e) => {
                         return Err(e);
                     }
@@ -557,6 +758,58 @@ impl Connection {
         }
     }
 
+    fn adhoc_streams_send_next(&mut self) -> Result<usize, Error> {
+        let mut total_bytes_sent = 0;
+        for adhoc_stream in &mut self.adhoc_streams {
+            loop {
+                // The finish is sent with the (possibly empty) last send buffer
+                let fin = adhoc_stream.send_finished && (adhoc_stream.send_queue.len() == 1);
+                if let Some(send_buf) = adhoc_stream.send_queue.front_mut() {
+                    match self.connection.stream_send(
+                        adhoc_stream.id,
+                        &send_buf.data[send_buf.sent..],
+                        fin,
+                    ) {
+                        Ok(bytes_sent) => {
+                            total_bytes_sent += bytes_sent;
+                            send_buf.sent += bytes_sent;
+                            if send_buf.sent >= send_buf.data.len() {
+                                adhoc_stream.send_queue.pop_front();
+                                if fin {
+                                    adhoc_stream.fin_sent = true;
+                                }
+                            } else {
+                                break;
+                            }
+                        }
+                        Err(Error::Done) => {
+                            break;
+                        }
+                        Err(Error::StreamStopped(_)) => {
+                            // Peer no longer wants anything on this stream
+                            adhoc_stream.send_queue.clear();
+                            adhoc_stream.fin_sent = true;
+                            break;
+                        }
+                        Err(e) => {
+                            return Err(e);
+                        }
+                    }
+                } else {
+                    break;
+                }
+            }
+        }
+        self.adhoc_streams_cleanup();
+        Ok(total_bytes_sent)
+    }
+
+    // Ad-hoc streams are forgotten once both sides have finished
+    fn adhoc_streams_cleanup(&mut self) {
+        self.adhoc_streams
+            .retain(|adhoc_stream| !(adhoc_stream.fin_sent && adhoc_stream.recv_finished));
+    }
+
     pub(super) fn recv_data(
         &mut self,
         data: &mut [u8],
@@ -586,6 +839,7 @@ impl Connection {
             self.main_stream_send_next()?;
             self.rt_stream_send_next()?;
             self.bkgd_stream_send_next()?;
+            self.adhoc_streams_send_next()?;
 
             Ok(RecvResult::StreamProcess(self.id))
         } else if self.connection.is_established() {
@@ -595,6 +849,7 @@ impl Connection {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn finish_establishment(
         &mut self,
         main_recv_data: Vec<u8>,
@@ -603,6 +858,7 @@ impl Connection {
         rt_recv_bytes_initial: usize,
         background_recv_data: Vec<u8>,
         background_recv_bytes: usize,
+        rt_max_pending_segments: usize,
     ) -> Result<(), Error> {
         // Create streams depending on connection type:
         if !self.connection.is_server() {
@@ -622,6 +878,7 @@ impl Connection {
         self.rt_recv.data = Some(rt_recv_data);
         self.bkgd_recv.target = background_recv_bytes;
         self.bkgd_recv.data = Some(background_recv_data);
+        self.rt_max_pending_segments = rt_max_pending_segments.max(1);
 
         self.established_once = true;
         Ok(())
@@ -629,6 +886,12 @@ impl Connection {
 
     // A returned Error::InvalidState indicates something went wrong with the read process
     pub(super) fn stream_process(&mut self) -> Result<StreamResult, Error> {
+        // Datagrams go first since they are the most latency sensitive
+        match self.connection.dgram_recv_vec() {
+            Ok(data) => return Ok(StreamResult::DatagramReadable(data)),
+            Err(Error::Done) => {}
+            Err(e) => return Err(e),
+        }
         if let Some(next_readable_stream) = self.connection.stream_readable_next() {
             if next_readable_stream == MAIN_STREAM_ID {
                 if let Some(mut recv_data) = self.main_recv.data.take() {
@@ -682,6 +945,9 @@ impl Connection {
                 } else {
                     Err(Error::InvalidStreamState(13))
                 }
+            } else if next_readable_stream & 0x2 == 0 {
+                // Any other bidirectional stream is an ad-hoc stream
+                self.stream_process_adhoc(next_readable_stream)
             } else if !self.connection.stream_finished(next_readable_stream) {
                 if let Some(recv_data) = self.rt_recv.data.take() {
                     self.stream_process_realtime(next_readable_stream, recv_data)
@@ -707,6 +973,198 @@ impl Connection {
         }
     }
 
+    fn stream_process_adhoc(&mut self, stream_id: u64) -> Result<StreamResult, Error> {
+        let index = match self
+            .adhoc_streams
+            .iter()
+            .position(|adhoc_stream| adhoc_stream.id == stream_id)
+        {
+            Some(index) => index,
+            None => {
+                // First data on a stream that the peer opened
+                self.connection
+                    .stream_priority(stream_id, ADHOC_STREAM_PRIORITY, true)?;
+                self.adhoc_streams.push(AdhocStream::new(stream_id));
+                self.adhoc_streams.len() - 1
+            }
+        };
+
+        let (bytes_read, is_finished, is_reset) = match self
+            .connection
+            .stream_recv(stream_id, &mut self.adhoc_recv_data)
+        {
+            Ok((bytes_read, is_finished)) => (bytes_read, is_finished, false),
+            // A reset is treated like a finish without any more data
+            Err(Error::StreamReset(_)) => (0, true, true),
+            Err(Error::Done) => return Ok(StreamResult::Nothing),
+            Err(e) => return Err(e),
+        };
+
+        if self.adhoc_streams[index].is_local_message {
+            // Only the finish of the peer side arrives on the message streams opened by this side
+            if is_finished {
+                self.adhoc_streams[index].recv_finished = true;
+                self.adhoc_streams_cleanup();
+            }
+            return Ok(StreamResult::Nothing);
+        }
+
+        // The header comes first on the streams that the peer opened
+        let mut data_start = 0;
+        let adhoc_stream = &mut self.adhoc_streams[index];
+        if adhoc_stream.header_len < ADHOC_HEADER_LEN {
+            data_start = (ADHOC_HEADER_LEN - adhoc_stream.header_len).min(bytes_read);
+            let header_end = adhoc_stream.header_len + data_start;
+            adhoc_stream.header[adhoc_stream.header_len..header_end]
+                .copy_from_slice(&self.adhoc_recv_data[..data_start]);
+            adhoc_stream.header_len = header_end;
+            if adhoc_stream.header_len == ADHOC_HEADER_LEN {
+                self.adhoc_stream_parse_header(index)?;
+            } else {
+                if is_finished {
+                    // A stream that ends before its header is complete never reached the application
+                    self.adhoc_streams[index].recv_finished = true;
+                    self.adhoc_streams[index].fin_sent = true;
+                    self.adhoc_streams_cleanup();
+                }
+                return Ok(StreamResult::Nothing);
+            }
+        }
+
+        let adhoc_stream = &mut self.adhoc_streams[index];
+        if adhoc_stream.profile == StreamProfile::ReliableOrdered {
+            let recv_data = self.adhoc_recv_data[data_start..bytes_read].to_vec();
+            let len = recv_data.len();
+            if is_finished {
+                adhoc_stream.recv_finished = true;
+                self.adhoc_streams_cleanup();
+            }
+            return Ok(StreamResult::AdhocStreamReadable((
+                stream_id,
+                StreamProfile::ReliableOrdered,
+                recv_data,
+                len,
+                is_finished,
+            )));
+        }
+
+        if !adhoc_stream.is_outdated {
+            if adhoc_stream.message.len() + (bytes_read - data_start) > ADHOC_MAX_MESSAGE_SIZE {
+                // The message is dropped and the peer gets told to stop sending it
+                adhoc_stream.abandon_recv(&mut self.connection)?;
+                self.adhoc_streams_send_next()?;
+                return Ok(StreamResult::Nothing);
+            }
+            adhoc_stream
+                .message
+                .extend_from_slice(&self.adhoc_recv_data[data_start..bytes_read]);
+        }
+        if !is_finished {
+            return Ok(StreamResult::Nothing);
+        }
+        adhoc_stream.recv_finished = true;
+        if !adhoc_stream.send_finished {
+            // The peer opened this stream only for the message so the local side gets finished too
+            adhoc_stream
+                .send_queue
+                .push_back(SendBuffer::new(Vec::new()));
+            adhoc_stream.send_finished = true;
+        }
+        let message = std::mem::take(&mut adhoc_stream.message);
+        let logical_id = adhoc_stream.logical_id;
+        let profile = adhoc_stream.profile;
+        let finishes_logical = adhoc_stream.finishes_logical;
+        let is_delivered = !(is_reset || adhoc_stream.is_outdated);
+        self.adhoc_streams_send_next()?;
+
+        if is_delivered {
+            let len = message.len();
+            Ok(StreamResult::AdhocStreamReadable((
+                logical_id,
+                profile,
+                message,
+                len,
+                finishes_logical,
+            )))
+        } else {
+            Ok(StreamResult::Nothing)
+        }
+    }
+
+    fn adhoc_stream_parse_header(&mut self, index: usize) -> Result<(), Error> {
+        let adhoc_stream = &mut self.adhoc_streams[index];
+        let (profile, logical_id, finishes_logical) = match parse_adhoc_header(&adhoc_stream.header)
+        {
+            Some(parsed) => parsed,
+            None => return Err(Error::InvalidStreamState(adhoc_stream.id)),
+        };
+        adhoc_stream.profile = profile;
+        adhoc_stream.finishes_logical = finishes_logical;
+        // The logical id of an ordered stream is always its own id
+        if profile != StreamProfile::ReliableOrdered {
+            adhoc_stream.logical_id = logical_id;
+        }
+        if profile != StreamProfile::Realtime {
+            return Ok(());
+        }
+
+        // Only the newest message of a realtime stream matters so older ones get stopped
+        let id = adhoc_stream.id;
+        match self
+            .adhoc_realtime_recv
+            .iter_mut()
+            .find(|realtime_recv| realtime_recv.logical_id == logical_id)
+        {
+            Some(realtime_recv) if realtime_recv.newest_id > id => {
+                self.adhoc_streams[index].abandon_recv(&mut self.connection)?;
+            }
+            Some(realtime_recv) => {
+                realtime_recv.newest_id = id;
+                realtime_recv.is_finished = finishes_logical;
+                for older_stream in &mut self.adhoc_streams {
+                    if (older_stream.logical_id == logical_id)
+                        && (older_stream.id < id)
+                        && !older_stream.is_local_message
+                        && !older_stream.recv_finished
+                    {
+                        older_stream.abandon_recv(&mut self.connection)?;
+                    }
+                }
+            }
+            None if logical_id < self.adhoc_realtime_recv_floor => {
+                // A late message of a forgotten realtime stream
+                self.adhoc_streams[index].abandon_recv(&mut self.connection)?;
+            }
+            None => {
+                if self.adhoc_realtime_recv.len() >= ADHOC_REALTIME_RECV_LIMIT {
+                    self.adhoc_realtime_recv_forget_one();
+                }
+                self.adhoc_realtime_recv.push(AdhocRealtimeRecv {
+                    logical_id,
+                    newest_id: id,
+                    is_finished: finishes_logical,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Forgets a finished realtime stream of the peer (or the one with the oldest newest message)
+    // Logical ids only increase with every opened stream so the untracked ones below the
+    // forgotten logical id are all done and any more of their messages get dropped
+    fn adhoc_realtime_recv_forget_one(&mut self) {
+        if let Some((position, _)) = self
+            .adhoc_realtime_recv
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, realtime_recv)| (!realtime_recv.is_finished, realtime_recv.newest_id))
+        {
+            let forgotten = self.adhoc_realtime_recv.swap_remove(position);
+            self.adhoc_realtime_recv_floor =
+                self.adhoc_realtime_recv_floor.max(forgotten.logical_id + 1);
+        }
+    }
+
     fn stream_process_realtime(
         &mut self,
         next_readable_stream: u64,
@@ -867,6 +1325,65 @@ impl Connection {
         self.recv_info.from
     }
 
+    #[inline]
+    pub(super) fn datagram_send(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.connection.dgram_send(data)
+    }
+
+    // None until the peer has shown datagram support during the handshake
+    #[inline]
+    pub(super) fn get_max_datagram_len(&self) -> Option<usize> {
+        self.connection.dgram_max_writable_len()
+    }
+
+    pub(super) fn get_stats(&self) -> ConnectionStats {
+        let stats = self.connection.stats();
+        let mut connection_stats = ConnectionStats {
+            rtt: Duration::ZERO,
+            min_rtt: None,
+            rtt_variance: Duration::ZERO,
+            congestion_window: 0,
+            delivery_rate: 0,
+            sent_bytes: stats.sent_bytes,
+            recv_bytes: stats.recv_bytes,
+            lost_bytes: stats.lost_bytes,
+            sent_packets: stats.sent,
+            recv_packets: stats.recv,
+            lost_packets: stats.lost,
+            rt_segment_drops: self.rt_segment_drops,
+        };
+        // Only one path is used currently (no connection migration) but the active one is picked in case
+        if let Some(path_stats) = self.connection.path_stats().find(|p| p.active) {
+            connection_stats.rtt = path_stats.rtt;
+            connection_stats.min_rtt = path_stats.min_rtt;
+            connection_stats.rtt_variance = path_stats.rttvar;
+            connection_stats.congestion_window = path_stats.cwnd;
+            connection_stats.delivery_rate = path_stats.delivery_rate;
+        }
+        connection_stats
+    }
+
+    #[inline]
+    pub(super) fn is_app_ready(&self) -> bool {
+        self.app_ready
+    }
+
+    // Returns true if the connection was not app ready before
+    #[inline]
+    pub(super) fn set_app_ready(&mut self) -> bool {
+        !std::mem::replace(&mut self.app_ready, true)
+    }
+
+    #[inline]
+    pub(super) fn get_main_stream_no_delay(&self) -> bool {
+        self.main_no_delay
+    }
+
+    #[inline]
+    pub(super) fn set_main_stream_no_delay(&mut self, no_delay: bool) {
+        self.main_no_delay = no_delay;
+    }
+
     pub(super) fn main_stream_send(&mut self, data_vec: Vec<u8>) -> Result<usize, Error> {
         self.main_send_queue.push_back(SendBuffer::new(data_vec));
         self.main_stream_send_next()
@@ -920,6 +1437,9 @@ impl Connection {
     ) -> Result<usize, Error> {
         if self.rt_send_finished {
             // Clear send queue and "finish" / shutdown the current send stream here
+            if !self.rt_send_queue.is_empty() {
+                self.rt_segment_drops += 1;
+            }
             self.rt_send_queue.clear();
             self.connection.stream_shutdown(
                 self.rt_send_stream_id,
@@ -1040,6 +1560,22 @@ impl Connection {
         self.bkgd_stream_send_next()
     }
 
+    // Resuming gives the queued data to the connection right away
+    pub(super) fn set_bkgd_send_paused(&mut self, paused: bool) -> Result<usize, Error> {
+        self.bkgd_send_paused = paused;
+        self.bkgd_stream_send_next()
+    }
+
+    // Returns the number of queued bytes that are not given to the connection yet
+    pub(super) fn bkgd_stream_flush(&mut self) -> Result<usize, Error> {
+        self.bkgd_stream_send_next()?;
+        Ok(self
+            .bkgd_send_queue
+            .iter()
+            .map(|send_buf| send_buf.data.len() - send_buf.sent)
+            .sum())
+    }
+
     // A returned Error::InvalidState indicates something went wrong with the read process
     // A returned Error::Done indicates the stream finished
     pub(super) fn bkgd_stream_read(
@@ -1080,4 +1616,194 @@ impl Connection {
             Err(e) => Err(e),
         }
     }
+
+    // Opening is done by giving the stream a send priority which also checks the peer stream limit
+    fn adhoc_stream_create(
+        &mut self,
+        logical_id_opt: Option<u64>,
+        profile: StreamProfile,
+        finishes_logical: bool,
+        data_vec_opt: Option<Vec<u8>>,
+    ) -> Result<u64, Error> {
+        let stream_id = self.adhoc_send_stream_id;
+        let priority = if profile == StreamProfile::Realtime {
+            REALTIME_ADHOC_STREAM_PRIORITY
+        } else {
+            ADHOC_STREAM_PRIORITY
+        };
+        self.connection.stream_priority(stream_id, priority, true)?;
+        self.adhoc_send_stream_id += 4;
+
+        let logical_id = logical_id_opt.unwrap_or(stream_id);
+        let mut adhoc_stream = AdhocStream::new(stream_id);
+        adhoc_stream.logical_id = logical_id;
+        adhoc_stream.profile = profile;
+        adhoc_stream.header_len = ADHOC_HEADER_LEN;
+        adhoc_stream
+            .send_queue
+            .push_back(SendBuffer::new(create_adhoc_header(
+                profile,
+                logical_id,
+                finishes_logical,
+            )));
+        if profile != StreamProfile::ReliableOrdered {
+            // A message stream is finished together with its only message
+            if let Some(data_vec) = data_vec_opt {
+                adhoc_stream.send_queue.push_back(SendBuffer::new(data_vec));
+            }
+            adhoc_stream.send_finished = true;
+            adhoc_stream.is_local_message = true;
+        }
+        self.adhoc_streams.push(adhoc_stream);
+        Ok(stream_id)
+    }
+
+    // The unordered and realtime profiles send an empty first message so the peer learns the Stream ID
+    pub(super) fn adhoc_stream_open(&mut self, profile: StreamProfile) -> Result<u64, Error> {
+        let stream_id = self.adhoc_stream_create(None, profile, false, None)?;
+        if profile != StreamProfile::ReliableOrdered {
+            self.adhoc_message_streams.push((stream_id, profile));
+        }
+        self.adhoc_streams_send_next()?;
+        Ok(stream_id)
+    }
+
+    // Older realtime messages that are still sending get reset since only the newest one matters
+    fn adhoc_realtime_abandon_send(&mut self, logical_id: u64) -> Result<(), Error> {
+        for adhoc_stream in &mut self.adhoc_streams {
+            if adhoc_stream.is_local_message
+                && (adhoc_stream.logical_id == logical_id)
+                && !adhoc_stream.fin_sent
+            {
+                match self
+                    .connection
+                    .stream_shutdown(adhoc_stream.id, quiche::Shutdown::Write, 0)
+                {
+                    Ok(_) => {}
+                    Err(Error::Done) => {}
+                    Err(e) => return Err(e),
+                }
+                adhoc_stream.send_queue.clear();
+                adhoc_stream.fin_sent = true;
+            }
+        }
+        Ok(())
+    }
+
+    // A returned Error::InvalidStreamState indicates an unknown or already finished stream
+    pub(super) fn adhoc_stream_send(
+        &mut self,
+        stream_id: u64,
+        data_vec: Vec<u8>,
+    ) -> Result<usize, Error> {
+        if let Some((_, profile)) = self
+            .adhoc_message_streams
+            .iter()
+            .find(|(message_id, _)| *message_id == stream_id)
+        {
+            let profile = *profile;
+            if profile == StreamProfile::Realtime {
+                self.adhoc_realtime_abandon_send(stream_id)?;
+            }
+            self.adhoc_stream_create(Some(stream_id), profile, false, Some(data_vec))?;
+            return self.adhoc_streams_send_next();
+        }
+
+        match self.adhoc_streams.iter_mut().find(|adhoc_stream| {
+            (adhoc_stream.id == stream_id)
+                && (adhoc_stream.profile == StreamProfile::ReliableOrdered)
+        }) {
+            Some(adhoc_stream) if !adhoc_stream.send_finished => {
+                adhoc_stream.send_queue.push_back(SendBuffer::new(data_vec));
+            }
+            _ => {
+                return Err(Error::InvalidStreamState(stream_id));
+            }
+        }
+        self.adhoc_streams_send_next()
+    }
+
+    // A returned Error::InvalidStreamState indicates an unknown or already finished stream
+    pub(super) fn adhoc_stream_finish(&mut self, stream_id: u64) -> Result<usize, Error> {
+        if let Some(position) = self
+            .adhoc_message_streams
+            .iter()
+            .position(|(message_id, _)| *message_id == stream_id)
+        {
+            // The finish is an empty last message
+            let (_, profile) = self.adhoc_message_streams.swap_remove(position);
+            self.adhoc_stream_create(Some(stream_id), profile, true, None)?;
+            return self.adhoc_streams_send_next();
+        }
+
+        match self.adhoc_streams.iter_mut().find(|adhoc_stream| {
+            (adhoc_stream.id == stream_id)
+                && (adhoc_stream.profile == StreamProfile::ReliableOrdered)
+        }) {
+            Some(adhoc_stream) if !adhoc_stream.send_finished => {
+                // An empty send buffer carries the finish if everything else was already sent
+                adhoc_stream
+                    .send_queue
+                    .push_back(SendBuffer::new(Vec::new()));
+                adhoc_stream.send_finished = true;
+            }
+            _ => {
+                return Err(Error::InvalidStreamState(stream_id));
+            }
+        }
+        self.adhoc_streams_send_next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: [StreamProfile; 3] = [
+        StreamProfile::ReliableOrdered,
+        StreamProfile::ReliableUnordered,
+        StreamProfile::Realtime,
+    ];
+
+    #[test]
+    fn adhoc_header_round_trips_for_every_profile() {
+        let logical_id = 0x0102_0304_0506_0708;
+        for profile in PROFILES {
+            for finishes_logical in [false, true] {
+                let header: [u8; ADHOC_HEADER_LEN] =
+                    create_adhoc_header(profile, logical_id, finishes_logical)
+                        .try_into()
+                        .unwrap();
+                assert_eq!(
+                    parse_adhoc_header(&header),
+                    Some((profile, logical_id, finishes_logical))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn adhoc_header_has_the_profile_byte_before_the_big_endian_logical_id() {
+        assert_eq!(
+            create_adhoc_header(StreamProfile::ReliableOrdered, 8, false),
+            [0, 0, 0, 0, 0, 0, 0, 0, 8]
+        );
+        assert_eq!(
+            create_adhoc_header(StreamProfile::ReliableUnordered, 0x0105, false),
+            [1, 0, 0, 0, 0, 0, 0, 1, 5]
+        );
+        assert_eq!(
+            create_adhoc_header(StreamProfile::Realtime, 9, true),
+            [0x82, 0, 0, 0, 0, 0, 0, 0, 9]
+        );
+    }
+
+    #[test]
+    fn adhoc_header_with_an_unknown_profile_is_rejected() {
+        let mut header = [0; ADHOC_HEADER_LEN];
+        header[0] = 3;
+        assert_eq!(parse_adhoc_header(&header), None);
+        header[0] = 3 | ADHOC_HEADER_FINISH_FLAG;
+        assert_eq!(parse_adhoc_header(&header), None);
+    }
 }
@@ -23,6 +23,8 @@
 // UDP Management Intended for use with QUIC
 
 use crate::endpoint::SocketAddr;
+#[cfg(feature = "network-simulation")]
+use crate::endpoint::TestNetworkConditions;
 
 #[cfg_attr(target_os = "windows", path = "udp/windows.rs")]
 #[cfg_attr(target_os = "linux", path = "udp/mio.rs")]
@@ -31,6 +33,8 @@ mod os;
 //use os::{AudioInput, AudioOutput, AudioOwner};
 
 use std::collections::BinaryHeap;
+#[cfg(feature = "network-simulation")]
+use std::time::Duration;
 use std::time::Instant;
 
 #[allow(dead_code)]
@@ -48,6 +52,8 @@ pub(super) const TARGET_MAX_DATAGRAM_SIZE: usize = 1232;
 pub(super) struct Socket {
     os_socket: os::UdpSocket,
     delayed_sends: BinaryHeap<DelayedSendPacket>,
+    #[cfg(feature = "network-simulation")]
+    network_simulation: Option<NetworkSimulation>,
 }
 
 #[derive(Debug)]
@@ -72,6 +78,8 @@ impl Socket {
         let socket = Socket {
             os_socket,
             delayed_sends: BinaryHeap::new(),
+            #[cfg(feature = "network-simulation")]
+            network_simulation: None,
         };
 
         Ok((socket, local_addr))
@@ -103,12 +111,32 @@ impl Socket {
         self.os_socket.get_next_send()
     }
 
+    #[cfg(feature = "network-simulation")]
+    pub(super) fn set_test_network_conditions(
+        &mut self,
+        conditions: Option<TestNetworkConditions>,
+    ) {
+        self.network_simulation = conditions.map(NetworkSimulation::new);
+    }
+
     pub(super) fn done_with_send_data(
         &mut self,
         to_addr: SocketAddr,
         len: usize,
         instant: Instant,
     ) -> Result<bool, SocketError> {
+        #[cfg(feature = "network-simulation")]
+        let instant = match &mut self.network_simulation {
+            Some(network_simulation) => match network_simulation.get_send_instant(instant) {
+                Some(i) => i,
+                None => {
+                    // Dropped packets are never sent and the send data gets reused
+                    return Ok(true);
+                }
+            },
+            None => instant,
+        };
+
         if instant <= Instant::now() {
             self.os_socket.done_with_send(to_addr, len);
             Ok(true)
@@ -153,6 +181,62 @@ impl Socket {
     }
 }
 
+// Packets held back for reordering get this extra delay on top of the latency and the maximum jitter
+#[cfg(feature = "network-simulation")]
+const REORDER_DELAY: Duration = Duration::from_millis(10);
+
+// Applies the test network conditions to outgoing packets using a seeded random number generator
+#[cfg(feature = "network-simulation")]
+struct NetworkSimulation {
+    conditions: TestNetworkConditions,
+    random_state: u64,
+}
+
+#[cfg(feature = "network-simulation")]
+impl NetworkSimulation {
+    fn new(conditions: TestNetworkConditions) -> Self {
+        NetworkSimulation {
+            conditions,
+            random_state: conditions.seed,
+        }
+    }
+
+    // SplitMix64 is small and good enough for simulating network conditions
+    fn next_random(&mut self) -> u64 {
+        self.random_state = self.random_state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.random_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Returns a value from 0 to 100 (exclusive)
+    fn next_percentage(&mut self) -> f32 {
+        ((self.next_random() >> 40) as f32) * (100.0 / ((1_u64 << 24) as f32))
+    }
+
+    // Returns None if the packet should be dropped
+    fn get_send_instant(&mut self, instant: Instant) -> Option<Instant> {
+        // The random values are always drawn in the same order to keep the results reproducible
+        let loss_value = self.next_percentage();
+        let reorder_value = self.next_percentage();
+        let jitter_value = self.next_random();
+        if loss_value < self.conditions.loss_pct {
+            return None;
+        }
+
+        let jitter_nanos = self.conditions.jitter.as_nanos() as u64;
+        let mut delay = self.conditions.latency;
+        if jitter_nanos > 0 {
+            delay += Duration::from_nanos(jitter_value % (jitter_nanos + 1));
+        }
+        if reorder_value < self.conditions.reorder_pct {
+            delay += self.conditions.jitter + REORDER_DELAY;
+        }
+        Some(instant + delay)
+    }
+}
+
 // A delayed send packet contains data that is sent from the socket only AFTER an Instant is reached
 struct DelayedSendPacket {
     data: [u8; TARGET_MAX_DATAGRAM_SIZE],
@@ -190,3 +274,42 @@ impl PartialEq for DelayedSendPacket {
         self.instant == other.instant
     }
 }
+
+#[cfg(all(test, feature = "network-simulation"))]
+mod tests {
+    use super::*;
+
+    fn get_test_conditions(seed: u64) -> TestNetworkConditions {
+        TestNetworkConditions {
+            latency: Duration::from_millis(20),
+            jitter: Duration::from_millis(5),
+            loss_pct: 10.0,
+            reorder_pct: 5.0,
+            seed,
+        }
+    }
+
+    // Send instants (None when dropped) of packets that would all be sent at the same instant
+    fn get_send_instants(seed: u64, instant: Instant) -> Vec<Option<Instant>> {
+        let mut network_simulation = NetworkSimulation::new(get_test_conditions(seed));
+        (0..1000)
+            .map(|_| network_simulation.get_send_instant(instant))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_drops_and_delays_the_same_packets() {
+        let instant = Instant::now();
+        let send_instants = get_send_instants(7, instant);
+        assert_eq!(send_instants, get_send_instants(7, instant));
+        assert_ne!(send_instants, get_send_instants(8, instant));
+
+        let num_dropped = send_instants.iter().filter(|i| i.is_none()).count();
+        assert!((50..150).contains(&num_dropped));
+        let max_delay = Duration::from_millis(25) + Duration::from_millis(5) + REORDER_DELAY;
+        for send_instant in send_instants.iter().flatten() {
+            let delay = *send_instant - instant;
+            assert!(delay >= Duration::from_millis(20) && delay <= max_delay);
+        }
+    }
+}
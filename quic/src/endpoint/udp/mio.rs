@@ -54,6 +54,14 @@ impl UdpSocket {
             Err(_e) => return None,
         };
 
+        if !ipv6_mode {
+            // Lets stateless messages get sent to an IPv4 broadcast address
+            match socket.set_broadcast(true) {
+                Ok(_) => {}
+                Err(_e) => return None,
+            }
+        }
+
         let poll = match mio::Poll::new() {
             Ok(p) => p,
             Err(_e) => return None,
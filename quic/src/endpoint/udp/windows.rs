@@ -116,6 +116,22 @@ impl UdpSocket {
             return None;
         }
 
+        if !is_ipv6 {
+            // Lets stateless messages get sent to an IPv4 broadcast address
+            let broadcast_enable: u32 = 1;
+            let wsa_error = unsafe {
+                WinSock::setsockopt(
+                    socket,
+                    WinSock::SOL_SOCKET,
+                    WinSock::SO_BROADCAST,
+                    Some(&broadcast_enable.to_ne_bytes()),
+                )
+            };
+            if wsa_error == WinSock::SOCKET_ERROR {
+                return None;
+            }
+        }
+
         let mut recv_msgs = Vec::with_capacity(RECV_MSGS);
         for _ in 0..RECV_MSGS {
             let msg = AsyncMessage::new(is_ipv6)?;
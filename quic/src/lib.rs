@@ -32,10 +32,12 @@
 /// QUIC Endpoint Module
 pub mod endpoint;
 use endpoint::{
-    ConnectionEndReason, ConnectionId, Endpoint, Error, NextEvent, ReadInfo, RecvEvent,
+    ConnectionEndReason, ConnectionId, Endpoint, Error, NextEvent, ReadInfo, RecvEvent, SocketAddr,
+    StreamId, StreamProfile,
 };
 
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 /// Required QUIC Endpoint Handler Event Callback Functions
 ///
@@ -43,9 +45,28 @@ use std::time::{Duration, Instant};
 /// These callbacks are expected to return within a couple milliseconds AT THE MOST
 /// for all processing cases.
 pub trait EndpointEventCallbacks {
-    /// Called when a new connection is started and is application ready.
+    /// Called when a new connection is started and the QUIC handshake is done.
+    ///
+    /// Applications with their own handshake should call Endpoint mark_ready once it is done.
+    ///
+    /// Sending with the Endpoint stream send functions (like main_stream_send) is supported
+    /// inside this callback and the data is sent out right away.
+    /// The exception is a server sending on the main or background stream before the client has
+    /// sent anything on that stream: QUIC only lets the client open these streams so the data
+    /// is queued and then sent as soon as the first data from the client on that stream arrives.
     fn connection_started(&mut self, endpoint: &mut Endpoint, cid: &ConnectionId);
 
+    /// Called once the application marked a connection as ready with Endpoint mark_ready.
+    ///
+    /// connection_started only means the QUIC handshake is done (the transport established phase).
+    /// This callback is the start of the application ready phase where the peer can be treated
+    /// as active, see Endpoint mark_ready for the phases.
+    ///
+    /// By default, this function does nothing.
+    fn connection_ready(&mut self, _endpoint: &mut Endpoint, _cid: &ConnectionId) {
+        // Do nothing by default
+    }
+
     /// Called when a connection has ended and should be cleaned up.
     ///
     /// Return true if you want the Endpoint Handler event loop to exit.
@@ -76,8 +97,22 @@ pub trait EndpointEventCallbacks {
     ///
     /// Return true if you want the Endpoint Handler event loop to exit.
     /// The event loop will return an Ok(false) indicating that the tick callback function caused the exit.
+    ///
+    /// The Endpoint stream send functions (like main_stream_send and background_stream_send) can be used
+    /// here to send data on a timer, such as pushing a file over the background stream a chunk at a time.
     fn tick(&mut self, endpoint: &mut Endpoint) -> bool;
 
+    /// Called right before a tick callback when the event loop fell far behind its tick schedule,
+    /// such as after the process was suspended (laptop sleep).
+    ///
+    /// Instead of calling the tick callback for every missed tick, the missed ticks are skipped
+    /// based on the TickPolicy and the tick count still increases by the number of skipped ticks.
+    ///
+    /// By default, this function does nothing when called.
+    fn time_jump(&mut self, _endpoint: &mut Endpoint, _time_jump: TimeJump) {
+        // Do nothing by default
+    }
+
     /// Called when there is something to read on the main stream.
     ///
     /// The main stream is a reliable (ordered) stream that focuses on communicating
@@ -92,6 +127,8 @@ pub trait EndpointEventCallbacks {
     /// initial_main_recv_size value.
     /// Returning a None will close the main stream but since the main stream is required,
     /// the connection will start the close process.
+    ///
+    /// When main_stream_recv_action is overwritten to reply right away this function is not called.
     fn main_stream_recv(
         &mut self,
         endpoint: &mut Endpoint,
@@ -99,6 +136,25 @@ pub trait EndpointEventCallbacks {
         read_data: &[u8],
     ) -> Option<usize>; // Just a usize in future where 0 represents close the stream...?
 
+    /// Called when there is something to read on the main stream and allows replying right away.
+    ///
+    /// This is the same as main_stream_recv except the returned MainRecvAction can also contain data
+    /// that gets sent back over the main stream before the next read, which makes simple
+    /// request / response protocols possible without a separate main_stream_send call.
+    ///
+    /// By default, this function calls main_stream_recv and does not reply.
+    fn main_stream_recv_action(
+        &mut self,
+        endpoint: &mut Endpoint,
+        cid: &ConnectionId,
+        read_data: &[u8],
+    ) -> MainRecvAction {
+        MainRecvAction {
+            next_read: self.main_stream_recv(endpoint, cid, read_data),
+            reply: None,
+        }
+    }
+
     /// Called when there is something to read on the real-time stream.
     ///
     /// The real-time "stream" is different than the main stream because it uses multiple
@@ -154,11 +210,218 @@ pub trait EndpointEventCallbacks {
         // Return None by default since the background stream is not managed
         None
     }
+
+    /// Called when there is something to read on an ad-hoc stream.
+    ///
+    /// Ad-hoc streams are opened with Endpoint open_stream by either side.
+    /// This also gets called (with empty read_data) when the peer opens a stream which is how
+    /// the application learns about its Stream ID and profile.
+    ///
+    /// For the ReliableOrdered profile the read_data is whatever data arrived on the stream so far
+    /// and can be any length. For the other profiles the read_data is always one complete message.
+    /// When finished is true the peer will not send anything more on this stream.
+    /// A ReliableOrdered stream gets cleaned up once the local side has also been finished with Endpoint stream_finish.
+    ///
+    /// By default, this function ignores the data. This function should be overwritten in order
+    /// to use ad-hoc streams.
+    fn stream_recv(
+        &mut self,
+        _endpoint: &mut Endpoint,
+        _cid: &ConnectionId,
+        _stream_id: StreamId,
+        _profile: StreamProfile,
+        _read_data: &[u8],
+        _finished: bool,
+    ) {
+        // Do nothing by default
+    }
+
+    /// Called when a stateless message is received (only with Endpoint set_accept_stateless_messages).
+    ///
+    /// Stateless messages arrive outside of any connection, such as a server discovery probe
+    /// that can be answered with Endpoint stateless_send to the from_addr.
+    /// The from_addr and the message are unauthenticated and can be spoofed.
+    ///
+    /// By default, this function ignores the message.
+    fn stateless_recv(
+        &mut self,
+        _endpoint: &mut Endpoint,
+        _from_addr: SocketAddr,
+        _message: &[u8],
+    ) {
+        // Do nothing by default
+    }
+
+    /// Called when a datagram sent with Endpoint send_datagram is received on a connection.
+    ///
+    /// Datagrams are unreliable and unordered so they can arrive in any order or not at all.
+    ///
+    /// By default, this function ignores the datagram.
+    fn datagram_recv(&mut self, _endpoint: &mut Endpoint, _cid: &ConnectionId, _data: &[u8]) {
+        // Do nothing by default
+    }
+}
+
+/// Main stream receive callback result
+pub struct MainRecvAction {
+    /// The number of bytes to read next on the main stream, same as the main_stream_recv return value.
+    pub next_read: Option<usize>,
+    /// Optional data to send over the main stream immediately.
+    pub reply: Option<Vec<u8>>,
+}
+
+/// Information about skipped ticks given to the time_jump callback
+#[derive(Debug, Clone, Copy)]
+pub struct TimeJump {
+    /// The number of ticks that were skipped without calling the tick callback.
+    pub skipped_ticks: u64,
+    /// How far behind the tick schedule the event loop was when the jump was detected.
+    pub gap: Duration,
+}
+
+/// What an EndpointHandler poll call did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollStatus {
+    /// An event was handled before the deadline so poll should be called again right away,
+    /// it keeps handling events (or waiting) until the deadline
+    Handled,
+    /// The deadline was reached (after handling anything that was due) so wait for the next deadline
+    DeadlineReached,
+    /// One of the callbacks ended the event loop with the value that run_event_loop would return
+    /// (true from connection_ended and false from tick)
+    Ended(bool),
+}
+
+/// A reasonable number of tick periods for TickPolicy::CatchUp to catch up before skipping ticks
+pub const MAX_TICK_CATCH_UP: u32 = 4;
+
+/// How the event loop handles ticks that were missed after falling behind its tick schedule
+///
+/// Missed ticks are always reported with the time_jump callback and still count towards the
+/// current tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickPolicy {
+    /// Skips every missed tick and restarts the tick schedule from the current instant
+    /// so that there is no burst of ticks after a stall
+    #[default]
+    Skip,
+    /// Skips the missed ticks except for a single tick that happens immediately
+    /// while keeping the original tick phase
+    Coalesce,
+    /// Calls the tick callback back to back for up to this many late tick periods so that the
+    /// average tick rate stays the same, falling further behind coalesces the missed ticks
+    CatchUp(u32),
+}
+
+/// Tick schedule that stays on a fixed period and handles missed ticks with a TickPolicy
+pub struct TickAccumulator {
+    tick_duration: Duration,
+    policy: TickPolicy,
+    next_tick_instant: Instant,
+}
+
+impl TickAccumulator {
+    /// Create a tick schedule where the first tick is due immediately
+    ///
+    /// Ticks that are late by less than max_catch_up_ticks periods are caught up one at a time.
+    pub fn new(tick_duration: Duration, max_catch_up_ticks: u32) -> Self {
+        Self::with_policy(tick_duration, TickPolicy::CatchUp(max_catch_up_ticks))
+    }
+
+    /// Create a tick schedule with the given missed tick policy where the first tick is due immediately
+    pub fn with_policy(tick_duration: Duration, policy: TickPolicy) -> Self {
+        TickAccumulator {
+            tick_duration,
+            policy,
+            next_tick_instant: Instant::now(),
+        }
+    }
+
+    /// Create a tick schedule where the first tick is due at the next whole multiple of the
+    /// tick duration since the (wall clock) epoch, such as SystemTime::UNIX_EPOCH or a time shared by a server
+    ///
+    /// Schedules of independently started processes that use the same epoch and tick duration
+    /// tick at roughly the same time. This is best-effort since the system clocks of different
+    /// machines are not synchronized (true lockstep needs a time sync protocol on top), but it keeps
+    /// the tick phases from being arbitrary. A zero tick duration makes the first tick due immediately.
+    pub fn aligned_to_epoch(
+        tick_duration: Duration,
+        policy: TickPolicy,
+        epoch: SystemTime,
+    ) -> Self {
+        let tick_nanos = tick_duration.as_nanos();
+        let delay = if tick_nanos == 0 {
+            Duration::ZERO
+        } else {
+            match SystemTime::now().duration_since(epoch) {
+                Ok(since_epoch) => {
+                    let phase = since_epoch.as_nanos() % tick_nanos;
+                    Duration::from_nanos(((tick_nanos - phase) % tick_nanos) as u64)
+                }
+                // The epoch is in the future so the ticks line up with it once it passes
+                Err(e) => Duration::from_nanos((e.duration().as_nanos() % tick_nanos) as u64),
+            }
+        };
+        TickAccumulator {
+            tick_duration,
+            policy,
+            next_tick_instant: Instant::now() + delay,
+        }
+    }
+
+    /// Change the missed tick policy without changing the tick schedule
+    pub fn set_policy(&mut self, policy: TickPolicy) {
+        self.policy = policy;
+    }
+
+    /// Instant that the next tick is due at
+    pub fn next_tick_instant(&self) -> Instant {
+        self.next_tick_instant
+    }
+
+    /// Moves the schedule forward after a tick happened at the now instant
+    ///
+    /// Returns a TimeJump when ticks had to be skipped to get back on schedule.
+    pub fn advance(&mut self, now: Instant) -> Option<TimeJump> {
+        self.next_tick_instant += self.tick_duration;
+        if self.tick_duration.is_zero() || now <= self.next_tick_instant {
+            return None;
+        }
+        let gap = now - self.next_tick_instant;
+        let tick_nanos = self.tick_duration.as_nanos();
+        let whole_missed_ticks = (gap.as_nanos() / tick_nanos) as u64;
+        match self.policy {
+            TickPolicy::Skip => {
+                // The tick that is already due gets skipped too
+                let skipped_ticks = whole_missed_ticks + 1;
+                self.next_tick_instant = now + self.tick_duration;
+                Some(TimeJump { skipped_ticks, gap })
+            }
+            TickPolicy::Coalesce | TickPolicy::CatchUp(_) => {
+                let max_catch_up_ticks = match self.policy {
+                    TickPolicy::CatchUp(max_catch_up_ticks) => max_catch_up_ticks.max(1),
+                    _ => 1,
+                };
+                if gap < self.tick_duration * max_catch_up_ticks {
+                    return None;
+                }
+                // Only whole missed periods are skipped to keep the original tick phase
+                let skipped_ticks = whole_missed_ticks;
+                self.next_tick_instant +=
+                    Duration::from_nanos(((skipped_ticks as u128) * tick_nanos) as u64);
+                Some(TimeJump { skipped_ticks, gap })
+            }
+        }
+    }
 }
 
 /// Main library structure that handles the QUIC Endpoint
 pub struct EndpointHandler<'a> {
     current_tick: u64,
+    tick_policy: TickPolicy,
+    tick_accumulator: TickAccumulator,
+    tick_epoch: Option<SystemTime>,
+    ticks_started: bool,
     endpoint: &'a mut Endpoint,
     events: &'a mut dyn EndpointEventCallbacks,
 }
@@ -169,6 +432,10 @@ impl<'a> EndpointHandler<'a> {
     pub fn new(endpoint: &'a mut Endpoint, events: &'a mut dyn EndpointEventCallbacks) -> Self {
         EndpointHandler {
             current_tick: 0,
+            tick_policy: TickPolicy::default(),
+            tick_accumulator: TickAccumulator::with_policy(Duration::ZERO, TickPolicy::default()),
+            tick_epoch: None,
+            ticks_started: false,
             endpoint,
             events,
         }
@@ -180,50 +447,222 @@ impl<'a> EndpointHandler<'a> {
     ///
     /// Communicates with the application code with the previously passed event callbacks
     ///
-    /// Tick "0" callback will happen immediately
+    /// Tick "0" callback will happen immediately on the first call (unless set_tick_epoch aligns it)
+    ///
+    /// Falling behind the tick schedule handles the missed ticks with the tick policy
+    /// (TickPolicy::Skip by default) and calls the time_jump callback before the next tick callback
+    ///
+    /// Calling this again with the same tick_duration continues the previous tick schedule
+    /// and tick count, so the time spent outside of the event loop shows up as a time_jump
+    /// before the next tick callback. A different tick_duration keeps the tick count but starts
+    /// a new schedule with an immediate tick. Call reset_ticks before calling this again to
+    /// start over from tick "0" instead.
     ///
     /// Returns true if this event loop function should be maybe called again
     ///  (ie. run a client endpoint in "low power" mode when it has no connections)
     pub fn run_event_loop(&mut self, tick_duration: Duration) -> Result<bool, Error> {
-        let start_instant = Instant::now();
-        let mut next_tick_instant = start_instant;
-
+        self.set_tick_duration(tick_duration);
         loop {
             // This function will sleep the thread while waiting for the next instant or recv udp data
-            match self.endpoint.get_next_event(next_tick_instant)? {
-                NextEvent::ReceivedData => {
-                    if self.run_recv_loop()? {
-                        return Ok(true);
-                    }
+            if let PollStatus::Ended(should_continue) =
+                self.handle_next_event(self.tick_accumulator.next_tick_instant())?
+            {
+                return Ok(should_continue);
+            }
+        }
+    }
+
+    /// Same as run_event_loop except that it also ends once the shutdown flag gets set (such as
+    /// from a signal handler thread) after sending a CONNECTION_CLOSE to every peer
+    ///
+    /// The flag is checked after every handled event and the thread never sleeps past the next tick,
+    /// so setting it ends the event loop within one tick duration.
+    /// The connections are left closing for the application to drop together with the Endpoint.
+    ///
+    /// Returns false when the shutdown flag ended the event loop, otherwise the same as run_event_loop
+    pub fn run_event_loop_until(
+        &mut self,
+        tick_duration: Duration,
+        shutdown: &AtomicBool,
+    ) -> Result<bool, Error> {
+        self.set_tick_duration(tick_duration);
+        loop {
+            if shutdown.load(Ordering::Acquire) {
+                self.endpoint.close_all_connections()?;
+                return Ok(false);
+            }
+            if let PollStatus::Ended(should_continue) =
+                self.handle_next_event(self.tick_accumulator.next_tick_instant())?
+            {
+                return Ok(should_continue);
+            }
+        }
+    }
+
+    /// Non-blocking alternative to run_event_loop for applications that keep control of the thread
+    ///
+    /// Handles the next endpoint event (received data, a tick or a connection ending)
+    /// and returns once it is handled or the deadline is reached, whichever comes first.
+    /// The thread sleeps until then so a deadline of Instant::now() only handles what is already there.
+    ///
+    /// Ticks follow the tick duration set with set_tick_duration (or a previous run_event_loop call),
+    /// which is zero by default so that every call ticks.
+    /// The tick count and schedule carry over between calls the same way as with run_event_loop.
+    /// When a tick is due any already received data gets handled before the tick callback.
+    /// Tick "0" callback will happen on the first call.
+    ///
+    /// A single thread can drive both the endpoint and a window by calling this with the next frame
+    /// instant as the deadline in between processing the window messages and drawing,
+    /// get_endpoint gives access to the endpoint (like to send a chat message) in between calls.
+    ///
+    /// Returns PollStatus::Handled when poll should be called again right away (an event was handled
+    /// before the deadline), PollStatus::DeadlineReached when it should wait for the next deadline
+    /// and PollStatus::Ended once one of the callbacks ended the event loop
+    ///  (the point where run_event_loop would have returned)
+    pub fn poll(&mut self, deadline: Instant) -> Result<PollStatus, Error> {
+        if !self.ticks_started {
+            self.set_tick_duration(self.tick_accumulator.tick_duration);
+        }
+        let next_tick_instant = self.tick_accumulator.next_tick_instant();
+        if next_tick_instant <= Instant::now() && self.endpoint.has_recv_data() {
+            // get_next_event returns the due tick right away so the received data gets handled first
+            let recv_result = self.run_recv_loop();
+            self.dispatch_ready_connections();
+            if recv_result? {
+                return Ok(PollStatus::Ended(true));
+            }
+        }
+        let wait_instant = if deadline < next_tick_instant {
+            deadline
+        } else {
+            next_tick_instant
+        };
+        // A zero tick duration ticks on every call so the deadline decides when to stop calling
+        match self.handle_next_event(wait_instant)? {
+            PollStatus::Handled if Instant::now() >= deadline => Ok(PollStatus::DeadlineReached),
+            poll_status => Ok(poll_status),
+        }
+    }
+
+    /// Get the endpoint that the handler drives, such as to send data in between poll calls
+    pub fn get_endpoint(&mut self) -> &mut Endpoint {
+        self.endpoint
+    }
+
+    /// Instant that the next tick callback is due at, useful to pick a poll deadline
+    pub fn get_next_tick_instant(&self) -> Instant {
+        self.tick_accumulator.next_tick_instant()
+    }
+
+    /// Set the tick duration used by poll
+    ///
+    /// The same tick_duration continues the previous tick schedule. A different tick_duration keeps
+    /// the tick count but starts a new schedule with an immediate tick.
+    pub fn set_tick_duration(&mut self, tick_duration: Duration) {
+        if !self.ticks_started || self.tick_accumulator.tick_duration != tick_duration {
+            self.tick_accumulator = match self.tick_epoch {
+                Some(epoch) => {
+                    TickAccumulator::aligned_to_epoch(tick_duration, self.tick_policy, epoch)
                 }
-                NextEvent::Tick => {
-                    next_tick_instant += tick_duration; // Does not currently check for skipped ticks / assumes computer processes all
-                    self.current_tick += 1;
+                None => TickAccumulator::with_policy(tick_duration, self.tick_policy),
+            };
+            self.ticks_started = true;
+        }
+    }
 
-                    if self.events.tick(self.endpoint) {
-                        return Ok(false);
-                    }
+    /// Align the start of new tick schedules to the wall clock epoch (see TickAccumulator::aligned_to_epoch)
+    /// instead of ticking immediately, None (the default) goes back to an immediate first tick
+    ///
+    /// This takes effect the next time a tick schedule starts, so it should be set before the first
+    /// run_event_loop (or poll) call or be followed by reset_ticks. The tick "0" callback then waits
+    /// for up to one tick duration. Alignment is best-effort because clocks of different machines
+    /// are not synchronized, but clients started at different times share the same tick phase
+    /// which reduces the drift between them.
+    pub fn set_tick_epoch(&mut self, epoch: Option<SystemTime>) {
+        self.tick_epoch = epoch;
+    }
+
+    // Returns PollStatus::Ended with the event loop return value once a callback ends the event loop
+    fn handle_next_event(&mut self, wait_instant: Instant) -> Result<PollStatus, Error> {
+        let loop_result = self.handle_endpoint_event(wait_instant);
+        self.dispatch_ready_connections();
+        loop_result
+    }
+
+    // Connections that got marked ready inside of a callback get their connection_ready callback here
+    fn dispatch_ready_connections(&mut self) {
+        loop {
+            let ready_connections = self.endpoint.take_ready_connections();
+            if ready_connections.is_empty() {
+                return;
+            }
+            for cid in ready_connections {
+                // The connection could have ended since it was marked ready
+                if self.endpoint.is_ready(&cid) {
+                    self.events.connection_ready(self.endpoint, &cid);
                 }
-                NextEvent::ConnectionEnded((cid, reason)) => {
-                    let remaining_connections = self.endpoint.get_num_connections();
-                    if self.events.connection_ended(
-                        self.endpoint,
-                        &cid,
-                        reason,
-                        remaining_connections,
-                    ) {
-                        return Ok(true);
-                    }
+            }
+        }
+    }
+
+    fn handle_endpoint_event(&mut self, wait_instant: Instant) -> Result<PollStatus, Error> {
+        match self.endpoint.get_next_event(wait_instant)? {
+            NextEvent::ReceivedData => {
+                if self.run_recv_loop()? {
+                    return Ok(PollStatus::Ended(true));
                 }
-                NextEvent::ConnectionEnding((cid, reason)) => {
-                    self.events
-                        .connection_ending_warning(self.endpoint, &cid, reason);
+            }
+            NextEvent::Tick => {
+                // Polling can wake up at its deadline before the next tick is due
+                if Instant::now() < self.tick_accumulator.next_tick_instant() {
+                    return Ok(PollStatus::DeadlineReached);
+                }
+                if let Some(time_jump) = self.tick_accumulator.advance(Instant::now()) {
+                    self.current_tick += time_jump.skipped_ticks;
+                    self.events.time_jump(self.endpoint, time_jump);
+                }
+                self.current_tick += 1;
+
+                if self.events.tick(self.endpoint) {
+                    return Ok(PollStatus::Ended(false));
                 }
-                NextEvent::AlreadyHandled => {
-                    // Do Nothing and try to call get_next_event ASAP
+            }
+            NextEvent::ConnectionEnded((cid, reason)) => {
+                let remaining_connections = self.endpoint.get_num_connections();
+                if self
+                    .events
+                    .connection_ended(self.endpoint, &cid, reason, remaining_connections)
+                {
+                    return Ok(PollStatus::Ended(true));
                 }
             }
+            NextEvent::ConnectionEnding((cid, reason)) => {
+                self.events
+                    .connection_ending_warning(self.endpoint, &cid, reason);
+            }
+            NextEvent::AlreadyHandled => {
+                // Do Nothing and try to call get_next_event ASAP
+            }
         }
+        Ok(PollStatus::Handled)
+    }
+
+    /// Change how missed ticks are handled, takes effect on the next tick
+    pub fn set_tick_policy(&mut self, policy: TickPolicy) {
+        self.tick_policy = policy;
+        self.tick_accumulator.set_policy(policy);
+    }
+
+    /// Number of ticks that happened so far including any skipped ticks
+    pub fn get_current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    /// Makes the next run_event_loop (or poll) call start over from tick "0" with an immediate tick
+    /// instead of continuing the previous tick schedule
+    pub fn reset_ticks(&mut self) {
+        self.current_tick = 0;
+        self.ticks_started = false;
     }
 
     fn run_recv_loop(&mut self) -> Result<bool, Error> {
@@ -234,13 +673,18 @@ impl<'a> EndpointHandler<'a> {
                 }
                 RecvEvent::MainStreamReceived((cid, verified_index, mut data_vec, mut len)) => {
                     loop {
-                        let target_len_opt =
-                            self.events
-                                .main_stream_recv(self.endpoint, &cid, &data_vec[..len]);
+                        let action = self.events.main_stream_recv_action(
+                            self.endpoint,
+                            &cid,
+                            &data_vec[..len],
+                        );
+                        if let Some(reply) = action.reply {
+                            self.endpoint.main_stream_reply(verified_index, reply)?;
+                        }
                         match self.endpoint.main_stream_read(
                             verified_index,
                             data_vec,
-                            target_len_opt,
+                            action.next_read,
                         )? {
                             ReadInfo::ReadData((new_data_vec, new_len)) => {
                                 data_vec = new_data_vec;
@@ -338,6 +782,23 @@ impl<'a> EndpointHandler<'a> {
                     }
                     // self.endpoint.connection_send(verified_index)?;
                 }
+                RecvEvent::StreamData((cid, stream_id, profile, data_vec, len, is_finished)) => {
+                    self.events.stream_recv(
+                        self.endpoint,
+                        &cid,
+                        stream_id,
+                        profile,
+                        &data_vec[..len],
+                        is_finished,
+                    );
+                }
+                RecvEvent::StatelessReceived((from_addr, message)) => {
+                    self.events
+                        .stateless_recv(self.endpoint, from_addr, &message);
+                }
+                RecvEvent::DatagramReceived((cid, data)) => {
+                    self.events.datagram_recv(self.endpoint, &cid, &data);
+                }
                 RecvEvent::ConnectionEnded((cid, reason)) => {
                     let remaining_connections = self.endpoint.get_num_connections();
                     if self.events.connection_ended(
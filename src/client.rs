@@ -619,6 +619,7 @@ impl swiftlet_graphics::Vulkan2dWindowCallbacks for Client {
                             let conn_state = NetworkStateConnection {
                                 name: user_name,
                                 state,
+                                stats: None,
                             };
                             self.connections.push(conn_state);
                         }
@@ -635,6 +636,7 @@ impl swiftlet_graphics::Vulkan2dWindowCallbacks for Client {
                                 self.new_state(self.connections[conn_ind].state)
                             }
                         }
+                        NetworkStateMessage::StatsUpdate(_) => {}
                     }
                     self.should_draw = true;
                 }
@@ -726,6 +728,9 @@ impl ClientRunner {
             1 << 25,
             glyphs,
             swiftlet_graphics::Vulkan2dWindowMode::Normal,
+            swiftlet_graphics::LatencyMode::LowLatency,
+            None, // Frames in flight (from the latency mode)
+            None, // Device index
         ) {
             Ok(r) => r,
             Err(e) => {
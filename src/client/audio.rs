@@ -29,7 +29,7 @@ use crate::communication::{
     TerminalAudioOutCommands,
 };
 
-use swiftlet_audio::opus::{Decoder, Encoder, OpusData};
+use swiftlet_audio::opus::{Decoder, Encoder, EncodingApplication, OpusData};
 
 pub(crate) fn audio_thread(channels: AudioThreadChannels) {
     let output = Output {
@@ -48,7 +48,7 @@ pub(crate) fn audio_thread(channels: AudioThreadChannels) {
         callback_count: 0,
         last_instant: Instant::now(),
         avg_duration: Duration::from_millis(0),
-        encoder: Encoder::new(false, true).unwrap(),
+        encoder: Encoder::new(48000, 1, EncodingApplication::Voip).unwrap(),
         data: [0; 512],
         data_len: 0,
         command_recv: channels.input_cmd_recv,
@@ -157,7 +157,11 @@ impl swiftlet_audio::OutputCallback for Output {
                     TerminalAudioOutCommands::PlayOpus(id) => {
                         for (index, opus_data) in self.opus_list.iter().enumerate() {
                             if opus_data.matches_id(id) {
-                                let decoder = match Decoder::new(opus_data.is_stereo()) {
+                                let channels = match opus_data.is_stereo() {
+                                    true => 2,
+                                    false => 1,
+                                };
+                                let decoder = match Decoder::new(48000, channels) {
                                     Ok(decoder) => decoder,
                                     Err(err) => {
                                         self.send_debug_str("Cannot Create Opus Decoder\n");
@@ -222,7 +226,11 @@ impl swiftlet_audio::OutputCallback for Output {
                         realtime.data_queue.push_back(output_data);
                     } else {
                         let is_stereo = music_data[0] > 0;
-                        let mut decoder = match Decoder::new(is_stereo) {
+                        let channels = match is_stereo {
+                            true => 2,
+                            false => 1,
+                        };
+                        let mut decoder = match Decoder::new(48000, channels) {
                             Ok(decoder) => decoder,
                             Err(err) => {
                                 self.send_debug_str("Cannot Create Opus Decoder\n");
@@ -291,7 +299,7 @@ impl swiftlet_audio::OutputCallback for Output {
                         }
                         realtime.data_queue.push_back(output_data);
                     } else {
-                        let decoder = match Decoder::new(false) {
+                        let decoder = match Decoder::new(48000, 1) {
                             Ok(decoder) => decoder,
                             Err(err) => {
                                 self.send_debug_str("Cannot Create Opus Decoder\n");
@@ -601,7 +609,7 @@ impl swiftlet_audio::InputTrait for Input {
                 match self.command_recv.pop() {
                     Err(PopError::Empty) => break,
                     Ok(TerminalAudioInCommands::Start) => {
-                        match Encoder::new(false, true) {
+                        match Encoder::new(48000, 1, EncodingApplication::Voip) {
                             Ok(enc) => {
                                 self.encoder = enc;
                                 return true;
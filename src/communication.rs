@@ -80,11 +80,22 @@ pub(crate) enum NetworkStateMessage {
     ConnectionsRefresh((Option<usize>, Vec<NetworkStateConnection>)),
     NewConnection((String, u8)),
     StateChange((usize, u8)),
+    StatsUpdate(Vec<Option<NetworkStateConnectionStats>>), // Stats of every connection in connection order
 }
 
 pub(crate) struct NetworkStateConnection {
     pub(crate) name: String,
     pub(crate) state: u8,
+    pub(crate) stats: Option<NetworkStateConnectionStats>, // Only known by the server
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct NetworkStateConnectionStats {
+    pub(crate) rtt: std::time::Duration,
+    pub(crate) packet_loss: f32,
+    pub(crate) sent_bytes: u64,
+    pub(crate) recv_bytes: u64,
+    pub(crate) send_bitrate: Option<u64>, // Bits per second since the previous update
 }
 
 #[cfg(feature = "client")]
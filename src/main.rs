@@ -28,6 +28,7 @@ const USERNAME_DEFAULT: &str = "Client";
 const PORT_DEFAULT: u16 = 9001;
 
 const DEBUG_STR: &str = "Debug";
+const DEBUG_MAX_LINES: usize = 1000; // Older debug lines get dropped from the console
 const CONNECTING_STR: &str = "Connecting...";
 
 const AUDIO_FILES: [&str; 3] = [
@@ -163,11 +164,42 @@ fn main() -> std::io::Result<()> {
 struct ConsoleStateCommon {
     title_string: String,
     debug_string: String,
-    debug_lines: u16,
+    debug_lines: u16, // Number of lines after wrapping to the debug pane width
     debug_scroll: u16,
+    debug_max_lines: usize,
     connections: Vec<NetworkStateConnection>,
 }
 
+impl ConsoleStateCommon {
+    // Appends the text and drops the oldest lines past the line cap
+    fn push_debug_string(&mut self, text: &str) {
+        self.debug_string.push_str(text);
+        let num_lines = self.debug_string.lines().count();
+        if num_lines > self.debug_max_lines {
+            let excess_lines = num_lines - self.debug_max_lines;
+            if let Some((newline_ind, _)) =
+                self.debug_string.match_indices('\n').nth(excess_lines - 1)
+            {
+                self.debug_string.drain(..=newline_ind);
+            }
+        }
+    }
+
+    // Recomputes the wrapped line count for the width inside of the debug pane borders
+    // (counting characters which is close to the word wrapping of the paragraph)
+    // and keeps the scroll position within the lines
+    fn update_debug_lines(&mut self, pane_width: u16) {
+        let width = (pane_width.saturating_sub(2) as usize).max(1);
+        let wrapped_lines: usize = self
+            .debug_string
+            .lines()
+            .map(|line| line.chars().count().div_ceil(width).max(1))
+            .sum();
+        self.debug_lines = wrapped_lines.clamp(1, u16::MAX as usize) as u16;
+        self.debug_scroll = self.debug_scroll.min(self.debug_lines - 1);
+    }
+}
+
 fn console_ui(frame: &mut ratatui::Frame, state: &ConsoleStateCommon, my_state: Option<usize>) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
@@ -203,6 +235,18 @@ fn console_ui(frame: &mut ratatui::Frame, state: &ConsoleStateCommon, my_state:
             state_test <<= 1;
         }
 
+        if let Some(stats) = &conn.stats {
+            row.push(Cell::from(" ")); // The L column does not have a state bit yet
+            row.push(Cell::from(format!("{}ms", stats.rtt.as_millis())));
+            row.push(Cell::from(format!("{:.1}%", stats.packet_loss * 100.0)));
+            row.push(Cell::from(format!("{}KB", stats.sent_bytes / 1000)));
+            row.push(Cell::from(format!("{}KB", stats.recv_bytes / 1000)));
+            match stats.send_bitrate {
+                Some(bitrate) => row.push(Cell::from(format!("{}kbps", bitrate / 1000))),
+                None => row.push(Cell::from(" ")),
+            }
+        }
+
         rows.push(Row::new(row));
     }
 
@@ -212,6 +256,11 @@ fn console_ui(frame: &mut ratatui::Frame, state: &ConsoleStateCommon, my_state:
         String::from("S"),
         String::from("V"),
         String::from("L"),
+        String::from("RTT"),
+        String::from("Loss"),
+        String::from("Sent"),
+        String::from("Recv"),
+        String::from("Bitrate"),
     ];
 
     let widths = [
@@ -220,6 +269,11 @@ fn console_ui(frame: &mut ratatui::Frame, state: &ConsoleStateCommon, my_state:
         Constraint::Length(1),
         Constraint::Length(1),
         Constraint::Length(1),
+        Constraint::Length(7),
+        Constraint::Length(6),
+        Constraint::Length(10),
+        Constraint::Length(10),
+        Constraint::Length(11),
     ];
 
     let table = Table::new(rows, widths)
@@ -236,6 +290,7 @@ fn console_ui(frame: &mut ratatui::Frame, state: &ConsoleStateCommon, my_state:
     // Render Debug Text
     frame.render_widget(
         Paragraph::new(state.debug_string.as_str())
+            .wrap(Wrap { trim: false })
             .scroll((state.debug_scroll, 0))
             .block(Block::new().borders(Borders::ALL).title(DEBUG_STR)),
         layout[1],
@@ -274,6 +329,7 @@ fn run_console_server(
         debug_string: String::from("Server Console Started!\n"),
         debug_lines: 1,
         debug_scroll: 0,
+        debug_max_lines: DEBUG_MAX_LINES,
         connections: Vec::new(),
     };
 
@@ -281,24 +337,30 @@ fn run_console_server(
 
     loop {
         if crossterm::event::poll(std::time::Duration::from_millis(50))? {
-            // Bool?
-            if let crossterm::event::Event::Key(key) = crossterm::event::read()? {
-                // Bool?
-                if key.kind == crossterm::event::KeyEventKind::Press {
-                    if key.code == crossterm::event::KeyCode::Char('q') {
-                        break;
-                    } else if key.code == crossterm::event::KeyCode::Up
-                        && state_common.debug_scroll > 0
-                    {
-                        state_common.debug_scroll -= 1;
-                        should_draw = true;
-                    } else if key.code == crossterm::event::KeyCode::Down
-                        && state_common.debug_scroll < (state_common.debug_lines - 1)
-                    {
-                        state_common.debug_scroll += 1;
-                        should_draw = true;
+            match crossterm::event::read()? {
+                crossterm::event::Event::Key(key) => {
+                    // Bool?
+                    if key.kind == crossterm::event::KeyEventKind::Press {
+                        if key.code == crossterm::event::KeyCode::Char('q') {
+                            break;
+                        } else if key.code == crossterm::event::KeyCode::Up
+                            && state_common.debug_scroll > 0
+                        {
+                            state_common.debug_scroll -= 1;
+                            should_draw = true;
+                        } else if key.code == crossterm::event::KeyCode::Down
+                            && state_common.debug_scroll < (state_common.debug_lines - 1)
+                        {
+                            state_common.debug_scroll += 1;
+                            should_draw = true;
+                        }
                     }
                 }
+                crossterm::event::Event::Resize(_, _) => {
+                    // The wrapped debug lines change with the width
+                    should_draw = true;
+                }
+                _ => {}
             }
         }
 
@@ -319,12 +381,22 @@ fn run_console_server(
                             let conn_state = NetworkStateConnection {
                                 name: user_name,
                                 state,
+                                stats: None,
                             };
                             state_common.connections.push(conn_state);
                         }
                         NetworkStateMessage::StateChange((entry, state)) => {
                             state_common.connections[entry].state = state;
                         }
+                        NetworkStateMessage::StatsUpdate(stats_vec) => {
+                            for (conn, stats_opt) in
+                                state_common.connections.iter_mut().zip(stats_vec)
+                            {
+                                if stats_opt.is_some() {
+                                    conn.stats = stats_opt;
+                                }
+                            }
+                        }
                     }
                     should_draw = true;
                 }
@@ -337,14 +409,14 @@ fn run_console_server(
                     break;
                 }
                 Ok(recv_string) => {
-                    state_common.debug_string.push_str(&recv_string);
-                    state_common.debug_lines += 1;
+                    state_common.push_debug_string(&recv_string);
                     should_draw = true;
                 }
             }
         }
 
         if should_draw {
+            state_common.update_debug_lines(terminal.size()?.width);
             terminal.draw(|frame| console_ui(frame, &state_common, None))?;
             should_draw = false;
         }
@@ -36,8 +36,8 @@ use std::time::{Duration, Instant};
 #[cfg(feature = "client")]
 use crate::communication::{ClientCommand, NetworkAudioOutPackets, NetworkAudioThreadChannels};
 use crate::communication::{
-    NetworkCommand, NetworkStateConnection, NetworkStateMessage, NetworkTerminalThreadChannels,
-    PopError, PushError, ServerCommand,
+    NetworkCommand, NetworkStateConnection, NetworkStateConnectionStats, NetworkStateMessage,
+    NetworkTerminalThreadChannels, PopError, PushError, ServerCommand,
 };
 
 // Use quic sub-library for internet communications
@@ -69,6 +69,7 @@ fn u8_to_str(data: &[u8]) -> String {
 }
 
 const MAX_CHAR_LENGTH: usize = 32;
+const STATS_MIN_FREE_STATE_SLOTS: usize = 16; // Stats updates get skipped while the terminal is behind on the state messages
 
 struct MusicStorage {
     is_stereo: bool,
@@ -144,6 +145,7 @@ struct ClientState {
     user_name_len: usize,
     state: u8, // Bit State [fileTransfer, musicServer, connectedVoice, voiceLoopback]
     rt_send: bool,
+    stats_sent: Option<(Instant, u64)>, // Sent bytes at the previous stats update
 }
 
 impl ClientState {
@@ -158,6 +160,7 @@ impl ClientState {
             user_name_len: 0,
             state: 0,
             rt_send: false,
+            stats_sent: None,
         };
         cs.user_name_len = 0;
 
@@ -258,9 +261,7 @@ impl ServerState {
         send_data.push(self.client_states[verified_index].state);
         //set_stream_msg_size(&mut send_data);
 
-        for cs in self.client_states.iter() {
-            let _ = endpoint.main_stream_send(&cs.cid, send_data.clone());
-        }
+        let _ = endpoint.main_stream_broadcast(&send_data);
 
         self.state_change_update(verified_index);
     }
@@ -353,9 +354,7 @@ impl ServerState {
                                 send_data.push(self.music_storage.len() as u8);
                                 set_stream_msg_size(&mut send_data);
 
-                                for cs in self.client_states.iter() {
-                                    let _ = endpoint.main_stream_send(&cs.cid, send_data.clone());
-                                }
+                                let _ = endpoint.main_stream_broadcast(&send_data);
                             }
                             _ => {
                                 // Deletion... so do nothing
@@ -401,6 +400,7 @@ impl ServerState {
         if let Some(cs) = ClientState::new(*cid, &read_data[1..username_len + 1]) {
             let cs_ind = self.client_states.len();
             self.client_states.push(cs);
+            let _ = endpoint.mark_ready(cid);
 
             // Send new client a state refresh
             let send_data = self.create_refresh_data(cs_ind);
@@ -478,6 +478,7 @@ impl ServerState {
             let conn_state = NetworkStateConnection {
                 name: u8_to_str(&cs.user_name[..cs.user_name_len]),
                 state: cs.state,
+                stats: None,
             };
             state_populate.push(conn_state);
         }
@@ -498,6 +499,44 @@ impl ServerState {
         let state_update = NetworkStateMessage::StateChange((verified_index, cs.state));
         let _ = self.terminal_channels.state_send.push(state_update);
     }
+
+    fn stats_update(&mut self, endpoint: &Endpoint) {
+        // The state messages that keep the connection list in sync go first
+        if self.terminal_channels.state_send.slots() < STATS_MIN_FREE_STATE_SLOTS {
+            return;
+        }
+        let mut stats_vec = Vec::with_capacity(self.client_states.len());
+        for cs in &mut self.client_states {
+            let mut stats_opt = None;
+            if let Ok(conn_stats) = endpoint.get_connection_stats(&cs.cid) {
+                let now = Instant::now();
+                let send_bitrate = match cs.stats_sent {
+                    Some((prev_instant, prev_sent_bytes)) => {
+                        let elapsed = (now - prev_instant).as_secs_f64();
+                        let sent_bits = conn_stats.sent_bytes.saturating_sub(prev_sent_bytes) * 8;
+                        if elapsed > 0.0 {
+                            Some(((sent_bits as f64) / elapsed) as u64)
+                        } else {
+                            None
+                        }
+                    }
+                    None => None,
+                };
+                cs.stats_sent = Some((now, conn_stats.sent_bytes));
+
+                stats_opt = Some(NetworkStateConnectionStats {
+                    rtt: conn_stats.rtt,
+                    packet_loss: conn_stats.get_packet_loss(),
+                    sent_bytes: conn_stats.sent_bytes,
+                    recv_bytes: conn_stats.recv_bytes,
+                    send_bitrate,
+                });
+            }
+            stats_vec.push(stats_opt);
+        }
+        let state_update = NetworkStateMessage::StatsUpdate(stats_vec);
+        let _ = self.terminal_channels.state_send.push(state_update);
+    }
 }
 
 impl EndpointEventCallbacks for ServerState {
@@ -603,6 +642,7 @@ impl EndpointEventCallbacks for ServerState {
                     Ok(NetworkCommand::Client(_)) => {}
                 }
             }
+            self.stats_update(endpoint);
             self.command_handler_tick = 0;
         }
 
@@ -957,8 +997,9 @@ impl ClientHandler {
     ) -> bool {
         match msg_type {
             StreamMsgType::ServerStateRefresh => {
-                // State Refresh
+                // State Refresh (the first one finishes the announce handshake)
                 self.handle_state_refresh(read_data);
+                let _ = endpoint.mark_ready(cid);
             }
             StreamMsgType::NewClient => {
                 self.handle_new_client(read_data);
@@ -1040,6 +1081,7 @@ impl ClientHandler {
             let conn_state = NetworkStateConnection {
                 name: client_name,
                 state: read_data[name_end],
+                stats: None,
             };
 
             state_populate.push(conn_state);
@@ -1389,6 +1431,7 @@ pub(crate) fn server_thread(
         rt_recv_first_bytes: 0,
         initial_background_recv_size: BUFFER_SIZE_PER_CONNECTION,
         background_recv_first_bytes: protocol::MESSAGE_HEADER_SIZE,
+        rt_max_pending_segments: 4,
     };
 
     let mut server_endpoint =
@@ -1438,6 +1481,7 @@ pub(crate) fn client_thread(
         rt_recv_first_bytes: protocol::MESSAGE_HEADER_SIZE,
         initial_background_recv_size: BUFFER_SIZE_PER_CONNECTION,
         background_recv_first_bytes: protocol::MESSAGE_HEADER_SIZE,
+        rt_max_pending_segments: 4,
     };
     let mut client_endpoint = match Endpoint::new_client_with_first_connection(
         server_address.is_ipv6(),